@@ -0,0 +1,202 @@
+//! MIDI activity log panel of the GUI: recent sent and received messages
+//! with timestamps, decoded names and raw bytes.
+
+use crate::monitor::describe;
+use anyhow::{Context, Result};
+use eframe::egui;
+use midi_ctrl::{Config, Message, MidiMap, Realtime};
+use std::collections::{BTreeSet, VecDeque};
+use std::fs;
+use std::path::PathBuf;
+use std::time::Instant;
+
+/// Entries kept; the oldest are dropped first.
+const CAPACITY: usize = 1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Sent,
+    Received,
+}
+
+/// One message as it went out or came in.
+#[derive(Debug, Clone)]
+pub struct Activity {
+    pub at: Instant,
+    pub direction: Direction,
+    pub port: String,
+    pub bytes: Vec<u8>,
+}
+
+/// Message types the log can be filtered by.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Kind {
+    Note,
+    Cc,
+    Program,
+    PitchBend,
+    Pressure,
+    SysEx,
+    Transport,
+    Clock,
+    Mtc,
+    Other,
+}
+
+impl Kind {
+    const ALL: [Kind; 10] = [
+        Kind::Note,
+        Kind::Cc,
+        Kind::Program,
+        Kind::PitchBend,
+        Kind::Pressure,
+        Kind::SysEx,
+        Kind::Transport,
+        Kind::Clock,
+        Kind::Mtc,
+        Kind::Other,
+    ];
+
+    fn of(message: Option<&Message>) -> Kind {
+        match message {
+            Some(Message::NoteOn { .. } | Message::NoteOff { .. }) => Kind::Note,
+            Some(Message::ControlChange { .. }) => Kind::Cc,
+            Some(Message::ProgramChange { .. }) => Kind::Program,
+            Some(Message::PitchBend { .. }) => Kind::PitchBend,
+            Some(Message::ChannelPressure { .. } | Message::PolyPressure { .. }) => Kind::Pressure,
+            Some(Message::SysEx(_)) => Kind::SysEx,
+            Some(Message::Realtime(Realtime::Clock | Realtime::ActiveSensing)) => Kind::Clock,
+            Some(Message::Realtime(_) | Message::SongPosition(_)) => Kind::Transport,
+            Some(Message::QuarterFrame { .. }) => Kind::Mtc,
+            None => Kind::Other,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Kind::Note => "Notes",
+            Kind::Cc => "CC",
+            Kind::Program => "Program",
+            Kind::PitchBend => "Pitch Bend",
+            Kind::Pressure => "Aftertouch",
+            Kind::SysEx => "SysEx",
+            Kind::Transport => "Transport",
+            Kind::Clock => "Clock",
+            Kind::Mtc => "MTC",
+            Kind::Other => "Other",
+        }
+    }
+}
+
+struct Entry {
+    kind: Kind,
+    line: String,
+}
+
+pub struct ActivityLog {
+    started: Instant,
+    entries: VecDeque<Entry>,
+    /// Types not recorded, so clock ticks do not push everything else out.
+    hidden: BTreeSet<Kind>,
+    saved: Option<PathBuf>,
+}
+
+impl Default for ActivityLog {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            entries: VecDeque::new(),
+            hidden: BTreeSet::from([Kind::Clock, Kind::Mtc]),
+            saved: None,
+        }
+    }
+}
+
+impl ActivityLog {
+    pub fn push(&mut self, activity: Activity, midi_map: &MidiMap) {
+        let message = Message::decode(&activity.bytes);
+        let kind = Kind::of(message.as_ref());
+        if self.hidden.contains(&kind) {
+            return;
+        }
+        let elapsed = activity.at.saturating_duration_since(self.started);
+        let arrow = match activity.direction {
+            Direction::Sent => "→",
+            Direction::Received => "←",
+        };
+        let name = message.map_or_else(|| "Unknown".to_string(), |m| describe(&m, midi_map));
+        let line = format!(
+            "[{:>9.3}] {} {}: {} [{:02X?}]",
+            elapsed.as_secs_f64(),
+            arrow,
+            activity.port,
+            name,
+            activity.bytes
+        );
+        if self.entries.len() == CAPACITY {
+            self.entries.pop_front();
+        }
+        self.entries.push_back(Entry { kind, line });
+    }
+
+    fn text(&self) -> String {
+        self.entries.iter().map(|e| format!("{}\n", e.line)).collect()
+    }
+
+    fn save(&self) -> Result<PathBuf> {
+        let dir = Config::dir().ok_or_else(|| anyhow::anyhow!("No config directory"))?;
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("activity.log");
+        fs::write(&path, self.text())
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Draws the filters, actions and entries. Returns an error message if
+    /// saving failed.
+    pub fn show(&mut self, ui: &mut egui::Ui) -> Option<String> {
+        let mut error = None;
+        ui.horizontal_wrapped(|ui| {
+            for kind in Kind::ALL {
+                let mut shown = !self.hidden.contains(&kind);
+                if ui.checkbox(&mut shown, kind.label()).changed() {
+                    if shown {
+                        self.hidden.remove(&kind);
+                    } else {
+                        self.hidden.insert(kind);
+                        self.entries.retain(|e| e.kind != kind);
+                    }
+                }
+            }
+            ui.separator();
+            if ui.button("Clear").clicked() {
+                self.entries.clear();
+            }
+            if ui.button("Copy").clicked() {
+                let text = self.text();
+                ui.output_mut(|o| o.copied_text = text);
+            }
+            if ui.button("Save").clicked() {
+                match self.save() {
+                    Ok(path) => self.saved = Some(path),
+                    Err(e) => error = Some(format!("Failed to save activity log: {:#}", e)),
+                }
+            }
+            if let Some(path) = &self.saved {
+                ui.label(format!("✓ Saved to {}", path.display()));
+            }
+        });
+
+        let row_height = ui.text_style_height(&egui::TextStyle::Monospace);
+        egui::ScrollArea::vertical()
+            .id_source("activity")
+            .stick_to_bottom(true)
+            .auto_shrink([false, false])
+            .show_rows(ui, row_height, self.entries.len(), |ui, rows| {
+                for entry in self.entries.range(rows) {
+                    ui.monospace(&entry.line);
+                }
+            });
+        error
+    }
+}
@@ -0,0 +1,215 @@
+//! Parameter automation: slider and knob moves recorded against the clock
+//! into lanes, one per parameter, each looping over a number of bars so
+//! filter sweeps and send throws replay in time with the transport.
+
+use crate::clock::PPQN;
+use crate::midi::Message;
+use crate::midi_map::{MidiMap, ParamAddress};
+use crate::transport::TICKS_PER_BAR;
+use crate::types::{Channel, Value7};
+use serde::{Deserialize, Serialize};
+
+/// Longest loop, in bars.
+pub const MAX_BARS: u32 = 16;
+/// How long a lane holds off after its parameter is moved by hand, and
+/// how close together a move's changes are to count as one, in ticks.
+const TOUCH_TICKS: u64 = PPQN as u64;
+
+fn one() -> u32 {
+    1
+}
+
+fn is_one(value: &u32) -> bool {
+    *value == 1
+}
+
+/// A value and the clock tick it is sent on, counted from the start of
+/// the loop.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutomationPoint {
+    pub tick: u64,
+    pub value: u8,
+}
+
+/// The recorded moves of one parameter on one channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AutomationLane {
+    pub channel: Channel,
+    /// Name as `set` takes it.
+    pub parameter: String,
+    /// Looked up by [`Automation::resolve`]; lanes without one are not
+    /// played.
+    #[serde(skip)]
+    pub address: Option<ParamAddress>,
+    /// Loop length.
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub bars: u32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mute: bool,
+    /// In tick order, at most one per tick.
+    #[serde(default, rename = "point")]
+    pub points: Vec<AutomationPoint>,
+}
+
+impl AutomationLane {
+    /// Clock ticks in one pass of the loop.
+    pub fn length(&self) -> u64 {
+        self.bars.clamp(1, MAX_BARS) as u64 * TICKS_PER_BAR
+    }
+
+    /// Sets the value sent on `tick` of the loop, replacing any there.
+    fn set_point(&mut self, tick: u64, value: u8) {
+        match self.points.binary_search_by_key(&tick, |point| point.tick) {
+            Ok(index) => self.points[index].value = value,
+            Err(index) => self.points.insert(index, AutomationPoint { tick, value }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Automation {
+    /// Loop length of lanes recorded from now on, in bars.
+    pub bars: u32,
+    #[serde(rename = "lane", skip_serializing_if = "Vec::is_empty")]
+    pub lanes: Vec<AutomationLane>,
+}
+
+impl Default for Automation {
+    fn default() -> Self {
+        Self { bars: 1, lanes: Vec::new() }
+    }
+}
+
+impl Automation {
+    pub fn is_empty(&self) -> bool {
+        self.lanes.is_empty()
+    }
+
+    /// Looks up the lanes' parameters in `midi_map`, e.g. after loading
+    /// or a change of device model.
+    pub fn resolve(&mut self, midi_map: &MidiMap) {
+        for lane in &mut self.lanes {
+            lane.address = midi_map.get_by_name(&lane.parameter).map(|param| param.address);
+        }
+    }
+}
+
+/// Writes parameter moves into lanes as the transport runs. A move
+/// replaces what the lane had over the stretch it covers, so recording
+/// over a loop again corrects it rather than piling up.
+#[derive(Debug, Default)]
+pub struct AutomationRecorder {
+    /// The parameters being moved and the tick of their latest change.
+    touched: Vec<(Channel, ParamAddress, u64)>,
+}
+
+impl AutomationRecorder {
+    /// Records `value` for `parameter` (at `address`) on `channel` at
+    /// clock tick `tick` (counted from Start), in its lane or a new one
+    /// `automation.bars` long.
+    pub fn record(
+        &mut self,
+        automation: &mut Automation,
+        channel: Channel,
+        parameter: &str,
+        address: ParamAddress,
+        tick: u64,
+        value: u8,
+    ) {
+        let found = automation.lanes.iter().position(|lane| {
+            let same = lane.address == Some(address) || lane.parameter == parameter;
+            lane.channel == channel && same
+        });
+        let index = found.unwrap_or_else(|| {
+            automation.lanes.push(AutomationLane {
+                channel,
+                parameter: parameter.to_string(),
+                address: Some(address),
+                bars: automation.bars.clamp(1, MAX_BARS),
+                mute: false,
+                points: Vec::new(),
+            });
+            automation.lanes.len() - 1
+        });
+        let lane = &mut automation.lanes[index];
+        let length = lane.length();
+
+        // Clear the stretch since the move's previous change
+        let touch = self.touched.iter_mut().find(|t| (t.0, t.1) == (channel, address));
+        let previous = touch.as_ref().map(|t| t.2);
+        let previous = previous.filter(|p| *p <= tick && tick - p <= TOUCH_TICKS);
+        if let Some(previous) = previous {
+            let (start, span) = (previous % length, tick - previous);
+            lane.points.retain(|point| {
+                let after = (point.tick + length - start) % length;
+                after == 0 || after > span
+            });
+        }
+        lane.set_point(tick % length, value);
+        match touch {
+            Some(touch) => touch.2 = tick,
+            None => self.touched.push((channel, address, tick)),
+        }
+    }
+
+    /// Forgets the moves in progress, e.g. when recording stops.
+    pub fn stop(&mut self) {
+        self.touched.clear();
+    }
+}
+
+/// Plays automation lanes as the clock ticks.
+#[derive(Debug, Default)]
+pub struct AutomationPlayer {
+    lanes: Vec<AutomationLane>,
+    enabled: bool,
+    /// Parameters moved by hand and the tick they last were.
+    touched: Vec<(Channel, ParamAddress, u64)>,
+}
+
+impl AutomationPlayer {
+    /// Replaces the lanes, taking effect from the next tick.
+    pub fn set_lanes(&mut self, lanes: Vec<AutomationLane>) {
+        self.lanes = lanes;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, on: bool) {
+        self.enabled = on;
+    }
+
+    /// Notes a parameter moved by hand at clock tick `tick`; its lane
+    /// holds off for a beat so the move is heard, e.g. while recording it.
+    pub fn touch(&mut self, channel: Channel, address: ParamAddress, tick: u64) {
+        self.touched.retain(|t| (t.0, t.1) != (channel, address));
+        self.touched.push((channel, address, tick));
+    }
+
+    /// What to send on clock tick `tick` (counted from Start).
+    pub fn tick(&mut self, tick: u64) -> Vec<Message> {
+        self.touched.retain(|t| t.2 <= tick && tick - t.2 < TOUCH_TICKS);
+        if !self.enabled {
+            return Vec::new();
+        }
+        let mut messages = Vec::new();
+        for lane in self.lanes.iter().filter(|lane| !lane.mute) {
+            let Some(address) = lane.address else {
+                continue;
+            };
+            if self.touched.iter().any(|t| (t.0, t.1) == (lane.channel, address)) {
+                continue;
+            }
+            let at = tick % lane.length();
+            let Ok(index) = lane.points.binary_search_by_key(&at, |point| point.tick) else {
+                continue;
+            };
+            let value = Value7::new(lane.points[index].value.min(127)).unwrap_or_default();
+            messages.extend(address.messages(lane.channel, value).into_iter().flatten());
+        }
+        messages
+    }
+}
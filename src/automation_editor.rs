@@ -0,0 +1,79 @@
+//! Automation page of the GUI: a row per recorded lane with its loop
+//! length and mute, and the moves drawn as a curve with the playhead.
+
+use eframe::egui;
+use midi_ctrl::automation::MAX_BARS;
+use midi_ctrl::{Automation, AutomationLane};
+
+const CURVE_SIZE: egui::Vec2 = egui::vec2(240.0, 24.0);
+
+/// Draws the lane's values over one pass of its loop as steps, and where
+/// clock tick `tick` is in it.
+fn curve(ui: &mut egui::Ui, lane: &AutomationLane, tick: Option<u64>) {
+    let (rect, _) = ui.allocate_exact_size(CURVE_SIZE, egui::Sense::hover());
+    let painter = ui.painter_at(rect);
+    painter.rect_filled(rect, 2.0, ui.visuals().extreme_bg_color);
+    let length = lane.length() as f32;
+    let x = |tick: u64| rect.left() + rect.width() * (tick as f32 / length).min(1.0);
+    let y = |value: u8| rect.bottom() - rect.height() * value.min(127) as f32 / 127.0;
+    if let Some(last) = lane.points.last() {
+        // The last value holds over the loop point
+        let mut line = vec![egui::pos2(rect.left(), y(last.value))];
+        for point in &lane.points {
+            line.push(egui::pos2(x(point.tick), line[line.len() - 1].y));
+            line.push(egui::pos2(x(point.tick), y(point.value)));
+        }
+        line.push(egui::pos2(rect.right(), y(last.value)));
+        let color = ui.visuals().selection.bg_fill;
+        painter.add(egui::Shape::line(line, egui::Stroke::new(1.5, color)));
+    }
+    if let Some(tick) = tick {
+        let at = x(tick % lane.length());
+        let stroke = egui::Stroke::new(1.0, ui.visuals().strong_text_color());
+        painter.vline(at, rect.y_range(), stroke);
+    }
+}
+
+/// Draws the lanes with the playhead at `tick`. Returns true when they
+/// were edited and should be sent and saved.
+pub fn show(ui: &mut egui::Ui, automation: &mut Automation, tick: Option<u64>) -> bool {
+    let mut edited = false;
+    let mut removed = None;
+    if automation.is_empty() {
+        ui.label("No lanes yet: record, start the transport and move a parameter.");
+    }
+    egui::Grid::new("automation_lanes").striped(true).show(ui, |ui| {
+        for (index, lane) in automation.lanes.iter_mut().enumerate() {
+            ui.label(format!("ch {:>2}", lane.channel));
+            if lane.address.is_some() {
+                ui.label(&lane.parameter);
+            } else {
+                let hover = "Not in the current parameter map; not played";
+                ui.weak(&lane.parameter).on_hover_text(hover);
+            }
+            let drag = egui::DragValue::new(&mut lane.bars)
+                .clamp_range(1..=MAX_BARS)
+                .suffix(" bar(s)");
+            edited |= ui.add(drag).on_hover_text("Loop length").changed();
+            edited |= ui.toggle_value(&mut lane.mute, "M").on_hover_text("Mute").changed();
+            curve(ui, lane, tick);
+            ui.label(format!("{} point(s)", lane.points.len()));
+            if ui.small_button("🗑").on_hover_text("Remove lane").clicked() {
+                removed = Some(index);
+            }
+            ui.end_row();
+        }
+    });
+    if let Some(index) = removed {
+        automation.lanes.remove(index);
+        edited = true;
+    }
+    ui.horizontal(|ui| {
+        ui.label("New lanes loop");
+        let drag = egui::DragValue::new(&mut automation.bars)
+            .clamp_range(1..=MAX_BARS)
+            .suffix(" bar(s)");
+        edited |= ui.add(drag).changed();
+    });
+    edited
+}
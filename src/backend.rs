@@ -0,0 +1,269 @@
+//! Port access behind a trait, so the engine can run against real MIDI
+//! ports (midir) or an in-memory mock.
+
+use anyhow::Result;
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiOutput, MidiOutputConnection};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Called with (timestamp in µs, raw message bytes) for each incoming message.
+pub type InputCallback = Box<dyn FnMut(u64, &[u8]) + Send>;
+
+/// An open output port.
+pub trait OutputConnection: Send {
+    fn send(&mut self, bytes: &[u8]) -> Result<()>;
+    fn close(self: Box<Self>);
+}
+
+/// An open input port; the callback runs until it is closed.
+pub trait InputConnection: Send {
+    fn close(self: Box<Self>);
+}
+
+/// Enumerates and opens MIDI ports.
+pub trait MidiBackend: Send + Sync {
+    /// Output port names, in port index order.
+    fn output_port_names(&self) -> Result<Vec<String>>;
+    /// Opens an output, returning the connection and the port's name.
+    fn open_output(&self, port_index: usize) -> Result<(Box<dyn OutputConnection>, String)>;
+    fn input_port_names(&self) -> Result<Vec<String>>;
+    /// Opens an input that delivers every message, SysEx and clock included.
+    fn open_input(
+        &self,
+        port_index: usize,
+        callback: InputCallback,
+    ) -> Result<(Box<dyn InputConnection>, String)>;
+}
+
+/// System MIDI ports via midir.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct MidirBackend;
+
+impl OutputConnection for MidiOutputConnection {
+    fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        MidiOutputConnection::send(self, bytes).map_err(|e| anyhow::anyhow!("{}", e))
+    }
+
+    fn close(self: Box<Self>) {
+        MidiOutputConnection::close(*self);
+    }
+}
+
+impl InputConnection for MidiInputConnection<()> {
+    fn close(self: Box<Self>) {
+        MidiInputConnection::close(*self);
+    }
+}
+
+impl MidiBackend for MidirBackend {
+    fn output_port_names(&self) -> Result<Vec<String>> {
+        let midi_out = MidiOutput::new("midi_ctrl")?;
+        let names = midi_out
+            .ports()
+            .iter()
+            .map(|p| {
+                midi_out
+                    .port_name(p)
+                    .unwrap_or_else(|_| "Unknown".to_string())
+            })
+            .collect();
+        Ok(names)
+    }
+
+    fn open_output(&self, port_index: usize) -> Result<(Box<dyn OutputConnection>, String)> {
+        let midi_out = MidiOutput::new("midi_ctrl")?;
+        let ports = midi_out.ports();
+        let port = ports
+            .get(port_index)
+            .ok_or_else(|| anyhow::anyhow!("No MIDI output port at index {}", port_index))?;
+        let port_name = midi_out
+            .port_name(port)
+            .unwrap_or_else(|_| "<unknown>".to_string());
+        let conn_out = midi_out
+            .connect(port, &format!("midi_ctrl-{}", port_name))
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok((Box::new(conn_out), port_name))
+    }
+
+    fn input_port_names(&self) -> Result<Vec<String>> {
+        let midi_in = MidiInput::new("midi_ctrl-in")?;
+        let names = midi_in
+            .ports()
+            .iter()
+            .map(|p| {
+                midi_in
+                    .port_name(p)
+                    .unwrap_or_else(|_| "Unknown".to_string())
+            })
+            .collect();
+        Ok(names)
+    }
+
+    fn open_input(
+        &self,
+        port_index: usize,
+        mut callback: InputCallback,
+    ) -> Result<(Box<dyn InputConnection>, String)> {
+        let mut midi_in = MidiInput::new("midi_ctrl-in")?;
+        // We want SysEx and clock as well, not just channel messages
+        midi_in.ignore(Ignore::None);
+        let ports = midi_in.ports();
+        let port = ports
+            .get(port_index)
+            .ok_or_else(|| anyhow::anyhow!("No MIDI input port at index {}", port_index))?;
+        let port_name = midi_in
+            .port_name(port)
+            .unwrap_or_else(|_| "<unknown>".to_string());
+        let conn = midi_in
+            .connect(
+                port,
+                &format!("midi_ctrl-in-{}", port_name),
+                move |timestamp_us, bytes, _| callback(timestamp_us, bytes),
+                (),
+            )
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+        Ok((Box::new(conn), port_name))
+    }
+}
+
+#[derive(Default)]
+struct MockState {
+    outputs: Vec<String>,
+    inputs: Vec<String>,
+    /// Everything sent, as (output port name, bytes), in order.
+    sent: Vec<(String, Vec<u8>)>,
+    /// Callbacks of open inputs, by port index.
+    listeners: BTreeMap<usize, InputCallback>,
+}
+
+/// In-memory backend for tests: records every send and lets the test feed
+/// messages into open inputs. Clones share the same ports and recording.
+#[derive(Clone, Default)]
+pub struct MockBackend {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockBackend {
+    /// A mock with the given output and input port names.
+    pub fn new(outputs: &[&str], inputs: &[&str]) -> Self {
+        let state = MockState {
+            outputs: outputs.iter().map(|s| s.to_string()).collect(),
+            inputs: inputs.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        };
+        Self {
+            state: Arc::new(Mutex::new(state)),
+        }
+    }
+
+    /// Replaces the output port list, e.g. to simulate a device unplug.
+    pub fn set_outputs(&self, outputs: &[&str]) {
+        self.state.lock().unwrap().outputs = outputs.iter().map(|s| s.to_string()).collect();
+    }
+
+    /// Everything sent so far, as (output port name, bytes).
+    pub fn sent(&self) -> Vec<(String, Vec<u8>)> {
+        self.state.lock().unwrap().sent.clone()
+    }
+
+    /// Returns and forgets everything sent so far.
+    pub fn take_sent(&self) -> Vec<(String, Vec<u8>)> {
+        std::mem::take(&mut self.state.lock().unwrap().sent)
+    }
+
+    /// Delivers `bytes` to the open input at `port_index`, as if the device
+    /// sent them. Returns false if that input is not open.
+    pub fn receive(&self, port_index: usize, timestamp_us: u64, bytes: &[u8]) -> bool {
+        // Take the callback out so it may call back into the mock
+        let callback = self.state.lock().unwrap().listeners.remove(&port_index);
+        let Some(mut callback) = callback else {
+            return false;
+        };
+        callback(timestamp_us, bytes);
+        self.state
+            .lock()
+            .unwrap()
+            .listeners
+            .entry(port_index)
+            .or_insert(callback);
+        true
+    }
+}
+
+struct MockOutput {
+    name: String,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl OutputConnection for MockOutput {
+    fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        self.state
+            .lock()
+            .unwrap()
+            .sent
+            .push((self.name.clone(), bytes.to_vec()));
+        Ok(())
+    }
+
+    fn close(self: Box<Self>) {}
+}
+
+struct MockInput {
+    port_index: usize,
+    state: Arc<Mutex<MockState>>,
+}
+
+impl InputConnection for MockInput {
+    fn close(self: Box<Self>) {
+        self.state
+            .lock()
+            .unwrap()
+            .listeners
+            .remove(&self.port_index);
+    }
+}
+
+impl MidiBackend for MockBackend {
+    fn output_port_names(&self) -> Result<Vec<String>> {
+        Ok(self.state.lock().unwrap().outputs.clone())
+    }
+
+    fn open_output(&self, port_index: usize) -> Result<(Box<dyn OutputConnection>, String)> {
+        let name = self
+            .state
+            .lock()
+            .unwrap()
+            .outputs
+            .get(port_index)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No MIDI output port at index {}", port_index))?;
+        let conn = MockOutput {
+            name: name.clone(),
+            state: self.state.clone(),
+        };
+        Ok((Box::new(conn), name))
+    }
+
+    fn input_port_names(&self) -> Result<Vec<String>> {
+        Ok(self.state.lock().unwrap().inputs.clone())
+    }
+
+    fn open_input(
+        &self,
+        port_index: usize,
+        callback: InputCallback,
+    ) -> Result<(Box<dyn InputConnection>, String)> {
+        let mut state = self.state.lock().unwrap();
+        let name = state
+            .inputs
+            .get(port_index)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("No MIDI input port at index {}", port_index))?;
+        state.listeners.insert(port_index, callback);
+        let conn = MockInput {
+            port_index,
+            state: self.state.clone(),
+        };
+        Ok((Box::new(conn), name))
+    }
+}
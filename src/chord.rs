@@ -0,0 +1,90 @@
+//! Chord symbols such as `C4maj7`, `F#3m`, `Bb2sus4`: a root note with
+//! its octave, followed by the chord quality.
+
+use crate::note::Note;
+use crate::types::Value7;
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Chord qualities and their intervals in semitones above the root.
+const QUALITIES: &[(&[&str], &[u8])] = &[
+    (&["", "maj", "M"], &[0, 4, 7]),
+    (&["m", "min"], &[0, 3, 7]),
+    (&["dim"], &[0, 3, 6]),
+    (&["aug", "+"], &[0, 4, 8]),
+    (&["sus2"], &[0, 2, 7]),
+    (&["sus4", "sus"], &[0, 5, 7]),
+    (&["5"], &[0, 7]),
+    (&["6"], &[0, 4, 7, 9]),
+    (&["m6"], &[0, 3, 7, 9]),
+    (&["7"], &[0, 4, 7, 10]),
+    (&["maj7", "M7"], &[0, 4, 7, 11]),
+    (&["m7", "min7"], &[0, 3, 7, 10]),
+    (&["m7b5"], &[0, 3, 6, 10]),
+    (&["dim7"], &[0, 3, 6, 9]),
+    (&["add9"], &[0, 4, 7, 14]),
+    (&["9"], &[0, 4, 7, 10, 14]),
+    (&["maj9", "M9"], &[0, 4, 7, 11, 14]),
+    (&["m9", "min9"], &[0, 3, 7, 10, 14]),
+];
+
+/// A parsed chord symbol.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    pub root: Note,
+    /// Quality as written, e.g. `maj7`.
+    pub quality: String,
+    intervals: &'static [u8],
+}
+
+impl Chord {
+    /// The chord's notes, lowest first.
+    pub fn notes(&self) -> Result<Vec<Value7>> {
+        let root = self.root.value().get();
+        self.intervals
+            .iter()
+            .map(|&interval| {
+                let note = root as u16 + interval as u16;
+                if note > Value7::MAX as u16 {
+                    bail!("Chord {} reaches above note 127", self);
+                }
+                Ok(Value7::from_low_bits(note))
+            })
+            .collect()
+    }
+}
+
+impl FromStr for Chord {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        // Root: letter, optional accidental, then a one-digit octave or -1
+        if !s.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            bail!("Invalid chord '{}' (expected e.g. C4maj7, F#3m)", s);
+        }
+        let mut end = 1;
+        if s[end..].starts_with(['#', 'b']) {
+            end += 1;
+        }
+        if s[end..].starts_with("-1") {
+            end += 2;
+        } else if s[end..].starts_with(|c: char| c.is_ascii_digit()) {
+            end += 1;
+        } else {
+            bail!("Invalid chord '{}' (expected e.g. C4maj7, F#3m)", s);
+        }
+        let root: Note = s[..end].parse()?;
+        let quality = &s[end..];
+        let Some(&(_, intervals)) = QUALITIES.iter().find(|(names, _)| names.contains(&quality)) else {
+            bail!("Unknown chord quality '{}' in '{}'", quality, s);
+        };
+        Ok(Self { root, quality: quality.to_string(), intervals })
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", self.root, self.quality)
+    }
+}
@@ -0,0 +1,2373 @@
+use anyhow::{Context, Result};
+use clap::Subcommand;
+use midi_ctrl::{
+    find_output_port, input_port_names, output_port_names, sysex, Channel, Chord, ClockSource,
+    Config, Controller, DeviceIdentity, DeviceModel, DeviceProfile, DryRunSink, Envelope,
+    EnvelopeTrigger, FrameRate, InputEvent, Lfo, LfoLength, LfoShape, MacroControl, MapFile,
+    Message, MidiController, MidiMap, MmcCommand, MockBackend, Note, ParamAddress, Pattern,
+    PatternChange, PortEvent, PortTarget, Randomizer, Realtime, SeqTrack, Severity, Snapshot,
+    SongEntry, TapTempo, Timecode, TransportProtocol, Value7,
+};
+use midi_ctrl::clock::{MAX_SWING, MIN_SWING, PPQN};
+use midi_ctrl::import;
+use midi_ctrl::envelope::MAX_ENVELOPES;
+use midi_ctrl::lfo::{MAX_HZ, MAX_LFOS, MIN_HZ};
+use midi_ctrl::randomize::DEFAULT_AMOUNT;
+use midi_ctrl::automation::MAX_BARS as MAX_AUTOMATION_BARS;
+use midi_ctrl::sequencer::MAX_DIVISION;
+use midi_ctrl::transport::TICKS_PER_BAR;
+use crate::fifo;
+use crate::strict::{self, NoPort};
+use crate::script::{self, Step};
+use rustyline::completion::{Completer, FilenameCompleter, Pair};
+use rustyline::error::ReadlineError;
+use rustyline::history::DefaultHistory;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::validate::Validator;
+use rustyline::{Editor, Helper};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const HELP: &str = "\
+Commands:
+  cc <controller> <value>     Send a Control Change
+  nrpn <msb> <lsb> <value>    Send an NRPN (value 0-16383)
+  noteon <note> <velocity>    Send Note On (note as 60 or C4, F#3, Bb2)
+  noteoff <note>              Send Note Off
+  note <note> [velocity] [ms] Play a note for a duration; velocity and
+                              length default to the device profile's
+  chord <chord> [velocity] [ms]
+                              Play a chord for a duration, e.g. C4maj7, F#3m
+  pc <program>                Send Program Change
+  pattern <A01-H16>           Select a pattern (Bank Select + PC)
+  bend <value>                Send Pitch Bend (-8192..8191, 0 = center)
+  at <value>                  Send Channel Aftertouch
+  polyat <note> <value>       Send Polyphonic Aftertouch
+  set <parameter> <value>     Set a parameter by name, e.g. filter-frequency
+                              or \"Filter Frequency\"; named settings work as
+                              values, e.g. set filter-type bandpass; macros
+                              from the config work as parameters
+  find <text>                 List parameters whose name or alias matches text,
+                              with current values (set takes aliases too)
+  chan [1-16]                 Show or set the channel for sends
+                              (append ch=<n> to any send to override it once)
+  start | stop | continue     Transport
+  spp <beat>                  Send Song Position Pointer (16th notes)
+  locate <bar>                Continue playback from the start of a bar
+  in <ms> <command>           Send a message command after a delay
+  onbar <command>             Send a message command on the next bar
+  pcmode [now|bar|end [bars]] Show or set when pc/pattern take effect while playing:
+                              at once, on the next bar, or when a pattern of
+                              the given length (default 1 bar) ends
+  at bar+N|beat+N <command>   Send a message command on the Nth next bar or beat
+  mmc <play|stop|pause|rec|punchout|ff|rew>
+                              Send an MMC transport command
+  mmc locate <hh:mm:ss:ff>    Send an MMC Locate
+  protocol [realtime|mmc|both] [device_id]
+                              Show or set how transport reaches the target port(s)
+  rstatus [on|off]            Show or set running status for the target port(s)
+  device [model|auto]         Show or set the device model of the target port(s):
+                              digitakt, digitakt-2, digitone, syntakt,
+                              model-samples, generic; auto recognizes it
+  sysex send <file> [delay_ms]
+                              Send a .syx file
+  sysex recv <file> [timeout_s]
+                              Capture an incoming SysEx dump to a file
+  id                          Query the device identity (needs --input)
+  sync [internal|external]    Show or set the clock source (external needs --input)
+  clock [on|off]              Show or set a continuous clock, independent of start/stop
+  bpm [20-300]                Show or set the tempo
+  swing [50-80]               Show or set the swing of the clock and sequencer (%)
+  tap                         Tap tempo: repeat in time to set the BPM
+  mtc [off|24|25|29.97|30]    Show or set MIDI Time Code output with the clock
+  port <index|all>            Route sends to one open port or all of them
+  ports                       List available ports (* = open)
+  status                      Show ports, channel, clock, held notes and queued sends
+  dryrun [on|off]             Show or set dry run: print sends instead of sending them
+  snap save|load <name>       Save the controller values sent/received this
+                              session, or send a saved set back
+  snap list                   List saved snapshots
+  resync                      Resend every controller value sent/received this
+                              session, paced, e.g. after power-cycling the device
+  seq [on|off]                Show the sequencer's tracks, or play them with the
+                              transport (on its clock, internal or external)
+  seq add                     Add a 16-step track on the channel
+  seq <track> <step> <note> [velocity] [ticks]
+                              Set a step (a 16th; length in clock ticks, 6 a step)
+  seq <track> <step> off      Clear a step
+  seq <track> <step> lock <parameter> <value>
+                              Send a parameter value just before the step plays
+  seq <track> <step> unlock [parameter]
+                              Remove a step's parameter lock(s)
+  seq <track> length <steps>  Set a track's steps (1-64)
+  seq <track> division <n>    Play a track's steps every n 16ths (1-16)
+  seq <track> mute|unmute|clear|remove
+                              Edit a track; seq edits are saved to the config
+  song [on|off]               Show the song, or follow it with the transport,
+                              sending each entry's pattern and snapshot in time
+  song add <pattern> [bars] [repeat] [snapshot]
+                              Add an entry: the pattern, its length in bars, times
+                              it plays, and a snapshot recalled as it starts
+  song remove <n>|clear       Remove song entries
+  song loop on|off            Start over after the last entry
+  auto [on|off]               Show the automation lanes recorded in the GUI, or
+                              replay them with the transport
+  auto <lane> mute|unmute|remove
+                              Edit a lane; auto clear removes them all
+  auto <lane> bars <n>        Set a lane's loop length (1-16 bars)
+  lfo                         Show the software LFOs
+  lfo add <parameter>         Add an LFO modulating a parameter on the channel
+  lfo <n> on|off|remove       Run, stop or remove an LFO
+  lfo <n> shape|rate|depth|offset <value>
+                              Set an LFO's shape (sine, triangle, saw, square,
+                              sample_hold), rate (Hz, or 1/16-1/2, 1bar-8bars
+                              on the clock), depth or offset (0-127)
+  env                         Show the envelope generators
+  env add <parameter>         Add an envelope sending a parameter on the channel
+  env <n> on|off|remove       Run, stop or remove an envelope
+  env <n> trigger start|step <track> <step>|note [note|any] [channel]
+                              Open it on Start (until Stop), on a sequencer
+                              step, or on a Note On (until its Note Off)
+  env <n> adsr <a> <d> <s> <r>
+                              Set attack, decay and release times and the
+                              sustain level (0-100%)
+  env <n> time beats|secs     Time the stages in beats or seconds
+  env <n> range <from> <peak> Set the values at rest and at the peak (0-127)
+  init [save]                 Send the target port's init patch (every parameter
+                              at its init value) on the channel, or save the
+                              parameter values sent this session as the init
+  random [amount%] [category] Move every parameter in a category (all if none
+                              given) by up to amount (default 25%) of its
+                              range, sending the new values on the channel
+  random undo                 Send the values the last random replaced
+  connect <index|name>        Open another output port
+  disconnect [index|all]      Close one output port, or all of them
+  sleep <ms>                  Pause before the next command
+  alias [name = cmd; cmd ...] List aliases, or define one ($1..$9 = arguments)
+  unalias <name>              Remove an alias
+  load <file.csv>             Schedule rows of delay_ms,type,channel,data1,data2
+                              (delays count from the previous row)
+  run <file>                  Run commands from a file (with sleep and
+                              repeat N { ... } blocks)
+  help                        Show this help
+  exit                        Quit";
+
+/// Arguments of `midi_ctrl import`.
+#[derive(clap::Args, Debug)]
+pub struct ImportArgs {
+    /// Electra One preset (.json) or TouchOSC layout (.tosc).
+    file: PathBuf,
+
+    /// Name to save the map as. Defaults to the file's name.
+    #[arg(long)]
+    name: Option<String>,
+
+    /// Use the map from now on instead of the device's built-in one.
+    #[arg(long = "use")]
+    use_map: bool,
+}
+
+/// Converts a controller template into a saved map.
+pub fn run_import(mut config: Config, args: ImportArgs) -> Result<()> {
+    let map = import::import_file(&args.file)?;
+    let name = match args.name {
+        Some(name) => name,
+        None => args
+            .file
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+            .ok_or_else(|| anyhow::anyhow!("Pass --name for the map"))?,
+    };
+    let path = map.save(&name)?;
+    println!("✓ Imported {} parameter(s) to {}", map.parameters.len(), path.display());
+    if args.use_map {
+        config.map = Some(name);
+        config.save()?;
+        println!("✓ Using map '{}'", config.map.as_deref().unwrap_or_default());
+    } else {
+        println!("Pass --use, or set map = \"{}\" in the config, to use it", name);
+    }
+    Ok(())
+}
+
+#[derive(Subcommand, Debug)]
+pub enum MapCommand {
+    /// Print a cheat sheet of the parameters: category, name, CC or NRPN,
+    /// range and default.
+    Export {
+        #[arg(long, value_enum, default_value_t = ExportFormat::Markdown)]
+        format: ExportFormat,
+
+        /// Write to this file instead of stdout.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Check a map for clashing addresses, out-of-range values and missing
+    /// defaults, and the config for settings naming unknown parameters.
+    Check {
+        /// Saved map name or file; the configured map, else the built-in one.
+        name: Option<String>,
+    },
+    /// Show parameters added, removed, renamed or moved to another address
+    /// from one map to another, e.g. between firmware versions.
+    Diff {
+        /// Saved map name or file to compare from.
+        from: String,
+        /// Saved map name or file to compare to.
+        to: String,
+    },
+}
+
+#[derive(clap::ValueEnum, Debug, Clone, Copy)]
+pub enum ExportFormat {
+    Csv,
+    #[value(alias = "md")]
+    Markdown,
+}
+
+/// A CSV field, quoted when it needs to be.
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+/// The map as a table, sorted by category and address.
+fn cheat_sheet(midi_map: &MidiMap, format: ExportFormat) -> String {
+    let mut params = midi_map.get_all_parameters();
+    params.sort_by(|a, b| (&a.category, a.address).cmp(&(&b.category, b.address)));
+    let header = ["Category", "Parameter", "Address", "Range", "Default"];
+    let rows = params.iter().map(|param| {
+        let range = param.range;
+        let default = match param.format.format(param.default) {
+            label if label == param.default.to_string() => label,
+            label => format!("{} ({})", label, param.default),
+        };
+        [
+            param.category.clone(),
+            param.name.clone(),
+            param.address.to_string(),
+            format!("{}-{}", range.min, range.max),
+            default,
+        ]
+    });
+    let mut out = String::new();
+    match format {
+        ExportFormat::Csv => {
+            out.push_str(&header.join(","));
+            out.push('\n');
+            for row in rows {
+                let fields: Vec<_> = row.iter().map(|f| csv_field(f)).collect();
+                out.push_str(&fields.join(","));
+                out.push('\n');
+            }
+        }
+        ExportFormat::Markdown => {
+            out.push_str(&format!("| {} |\n", header.join(" | ")));
+            out.push_str(&format!("|{}\n", "---|".repeat(header.len())));
+            for row in rows {
+                let fields: Vec<_> = row.iter().map(|f| f.replace('|', "\\|")).collect();
+                out.push_str(&format!("| {} |\n", fields.join(" | ")));
+            }
+        }
+    }
+    out
+}
+
+/// `model` picks the built-in map when no custom one is configured.
+pub fn run_map(config: &Config, model: DeviceModel, command: MapCommand) -> Result<()> {
+    match command {
+        MapCommand::Export { format, output } => {
+            let sheet = cheat_sheet(&config.midi_map(model), format);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, sheet)
+                        .with_context(|| format!("Failed to write {}", path.display()))?;
+                    println!("✓ Wrote {}", path.display());
+                }
+                None => print!("{}", sheet),
+            }
+        }
+        MapCommand::Check { name } => {
+            let (label, map) = match name.or_else(|| config.map.clone()) {
+                Some(name) => (format!("map '{}'", name), MapFile::load(&name)?),
+                None => {
+                    let map = MapFile::from_midi_map(&MidiMap::for_device(model));
+                    (format!("built-in {} map", model), map)
+                }
+            };
+            let mut issues = map.check();
+            issues.extend(config.check_map(&map.midi_map()));
+            if issues.is_empty() {
+                println!("✓ No problems in the {}", label);
+                return Ok(());
+            }
+            for issue in &issues {
+                let symbol = match issue.severity {
+                    Severity::Error => "✗",
+                    Severity::Warning => "!",
+                };
+                println!("{} {}", symbol, issue);
+            }
+            let errors = issues.iter().filter(|i| i.severity == Severity::Error).count();
+            if errors > 0 {
+                anyhow::bail!("{} error(s) in the {}", errors, label);
+            }
+            println!("{} warning(s) in the {}", issues.len(), label);
+        }
+        MapCommand::Diff { from, to } => {
+            let changes = MapFile::load(&from)?.diff(&MapFile::load(&to)?);
+            if changes.is_empty() {
+                println!("✓ No differences between {} and {}", from, to);
+            }
+            for change in &changes {
+                println!("{}", change);
+            }
+        }
+    }
+    Ok(())
+}
+
+/// `midi_ctrl profile` subcommands.
+#[cfg(feature = "profile-repo")]
+#[derive(Subcommand, Debug)]
+pub enum ProfileCommand {
+    /// List the profiles the repository offers.
+    List,
+    /// Download a profile, verify its checksum and save it as a map.
+    Install { name: String },
+}
+
+#[cfg(feature = "profile-repo")]
+pub fn run_profile(config: &Config, command: ProfileCommand) -> Result<()> {
+    let url = config.profile_repo.as_deref().ok_or_else(|| {
+        anyhow::anyhow!("No profile repository; set profile_repo in the config to its URL")
+    })?;
+    let mut repo = midi_ctrl::profile_repo::ProfileRepo::open(url)?;
+    match command {
+        ProfileCommand::List => {
+            for profile in repo.index()?.profiles {
+                match profile.description.as_str() {
+                    "" => println!("  {}", profile.name),
+                    description => println!("  {:<20} {}", profile.name, description),
+                }
+            }
+        }
+        ProfileCommand::Install { name } => {
+            let path = repo.install(&name)?;
+            println!("✓ Installed {} to {}", name, path.display());
+            println!("→ Use it with map = \"{}\" in the config", name);
+        }
+    }
+    if repo.offline() {
+        println!("◌ Offline: used the cached copy of {}", url);
+    }
+    Ok(())
+}
+
+/// A single message for `midi_ctrl send`.
+#[derive(Subcommand, Debug)]
+#[command(rename_all = "lower")]
+pub enum SendCommand {
+    /// Control Change
+    Cc { controller: Controller, value: Value7 },
+    /// NRPN (value 0-16383)
+    Nrpn { msb: Value7, lsb: Value7, value: u16 },
+    /// Note On
+    NoteOn { note: Note, velocity: Value7 },
+    /// Note Off
+    NoteOff { note: Note },
+    /// Program Change
+    Pc { program: Value7 },
+    /// Pattern select (Bank Select + PC), e.g. A01
+    Pattern { pattern: Pattern },
+    /// Pitch Bend (-8192..8191, 0 = center)
+    Bend {
+        #[arg(allow_negative_numbers = true)]
+        value: i16,
+    },
+    /// Channel Aftertouch
+    At { value: Value7 },
+    /// Polyphonic Aftertouch
+    PolyAt { note: Note, value: Value7 },
+    /// Transport Start
+    Start,
+    /// Transport Stop
+    Stop,
+    /// Transport Continue
+    Continue,
+    /// Song Position Pointer (16th notes)
+    Spp { beat: u16 },
+}
+
+/// How long a SysEx dump may go quiet before the capture is considered done.
+const SYSEX_IDLE: Duration = Duration::from_millis(500);
+
+/// Incoming SysEx being collected by `sysex recv`.
+#[derive(Default)]
+struct SysexCapture {
+    messages: Vec<Vec<u8>>,
+    last_received: Option<Instant>,
+}
+
+/// Command-line switches for the interactive and JSON modes.
+#[derive(Debug, Default)]
+pub struct Options {
+    pub dry_run: bool,
+    pub listen_fifo: Option<PathBuf>,
+    /// End the session at the first error instead of reporting it and
+    /// reading on.
+    pub strict: bool,
+}
+
+/// Scripts and aliases may run other scripts and aliases, but not
+/// endlessly.
+const MAX_SCRIPT_DEPTH: usize = 8;
+
+/// Command names offered by tab completion.
+const COMMANDS: &[&str] = &[
+    "cc", "nrpn", "noteon", "noteoff", "note", "chord", "pc", "pattern", "bend", "at", "polyat",
+    "set", "find", "chan", "start", "stop", "continue", "spp", "locate", "in", "onbar", "pcmode",
+    "mmc", "protocol", "rstatus", "device", "sysex", "id", "sync", "clock", "bpm", "swing", "tap",
+    "mtc", "port", "ports", "connect", "disconnect", "status", "snap", "resync", "init", "random",
+    "alias", "unalias", "sleep", "run", "load", "dryrun", "seq", "song", "auto", "lfo", "env",
+    "help", "exit",
+];
+
+/// Tab completion for the prompt: command names, parameter names after
+/// `set`, and file names after `run`, `load` and `sysex`.
+struct CliHelper {
+    params: Vec<String>,
+    aliases: Vec<String>,
+    files: FilenameCompleter,
+}
+
+impl Completer for CliHelper {
+    type Candidate = Pair;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        ctx: &rustyline::Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<Pair>)> {
+        let before = &line[..pos];
+        let start = before.rfind(' ').map_or(0, |i| i + 1);
+        let word = &before[start..];
+        let words: Vec<&str> = before[..start].split_whitespace().collect();
+        // Scheduled sends complete like the command they wrap
+        let words = match words.as_slice() {
+            ["in", _, rest @ ..] | ["onbar", rest @ ..] => rest,
+            words => words,
+        };
+
+        let candidates: Vec<&str> = match words {
+            [] => COMMANDS.iter().copied().chain(self.aliases.iter().map(String::as_str)).collect(),
+            ["unalias"] => self.aliases.iter().map(String::as_str).collect(),
+            ["set"] => self.params.iter().map(String::as_str).collect(),
+            ["run"] | ["load"] | ["sysex", _] => return self.files.complete(line, pos, ctx),
+            _ => Vec::new(),
+        };
+        let matches = candidates
+            .into_iter()
+            .filter(|c| c.starts_with(word))
+            .map(|c| Pair { display: c.to_string(), replacement: c.to_string() })
+            .collect();
+        Ok((start, matches))
+    }
+}
+
+impl Hinter for CliHelper {
+    type Hint = String;
+}
+
+impl Highlighter for CliHelper {}
+
+impl Validator for CliHelper {}
+
+impl Helper for CliHelper {}
+
+struct Session {
+    ctrl: MidiController,
+    has_input: bool,
+    capture: Arc<Mutex<Option<SysexCapture>>>,
+    config: Config,
+    /// Device model `midi_map` is for.
+    model: DeviceModel,
+    /// Model named by the last identity reply.
+    identified: Option<DeviceModel>,
+    midi_map: MidiMap,
+    tap_tempo: TapTempo,
+    randomizer: Randomizer,
+    /// Scripts and aliases currently running (nested `run`s).
+    script_depth: usize,
+}
+
+fn parse_u8(arg: Option<&str>, what: &str) -> Result<u8> {
+    let arg = arg.ok_or_else(|| anyhow::anyhow!("Missing {}", what))?;
+    arg.parse::<u8>()
+        .with_context(|| format!("Invalid {} '{}'", what, arg))
+}
+
+/// Parses a validated value such as a [`Channel`] or [`Value7`]; their
+/// own errors already say what was wrong.
+fn parse_arg<T>(arg: Option<&str>, what: &str) -> Result<T>
+where
+    T: std::str::FromStr<Err = anyhow::Error>,
+{
+    arg.ok_or_else(|| anyhow::anyhow!("Missing {}", what))?.parse()
+}
+
+fn parse_u64(arg: &str, what: &str) -> Result<u64> {
+    arg.parse::<u64>()
+        .with_context(|| format!("Invalid {} '{}'", what, arg))
+}
+
+/// Parses `bar`, `bar+N`, `beat` or `beat+N` into (clock ticks per unit,
+/// how many boundaries ahead, unit name).
+fn parse_boundary(spec: &str) -> Result<(u64, u64, &'static str)> {
+    let (unit, name, rest) = if let Some(rest) = spec.strip_prefix("beat") {
+        (PPQN as u64, "beat", rest)
+    } else if let Some(rest) = spec.strip_prefix("bar") {
+        (TICKS_PER_BAR, "bar", rest)
+    } else {
+        anyhow::bail!("Expected bar+N or beat+N, got '{}'", spec);
+    };
+    let count = match rest {
+        "" => 1,
+        _ => rest
+            .strip_prefix('+')
+            .and_then(|n| n.parse::<u64>().ok())
+            .filter(|&n| n > 0)
+            .ok_or_else(|| anyhow::anyhow!("Invalid boundary '{}' (expected e.g. bar+1)", spec))?,
+    };
+    Ok((unit, count, name))
+}
+
+/// Splits a line at whitespace; a `"quoted phrase"` is one word (without
+/// the quotes).
+fn split_words(line: &str) -> Result<Vec<&str>> {
+    let mut words = Vec::new();
+    let mut rest = line.trim_start();
+    while !rest.is_empty() {
+        let word;
+        if let Some(quoted) = rest.strip_prefix('"') {
+            let end = quoted
+                .find('"')
+                .ok_or_else(|| anyhow::anyhow!("Unterminated quote"))?;
+            word = &quoted[..end];
+            rest = &quoted[end + 1..];
+        } else {
+            let end = rest.find(char::is_whitespace).unwrap_or(rest.len());
+            word = &rest[..end];
+            rest = &rest[end..];
+        }
+        words.push(word);
+        rest = rest.trim_start();
+    }
+    Ok(words)
+}
+
+/// Splits a command line into words, taking out a `ch=<n>` word that
+/// overrides the session channel for this one command.
+fn split_channel_override(line: &str) -> Result<(Vec<&str>, Option<Channel>)> {
+    let mut channel = None;
+    let mut words = Vec::new();
+    for word in split_words(line)? {
+        match word.strip_prefix("ch=") {
+            Some(ch) => channel = Some(ch.parse()?),
+            None => words.push(word),
+        }
+    }
+    Ok((words, channel))
+}
+
+/// Builds the messages for a channel-message command, or `None` if `cmd`
+/// is not one. Shared by immediate and scheduled sends.
+fn parse_messages<'a>(
+    cmd: &str,
+    args: &mut impl Iterator<Item = &'a str>,
+    channel: Channel,
+) -> Result<Option<Vec<Message>>> {
+    let messages = match cmd {
+        "cc" => {
+            let controller = parse_arg(args.next(), "controller")?;
+            let value = parse_arg(args.next(), "value")?;
+            vec![Message::ControlChange { channel, controller, value }]
+        }
+        "nrpn" => {
+            let msb = parse_arg(args.next(), "NRPN MSB")?;
+            let lsb = parse_arg(args.next(), "NRPN LSB")?;
+            let value = args.next().ok_or_else(|| anyhow::anyhow!("Missing value"))?;
+            let value = value
+                .parse::<u16>()
+                .with_context(|| format!("Invalid value '{}'", value))?;
+            Message::nrpn(channel, msb, lsb, value)?
+        }
+        "noteon" => {
+            let note = parse_arg::<Note>(args.next(), "note")?.value();
+            let velocity = parse_arg(args.next(), "velocity")?;
+            vec![Message::NoteOn { channel, note, velocity }]
+        }
+        "noteoff" => {
+            let note = parse_arg::<Note>(args.next(), "note")?.value();
+            vec![Message::NoteOff { channel, note, velocity: Value7::default() }]
+        }
+        "pc" => {
+            let program = parse_arg(args.next(), "program")?;
+            vec![Message::ProgramChange { channel, program }]
+        }
+        "pattern" => {
+            let pattern: Pattern = args
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Missing pattern"))?
+                .parse()?;
+            pattern.messages(channel)
+        }
+        "bend" => {
+            let bend = args.next().ok_or_else(|| anyhow::anyhow!("Missing value"))?;
+            let bend = bend
+                .parse::<i16>()
+                .with_context(|| format!("Invalid value '{}'", bend))?;
+            vec![Message::pitch_bend(channel, bend)?]
+        }
+        "at" => {
+            let pressure = parse_arg(args.next(), "value")?;
+            vec![Message::ChannelPressure { channel, pressure }]
+        }
+        "polyat" => {
+            let note = parse_arg::<Note>(args.next(), "note")?.value();
+            let pressure = parse_arg(args.next(), "value")?;
+            vec![Message::PolyPressure { channel, note, pressure }]
+        }
+        _ => return Ok(None),
+    };
+    Ok(Some(messages))
+}
+
+fn spawn_input_printer(events: Receiver<InputEvent>, capture: Arc<Mutex<Option<SysexCapture>>>) {
+    thread::spawn(move || {
+        for event in events {
+            match event.message {
+                Some(Message::SysEx(payload)) => {
+                    let mut capture = capture.lock().unwrap();
+                    if let Some(cap) = capture.as_mut() {
+                        cap.messages.push(payload);
+                        cap.last_received = Some(Instant::now());
+                    } else {
+                        println!("← SysEx ({} bytes)", payload.len());
+                    }
+                }
+                Some(Message::Realtime(_)) | Some(Message::QuarterFrame { .. }) => {}
+                Some(msg) => println!("← {}", msg),
+                None => println!("← {:02X?}", event.bytes),
+            }
+        }
+    });
+}
+
+impl Session {
+    fn send(&mut self, msg: Message) -> Result<()> {
+        self.ctrl.send(&msg)?;
+        println!("→ {}", msg);
+        Ok(())
+    }
+
+    /// Parses the message command following `in`/`onbar`.
+    fn scheduled_messages<'a>(
+        &self,
+        mut args: impl Iterator<Item = &'a str>,
+        channel: Channel,
+    ) -> Result<Vec<Message>> {
+        let cmd = args.next().ok_or_else(|| anyhow::anyhow!("Missing command to schedule"))?;
+        parse_messages(cmd, &mut args, channel)?
+            .ok_or_else(|| anyhow::anyhow!("'{}' cannot be scheduled", cmd))
+    }
+
+    fn mmc<'a>(&mut self, mut args: impl Iterator<Item = &'a str>) -> Result<()> {
+        let cmd = match args.next() {
+            Some("play") => MmcCommand::Play,
+            Some("stop") => MmcCommand::Stop,
+            Some("pause") => MmcCommand::Pause,
+            Some("rec") => MmcCommand::RecordStrobe,
+            Some("punchout") => MmcCommand::RecordExit,
+            Some("ff") => MmcCommand::FastForward,
+            Some("rew") => MmcCommand::Rewind,
+            Some("locate") => {
+                let tc = args.next().ok_or_else(|| anyhow::anyhow!("Missing timecode"))?;
+                MmcCommand::Locate(tc.parse::<Timecode>()?)
+            }
+            Some(other) => anyhow::bail!("Unknown MMC command '{}'", other),
+            None => anyhow::bail!("Usage: mmc <play|stop|pause|rec|punchout|ff|rew|locate>"),
+        };
+        self.ctrl.mmc(cmd)?;
+        println!("→ {}", cmd);
+        Ok(())
+    }
+
+    /// Applies `change` to the profiles of the targeted ports (if given),
+    /// remembers them in the config and prints the result.
+    fn update_profiles(&mut self, change: Option<&dyn Fn(&mut DeviceProfile)>) -> Result<()> {
+        for (idx, name) in self.ctrl.target_ports() {
+            let mut profile = self.ctrl.device_profile(&name);
+            if let Some(change) = change {
+                change(&mut profile);
+                self.ctrl.set_device_profile(&name, profile);
+                self.config.devices.insert(name.clone(), profile);
+            }
+            println!(
+                "  #{}: {} — {}, {:?} (MMC device {}), running status {}",
+                idx,
+                name,
+                self.config.model_for(&name, self.identified),
+                profile.transport,
+                profile.mmc_device_id,
+                if profile.running_status { "on" } else { "off" }
+            );
+        }
+        if change.is_some() {
+            self.config.save()?;
+        }
+        Ok(())
+    }
+
+    /// Shows or changes the transport protocol of the targeted ports.
+    fn protocol<'a>(&mut self, mut args: impl Iterator<Item = &'a str>) -> Result<()> {
+        let protocol = match args.next() {
+            Some("realtime") => Some(TransportProtocol::Realtime),
+            Some("mmc") => Some(TransportProtocol::Mmc),
+            Some("both") => Some(TransportProtocol::Both),
+            Some(other) => anyhow::bail!("Unknown transport protocol '{}'", other),
+            None => None,
+        };
+        let device_id = args.next().map(|id| parse_u8(Some(id), "device ID")).transpose()?;
+        match protocol {
+            Some(protocol) => self.update_profiles(Some(&|profile: &mut DeviceProfile| {
+                profile.transport = protocol;
+                if let Some(id) = device_id {
+                    profile.mmc_device_id = id;
+                }
+            })),
+            None => self.update_profiles(None),
+        }
+    }
+
+    /// Shows or changes the device model of the targeted ports. A new model
+    /// brings its transport protocol along; `auto` goes back to recognizing
+    /// the device.
+    fn device<'a>(&mut self, mut args: impl Iterator<Item = &'a str>) -> Result<()> {
+        match args.next() {
+            None => return self.update_profiles(None),
+            Some("auto") => self.update_profiles(Some(&|profile: &mut DeviceProfile| {
+                profile.model = None;
+            }))?,
+            Some(model) => {
+                let model: DeviceModel = model.parse()?;
+                self.update_profiles(Some(&|profile: &mut DeviceProfile| {
+                    profile.model = Some(model);
+                    profile.transport = model.default_profile().transport;
+                }))?;
+            }
+        }
+        self.use_device_map();
+        Ok(())
+    }
+
+    /// The `seq` command. Edits reach the sequencer straight away and are
+    /// saved to the config.
+    fn sequence(&mut self, args: &[&str], channel: Channel) -> Result<()> {
+        let tracks = &mut self.config.sequence;
+        match args {
+            [] => {
+                let state = if self.ctrl.sequencer_enabled() { "on" } else { "off" };
+                println!("Sequencer {}", state);
+                for (number, track) in tracks.iter().enumerate() {
+                    let steps: String =
+                        track.steps.iter().map(|s| if s.on { 'x' } else { '.' }).collect();
+                    let mute = if track.mute { " (muted)" } else { "" };
+                    let division = match track.division {
+                        1 => String::new(),
+                        n => format!(" ÷{}", n),
+                    };
+                    let channel = track.channel;
+                    println!("  {} ch {:>2}  {}{}{}", number + 1, channel, steps, division, mute);
+                }
+                return Ok(());
+            }
+            [state @ ("on" | "off")] => {
+                self.ctrl.set_sequencer_enabled(*state == "on");
+                println!("✓ Sequencer {}", state);
+                return Ok(());
+            }
+            ["add"] => {
+                tracks.push(SeqTrack::new(channel));
+                println!("✓ Added track {} on ch {}", tracks.len(), channel);
+            }
+            [track, edit @ ..] => {
+                let number = parse_u64(track, "track")? as usize;
+                let index = number.checked_sub(1).filter(|i| *i < tracks.len());
+                let index = index.ok_or_else(|| anyhow::anyhow!("No track {}", number))?;
+                let track = &mut tracks[index];
+                match edit {
+                    ["length", steps] => track.set_length(parse_u64(steps, "length")? as usize),
+                    ["division", division] => {
+                        let division = parse_u64(division, "division")?;
+                        track.division = division.clamp(1, MAX_DIVISION as u64) as u8;
+                    }
+                    ["mute"] => track.mute = true,
+                    ["unmute"] => track.mute = false,
+                    ["clear"] => track.steps.iter_mut().for_each(|step| step.on = false),
+                    ["remove"] => {
+                        tracks.remove(index);
+                    }
+                    [step, rest @ ..] if !rest.is_empty() && rest.len() <= 3 => {
+                        let number = parse_u64(step, "step")? as usize;
+                        let step = number
+                            .checked_sub(1)
+                            .and_then(|i| track.steps.get_mut(i))
+                            .ok_or_else(|| anyhow::anyhow!("No step {}", number))?;
+                        match rest {
+                            ["off"] => step.on = false,
+                            ["lock", name, value] => {
+                                let Some(param) = self.midi_map.get_by_name(name) else {
+                                    anyhow::bail!("Unknown parameter '{}'", name);
+                                };
+                                let value = match param.format.value_of(value) {
+                                    Some(value) => value,
+                                    None => parse_arg::<Value7>(Some(value), "value")?.get(),
+                                };
+                                let value = param.range.clamp(value);
+                                step.set_lock(&param.name, param.address, value);
+                            }
+                            ["unlock"] => step.locks.clear(),
+                            ["unlock", name] => {
+                                // Locks keep the full name; `name` may be a slug
+                                let param = self.midi_map.get_by_name(name);
+                                let name = param.map_or(name.to_string(), |p| p.name);
+                                step.locks.retain(|lock| lock.parameter != name);
+                            }
+                            [note, more @ ..] => {
+                                step.note = note.parse()?;
+                                if let Some(velocity) = more.first() {
+                                    step.velocity = velocity.parse()?;
+                                }
+                                if let Some(ticks) = more.get(1) {
+                                    step.length = parse_u64(ticks, "length")?.clamp(1, 255) as u8;
+                                }
+                                step.on = true;
+                            }
+                            [] => {}
+                        }
+                    }
+                    _ => anyhow::bail!("Usage: seq <track> <step> <note> [velocity] [ticks]"),
+                }
+                println!("✓ Updated the sequence");
+            }
+        }
+        self.load_sequence();
+        self.config.save()
+    }
+
+    /// The `song` command: shows, edits or plays the song arrangement.
+    fn song(&mut self, args: &[&str], channel: Channel) -> Result<()> {
+        let song = &mut self.config.song;
+        match args {
+            [] => {
+                let state = if self.ctrl.song_enabled() { "on" } else { "off" };
+                let looped = if song.looped { ", looped" } else { "" };
+                println!("Song {} ({} bar(s){})", state, song.length(), looped);
+                for (number, entry) in song.entries.iter().enumerate() {
+                    let snapshot = entry.snapshot.as_deref().map(|s| format!(", snapshot '{}'", s));
+                    println!(
+                        "  {} {}  {} bar(s) x{}{}",
+                        number + 1,
+                        entry.pattern,
+                        entry.bars,
+                        entry.repeat,
+                        snapshot.unwrap_or_default()
+                    );
+                }
+                return Ok(());
+            }
+            [state @ ("on" | "off")] => {
+                self.ctrl.set_song_enabled(*state == "on");
+                println!("✓ Song {}", state);
+                return Ok(());
+            }
+            ["loop", state @ ("on" | "off")] => song.looped = *state == "on",
+            ["add", pattern, rest @ ..] if rest.len() <= 3 => {
+                let mut entry = SongEntry::new(pattern.parse()?);
+                if let Some(bars) = rest.first() {
+                    entry.bars = parse_u64(bars, "bars")?.clamp(1, u32::MAX as u64) as u32;
+                }
+                if let Some(repeat) = rest.get(1) {
+                    entry.repeat = parse_u64(repeat, "repeat")?.clamp(1, u32::MAX as u64) as u32;
+                }
+                if let Some(name) = rest.get(2) {
+                    Snapshot::load(name)?;
+                    entry.snapshot = Some(name.to_string());
+                }
+                song.entries.push(entry);
+            }
+            ["remove", number] => {
+                let number = parse_u64(number, "entry")? as usize;
+                let index = number.checked_sub(1).filter(|i| *i < song.entries.len());
+                let index = index.ok_or_else(|| anyhow::anyhow!("No entry {}", number))?;
+                song.entries.remove(index);
+            }
+            ["clear"] => song.entries.clear(),
+            _ => anyhow::bail!("Usage: song add <pattern> [bars] [repeat] [snapshot]"),
+        }
+        println!("✓ Updated the song");
+        self.ctrl.set_song(&self.config.song, channel)?;
+        self.config.save()
+    }
+
+    /// The `auto` command: shows, edits or plays the automation lanes.
+    fn automation(&mut self, args: &[&str]) -> Result<()> {
+        let lanes = &mut self.config.automation.lanes;
+        match args {
+            [] => {
+                let state = if self.ctrl.automation_enabled() { "on" } else { "off" };
+                println!("Automation {} ({} lane(s))", state, lanes.len());
+                for (number, lane) in lanes.iter().enumerate() {
+                    let mute = if lane.mute { ", muted" } else { "" };
+                    let missing = if lane.address.is_none() { ", not in the map" } else { "" };
+                    println!(
+                        "  {} {} (ch {})  {} bar(s), {} point(s){}{}",
+                        number + 1,
+                        lane.parameter,
+                        lane.channel,
+                        lane.bars,
+                        lane.points.len(),
+                        mute,
+                        missing
+                    );
+                }
+                return Ok(());
+            }
+            [state @ ("on" | "off")] => {
+                self.ctrl.set_automation_enabled(*state == "on");
+                println!("✓ Automation {}", state);
+                return Ok(());
+            }
+            ["clear"] => lanes.clear(),
+            [number, rest @ ..] => {
+                let number = parse_u64(number, "lane")? as usize;
+                let index = number.checked_sub(1).filter(|i| *i < lanes.len());
+                let index = index.ok_or_else(|| anyhow::anyhow!("No lane {}", number))?;
+                match rest {
+                    ["mute"] => lanes[index].mute = true,
+                    ["unmute"] => lanes[index].mute = false,
+                    ["remove"] => {
+                        lanes.remove(index);
+                    }
+                    ["bars", bars] => {
+                        let bars = parse_u64(bars, "bars")?;
+                        if !(1..=MAX_AUTOMATION_BARS as u64).contains(&bars) {
+                            anyhow::bail!("Loop of {} bars out of range (1-16)", bars);
+                        }
+                        lanes[index].bars = bars as u32;
+                    }
+                    _ => anyhow::bail!("Usage: auto <lane> mute|unmute|remove|bars <n>"),
+                }
+            }
+        }
+        println!("✓ Updated the automation");
+        self.load_automation();
+        self.config.save()
+    }
+
+    /// The `lfo` command: shows or edits the software LFOs.
+    fn lfo(&mut self, args: &[&str], channel: Channel) -> Result<()> {
+        let lfos = &mut self.config.lfos;
+        match args {
+            [] => {
+                if lfos.is_empty() {
+                    println!("No LFOs ('lfo add <parameter>' to add one)");
+                }
+                for (number, lfo) in lfos.iter().enumerate() {
+                    let rate = match lfo.sync {
+                        Some(length) => length.label().to_string(),
+                        None => format!("{} Hz", lfo.hz),
+                    };
+                    println!(
+                        "  {} {} {} (ch {})  {}, {}, depth {}, offset {}",
+                        number + 1,
+                        if lfo.enabled { "on " } else { "off" },
+                        lfo.parameter,
+                        lfo.channel,
+                        lfo.shape.label(),
+                        rate,
+                        lfo.depth,
+                        lfo.offset
+                    );
+                }
+                return Ok(());
+            }
+            ["add"] => anyhow::bail!("Usage: lfo add <parameter>"),
+            ["add", name @ ..] => {
+                if lfos.len() >= MAX_LFOS {
+                    anyhow::bail!("All {} LFOs are in use", MAX_LFOS);
+                }
+                let name = name.join(" ");
+                let param = self
+                    .midi_map
+                    .get_by_name(&name)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown parameter '{}'", name))?;
+                lfos.push(Lfo { enabled: true, channel, parameter: param.name, ..Lfo::default() });
+            }
+            [number, rest @ ..] => {
+                let number = parse_u64(number, "LFO")? as usize;
+                let index = number.checked_sub(1).filter(|i| *i < lfos.len());
+                let index = index.ok_or_else(|| anyhow::anyhow!("No LFO {}", number))?;
+                let lfo = &mut lfos[index];
+                match rest {
+                    ["on"] => lfo.enabled = true,
+                    ["off"] => lfo.enabled = false,
+                    ["remove"] => {
+                        lfos.remove(index);
+                    }
+                    ["shape", shape] => {
+                        lfo.shape = match shape.to_ascii_lowercase().as_str() {
+                            "sine" => LfoShape::Sine,
+                            "triangle" | "tri" => LfoShape::Triangle,
+                            "saw" => LfoShape::Saw,
+                            "square" => LfoShape::Square,
+                            "sample_hold" | "s&h" | "sh" => LfoShape::SampleHold,
+                            _ => anyhow::bail!("Unknown shape '{}'", shape),
+                        };
+                    }
+                    ["rate", rate] => {
+                        let length = LfoLength::ALL
+                            .into_iter()
+                            .find(|l| l.label().replace(' ', "").eq_ignore_ascii_case(rate));
+                        if let Some(length) = length {
+                            lfo.sync = Some(length);
+                        } else {
+                            let hz = rate.trim_end_matches("Hz").trim_end_matches("hz");
+                            let hz: f32 =
+                                hz.parse().map_err(|_| anyhow::anyhow!("Invalid rate '{}'", rate))?;
+                            if !(MIN_HZ..=MAX_HZ).contains(&hz) {
+                                let range = format!("{}-{}", MIN_HZ, MAX_HZ);
+                                anyhow::bail!("Rate {} Hz out of range ({})", hz, range);
+                            }
+                            (lfo.sync, lfo.hz) = (None, hz);
+                        }
+                    }
+                    ["depth", value] => {
+                        lfo.depth = parse_arg::<Value7>(Some(value), "depth")?.get();
+                    }
+                    ["offset", value] => {
+                        lfo.offset = parse_arg::<Value7>(Some(value), "offset")?.get();
+                    }
+                    _ => anyhow::bail!("Usage: lfo <n> on|off|remove|shape|rate|depth|offset"),
+                }
+            }
+        }
+        println!("✓ Updated the LFOs");
+        self.load_lfos();
+        self.config.save()
+    }
+
+    /// The `env` command: shows or edits the envelope generators.
+    fn envelope(&mut self, args: &[&str], channel: Channel) -> Result<()> {
+        let envelopes = &mut self.config.envelopes;
+        match args {
+            [] => {
+                if envelopes.is_empty() {
+                    println!("No envelopes ('env add <parameter>' to add one)");
+                }
+                for (number, env) in envelopes.iter().enumerate() {
+                    println!(
+                        "  {} {} {} (ch {})  on {}, ADSR {}/{}/{}%/{} {}, {}→{}",
+                        number + 1,
+                        if env.enabled { "on " } else { "off" },
+                        env.parameter,
+                        env.channel,
+                        env.trigger,
+                        env.attack,
+                        env.decay,
+                        env.sustain,
+                        env.release,
+                        if env.sync { "beats" } else { "s" },
+                        env.from,
+                        env.peak
+                    );
+                }
+                return Ok(());
+            }
+            ["add"] => anyhow::bail!("Usage: env add <parameter>"),
+            ["add", name @ ..] => {
+                if envelopes.len() >= MAX_ENVELOPES {
+                    anyhow::bail!("All {} envelopes are in use", MAX_ENVELOPES);
+                }
+                let name = name.join(" ");
+                let param = self
+                    .midi_map
+                    .get_by_name(&name)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown parameter '{}'", name))?;
+                let (enabled, parameter) = (true, param.name);
+                envelopes.push(Envelope { enabled, channel, parameter, ..Envelope::default() });
+            }
+            [number, rest @ ..] => {
+                let number = parse_u64(number, "envelope")? as usize;
+                let index = number.checked_sub(1).filter(|i| *i < envelopes.len());
+                let index = index.ok_or_else(|| anyhow::anyhow!("No envelope {}", number))?;
+                let env = &mut envelopes[index];
+                let time = |arg: &str| match arg.parse::<f32>() {
+                    Ok(time) if time >= 0.0 => Ok(time),
+                    _ => Err(anyhow::anyhow!("Invalid time '{}'", arg)),
+                };
+                match rest {
+                    ["on"] => env.enabled = true,
+                    ["off"] => env.enabled = false,
+                    ["remove"] => {
+                        envelopes.remove(index);
+                    }
+                    ["trigger", "start"] => env.trigger = EnvelopeTrigger::Start,
+                    ["trigger", "step", track, step] => {
+                        let track = parse_u64(track, "track")? as usize;
+                        let step = parse_u64(step, "step")? as usize;
+                        if track == 0 || step == 0 {
+                            anyhow::bail!("Tracks and steps are numbered from 1");
+                        }
+                        env.trigger = EnvelopeTrigger::Step { track, step };
+                    }
+                    ["trigger", "note", rest @ ..] if rest.len() <= 2 => {
+                        let note = match rest.first() {
+                            None | Some(&"any") => None,
+                            Some(note) => Some(parse_arg(Some(note), "note")?),
+                        };
+                        let channel = match rest.get(1) {
+                            Some(channel) => Some(parse_arg(Some(channel), "channel")?),
+                            None => None,
+                        };
+                        env.trigger = EnvelopeTrigger::Note { note, channel };
+                    }
+                    ["adsr", attack, decay, sustain, release] => {
+                        let sustain = parse_u64(sustain, "sustain")?;
+                        if sustain > 100 {
+                            anyhow::bail!("Sustain {}% out of range (0-100)", sustain);
+                        }
+                        env.attack = time(attack)?;
+                        env.decay = time(decay)?;
+                        env.sustain = sustain as u8;
+                        env.release = time(release)?;
+                    }
+                    ["time", "beats"] => env.sync = true,
+                    ["time", "secs"] => env.sync = false,
+                    ["range", from, peak] => {
+                        env.from = parse_arg::<Value7>(Some(from), "value")?.get();
+                        env.peak = parse_arg::<Value7>(Some(peak), "value")?.get();
+                    }
+                    _ => anyhow::bail!("Usage: env <n> on|off|remove|trigger|adsr|time|range"),
+                }
+            }
+        }
+        println!("✓ Updated the envelopes");
+        self.load_envelopes();
+        self.config.save()
+    }
+
+    /// The `random` command: randomizes parameters on `channel`, or
+    /// undoes the last randomization.
+    fn randomize(&mut self, args: &[&str], channel: Channel) -> Result<()> {
+        if args == ["undo"] {
+            let undo = self.randomizer.undo().ok_or_else(|| anyhow::anyhow!("Nothing to undo"))?;
+            // Back on the channel randomized, whichever is current now
+            for &(address, value) in &undo.values {
+                self.ctrl.send_param(undo.channel, address, Value7::new(value)?)?;
+            }
+            println!("→ Restored {} parameter(s) on ch {}", undo.values.len(), undo.channel);
+            return Ok(());
+        }
+        let amount = args.first().and_then(|arg| arg.trim_end_matches('%').parse::<u8>().ok());
+        let (amount, category) = match amount {
+            Some(amount) => (amount, args[1..].join(" ")),
+            None => (DEFAULT_AMOUNT, args.join(" ")),
+        };
+        if amount > 100 {
+            anyhow::bail!("Amount {}% out of range (0-100)", amount);
+        }
+        let category = Some(category.as_str()).filter(|c| !c.is_empty());
+        let params = self.midi_map.get_all_parameters();
+        if let Some(category) = category
+            && !params.iter().any(|p| p.category.eq_ignore_ascii_case(category))
+        {
+            anyhow::bail!("Unknown category '{}'", category);
+        }
+        let ctrl = &self.ctrl;
+        let current = |address| ctrl.param_value(channel, address).map(Value7::get);
+        let changes =
+            self.randomizer.randomize(&self.midi_map, category, amount, None, channel, current);
+        for &(address, value) in &changes {
+            self.ctrl.send_param(channel, address, Value7::new(value)?)?;
+        }
+        let scope = category.unwrap_or("all parameters");
+        println!("→ Randomized {} parameter(s) in {} by {}%", changes.len(), scope, amount);
+        Ok(())
+    }
+
+    /// Velocity and length (ms) of a played note: as given, else the first
+    /// targeted port's profile defaults.
+    fn note_settings(&self, velocity: Option<&str>, ms: Option<&str>) -> Result<(Value7, u64)> {
+        let profile = match self.ctrl.target_ports().first() {
+            Some((_, name)) => self.ctrl.device_profile(name),
+            None => self.config.default_model().default_profile(),
+        };
+        let velocity = match velocity {
+            Some(_) => parse_arg(velocity, "velocity")?,
+            None => profile.velocity,
+        };
+        let ms = match ms {
+            Some(ms) => parse_u64(ms, "duration")?,
+            None => profile.note_length,
+        };
+        Ok((velocity, ms))
+    }
+
+    /// Moves a macro's targets to where the macro at `position` puts them.
+    fn set_macro(&mut self, control: &MacroControl, position: u8, channel: Channel) -> Result<()> {
+        let values = control.values(position, &self.midi_map);
+        if values.is_empty() {
+            anyhow::bail!("Macro '{}' has no parameters in the current map", control.name);
+        }
+        let mut sent = Vec::new();
+        for (address, value) in values {
+            self.ctrl.send_param(channel, address, Value7::new(value)?)?;
+            sent.push(format!("{} {}", self.midi_map.get_address_name(address), value));
+        }
+        println!("→ {} = {} ({})", control.name, position, sent.join(", "));
+        Ok(())
+    }
+
+    /// Switches parameter names to the model of the first targeted port,
+    /// set for the port or recognized.
+    fn use_device_map(&mut self) {
+        let model = match self.ctrl.target_ports().first() {
+            Some((_, name)) => self.config.model_for(name, self.identified),
+            None => self.config.default_model(),
+        };
+        if model != self.model {
+            self.model = model;
+            self.midi_map = self.config.midi_map(model);
+            println!("→ Using the {} parameter map ('device <model>' to change)", model);
+            self.load_sequence();
+            self.load_automation();
+            self.load_lfos();
+            self.load_envelopes();
+        }
+    }
+
+    /// Hands the config's sequence to the sequencer, its parameter locks
+    /// looked up in the current map.
+    fn load_sequence(&mut self) {
+        let mut tracks = self.config.sequence.clone();
+        tracks.iter_mut().for_each(|track| track.resolve_locks(&self.midi_map));
+        self.ctrl.set_sequence(tracks);
+    }
+
+    /// Hands the config's automation to the player, its parameters looked
+    /// up in the current map.
+    fn load_automation(&mut self) {
+        self.config.automation.resolve(&self.midi_map);
+        self.ctrl.set_automation(self.config.automation.lanes.clone());
+    }
+
+    /// Hands the config's LFOs to the controller, their parameters looked
+    /// up in the current map.
+    fn load_lfos(&mut self) {
+        self.config.lfos.iter_mut().for_each(|lfo| lfo.resolve(&self.midi_map));
+        self.ctrl.set_lfos(self.config.lfos.clone());
+    }
+
+    /// Hands the config's envelopes to the controller, their parameters
+    /// looked up in the current map.
+    fn load_envelopes(&mut self) {
+        self.config.envelopes.iter_mut().for_each(|env| env.resolve(&self.midi_map));
+        self.ctrl.set_envelopes(self.config.envelopes.clone());
+    }
+
+    /// Remembers the model an identity reply names and follows it.
+    fn identified(&mut self, identity: &DeviceIdentity) {
+        self.identified = DeviceModel::from_identity(identity);
+        self.use_device_map();
+    }
+
+    fn sysex<'a>(&mut self, mut args: impl Iterator<Item = &'a str>) -> Result<()> {
+        let usage = "Usage: sysex send <file> [delay_ms] | sysex recv <file> [timeout_s]";
+        let (Some(action), Some(file)) = (args.next(), args.next()) else {
+            anyhow::bail!(usage);
+        };
+        let path = Path::new(file);
+
+        match action {
+            "send" => {
+                if let Some(ms) = args.next() {
+                    self.ctrl
+                        .set_sysex_delay(Duration::from_millis(parse_u64(ms, "delay")?));
+                }
+                let sent = self.ctrl.send_sysex_file(path)?;
+                println!("→ Sent {} SysEx packet(s) from {}", sent, path.display());
+            }
+            "recv" => {
+                if !self.has_input {
+                    anyhow::bail!("No input port open; restart with --input <index>");
+                }
+                let timeout = match args.next() {
+                    Some(s) => Duration::from_secs(parse_u64(s, "timeout")?),
+                    None => Duration::from_secs(30),
+                };
+                let messages = self.capture_sysex(timeout)?;
+                sysex::write_file(path, &messages)?;
+                println!("✓ Saved {} SysEx message(s) to {}", messages.len(), path.display());
+            }
+            _ => anyhow::bail!(usage),
+        }
+        Ok(())
+    }
+
+    /// Blocks until a dump has arrived and gone quiet, or `timeout` passes.
+    fn capture_sysex(&mut self, timeout: Duration) -> Result<Vec<Vec<u8>>> {
+        *self.capture.lock().unwrap() = Some(SysexCapture::default());
+        println!("Waiting for SysEx (timeout {}s)...", timeout.as_secs());
+
+        let started = Instant::now();
+        loop {
+            thread::sleep(Duration::from_millis(50));
+            let mut capture = self.capture.lock().unwrap();
+            let done = match capture.as_ref().and_then(|c| c.last_received) {
+                Some(last) => last.elapsed() >= SYSEX_IDLE,
+                None => started.elapsed() >= timeout,
+            };
+            if done {
+                let messages = capture.take().map(|c| c.messages).unwrap_or_default();
+                if messages.is_empty() {
+                    anyhow::bail!("No SysEx received within {}s", timeout.as_secs());
+                }
+                return Ok(messages);
+            }
+        }
+    }
+
+    /// Remembers the open ports for next start.
+    fn save_last_ports(&mut self) {
+        self.config.last_ports = self.ctrl.ports().into_iter().map(|(_, name)| name).collect();
+        if let Err(e) = self.config.save() {
+            eprintln!("✗ Failed to save config: {:#}", e);
+        }
+    }
+
+    fn list_ports(&self) -> Result<()> {
+        let open = self.ctrl.ports();
+        println!("MIDI output ports:");
+        for (i, name) in output_port_names()?.iter().enumerate() {
+            let mark = if open.iter().any(|(idx, _)| *idx == i) { "*" } else { " " };
+            println!(" {}#{}: {}", mark, i, name);
+        }
+        println!("MIDI input ports:");
+        let input = self.ctrl.input_port_name();
+        for (i, name) in input_port_names()?.iter().enumerate() {
+            let mark = if input == Some(name.as_str()) { "*" } else { " " };
+            println!(" {}#{}: {}", mark, i, name);
+        }
+        Ok(())
+    }
+
+    fn status(&self) {
+        let target = self.ctrl.target();
+        let ports = self.ctrl.ports();
+        if ports.is_empty() {
+            println!("Outputs:    none");
+        }
+        for (i, (idx, name)) in ports.iter().enumerate() {
+            let routed = target == PortTarget::All || target == PortTarget::Port(*idx);
+            println!(
+                "{} #{}: {}{}",
+                if i == 0 { "Outputs:   " } else { "           " },
+                idx,
+                name,
+                if routed { "" } else { " (not targeted)" }
+            );
+        }
+        println!("Input:      {}", self.ctrl.input_port_name().unwrap_or("none"));
+        if let Some(id) = self.ctrl.device_identity() {
+            println!("Device:     {}", id);
+        }
+        println!("Channel:    {}", self.ctrl.channel());
+        println!(
+            "Clock:      {:?}, {} at {:.1} BPM{}",
+            self.ctrl.clock_source(),
+            if self.ctrl.clock_running() { "running" } else { "stopped" },
+            self.ctrl.bpm(),
+            if self.ctrl.free_clock() { " (continuous)" } else { "" }
+        );
+        let transport = self.ctrl.transport();
+        println!(
+            "Transport:  {} at {}",
+            if transport.is_running() { "playing" } else { "stopped" },
+            transport.position()
+        );
+        match self.ctrl.mtc_rate() {
+            Some(rate) => println!("MTC:        {} fps", rate),
+            None => println!("MTC:        off"),
+        }
+        let notes: Vec<String> = self
+            .ctrl
+            .held_notes()
+            .into_iter()
+            .map(|(channel, note)| format!("{} (ch {})", Note::from(note), channel))
+            .collect();
+        println!(
+            "Held notes: {}",
+            if notes.is_empty() { "none".to_string() } else { notes.join(", ") }
+        );
+        println!("Scheduled:  {} pending send(s)", self.ctrl.pending_sends());
+    }
+
+    /// Runs a script file. Returns `Ok(false)` if it ran `exit`.
+    fn run_script(&mut self, path: &Path) -> Result<bool> {
+        if self.script_depth >= MAX_SCRIPT_DEPTH {
+            anyhow::bail!("Scripts nested more than {} deep", MAX_SCRIPT_DEPTH);
+        }
+        let source = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let steps = script::parse(&source).with_context(|| path.display().to_string())?;
+
+        self.script_depth += 1;
+        let result = self.run_steps(&steps);
+        self.script_depth -= 1;
+        result.with_context(|| path.display().to_string())
+    }
+
+    /// Runs an alias's commands. A `ch=` override applies to each.
+    fn run_alias(&mut self, name: &str, args: &[&str], channel: Option<Channel>) -> Result<bool> {
+        if self.script_depth >= MAX_SCRIPT_DEPTH {
+            anyhow::bail!("Aliases nested more than {} deep", MAX_SCRIPT_DEPTH);
+        }
+        let body = self.config.aliases.get(name).cloned().unwrap_or_default();
+        let commands = script::expand_alias(&body, args).with_context(|| name.to_string())?;
+
+        self.script_depth += 1;
+        let mut result = Ok(true);
+        for command in commands {
+            let command = match channel {
+                Some(ch) => format!("{} ch={}", command, ch),
+                None => command,
+            };
+            result = self.execute(&command).with_context(|| name.to_string());
+            if !matches!(result, Ok(true)) {
+                break;
+            }
+        }
+        self.script_depth -= 1;
+        result
+    }
+
+    /// `alias` lists, `alias name = commands` defines and saves.
+    fn alias(&mut self, definition: &str) -> Result<()> {
+        if definition.is_empty() {
+            for (name, body) in &self.config.aliases {
+                println!("  {} = {}", name, body);
+            }
+            return Ok(());
+        }
+        let Some((name, body)) = definition.split_once('=') else {
+            anyhow::bail!("Usage: alias <name> = <command>; <command> ...");
+        };
+        let (name, body) = (name.trim(), body.trim());
+        if name.is_empty() || name.contains(char::is_whitespace) {
+            anyhow::bail!("Alias name must be a single word");
+        }
+        if COMMANDS.contains(&name) {
+            anyhow::bail!("'{}' is a built-in command", name);
+        }
+        if body.is_empty() {
+            anyhow::bail!("Alias '{}' has no commands", name);
+        }
+        self.config.aliases.insert(name.to_string(), body.to_string());
+        self.config.save()?;
+        println!("✓ {} = {}", name, body);
+        Ok(())
+    }
+
+    fn run_steps(&mut self, steps: &[Step]) -> Result<bool> {
+        for step in steps {
+            match step {
+                Step::Command { line, text } => {
+                    println!("> {}", text);
+                    if !self.execute(text).with_context(|| format!("line {}", line))? {
+                        return Ok(false);
+                    }
+                }
+                Step::Repeat { count, body } => {
+                    for _ in 0..*count {
+                        if !self.run_steps(body)? {
+                            return Ok(false);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(true)
+    }
+
+    /// Runs one command line. Returns `Ok(false)` when the loop should exit.
+    fn execute(&mut self, line: &str) -> Result<bool> {
+        // The body of a definition is kept verbatim, ch= included
+        if let Some(definition) = line.strip_prefix("alias")
+            && (definition.is_empty() || definition.starts_with(char::is_whitespace))
+        {
+            self.alias(definition.trim())?;
+            return Ok(true);
+        }
+
+        let (words, channel_override) = split_channel_override(line)?;
+        let mut args = words.into_iter().peekable();
+        let Some(cmd) = args.next() else {
+            return Ok(true);
+        };
+        let channel = channel_override.unwrap_or(self.ctrl.channel());
+
+        // `at bar+N`/`at beat+N` quantizes; `at <value>` is aftertouch
+        if cmd == "at"
+            && let Some(spec) = args.next_if(|w| w.starts_with("bar") || w.starts_with("beat"))
+        {
+            let (unit, count, name) = parse_boundary(spec)?;
+            let messages = self.scheduled_messages(args, channel)?;
+            let at = self
+                .ctrl
+                .boundary_at(unit, count)
+                .ok_or_else(|| anyhow::anyhow!("Transport is stopped; no {} to wait for", name))?;
+            self.ctrl.schedule_at(at, &messages)?;
+            println!("⏲ Queued {} message(s) for {}+{}", messages.len(), name, count);
+            return Ok(true);
+        }
+
+        if let Some(messages) = parse_messages(cmd, &mut args, channel)? {
+            let change = self.config.pattern_change;
+            let bars = self.config.pattern_bars.unwrap_or(1);
+            if matches!(cmd, "pc" | "pattern")
+                && let Some(at) = self.ctrl.pattern_change_at(change, bars)
+            {
+                self.ctrl.schedule_at(at, &messages)?;
+                println!("⏲ Queued {} message(s) for the {}", messages.len(), change.label());
+                return Ok(true);
+            }
+            for msg in messages {
+                self.send(msg)?;
+            }
+            return Ok(true);
+        }
+
+        match cmd {
+            "note" => {
+                let note = parse_arg::<Note>(args.next(), "note")?;
+                let (velocity, ms) = self.note_settings(args.next(), args.next())?;
+                self.ctrl
+                    .play_notes(channel, &[note.value()], velocity, Duration::from_millis(ms))?;
+                println!("→ {} vel {} for {} ms (ch {})", note, velocity, ms, channel);
+            }
+            "chord" => {
+                let chord = parse_arg::<Chord>(args.next(), "chord")?;
+                let (velocity, ms) = self.note_settings(args.next(), args.next())?;
+                let notes = chord.notes()?;
+                self.ctrl.play_notes(channel, &notes, velocity, Duration::from_millis(ms))?;
+                let names: Vec<String> = notes.iter().map(|&n| Note::from(n).to_string()).collect();
+                let names = names.join(" ");
+                println!("→ {} ({}) vel {} for {} ms (ch {})", chord, names, velocity, ms, channel);
+            }
+            "find" => {
+                let text = args.collect::<Vec<_>>().join(" ");
+                let params = self.midi_map.search(&text);
+                if params.is_empty() {
+                    anyhow::bail!("No parameter matching '{}'", text);
+                }
+                let values = self.ctrl.cc_values();
+                for param in params {
+                    let value = param
+                        .cc()
+                        .and_then(|cc| {
+                            values.iter().find(|(ch, other, _)| *ch == channel && other.get() == cc)
+                        })
+                        .map_or("-".to_string(), |(_, _, value)| value.to_string());
+                    println!(
+                        "  {:<10} {:<28} {:>3}  {}  ({})",
+                        param.address.to_string(),
+                        param.name,
+                        value,
+                        param.slug(),
+                        param.category
+                    );
+                }
+            }
+            "chan" => {
+                if let Some(arg) = args.next() {
+                    self.ctrl.set_channel(arg.parse()?);
+                }
+                println!("✓ Channel {}", self.ctrl.channel());
+            }
+            "set" => {
+                let name = args.next().ok_or_else(|| anyhow::anyhow!("Missing parameter"))?;
+                // Macros go by name too, where no parameter has it
+                let control = match self.midi_map.get_by_name(name) {
+                    Some(_) => None,
+                    None => self.config.find_macro(name).cloned(),
+                };
+                if let Some(control) = control {
+                    let position = parse_arg::<Value7>(args.next(), "value")?.get();
+                    self.set_macro(&control, position, channel)?;
+                    return Ok(true);
+                }
+                let param = self
+                    .midi_map
+                    .get_by_name(name)
+                    .ok_or_else(|| anyhow::anyhow!("Unknown parameter '{}'", name))?;
+                // Named settings, e.g. `set filter-type bandpass`
+                let raw = args.next();
+                let value = match raw.and_then(|label| param.format.value_of(label)) {
+                    Some(value) => value,
+                    None => parse_arg::<Value7>(raw, "value")?.get(),
+                };
+                let value = Value7::new(param.range.clamp(value))?;
+                self.ctrl.send_param(channel, param.address, value)?;
+                println!("→ {} = {}", param.name, value);
+            }
+            "in" => {
+                let ms = parse_u64(
+                    args.next().ok_or_else(|| anyhow::anyhow!("Missing delay"))?,
+                    "delay",
+                )?;
+                let messages = self.scheduled_messages(args, channel)?;
+                self.ctrl.schedule_in(Duration::from_millis(ms), &messages)?;
+                println!("⏲ Queued {} message(s) in {} ms", messages.len(), ms);
+            }
+            "onbar" => {
+                let messages = self.scheduled_messages(args, channel)?;
+                self.ctrl.schedule_next_bar(&messages)?;
+                println!("⏲ Queued {} message(s) for the next bar", messages.len());
+            }
+            "pcmode" => {
+                if let Some(arg) = args.next() {
+                    self.config.pattern_change = match arg {
+                        "now" => PatternChange::Now,
+                        "bar" => PatternChange::NextBar,
+                        "end" => PatternChange::PatternEnd,
+                        other => {
+                            anyhow::bail!("Unknown pattern change '{}' (now, bar, end)", other)
+                        }
+                    };
+                    if let Some(bars) = args.next() {
+                        let bars = parse_u64(bars, "bars")?;
+                        if !(1..=64).contains(&bars) {
+                            anyhow::bail!("Pattern length {} out of range (1-64 bars)", bars);
+                        }
+                        self.config.pattern_bars = Some(bars as u32);
+                    }
+                    self.config.save()?;
+                }
+                match self.config.pattern_change {
+                    PatternChange::Now => println!("✓ Pattern changes at once"),
+                    PatternChange::NextBar => println!("✓ Pattern changes on the next bar"),
+                    PatternChange::PatternEnd => println!(
+                        "✓ Pattern changes on the pattern end ({} bar(s))",
+                        self.config.pattern_bars.unwrap_or(1)
+                    ),
+                }
+            }
+            "start" => {
+                self.ctrl.start()?;
+                println!("► Start");
+            }
+            "stop" => {
+                self.ctrl.stop()?;
+                println!("⏹ Stop");
+            }
+            "continue" => {
+                self.ctrl.resume()?;
+                println!("→ Continue");
+            }
+            "spp" => {
+                let beat = args.next().ok_or_else(|| anyhow::anyhow!("Missing beat"))?;
+                let beat = beat
+                    .parse::<u16>()
+                    .with_context(|| format!("Invalid beat '{}'", beat))?;
+                self.ctrl.song_position(beat)?;
+                println!("→ Song Position {} ({})", beat, self.ctrl.transport().position());
+            }
+            "locate" => {
+                let bar = parse_u64(
+                    args.next().ok_or_else(|| anyhow::anyhow!("Missing bar"))?,
+                    "bar",
+                )?;
+                self.ctrl.continue_from_bar(bar)?;
+                println!("→ Continue from bar {}", bar);
+            }
+            "mmc" => self.mmc(args)?,
+            "protocol" => self.protocol(args)?,
+            "device" => self.device(args)?,
+            "rstatus" => match args.next() {
+                Some("on") => {
+                    self.update_profiles(Some(&|p: &mut DeviceProfile| p.running_status = true))?
+                }
+                Some("off") => {
+                    self.update_profiles(Some(&|p: &mut DeviceProfile| p.running_status = false))?
+                }
+                Some(other) => anyhow::bail!("Expected on or off, got '{}'", other),
+                None => self.update_profiles(None)?,
+            },
+            "sysex" => self.sysex(args)?,
+            "id" => {
+                let id = self.ctrl.identify(Duration::from_millis(1000))?;
+                println!("✓ {}", id);
+                self.identified(&id);
+            }
+            "sync" => {
+                match args.next() {
+                    Some("internal") => self.ctrl.set_clock_source(ClockSource::Internal)?,
+                    Some("external") => self.ctrl.set_clock_source(ClockSource::External)?,
+                    Some(other) => anyhow::bail!("Unknown clock source '{}'", other),
+                    None => {}
+                }
+                let transport = self.ctrl.transport();
+                println!(
+                    "⏱ {:?} clock, {:.1} BPM, {}",
+                    self.ctrl.clock_source(),
+                    self.ctrl.bpm(),
+                    if transport.is_running() { "running" } else { "stopped" }
+                );
+            }
+            "clock" => {
+                match args.next() {
+                    Some("on") => self.ctrl.set_free_clock(true)?,
+                    Some("off") => self.ctrl.set_free_clock(false)?,
+                    Some(other) => anyhow::bail!("Expected on or off, got '{}'", other),
+                    None => {}
+                }
+                println!(
+                    "⏱ Clock {} at {:.1} BPM{}",
+                    if self.ctrl.clock_running() { "running" } else { "stopped" },
+                    self.ctrl.bpm(),
+                    if self.ctrl.free_clock() { " (continuous)" } else { "" }
+                );
+            }
+            "bpm" => {
+                if let Some(arg) = args.next() {
+                    let bpm = arg
+                        .parse::<f32>()
+                        .with_context(|| format!("Invalid BPM '{}'", arg))?;
+                    if !(20.0..=300.0).contains(&bpm) {
+                        anyhow::bail!("BPM {} out of range (20-300)", bpm);
+                    }
+                    self.ctrl.set_bpm(bpm);
+                }
+                println!("⏱ {:.1} BPM", self.ctrl.bpm());
+            }
+            "swing" => {
+                if let Some(arg) = args.next() {
+                    let swing = parse_u64(arg.trim_end_matches('%'), "swing")?;
+                    if !(MIN_SWING as u64..=MAX_SWING as u64).contains(&swing) {
+                        anyhow::bail!("Swing {}% out of range (50-80)", swing);
+                    }
+                    self.ctrl.set_swing(swing as u8);
+                }
+                println!("⏱ Swing {}%", self.ctrl.swing());
+            }
+            "tap" => {
+                if self.ctrl.clock_source() == ClockSource::External {
+                    anyhow::bail!("Following an external clock");
+                }
+                match self.tap_tempo.tap(Instant::now()) {
+                    Some(bpm) => {
+                        self.ctrl.set_bpm(bpm);
+                        println!("⏱ {:.1} BPM ({} taps)", bpm, self.tap_tempo.taps());
+                    }
+                    None => println!("⏱ Tap again in time"),
+                }
+            }
+            "mtc" => {
+                match args.next() {
+                    Some("off") => self.ctrl.set_mtc_rate(None),
+                    Some(rate) => self.ctrl.set_mtc_rate(Some(rate.parse::<FrameRate>()?)),
+                    None => {}
+                }
+                match self.ctrl.mtc_rate() {
+                    Some(rate) => println!("⏱ MTC at {} fps", rate),
+                    None => println!("⏱ MTC off"),
+                }
+            }
+            "port" => {
+                let target = match args.next() {
+                    Some("all") => PortTarget::All,
+                    Some(idx) => PortTarget::Port(
+                        idx.parse().with_context(|| format!("Invalid port '{}'", idx))?,
+                    ),
+                    None => {
+                        for (idx, name) in self.ctrl.ports() {
+                            println!("  #{}: {}", idx, name);
+                        }
+                        println!("Sending to: {:?}", self.ctrl.target());
+                        return Ok(true);
+                    }
+                };
+                if let PortTarget::Port(idx) = target
+                    && self.ctrl.port_name(idx).is_none()
+                {
+                    anyhow::bail!("Port {} is not open", idx);
+                }
+                self.ctrl.set_target(target);
+                self.use_device_map();
+                println!("✓ Sending to {:?}", target);
+            }
+            "ports" => self.list_ports()?,
+            "status" => self.status(),
+            "dryrun" => {
+                match args.next() {
+                    Some("on") => self.ctrl.set_dry_run(Some(dry_run_sink())),
+                    Some("off") => self.ctrl.set_dry_run(None),
+                    Some(other) => anyhow::bail!("Expected on or off, got '{}'", other),
+                    None => {}
+                }
+                println!("Dry run {}", if self.ctrl.dry_run() { "on" } else { "off" });
+            }
+            "seq" => self.sequence(&args.collect::<Vec<_>>(), channel)?,
+            "song" => self.song(&args.collect::<Vec<_>>(), channel)?,
+            "auto" => self.automation(&args.collect::<Vec<_>>())?,
+            "lfo" => self.lfo(&args.collect::<Vec<_>>(), channel)?,
+            "env" => self.envelope(&args.collect::<Vec<_>>(), channel)?,
+            "snap" => match (args.next(), args.next()) {
+                (Some("save"), Some(name)) => {
+                    let snapshot = Snapshot::from_values(&self.ctrl.cc_values());
+                    snapshot.save(name)?;
+                    println!("✓ Saved {} controller value(s) to '{}'", snapshot.cc.len(), name);
+                }
+                (Some("load"), Some(name)) => {
+                    let snapshot = Snapshot::load(name)?;
+                    self.ctrl.send_batch(&snapshot.messages())?;
+                    println!("→ Sent {} controller value(s) from '{}'", snapshot.cc.len(), name);
+                }
+                (Some("list"), None) => {
+                    for name in Snapshot::list()? {
+                        println!("  {}", name);
+                    }
+                }
+                _ => anyhow::bail!("Usage: snap save <name> | snap load <name> | snap list"),
+            },
+            "resync" => {
+                let params: Vec<_> = self
+                    .ctrl
+                    .cc_values()
+                    .into_iter()
+                    .map(|(channel, controller, value)| {
+                        (channel, ParamAddress::Cc(controller.get()), value)
+                    })
+                    .collect();
+                let duration = self.ctrl.sync_params(&params)?;
+                println!("→ Resending {} controller value(s) over {:?}", params.len(), duration);
+            }
+            "init" => {
+                let port_name = self.ctrl.target_ports().first().map(|(_, name)| name.clone());
+                match args.next() {
+                    None => {
+                        let patch = self.config.init_patch(port_name.as_deref(), &self.midi_map);
+                        for &(address, value) in &patch {
+                            self.ctrl.send_param(channel, address, Value7::new(value)?)?;
+                        }
+                        let count = patch.len();
+                        println!(
+                            "→ Sent the init patch ({} parameters) on ch {}",
+                            count, channel
+                        );
+                    }
+                    Some("save") => {
+                        let port_name =
+                            port_name.ok_or_else(|| anyhow::anyhow!("No output port open"))?;
+                        // Only CC values are tracked
+                        let values: Vec<_> = self
+                            .ctrl
+                            .cc_values()
+                            .into_iter()
+                            .filter(|&(ch, _, _)| ch == channel)
+                            .map(|(_, controller, value)| {
+                                (ParamAddress::Cc(controller.get()), value.get())
+                            })
+                            .collect();
+                        self.config.set_init_patch(&port_name, &self.midi_map, values);
+                        self.config.save()?;
+                        let count = self.config.init.get(&port_name).map_or(0, |p| p.len());
+                        println!(
+                            "✓ Saved {} changed value(s) as the init for {}",
+                            count, port_name
+                        );
+                    }
+                    Some(_) => anyhow::bail!("Usage: init [save]"),
+                }
+            }
+            "random" => self.randomize(&args.collect::<Vec<_>>(), channel)?,
+            "connect" => {
+                let arg = args.next().ok_or_else(|| anyhow::anyhow!("Missing port"))?;
+                let port = match arg.parse::<usize>() {
+                    Ok(idx) => idx,
+                    Err(_) => find_output_port(arg)?
+                        .ok_or_else(|| anyhow::anyhow!("No MIDI output port matching '{}'", arg))?,
+                };
+                self.ctrl.connect(port).map_err(strict::port_error)?;
+                println!(
+                    "✓ Connected to {} (#{})",
+                    self.ctrl.port_name(port).unwrap_or_default(),
+                    port
+                );
+                self.save_last_ports();
+                self.use_device_map();
+            }
+            "disconnect" => {
+                match args.next() {
+                    None | Some("all") => {
+                        self.ctrl.disconnect();
+                        println!("✓ Disconnected all ports");
+                    }
+                    Some(idx) => {
+                        let idx = idx
+                            .parse::<usize>()
+                            .with_context(|| format!("Invalid port '{}'", idx))?;
+                        let name = self
+                            .ctrl
+                            .port_name(idx)
+                            .ok_or_else(|| anyhow::anyhow!("Port {} is not open", idx))?;
+                        self.ctrl.disconnect_port(idx);
+                        println!("✓ Disconnected {} (#{})", name, idx);
+                    }
+                }
+                self.save_last_ports();
+            }
+            "sleep" => {
+                let ms = parse_u64(
+                    args.next().ok_or_else(|| anyhow::anyhow!("Missing duration"))?,
+                    "duration",
+                )?;
+                thread::sleep(Duration::from_millis(ms));
+            }
+            "load" => {
+                let file = args.next().ok_or_else(|| anyhow::anyhow!("Missing file"))?;
+                let source = std::fs::read_to_string(file)
+                    .with_context(|| format!("Failed to read {}", file))?;
+                let events =
+                    script::parse_events(&source, channel).with_context(|| file.to_string())?;
+                let start = Instant::now();
+                for (offset, message) in &events {
+                    self.ctrl.schedule_at(start + *offset, std::slice::from_ref(message))?;
+                }
+                let total = events.last().map_or(Duration::ZERO, |(offset, _)| *offset);
+                println!("⏲ Queued {} event(s) over {} ms", events.len(), total.as_millis());
+            }
+            "run" => {
+                let file = args.next().ok_or_else(|| anyhow::anyhow!("Missing file"))?;
+                return self.run_script(Path::new(file));
+            }
+            "help" => println!("{}", HELP),
+            "exit" | "quit" => return Ok(false),
+            "unalias" => {
+                let name = args.next().ok_or_else(|| anyhow::anyhow!("Missing alias name"))?;
+                if self.config.aliases.remove(name).is_none() {
+                    anyhow::bail!("No alias named '{}'", name);
+                }
+                self.config.save()?;
+                println!("✓ Removed alias {}", name);
+            }
+            name if self.config.aliases.contains_key(name) => {
+                let args: Vec<&str> = args.collect();
+                return self.run_alias(name, &args, channel_override);
+            }
+            other => anyhow::bail!("Unknown command '{}' (try 'help')", other),
+        }
+        Ok(true)
+    }
+}
+
+/// Prints each would-be send as the decoded message and its bytes.
+fn dry_run_sink() -> DryRunSink {
+    Arc::new(|port: &str, bytes: &[u8]| match Message::decode(bytes) {
+        Some(message) => println!("◌ {}: {} {:02X?}", port, message, bytes),
+        None => println!("◌ {}: {:02X?}", port, bytes),
+    })
+}
+
+/// A controller with one stand-in output, for dry runs without a device.
+fn dry_run_controller(channel: Channel) -> Result<MidiController> {
+    let backend = Arc::new(MockBackend::new(&["Dry run"], &[]));
+    let mut ctrl = MidiController::with_backend(channel, backend);
+    ctrl.set_dry_run(Some(dry_run_sink()));
+    ctrl.connect(0)?;
+    Ok(ctrl)
+}
+
+/// A command line for the dispatcher, from the prompt or the pipe.
+enum CliInput {
+    Prompt(String),
+    Fifo(String),
+    Eof,
+}
+
+/// Runs the prompt on its own thread so piped commands can run while it
+/// waits for typing. Each prompt is shown only after the main loop sends
+/// the current alias names (for completion) over `next`.
+fn spawn_prompt(
+    mut editor: Editor<CliHelper, DefaultHistory>,
+    history: Option<PathBuf>,
+    tx: Sender<CliInput>,
+    next: Receiver<Vec<String>>,
+) {
+    thread::spawn(move || {
+        while let Ok(aliases) = next.recv() {
+            if let Some(helper) = editor.helper_mut() {
+                helper.aliases = aliases;
+            }
+            let line = match editor.readline("> ") {
+                Ok(line) => line,
+                Err(ReadlineError::Interrupted) | Err(ReadlineError::Eof) => break,
+                Err(e) => {
+                    eprintln!("✗ {}", e);
+                    break;
+                }
+            };
+            if !line.trim().is_empty() {
+                let _ = editor.add_history_entry(line.as_str());
+                // Saved as we go, since nothing waits for this thread
+                // at exit
+                if let Some(path) = &history {
+                    let saved = path
+                        .parent()
+                        .map_or(Ok(()), std::fs::create_dir_all)
+                        .and_then(|_| editor.save_history(path).map_err(std::io::Error::other));
+                    if let Err(e) = saved {
+                        eprintln!("✗ Failed to save history: {}", e);
+                    }
+                }
+            }
+            if tx.send(CliInput::Prompt(line)).is_err() {
+                return;
+            }
+        }
+        let _ = tx.send(CliInput::Eof);
+    });
+}
+
+/// Fails with the list of available ports when none were selected.
+pub fn require_ports(ports: &[usize]) -> Result<()> {
+    if ports.is_empty() {
+        eprintln!("Available MIDI output ports:");
+        for (i, name) in output_port_names()?.iter().enumerate() {
+            eprintln!("  #{}: {}", i, name);
+        }
+        eprintln!("Available MIDI input ports:");
+        for (i, name) in input_port_names()?.iter().enumerate() {
+            eprintln!("  #{}: {}", i, name);
+        }
+        let message = "No port selected; pass --port <index> or --port-name <name>";
+        return Err(NoPort(message.to_string()).into());
+    }
+    Ok(())
+}
+
+/// Opens the ports, sends one message and exits. Nothing is tracked or
+/// cleaned up afterwards, so a Note On stays on and Start leaves the
+/// device running on its own clock.
+pub fn run_send(
+    ports: Vec<usize>,
+    channel: Channel,
+    config: Config,
+    command: SendCommand,
+    dry_run: bool,
+) -> Result<()> {
+    let mut ctrl = if dry_run && ports.is_empty() {
+        dry_run_controller(channel)?
+    } else {
+        require_ports(&ports)?;
+        let mut ctrl = MidiController::new(channel);
+        for (name, profile) in &config.port_profiles(&output_port_names()?) {
+            ctrl.set_device_profile(name, *profile);
+        }
+        ctrl.set_default_profile(config.default_model().default_profile());
+        if dry_run {
+            ctrl.set_dry_run(Some(dry_run_sink()));
+        }
+        for port in ports {
+            ctrl.connect(port).map_err(strict::port_error)?;
+        }
+        ctrl
+    };
+
+    let messages = match command {
+        SendCommand::Cc { controller, value } => {
+            vec![Message::ControlChange { channel, controller, value }]
+        }
+        SendCommand::Nrpn { msb, lsb, value } => Message::nrpn(channel, msb, lsb, value)?,
+        SendCommand::NoteOn { note, velocity } => {
+            vec![Message::NoteOn { channel, note: note.value(), velocity }]
+        }
+        SendCommand::NoteOff { note } => {
+            vec![Message::NoteOff { channel, note: note.value(), velocity: Value7::default() }]
+        }
+        SendCommand::Pc { program } => vec![Message::ProgramChange { channel, program }],
+        SendCommand::Pattern { pattern } => pattern.messages(channel),
+        SendCommand::Bend { value } => vec![Message::pitch_bend(channel, value)?],
+        SendCommand::At { value } => vec![Message::ChannelPressure { channel, pressure: value }],
+        SendCommand::PolyAt { note, value } => {
+            vec![Message::PolyPressure { channel, note: note.value(), pressure: value }]
+        }
+        SendCommand::Start => {
+            ctrl.send_transport(Message::Realtime(Realtime::Start), MmcCommand::Play)?;
+            Vec::new()
+        }
+        SendCommand::Stop => {
+            ctrl.send_transport(Message::Realtime(Realtime::Stop), MmcCommand::Stop)?;
+            Vec::new()
+        }
+        SendCommand::Continue => {
+            ctrl.send_transport(Message::Realtime(Realtime::Continue), MmcCommand::Play)?;
+            Vec::new()
+        }
+        SendCommand::Spp { beat } => vec![Message::SongPosition(beat)],
+    };
+    if !messages.is_empty() {
+        ctrl.send_batch(&messages)?;
+    }
+    ctrl.disconnect();
+    Ok(())
+}
+
+pub fn run_cli(
+    ports: Vec<usize>,
+    input: Option<usize>,
+    channel: Channel,
+    config: Config,
+    options: &Options,
+) -> Result<()> {
+    let dry_run = options.dry_run;
+    let mut session = Session {
+        ctrl: if dry_run && ports.is_empty() {
+            dry_run_controller(channel)?
+        } else {
+            MidiController::new(channel)
+        },
+        has_input: false,
+        capture: Arc::new(Mutex::new(None)),
+        model: config.default_model(),
+        identified: None,
+        midi_map: config.midi_map(config.default_model()),
+        config,
+        tap_tempo: TapTempo::default(),
+        randomizer: Randomizer::default(),
+        script_depth: 0,
+    };
+    for (name, profile) in &session.config.port_profiles(&output_port_names()?) {
+        session.ctrl.set_device_profile(name, *profile);
+    }
+    session.ctrl.set_default_profile(session.config.default_model().default_profile());
+    session.load_sequence();
+    session.load_automation();
+    session.load_lfos();
+    session.load_envelopes();
+    if let Err(e) = session.ctrl.set_song(&session.config.song, channel) {
+        eprintln!("✗ Failed to load the song: {:#}", e);
+    }
+    if dry_run {
+        session.ctrl.set_dry_run(Some(dry_run_sink()));
+        println!("Dry run: messages are printed, not sent.");
+    }
+    if ports.is_empty() && !dry_run {
+        if options.strict {
+            require_ports(&ports)?;
+        }
+        session.list_ports()?;
+        println!("No port open; use 'connect <index|name>'.");
+    }
+    for &port in &ports {
+        session.ctrl.connect(port).map_err(strict::port_error)?;
+        println!(
+            "✓ Connected to {} (#{}), channel {}",
+            session.ctrl.port_name(port).unwrap_or_default(),
+            port,
+            channel
+        );
+    }
+    if !ports.is_empty() {
+        session.save_last_ports();
+        session.use_device_map();
+    }
+    if let Some(input) = input {
+        let events = session.ctrl.connect_input(input)?;
+        session.has_input = true;
+        println!(
+            "✓ Listening on {} (#{})",
+            session.ctrl.input_port_name().unwrap_or("<unknown>"),
+            input
+        );
+        spawn_input_printer(events, session.capture.clone());
+        match session.ctrl.identify(Duration::from_millis(1000)) {
+            Ok(id) => {
+                println!("✓ Device: {}", id);
+                session.identified(&id);
+            }
+            Err(e) => eprintln!("✗ {}", e),
+        }
+    }
+    println!("Type 'help' for commands.");
+
+    let mut editor: Editor<CliHelper, DefaultHistory> =
+        Editor::new().context("Failed to set up the terminal")?;
+    editor.set_helper(Some(CliHelper {
+        params: session
+            .midi_map
+            .get_all_parameters()
+            .iter()
+            .flat_map(|p| std::iter::once(p.slug()).chain(p.alias_slugs()))
+            .chain(session.config.macro_controls.iter().map(MacroControl::slug))
+            .collect(),
+        aliases: Vec::new(),
+        files: FilenameCompleter::new(),
+    }));
+    let history = Config::dir().map(|d| d.join("history"));
+    if let Some(path) = &history {
+        // Missing on first run
+        let _ = editor.load_history(path);
+    }
+
+    // The prompt and the pipe feed one channel, so both go through the
+    // same dispatcher one command at a time
+    let (input_tx, input_rx) = mpsc::channel();
+    if let Some(path) = &options.listen_fifo {
+        fifo::listen_fifo(path, input_tx.clone(), CliInput::Fifo)?;
+        println!("✓ Listening for commands on {}", path.display());
+    }
+    let (prompt_tx, prompt_rx) = mpsc::channel();
+    spawn_prompt(editor, history, input_tx, prompt_rx);
+
+    // The prompt handles Ctrl+C itself; this catches it while a command
+    // is running
+    let interrupted = Arc::new(AtomicBool::new(false));
+    let flag = interrupted.clone();
+    ctrlc::set_handler(move || flag.store(true, Ordering::Relaxed))
+        .context("Failed to install Ctrl+C handler")?;
+
+    let _ = prompt_tx.send(session.config.aliases.keys().cloned().collect());
+    // With --strict, the error that ended the session
+    let mut failure = None;
+    while let Ok(input) = input_rx.recv() {
+        let (line, from_prompt) = match input {
+            CliInput::Prompt(line) => (line, true),
+            CliInput::Fifo(line) => {
+                println!("◂ {}", line.trim());
+                (line, false)
+            }
+            CliInput::Eof => break,
+        };
+        // Pick up unplugged/replugged devices before running the command
+        match session.ctrl.check_ports() {
+            Ok(events) => {
+                for event in events {
+                    match event {
+                        PortEvent::Lost(name) if options.strict => {
+                            failure = Some(NoPort(format!("Lost port {}", name)).into());
+                        }
+                        PortEvent::Lost(name) => eprintln!("✗ Lost port {}", name),
+                        PortEvent::Reconnected { name, port } => {
+                            println!("✓ Reconnected {} (#{})", name, port)
+                        }
+                    }
+                }
+            }
+            Err(e) => eprintln!("✗ Failed to scan MIDI ports: {}", e),
+        }
+        if failure.is_some() {
+            break;
+        }
+        match session.execute(line.trim()) {
+            Ok(true) => {}
+            Ok(false) if from_prompt => break,
+            // The prompt thread is mid-read and would leave the terminal
+            // in raw mode if the session ended under it
+            Ok(false) => eprintln!("✗ 'exit' is only accepted at the prompt"),
+            Err(e) if options.strict => {
+                failure = Some(e);
+                break;
+            }
+            Err(e) => eprintln!("✗ {:#}", e),
+        }
+        if interrupted.load(Ordering::Relaxed) {
+            break;
+        }
+        // Only now show the next prompt, so command output comes first
+        if from_prompt {
+            let _ = prompt_tx.send(session.config.aliases.keys().cloned().collect());
+        }
+    }
+
+    println!("Shutting down…");
+    if let Err(e) = session.ctrl.shutdown() {
+        eprintln!("✗ {}", e);
+    }
+    failure.map_or(Ok(()), Err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(line: &str) -> Result<Option<Vec<Message>>> {
+        let mut words = line.split_whitespace();
+        let cmd = words.next().unwrap_or_default();
+        parse_messages(cmd, &mut words, Channel::new(2)?)
+    }
+
+    fn encoded(line: &str) -> Vec<u8> {
+        let messages = parse(line).unwrap().unwrap();
+        Message::encode_all(&messages, false).unwrap()
+    }
+
+    #[test]
+    fn channel_messages() {
+        assert_eq!(encoded("cc 74 100"), [0xB1, 74, 100]);
+        assert_eq!(encoded("noteon C4 90"), [0x91, 60, 90]);
+        assert_eq!(encoded("noteon 61 90"), [0x91, 61, 90]);
+        assert_eq!(encoded("noteoff F#3"), [0x81, 54, 0]);
+        assert_eq!(encoded("pc 5"), [0xC1, 5]);
+        assert_eq!(encoded("bend -8192"), [0xE1, 0, 0]);
+        assert_eq!(encoded("bend 0"), [0xE1, 0, 64]);
+        assert_eq!(encoded("at 7"), [0xD1, 7]);
+        assert_eq!(encoded("polyat A0 3"), [0xA1, 21, 3]);
+        assert_eq!(encoded("nrpn 1 2 300"), [0xB1, 99, 1, 0xB1, 98, 2, 0xB1, 6, 2, 0xB1, 38, 44]);
+        assert_eq!(encoded("pattern B02"), [0xB1, 0, 0, 0xC1, 17]);
+    }
+
+    #[test]
+    fn other_commands_are_not_messages() {
+        assert!(parse("start").unwrap().is_none());
+        assert!(parse("set Filter 3").unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_bad_arguments() {
+        for line in [
+            "cc 128 1",
+            "cc 1 128",
+            "cc 1",
+            "noteon H4 100",
+            "noteon C10 100",
+            "pc",
+            "bend 8192",
+            "nrpn 1 2 16384",
+            "pattern A00",
+        ] {
+            assert!(parse(line).is_err(), "'{}' parsed", line);
+        }
+    }
+}
@@ -0,0 +1,215 @@
+use crate::scheduler::{JobId, SchedulerHandle};
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{debug, trace, warn};
+
+/// MIDI clock resolution: 24 pulses per quarter note.
+pub const PPQN: u32 = 24;
+
+/// Swing range in percent: 50 is straight, 66 about triplet feel.
+pub const MIN_SWING: u8 = 50;
+pub const MAX_SWING: u8 = 80;
+
+pub fn tick_period(bpm: f32) -> Duration {
+    Duration::from_secs_f64(60.0 / (bpm.max(1.0) as f64 * PPQN as f64))
+}
+
+/// Time from pulse `pulse` (counted from a downbeat) to the next with
+/// `swing` percent: the first 16th of each 8th stretches to that share of
+/// it and the off-beat 16th shrinks to make up, so beats stay on time.
+fn swung_period(bpm: f32, swing: u8, pulse: u64) -> Duration {
+    let sixteenth = PPQN as u64 / 4;
+    let swing = swing.clamp(MIN_SWING, MAX_SWING) as f64;
+    let share = if pulse % (sixteenth * 2) < sixteenth { swing } else { 100.0 - swing };
+    tick_period(bpm).mul_f64(share / 50.0)
+}
+
+/// How often the tick job reports its timing: one line per interval
+/// instead of one per pulse.
+const TIMING_REPORT_EVERY: Duration = Duration::from_secs(1);
+
+/// Tick lateness gathered between timing reports.
+#[derive(Default)]
+struct TickTiming {
+    window_start: Option<Instant>,
+    ticks: u32,
+    worst_lag: Duration,
+}
+
+impl TickTiming {
+    fn record(&mut self, due: Instant, now: Instant, period: Duration) {
+        self.ticks += 1;
+        self.worst_lag = self.worst_lag.max(now.saturating_duration_since(due));
+        let start = *self.window_start.get_or_insert(now);
+        if now.duration_since(start) < TIMING_REPORT_EVERY {
+            return;
+        }
+        let worst_lag_us = self.worst_lag.as_micros() as u64;
+        if self.worst_lag > period / 2 {
+            warn!(target: "clock", ticks = self.ticks, worst_lag_us, "Clock ticks running late");
+        } else {
+            trace!(target: "clock", ticks = self.ticks, worst_lag_us, "Clock timing");
+        }
+        *self = Self { window_start: Some(now), ..Self::default() };
+    }
+}
+
+/// Continuous 24 PPQN clock generator, run as a repeating job on the
+/// [`Scheduler`](crate::scheduler::Scheduler).
+///
+/// Ticks are scheduled against absolute deadlines (`start + n * period`),
+/// so jitter does not accumulate into tempo drift. Tempo and swing changes
+/// take effect from the next tick.
+pub struct Clock {
+    /// Tempo as `f32` bits so the tick job can read it lock-free.
+    bpm: Arc<AtomicU32>,
+    /// Swing in percent, see [`MIN_SWING`].
+    swing: Arc<AtomicU8>,
+    running: Arc<AtomicBool>,
+    scheduler: SchedulerHandle,
+    job: Option<JobId>,
+}
+
+impl Clock {
+    pub fn new(bpm: f32, scheduler: SchedulerHandle) -> Self {
+        Self {
+            bpm: Arc::new(AtomicU32::new(bpm.to_bits())),
+            swing: Arc::new(AtomicU8::new(MIN_SWING)),
+            running: Arc::new(AtomicBool::new(false)),
+            scheduler,
+            job: None,
+        }
+    }
+
+    pub fn bpm(&self) -> f32 {
+        f32::from_bits(self.bpm.load(Ordering::Relaxed))
+    }
+
+    /// The tempo as the tick job reads it, for other jobs to follow.
+    pub fn shared_bpm(&self) -> Arc<AtomicU32> {
+        self.bpm.clone()
+    }
+
+    pub fn set_bpm(&self, bpm: f32) {
+        debug!(target: "clock", bpm, "Tempo set");
+        self.bpm.store(bpm.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn swing(&self) -> u8 {
+        self.swing.load(Ordering::Relaxed)
+    }
+
+    /// Sets the swing, kept within 50-80%.
+    pub fn set_swing(&self, swing: u8) {
+        let swing = swing.clamp(MIN_SWING, MAX_SWING);
+        debug!(target: "clock", swing, "Swing set");
+        self.swing.store(swing, Ordering::Relaxed);
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    /// Starts calling `tick` every clock pulse until [`stop`](Self::stop),
+    /// the first being pulse `first_pulse` from the start of the song (for
+    /// swing). Restarts the clock if it is already running.
+    pub fn start<F>(&mut self, first_pulse: u64, mut tick: F)
+    where
+        F: FnMut() + Send + 'static,
+    {
+        self.stop();
+        self.running.store(true, Ordering::Relaxed);
+
+        let (bpm, swing) = (self.bpm.clone(), self.swing.clone());
+        let mut pulse = first_pulse;
+        let mut timing = TickTiming::default();
+        debug!(target: "clock", bpm = self.bpm(), "Clock started");
+        self.job = Some(self.scheduler.repeating(Instant::now(), move |due| {
+            let fired = Instant::now();
+            tick();
+            let bpm = f32::from_bits(bpm.load(Ordering::Relaxed));
+            let period = swung_period(bpm, swing.load(Ordering::Relaxed), pulse);
+            pulse += 1;
+            timing.record(due, fired, period);
+            let next = due + period;
+            // After a long stall (suspend, debugger) resync rather than
+            // firing a burst of catch-up ticks.
+            let now = Instant::now();
+            if now > next + period {
+                let stalled_ms = (now - next).as_millis() as u64;
+                warn!(target: "clock", stalled_ms, "Clock resynced");
+                Some(now)
+            } else {
+                Some(next)
+            }
+        }));
+    }
+
+    /// Stops the clock; no tick is sent after this returns.
+    pub fn stop(&mut self) {
+        self.running.store(false, Ordering::Relaxed);
+        if let Some(job) = self.job.take() {
+            self.scheduler.cancel(job);
+            debug!(target: "clock", "Clock stopped");
+        }
+    }
+}
+
+impl Drop for Clock {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Tempo from taps on a button or key, averaged over the last few beats.
+#[derive(Debug, Default)]
+pub struct TapTempo {
+    last_tap: Option<Instant>,
+    intervals: Vec<Duration>,
+}
+
+impl TapTempo {
+    /// A pause this long starts a new tap sequence.
+    const RESET_AFTER: Duration = Duration::from_secs(2);
+    /// Intervals averaged over.
+    const MAX_INTERVALS: usize = 8;
+    /// Intervals further than this fraction from the median are ignored.
+    const OUTLIER: f64 = 0.25;
+
+    /// Records a tap at `now`; returns the tempo once there are two taps.
+    pub fn tap(&mut self, now: Instant) -> Option<f32> {
+        let last = self.last_tap.replace(now);
+        let interval = now.saturating_duration_since(last?);
+        if interval > Self::RESET_AFTER {
+            self.intervals.clear();
+            return None;
+        }
+        if self.intervals.len() == Self::MAX_INTERVALS {
+            self.intervals.remove(0);
+        }
+        self.intervals.push(interval);
+        self.bpm()
+    }
+
+    /// Averaged tempo, ignoring mistimed taps.
+    pub fn bpm(&self) -> Option<f32> {
+        let mut sorted: Vec<f64> = self.intervals.iter().map(Duration::as_secs_f64).collect();
+        sorted.sort_by(f64::total_cmp);
+        let median = *sorted.get(sorted.len() / 2)?;
+        let kept: Vec<f64> = sorted
+            .into_iter()
+            .filter(|i| (i - median).abs() <= median * Self::OUTLIER)
+            .collect();
+        let mean = kept.iter().sum::<f64>() / kept.len() as f64;
+        (mean > 0.0).then(|| (60.0 / mean).clamp(20.0, 300.0) as f32)
+    }
+
+    /// Taps so far in the current sequence.
+    pub fn taps(&self) -> usize {
+        match self.last_tap {
+            Some(_) => self.intervals.len() + 1,
+            None => 0,
+        }
+    }
+}
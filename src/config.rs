@@ -0,0 +1,482 @@
+use crate::automation::Automation;
+use crate::device::DeviceModel;
+use crate::envelope::Envelope;
+use crate::harmony::ChordMode;
+use crate::lfo::Lfo;
+use crate::macro_control::MacroControl;
+use crate::map_file::{MapFile, MapIssue};
+use crate::midi_map::{MidiMap, ParamAddress, ParamRange};
+use crate::midi_track::MidiTrack;
+use crate::mmc::{TransportProtocol, ALL_DEVICES};
+use crate::schema::{self, CONFIG_MIGRATIONS, CONFIG_VERSION};
+use crate::sequencer::SeqTrack;
+use crate::song::Song;
+use crate::note::Note;
+use crate::note_repeat::NoteRepeat;
+use crate::pattern::PatternChange;
+use crate::recorder::Recording;
+use crate::takeover::Binding;
+use crate::types::Value7;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::PathBuf;
+use tracing::warn;
+
+/// User settings persisted between runs in `<config dir>/midi_ctrl/config.toml`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    /// Names of the output ports used last time. Names are stable across
+    /// reboots where port indices are not.
+    pub last_ports: Vec<String>,
+    /// Device model assumed for ports without one in their profile that
+    /// are not recognized by name or identity reply.
+    pub device: DeviceModel,
+    /// Model from `--device`, used for ports without one in their profile
+    /// instead of recognizing them. Not saved.
+    #[serde(skip)]
+    pub forced_device: Option<DeviceModel>,
+    /// Per-device settings keyed by output port name.
+    pub devices: BTreeMap<String, DeviceProfile>,
+    /// CLI command aliases: name to `;`-separated commands, which may use
+    /// the alias's arguments as `$1`..`$9`.
+    pub aliases: BTreeMap<String, String>,
+    /// GUI drum pad layout.
+    pub pads: PadLayout,
+    pub gui: GuiSettings,
+    /// Input port of an external controller driving parameters.
+    pub controller_input: Option<String>,
+    /// Controller CCs bound to parameters.
+    pub bindings: Vec<Binding>,
+    /// Saved map (see [`MapFile`]) used instead of the device model's.
+    pub map: Option<String>,
+    /// Where `profile install` fetches community maps: an HTTP(S) base URL
+    /// or a git repository.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile_repo: Option<String>,
+    /// Limits and curves by parameter name, e.g. to keep the filter within
+    /// 30-90 during a set.
+    pub ranges: BTreeMap<String, ParamRange>,
+    /// Init patches by output port name, like `devices`: parameter names
+    /// and the values they start from instead of the map's defaults.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub init: BTreeMap<String, BTreeMap<String, u8>>,
+    /// Controls moving several parameters at once, settable by name.
+    #[serde(rename = "macro", skip_serializing_if = "Vec::is_empty")]
+    pub macro_controls: Vec<MacroControl>,
+    /// Devices of a multi-device session, each with its own GUI tab.
+    #[serde(rename = "instance", skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<DeviceInstance>,
+    /// Names for the Digitakt MIDI tracks' CC knobs, by track.
+    #[serde(rename = "midi_track", skip_serializing_if = "Vec::is_empty")]
+    pub midi_tracks: Vec<MidiTrack>,
+    /// Playing chords in a key from single notes (pads, keyboard and
+    /// controller input).
+    pub chord_mode: ChordMode,
+    /// Retriggering held pads and keys on the clock.
+    pub note_repeat: NoteRepeat,
+    /// The step sequencer's tracks.
+    #[serde(rename = "sequence", skip_serializing_if = "Vec::is_empty")]
+    pub sequence: Vec<SeqTrack>,
+    /// Input quantization and count-in for recording into the sequencer.
+    pub recording: Recording,
+    /// Software LFOs modulating parameters.
+    #[serde(rename = "lfo", skip_serializing_if = "Vec::is_empty")]
+    pub lfos: Vec<Lfo>,
+    /// Envelope generators sent as parameter changes.
+    #[serde(rename = "envelope", skip_serializing_if = "Vec::is_empty")]
+    pub envelopes: Vec<Envelope>,
+    /// Recorded parameter moves, replayed in a loop with the transport.
+    #[serde(skip_serializing_if = "Automation::is_empty")]
+    pub automation: Automation,
+    /// When pattern changes wait for while playing (the GUI's pattern
+    /// grid, and `pc` and `pattern` in the CLI).
+    pub pattern_change: PatternChange,
+    /// Length of the patterns played in bars, for changes on a pattern's
+    /// end; one bar if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pattern_bars: Option<u32>,
+    /// The song mode arrangement.
+    #[serde(skip_serializing_if = "Song::is_empty")]
+    pub song: Song,
+}
+
+/// Light or dark GUI.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+}
+
+/// How the GUI looks and lays out its controls.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GuiSettings {
+    pub theme: Theme,
+    /// Highlight color (RGB) for selections and active controls.
+    pub accent: [u8; 3],
+    /// Zoom of the whole interface, 1.0 being the system's scale.
+    pub scale: f32,
+    /// Bigger hit targets and taller sliders, for touchscreens.
+    pub touch: bool,
+    pub layout: PanelLayout,
+    /// Parameter sends per second at most while sweeping; each send
+    /// carries only the latest value per parameter. 0 sends every change.
+    pub cc_rate: u32,
+    /// Knobs instead of sliders for every parameter.
+    pub knobs: bool,
+    /// Categories shown as knobs when `knobs` is off.
+    pub knob_categories: BTreeSet<String>,
+    /// Starred parameter names, in the Performance page's order.
+    pub favorites: Vec<String>,
+    /// Parameter names on the fullscreen view's macro controls, by slot.
+    /// Empty names are unassigned slots.
+    pub macros: Vec<String>,
+}
+
+impl Default for GuiSettings {
+    fn default() -> Self {
+        Self {
+            theme: Theme::Dark,
+            accent: [230, 140, 20],
+            scale: 1.0,
+            touch: false,
+            cc_rate: 60,
+            layout: PanelLayout::default(),
+            knobs: false,
+            knob_categories: BTreeSet::new(),
+            favorites: Vec::new(),
+            macros: Vec::new(),
+        }
+    }
+}
+
+/// Pages docked beside the main area and the sizes of resizable panels.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PanelLayout {
+    /// Page shown left of the main area, by name (e.g. `mixer`).
+    pub left: Option<String>,
+    pub right: Option<String>,
+    pub left_width: f32,
+    pub right_width: f32,
+    pub activity_height: f32,
+    /// Columns of parameter categories; 0 fits as many as the width allows.
+    pub parameter_columns: u8,
+}
+
+impl Default for PanelLayout {
+    fn default() -> Self {
+        Self {
+            left: None,
+            right: None,
+            left_width: 360.0,
+            right_width: 360.0,
+            activity_height: 200.0,
+            parameter_columns: 0,
+        }
+    }
+}
+
+/// Size and notes of the GUI drum pad grid.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PadLayout {
+    pub rows: u8,
+    pub columns: u8,
+    /// Notes left to right from the top row. Pads past the end carry on
+    /// chromatically from the last.
+    pub notes: Vec<Note>,
+    /// Velocity for every hit; without it, velocity follows how high on
+    /// the pad the click lands.
+    pub fixed_velocity: Option<Value7>,
+}
+
+impl Default for PadLayout {
+    fn default() -> Self {
+        // C1 upwards, one note per pad
+        let notes = (36..52).filter_map(|n| Value7::new(n).ok()).map(Note::new).collect();
+        Self { rows: 4, columns: 4, notes, fixed_velocity: None }
+    }
+}
+
+impl PadLayout {
+    /// Note of pad `index`, counting left to right from the top row.
+    pub fn note(&self, index: usize) -> Note {
+        match self.notes.get(index) {
+            Some(note) => *note,
+            None => {
+                let last = self.notes.last().map_or(36, |n| n.value().get() as usize);
+                let extra = index + 1 - self.notes.len().max(1);
+                let number = (last + extra).min(Value7::MAX as usize) as u8;
+                Value7::new(number).map(Note::new).unwrap_or_default()
+            }
+        }
+    }
+
+    /// Assigns a note to pad `index`, filling any gap chromatically.
+    pub fn set_note(&mut self, index: usize, note: Note) {
+        while self.notes.len() <= index {
+            self.notes.push(self.note(self.notes.len()));
+        }
+        self.notes[index] = note;
+    }
+}
+
+/// How a particular device wants to be driven.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DeviceProfile {
+    /// Realtime Start/Stop, MMC, or both.
+    pub transport: TransportProtocol,
+    /// MMC device ID; 127 addresses all devices.
+    pub mmc_device_id: u8,
+    /// Omit repeated status bytes in batched sends. Off by default since
+    /// some devices mis-parse it.
+    pub running_status: bool,
+    /// Device model on this port, overriding the global one.
+    pub model: Option<DeviceModel>,
+    /// Note of the first drum pad, the rest counting up chromatically;
+    /// unset keeps the pad layout's notes.
+    pub pad_note: Option<Note>,
+    /// Starting velocity of the keyboard, and of `note` and `chord`
+    /// without one.
+    pub velocity: Value7,
+    /// Length of `note` and `chord` without one, in ms.
+    pub note_length: u64,
+}
+
+impl Default for DeviceProfile {
+    fn default() -> Self {
+        Self {
+            transport: TransportProtocol::default(),
+            mmc_device_id: ALL_DEVICES,
+            running_status: false,
+            model: None,
+            pad_note: None,
+            velocity: Value7::new(100).unwrap_or_default(),
+            note_length: 250,
+        }
+    }
+}
+
+/// One device of a multi-device session, e.g. a Digitakt on channels 1-8
+/// of one port and a synth on channel 16 of another.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeviceInstance {
+    /// Tab label, e.g. `Digitakt`.
+    pub name: String,
+    /// Output port name, or part of it.
+    pub port: String,
+    /// Channel (1-16) of the first track.
+    #[serde(default = "first_channel")]
+    pub channel: u8,
+    /// Tracks on consecutive channels; the model's track count if unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tracks: Option<u8>,
+    /// Transport settings and model. Instances sharing a port share the
+    /// first one's transport settings.
+    #[serde(flatten)]
+    pub profile: DeviceProfile,
+}
+
+fn first_channel() -> u8 {
+    1
+}
+
+impl DeviceInstance {
+    /// Index of the instance's output port: the one named exactly, else
+    /// the first whose name contains `port` (ignoring case).
+    pub fn port_index(&self, port_names: &[String]) -> Option<usize> {
+        let pattern = self.port.to_lowercase();
+        port_names
+            .iter()
+            .position(|name| *name == self.port)
+            .or_else(|| port_names.iter().position(|name| name.to_lowercase().contains(&pattern)))
+    }
+
+    /// Its tracks, fitted to the channels from `channel` up.
+    pub fn tracks(&self, model: DeviceModel) -> u8 {
+        let channel = self.channel.clamp(1, 16);
+        self.tracks.unwrap_or(model.tracks()).clamp(1, 17 - channel)
+    }
+}
+
+impl Config {
+    /// Parameter map of `model`, or the configured map file, with the
+    /// configured ranges. Problems with the map, and settings it leaves
+    /// without a parameter or category, are logged.
+    pub fn midi_map(&self, model: DeviceModel) -> MidiMap {
+        let custom = self.map.as_deref().and_then(|name| match MapFile::load(name) {
+            Ok(map) => {
+                for issue in map.check() {
+                    warn!("Map '{}': {}", name, issue);
+                }
+                Some(map.midi_map())
+            }
+            Err(e) => {
+                warn!("{:#}; using the {} map", e, model);
+                None
+            }
+        });
+        let mut midi_map = custom.unwrap_or_else(|| MidiMap::for_device(model));
+        midi_map.apply_ranges(&self.ranges);
+        for issue in self.check_map(&midi_map) {
+            warn!("{}", issue);
+        }
+        midi_map
+    }
+
+    /// Settings naming categories or parameters `midi_map` does not have,
+    /// e.g. left over from another map.
+    pub fn check_map(&self, midi_map: &MidiMap) -> Vec<MapIssue> {
+        let mut issues = Vec::new();
+        let params = midi_map.get_all_parameters();
+        for category in &self.gui.knob_categories {
+            if !params.iter().any(|p| p.category == *category) {
+                let message = format!("Knob category '{}' has no parameters", category);
+                issues.push(MapIssue::warning(message));
+            }
+        }
+        let targets = self.macro_controls.iter().flat_map(|m| &m.targets);
+        let names = self.ranges.keys().map(|name| ("Range for", name));
+        let names = names.chain(targets.map(|target| ("Macro target", &target.parameter)));
+        let names = names.chain(self.gui.favorites.iter().map(|name| ("Favorite", name)));
+        let macros = self.gui.macros.iter().filter(|name| !name.is_empty());
+        for (what, name) in names.chain(macros.map(|name| ("Macro slot", name))) {
+            if midi_map.get_by_name(name).is_none() {
+                let message = format!("{} '{}': no such parameter", what, name);
+                issues.push(MapIssue::warning(message));
+            }
+        }
+        issues
+    }
+
+    /// The init patch for the device on `port_name`: every parameter of
+    /// `midi_map` at the value saved for the port, else its default, kept
+    /// within its range.
+    pub fn init_patch(
+        &self,
+        port_name: Option<&str>,
+        midi_map: &MidiMap,
+    ) -> Vec<(ParamAddress, u8)> {
+        let saved = port_name.and_then(|name| self.init.get(name));
+        midi_map
+            .get_all_parameters()
+            .into_iter()
+            .map(|param| {
+                let value = saved
+                    .and_then(|v| v.get(&param.name).or_else(|| v.get(&param.slug())))
+                    .copied()
+                    .unwrap_or(param.default);
+                (param.address, param.range.clamp(value))
+            })
+            .collect()
+    }
+
+    /// Saves `values` as the init patch of the device on `port_name`,
+    /// keeping only those that differ from the defaults.
+    pub fn set_init_patch(
+        &mut self,
+        port_name: &str,
+        midi_map: &MidiMap,
+        values: impl IntoIterator<Item = (ParamAddress, u8)>,
+    ) {
+        let patch: BTreeMap<String, u8> = values
+            .into_iter()
+            .filter_map(|(address, value)| midi_map.get_by_address(address).map(|p| (p, value)))
+            .filter(|(param, value)| param.default != *value)
+            .map(|(param, value)| (param.name, value))
+            .collect();
+        if patch.is_empty() {
+            self.init.remove(port_name);
+        } else {
+            self.init.insert(port_name.to_string(), patch);
+        }
+    }
+
+    /// The macro called `name` (any case, or its slug).
+    pub fn find_macro(&self, name: &str) -> Option<&MacroControl> {
+        self.macro_controls.iter().find(|m| m.is_named(name))
+    }
+
+    /// MIDI track `track`'s knobs, added unnamed if it has none yet.
+    pub fn midi_track_mut(&mut self, track: u8) -> &mut MidiTrack {
+        let index = match self.midi_tracks.iter().position(|t| t.track == track) {
+            Some(index) => index,
+            None => {
+                self.midi_tracks.push(MidiTrack::new(track));
+                self.midi_tracks.sort_by_key(|t| t.track);
+                self.midi_tracks.iter().position(|t| t.track == track).unwrap_or_default()
+            }
+        };
+        &mut self.midi_tracks[index]
+    }
+
+    /// Device model when no port is open: `--device`, else the default.
+    pub fn default_model(&self) -> DeviceModel {
+        self.forced_device.unwrap_or(self.device)
+    }
+
+    /// Device model on the output port `port_name`: the one set for the
+    /// port, else `--device`, else recognized from the port name or the
+    /// `identified` model, else the configured default.
+    pub fn model_for(&self, port_name: &str, identified: Option<DeviceModel>) -> DeviceModel {
+        self.devices
+            .get(port_name)
+            .and_then(|p| p.model)
+            .or(self.forced_device)
+            .or_else(|| DeviceModel::from_port_name(port_name))
+            .or(identified)
+            .unwrap_or(self.device)
+    }
+
+    /// Device profiles by output port name: the saved ones, overridden by
+    /// those of the session's instances on ports in `port_names`.
+    pub fn port_profiles(&self, port_names: &[String]) -> BTreeMap<String, DeviceProfile> {
+        let mut profiles = self.devices.clone();
+        let mut claimed = BTreeSet::new();
+        for instance in &self.instances {
+            let Some(name) = instance.port_index(port_names).map(|i| &port_names[i]) else {
+                continue;
+            };
+            if claimed.insert(name) {
+                profiles.insert(name.clone(), instance.profile);
+            }
+        }
+        profiles
+    }
+
+    pub fn dir() -> Option<PathBuf> {
+        dirs::config_dir().map(|d| d.join("midi_ctrl"))
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        Self::dir().map(|d| d.join("config.toml"))
+    }
+
+    /// Loads the config, falling back to defaults if there is none yet.
+    pub fn load() -> Result<Self> {
+        let Some(path) = Self::path().filter(|p| p.exists()) else {
+            return Ok(Self::default());
+        };
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let what = path.display().to_string();
+        schema::parse(&text, &CONFIG_MIGRATIONS, &what)
+            .with_context(|| format!("Invalid config {}", what))
+    }
+
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path().ok_or_else(|| anyhow::anyhow!("No config directory"))?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, schema::to_string(self, CONFIG_VERSION)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
@@ -0,0 +1,1496 @@
+use crate::automation::{AutomationLane, AutomationPlayer};
+use crate::backend::{MidiBackend, MidirBackend, OutputConnection};
+use crate::clock::{tick_period, Clock, MIN_SWING, PPQN};
+use crate::config::DeviceProfile;
+use crate::envelope::{Envelope, EnvelopeBank, Gate};
+use crate::identity::{DeviceIdentity, IDENTITY_REQUEST};
+use crate::lfo::{Lfo, LfoBank, LFO_PERIOD};
+use crate::midi::{Message, Realtime};
+use crate::midi_in::{InputEvent, MidiInputHandle};
+use crate::midi_map::ParamAddress;
+use crate::mmc::MmcCommand;
+use crate::mtc::{self, MtcGenerator};
+use crate::note_repeat::{NoteRepeat, Repeater};
+use crate::pattern::{Pattern, PatternChange};
+use crate::scheduler::{JobId, Scheduler};
+use crate::recorder::Recording;
+use crate::sequencer::{SeqTrack, Sequencer};
+use crate::song::{Song, SongPlayer};
+use crate::sysex;
+use crate::timecode::{FrameRate, Timecode};
+use crate::types::{Channel, Controller, Value7};
+use crate::transport::{ClockFollower, ClockSource, Position, Transport, TICKS_PER_BAR};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::sync::mpsc::{self, Receiver};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::debug;
+
+/// Lists the names of all available MIDI output ports, in port index order.
+pub fn output_port_names() -> Result<Vec<String>> {
+    MidirBackend.output_port_names()
+}
+
+/// Index of the first output port whose name contains `pattern`
+/// (case-insensitive).
+pub fn find_output_port(pattern: &str) -> Result<Option<usize>> {
+    let pattern = pattern.to_lowercase();
+    Ok(output_port_names()?
+        .iter()
+        .position(|name| name.to_lowercase().contains(&pattern)))
+}
+
+/// How far ahead of a bar or beat's clock pulse quantized sends go out.
+const QUANTIZE_LEAD: Duration = Duration::from_millis(1);
+
+/// Whether the backend accepts several messages in one `send`. The ALSA
+/// sequencer encodes a single event per call, so batches there are sent
+/// message by message (running status has no meaning on the sequencer).
+const PACKED_SENDS: bool = !cfg!(target_os = "linux");
+
+/// Parameters per burst in [`MidiController::sync_params`], and the gap
+/// between bursts: enough for DIN MIDI (about 3 bytes per ms) to drain a
+/// burst of NRPNs before the next.
+const SYNC_BURST: usize = 8;
+const SYNC_SPACING: Duration = Duration::from_millis(40);
+
+/// Which open output(s) a send goes to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PortTarget {
+    /// Broadcast to every open output.
+    #[default]
+    All,
+    Port(usize),
+}
+
+/// A change in output availability found by [`MidiController::check_ports`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PortEvent {
+    /// The port vanished (device unplugged or power-cycled).
+    Lost(String),
+    /// A lost port reappeared and was reopened, possibly at a new index.
+    Reconnected { name: String, port: usize },
+}
+
+/// Why a send did not go out; attached to the error so callers can tell a
+/// missing port from a port that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SendError {
+    /// No output is open.
+    NotConnected,
+    /// The targeted output is not open.
+    PortNotConnected(usize),
+    /// The output (named) rejected the bytes.
+    Failed(String),
+}
+
+impl std::fmt::Display for SendError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SendError::NotConnected => write!(f, "Not connected"),
+            SendError::PortNotConnected(idx) => write!(f, "Port {} is not connected", idx),
+            SendError::Failed(name) => write!(f, "Failed to send to {}", name),
+        }
+    }
+}
+
+impl std::error::Error for SendError {}
+
+struct Output {
+    conn: Box<dyn OutputConnection>,
+    name: String,
+}
+
+/// Receives (port name, bytes) for every send while dry run is on, or
+/// every send at all when used as a send monitor.
+pub type DryRunSink = Arc<dyn Fn(&str, &[u8]) + Send + Sync>;
+
+/// Wraps every opened output so dry run and the send monitor see all
+/// sends, including the clock's and scheduled ones, in one place.
+struct SwitchedOutput {
+    inner: Box<dyn OutputConnection>,
+    name: String,
+    dry_run: Arc<Mutex<Option<DryRunSink>>>,
+    monitor: Arc<Mutex<Option<DryRunSink>>>,
+}
+
+impl OutputConnection for SwitchedOutput {
+    fn send(&mut self, bytes: &[u8]) -> Result<()> {
+        let sink = self.dry_run.lock().unwrap().clone();
+        let result = match sink {
+            Some(sink) => {
+                sink(&self.name, bytes);
+                Ok(())
+            }
+            None => self.inner.send(bytes),
+        };
+        if result.is_ok()
+            && let Some(monitor) = self.monitor.lock().unwrap().clone()
+        {
+            monitor(&self.name, bytes);
+        }
+        result
+    }
+
+    fn close(self: Box<Self>) {
+        self.inner.close();
+    }
+}
+
+/// Open outputs keyed by port index, shared with the clock thread.
+type Outputs = Arc<Mutex<BTreeMap<usize, Output>>>;
+
+/// What the device was last told: notes still sounding, so they can be
+/// released on shutdown, and the latest value of every controller and
+/// NRPN parameter.
+#[derive(Default)]
+struct SentState {
+    notes: BTreeSet<(Channel, Value7)>,
+    cc: BTreeMap<(Channel, Controller), Value7>,
+    /// The NRPN number (MSB, LSB) selected on each channel for data entry.
+    nrpn_number: BTreeMap<Channel, (Option<u8>, Option<u8>)>,
+    /// Data entry MSB by channel and NRPN number.
+    nrpn: BTreeMap<(Channel, u8, u8), Value7>,
+}
+
+type SharedState = Arc<Mutex<SentState>>;
+
+/// Data entry, increment/decrement and (N)RPN select controllers: they
+/// only mean something in sequence, so they are not kept as state.
+const PARAMETER_NUMBER_CCS: [u8; 8] = [6, 38, 96, 97, 98, 99, 100, 101];
+
+fn track(state: &SharedState, messages: &[Message]) {
+    let mut state = state.lock().unwrap();
+    for msg in messages {
+        match *msg {
+            Message::NoteOn { channel, note, .. } => {
+                state.notes.insert((channel, note));
+            }
+            Message::NoteOff { channel, note, .. } => {
+                state.notes.remove(&(channel, note));
+            }
+            Message::ControlChange { channel, controller, value } => match controller.get() {
+                99 => state.nrpn_number.entry(channel).or_default().0 = Some(value.get()),
+                98 => state.nrpn_number.entry(channel).or_default().1 = Some(value.get()),
+                // Selecting an RPN moves data entry off the NRPN
+                100 | 101 => {
+                    state.nrpn_number.remove(&channel);
+                }
+                6 => {
+                    if let Some(&(Some(msb), Some(lsb))) = state.nrpn_number.get(&channel) {
+                        state.nrpn.insert((channel, msb, lsb), value);
+                    }
+                }
+                cc if !PARAMETER_NUMBER_CCS.contains(&cc) => {
+                    state.cc.insert((channel, controller), value);
+                }
+                _ => {}
+            },
+            _ => {}
+        }
+    }
+}
+
+/// Sends messages from the clock or input thread to every open output,
+/// like the clock itself.
+fn send_to_all(outputs: &Outputs, state: &SharedState, messages: &[Message]) {
+    if messages.is_empty() {
+        return;
+    }
+    for output in outputs.lock().unwrap().values_mut() {
+        for bytes in messages.iter().filter_map(|m| m.encode().ok()) {
+            let _ = output.conn.send(&bytes);
+        }
+    }
+    track(state, messages);
+}
+
+/// The tempo: the internal clock's `bpm`, or the estimate from the
+/// incoming clock when following an external master.
+fn tempo(bpm: &AtomicU32, external: &AtomicBool, follower: &Mutex<ClockFollower>) -> f32 {
+    let following = external.load(Ordering::Relaxed);
+    let estimate = following.then(|| follower.lock().unwrap().bpm()).flatten();
+    estimate.unwrap_or_else(|| f32::from_bits(bpm.load(Ordering::Relaxed)))
+}
+
+/// What plays on the clock, on the clock thread or the input's for an
+/// external clock.
+#[derive(Clone, Default)]
+struct Players {
+    sequencer: Arc<Mutex<Sequencer>>,
+    /// Held notes repeating on the clock, like the sequencer.
+    repeater: Arc<Mutex<Repeater>>,
+    song: Arc<Mutex<SongPlayer>>,
+    automation: Arc<Mutex<AutomationPlayer>>,
+    /// Triggered by the sequencer's steps; sent by the modulation job.
+    envelopes: Arc<Mutex<EnvelopeBank>>,
+}
+
+/// Advances the transport one clock tick, playing the song's changes, the
+/// automation and the sequencer's notes for it while the transport runs,
+/// and repeating held notes.
+fn clock_tick(transport: &Transport, players: &Players, outputs: &Outputs, state: &SharedState) {
+    let tick = transport.ticks();
+    let running = transport.is_running();
+    transport.tick();
+    if running {
+        send_to_all(outputs, state, &players.song.lock().unwrap().tick(tick));
+        send_to_all(outputs, state, &players.automation.lock().unwrap().tick(tick));
+        let mut sequencer = players.sequencer.lock().unwrap();
+        let messages = sequencer.tick(tick);
+        let mut envelopes = players.envelopes.lock().unwrap();
+        for &(track, step) in sequencer.played() {
+            envelopes.open(Gate::Step(track, step));
+        }
+        drop((sequencer, envelopes));
+        send_to_all(outputs, state, &messages);
+    }
+    let messages = players.repeater.lock().unwrap().pulse(running.then_some(tick));
+    send_to_all(outputs, state, &messages);
+}
+
+/// MIDI engine shared by the GUI and CLI frontends.
+///
+/// Owns the output connections and the transport state; every send goes
+/// through here so frontends never touch raw bytes.
+pub struct MidiController {
+    backend: Arc<dyn MidiBackend>,
+    outputs: Outputs,
+    target: PortTarget,
+    /// Outputs that disappeared, by name and last index, awaiting reconnect.
+    lost: Vec<(String, usize)>,
+    input: Option<MidiInputHandle>,
+    /// Last Identity Reply seen on the input, filled in by the input callback.
+    identity: Arc<Mutex<Option<DeviceIdentity>>>,
+    channel: Channel,
+    scheduler: Scheduler,
+    clock: Clock,
+    mtc: MtcGenerator,
+    /// Frame rate for MTC output alongside the clock; `None` disables it.
+    mtc_rate: Option<FrameRate>,
+    transport: Arc<Transport>,
+    /// Set while following an external clock (see [`ClockSource`]).
+    external_clock: Arc<AtomicBool>,
+    follower: Arc<Mutex<ClockFollower>>,
+    sysex_delay: Duration,
+    state: SharedState,
+    /// Device settings by output port name, so they survive reconnects.
+    profiles: BTreeMap<String, DeviceProfile>,
+    /// Settings for ports without a profile of their own.
+    default_profile: DeviceProfile,
+    /// Clock keeps running while the transport is stopped.
+    free_clock: bool,
+    dry_run: Arc<Mutex<Option<DryRunSink>>>,
+    monitor: Arc<Mutex<Option<DryRunSink>>>,
+    players: Players,
+    lfos: Arc<Mutex<LfoBank>>,
+    /// Sends the LFOs' and envelopes' values while any has a parameter.
+    modulation_job: Option<JobId>,
+}
+
+impl MidiController {
+    /// An engine driving the system's MIDI ports.
+    pub fn new(channel: Channel) -> Self {
+        Self::with_backend(channel, Arc::new(MidirBackend))
+    }
+
+    /// An engine on a specific backend, e.g. a
+    /// [`MockBackend`](crate::backend::MockBackend) in tests.
+    pub fn with_backend(channel: Channel, backend: Arc<dyn MidiBackend>) -> Self {
+        let scheduler = Scheduler::new();
+        Self {
+            backend,
+            outputs: Arc::new(Mutex::new(BTreeMap::new())),
+            target: PortTarget::All,
+            lost: Vec::new(),
+            input: None,
+            identity: Arc::new(Mutex::new(None)),
+            channel,
+            clock: Clock::new(120.0, scheduler.handle()),
+            mtc: MtcGenerator::new(scheduler.handle()),
+            scheduler,
+            mtc_rate: None,
+            transport: Arc::new(Transport::default()),
+            external_clock: Arc::new(AtomicBool::new(false)),
+            follower: Arc::new(Mutex::new(ClockFollower::default())),
+            sysex_delay: sysex::DEFAULT_PACKET_DELAY,
+            state: Arc::new(Mutex::new(SentState::default())),
+            profiles: BTreeMap::new(),
+            default_profile: DeviceProfile::default(),
+            free_clock: false,
+            dry_run: Arc::new(Mutex::new(None)),
+            monitor: Arc::new(Mutex::new(None)),
+            players: Players::default(),
+            lfos: Arc::new(Mutex::new(LfoBank::default())),
+            modulation_job: None,
+        }
+    }
+
+    /// Opens an output port alongside any already open. Reconnecting an
+    /// open port replaces its connection.
+    pub fn connect(&mut self, port_index: usize) -> Result<()> {
+        let (conn, name) = self.backend.open_output(port_index)?;
+        debug!(port = port_index, "Opened output {}", name);
+        let conn = Box::new(SwitchedOutput {
+            inner: conn,
+            name: name.clone(),
+            dry_run: self.dry_run.clone(),
+            monitor: self.monitor.clone(),
+        });
+        let old = self.outputs.lock().unwrap().insert(port_index, Output { conn, name });
+        if let Some(old) = old {
+            old.conn.close();
+        }
+        Ok(())
+    }
+
+    /// Leaves connected devices silent and stopped before exit: drops
+    /// scheduled sends, releases every note still on, sends Stop if the
+    /// clock is running, then closes all ports. Keeps going past send
+    /// errors and returns the first one.
+    pub fn shutdown(&mut self) -> Result<()> {
+        self.scheduler.handle().clear();
+        self.target = PortTarget::All;
+        let mut result = Ok(());
+        if self.is_connected() {
+            let note_offs: Vec<Message> = std::mem::take(&mut self.state.lock().unwrap().notes)
+                .into_iter()
+                .map(|(channel, note)| Message::NoteOff { channel, note, velocity: Value7::default() })
+                .collect();
+            if !note_offs.is_empty() {
+                result = result.and(self.send_batch(&note_offs));
+            }
+            if self.clock.is_running() {
+                result = result.and(self.stop());
+            }
+        }
+        self.disconnect_input();
+        self.disconnect();
+        result
+    }
+
+    /// Stops the clock (and MTC) and closes every open output.
+    pub fn disconnect(&mut self) {
+        self.stop_clock();
+        let outputs = std::mem::take(&mut *self.outputs.lock().unwrap());
+        for (_, output) in outputs {
+            debug!("Closed output {}", output.name);
+            output.conn.close();
+        }
+        self.lost.clear();
+        self.target = PortTarget::All;
+    }
+
+    pub fn disconnect_port(&mut self, port_index: usize) {
+        let output = self.outputs.lock().unwrap().remove(&port_index);
+        if let Some(output) = output {
+            output.conn.close();
+        }
+        if self.target == PortTarget::Port(port_index) {
+            self.target = PortTarget::All;
+        }
+    }
+
+    /// Polls the port list, dropping outputs whose device went away and
+    /// reopening lost outputs by name once they come back.
+    pub fn check_ports(&mut self) -> Result<Vec<PortEvent>> {
+        let names = self.backend.output_port_names()?;
+        let mut events = Vec::new();
+
+        // Ports are identified by name; an index now showing a different
+        // name means the device is gone or the list was renumbered.
+        let gone: Vec<usize> = self
+            .outputs
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(idx, output)| names.get(**idx) != Some(&output.name))
+            .map(|(idx, _)| *idx)
+            .collect();
+        for idx in gone {
+            let output = self.outputs.lock().unwrap().remove(&idx);
+            if let Some(output) = output {
+                output.conn.close();
+                debug!(port = idx, "Output {} went away", output.name);
+                events.push(PortEvent::Lost(output.name.clone()));
+                self.lost.push((output.name, idx));
+            }
+        }
+
+        for (name, old_idx) in std::mem::take(&mut self.lost) {
+            let reopened = names
+                .iter()
+                .position(|n| *n == name)
+                .filter(|idx| self.connect(*idx).is_ok());
+            match reopened {
+                Some(idx) => {
+                    if self.target == PortTarget::Port(old_idx) {
+                        self.target = PortTarget::Port(idx);
+                    }
+                    events.push(PortEvent::Reconnected { name, port: idx });
+                }
+                None => self.lost.push((name, old_idx)),
+            }
+        }
+        Ok(events)
+    }
+
+    /// Names of outputs that vanished and are waiting to be reconnected.
+    pub fn lost_ports(&self) -> Vec<&str> {
+        self.lost.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    /// Opens an input port; received messages arrive on the returned channel.
+    /// Replaces any previously opened input.
+    pub fn connect_input(&mut self, port_index: usize) -> Result<Receiver<InputEvent>> {
+        let (tx, rx) = mpsc::channel();
+        self.disconnect_input();
+        let identity = self.identity.clone();
+        let transport = self.transport.clone();
+        let external_clock = self.external_clock.clone();
+        let follower = self.follower.clone();
+        let state = self.state.clone();
+        let (outputs, players) = (self.outputs.clone(), self.players.clone());
+        self.input = Some(MidiInputHandle::open(&*self.backend, port_index, move |event: InputEvent| {
+            match &event.message {
+                Some(Message::SysEx(payload)) => {
+                    if let Some(id) = DeviceIdentity::parse(payload) {
+                        *identity.lock().unwrap() = Some(id);
+                    }
+                }
+                Some(Message::Realtime(rt)) if external_clock.load(Ordering::Relaxed) => match rt {
+                    Realtime::Clock => {
+                        clock_tick(&transport, &players, &outputs, &state);
+                        follower.lock().unwrap().tick(event.timestamp_us);
+                    }
+                    Realtime::Start => {
+                        let messages = players.sequencer.lock().unwrap().release();
+                        send_to_all(&outputs, &state, &messages);
+                        send_to_all(&outputs, &state, &players.song.lock().unwrap().cue_in(0));
+                        transport.start();
+                        players.envelopes.lock().unwrap().open(Gate::Transport);
+                    }
+                    Realtime::Continue => transport.resume(),
+                    Realtime::Stop => {
+                        transport.stop();
+                        let messages = players.sequencer.lock().unwrap().release();
+                        send_to_all(&outputs, &state, &messages);
+                        players.envelopes.lock().unwrap().close(Gate::Transport);
+                    }
+                    _ => {}
+                },
+                Some(Message::SongPosition(beats)) if external_clock.load(Ordering::Relaxed) => {
+                    transport.locate(*beats as u64);
+                }
+                Some(Message::NoteOn { channel, note, velocity }) => {
+                    let mut envelopes = players.envelopes.lock().unwrap();
+                    match velocity.get() {
+                        0 => envelopes.close(Gate::Note(*channel, *note)),
+                        _ => envelopes.open(Gate::Note(*channel, *note)),
+                    }
+                }
+                Some(Message::NoteOff { channel, note, .. }) => {
+                    players.envelopes.lock().unwrap().close(Gate::Note(*channel, *note));
+                }
+                // Knob turns on the device change its state too
+                Some(msg @ Message::ControlChange { .. }) => track(&state, std::slice::from_ref(msg)),
+                _ => {}
+            }
+            let _ = tx.send(event);
+        })?);
+        Ok(rx)
+    }
+
+    /// Sends an Identity Request and waits for the reply on the open input.
+    pub fn identify(&mut self, timeout: Duration) -> Result<DeviceIdentity> {
+        if self.input.is_none() {
+            anyhow::bail!("No input port open to receive the identity reply");
+        }
+        *self.identity.lock().unwrap() = None;
+        self.send(&Message::SysEx(IDENTITY_REQUEST.to_vec()))?;
+
+        let started = Instant::now();
+        while started.elapsed() < timeout {
+            if let Some(id) = self.identity.lock().unwrap().clone() {
+                return Ok(id);
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        anyhow::bail!("No identity reply within {} ms", timeout.as_millis())
+    }
+
+    /// Latest value of every controller sent to or received from the
+    /// device, ordered by channel and controller.
+    pub fn cc_values(&self) -> Vec<(Channel, Controller, Value7)> {
+        self.state
+            .lock()
+            .unwrap()
+            .cc
+            .iter()
+            .map(|(&(channel, controller), &value)| (channel, controller, value))
+            .collect()
+    }
+
+    /// Latest 7-bit value of a parameter sent to or received from the
+    /// device, whichever way it is addressed.
+    pub fn param_value(&self, channel: Channel, address: ParamAddress) -> Option<Value7> {
+        let state = self.state.lock().unwrap();
+        match address {
+            ParamAddress::Cc(cc) | ParamAddress::Cc14(cc) => {
+                state.cc.get(&(channel, Controller::new(cc).ok()?)).copied()
+            }
+            ParamAddress::Nrpn { msb, lsb } => state.nrpn.get(&(channel, msb, lsb)).copied(),
+        }
+    }
+
+    /// Identity from the most recent reply, if the device has answered.
+    pub fn device_identity(&self) -> Option<DeviceIdentity> {
+        self.identity.lock().unwrap().clone()
+    }
+
+    pub fn disconnect_input(&mut self) {
+        if let Some(input) = self.input.take() {
+            input.close();
+        }
+        // Nothing left to follow
+        self.external_clock.store(false, Ordering::Relaxed);
+        self.swing_steps();
+    }
+
+    pub fn input_port_name(&self) -> Option<&str> {
+        self.input.as_ref().map(|i| i.port_name())
+    }
+
+    pub fn is_connected(&self) -> bool {
+        !self.outputs.lock().unwrap().is_empty()
+    }
+
+    /// Open outputs as (port index, name), in port order.
+    pub fn ports(&self) -> Vec<(usize, String)> {
+        self.outputs
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(idx, output)| (*idx, output.name.clone()))
+            .collect()
+    }
+
+    pub fn port_name(&self, port_index: usize) -> Option<String> {
+        self.outputs.lock().unwrap().get(&port_index).map(|o| o.name.clone())
+    }
+
+    pub fn target(&self) -> PortTarget {
+        self.target
+    }
+
+    /// Open outputs the current target sends to, as (port index, name).
+    pub fn target_ports(&self) -> Vec<(usize, String)> {
+        self.ports()
+            .into_iter()
+            .filter(|(idx, _)| match self.target {
+                PortTarget::All => true,
+                PortTarget::Port(target) => *idx == target,
+            })
+            .collect()
+    }
+
+    /// Routes subsequent sends to one open output, or to all of them.
+    pub fn set_target(&mut self, target: PortTarget) {
+        self.target = target;
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+    }
+
+    pub fn set_channel(&mut self, channel: Channel) {
+        self.channel = channel;
+    }
+
+    /// Current tempo: the internal clock's, or the estimate from the
+    /// incoming clock when following an external master.
+    pub fn bpm(&self) -> f32 {
+        tempo(&self.clock.shared_bpm(), &self.external_clock, &self.follower)
+    }
+
+    pub fn dry_run(&self) -> bool {
+        self.dry_run.lock().unwrap().is_some()
+    }
+
+    /// With a sink, every send goes to it instead of the ports; `None`
+    /// sends for real again.
+    pub fn set_dry_run(&mut self, sink: Option<DryRunSink>) {
+        *self.dry_run.lock().unwrap() = sink;
+    }
+
+    /// With a sink, every successful send (dry run included) is also
+    /// passed to it, e.g. for an activity log.
+    pub fn set_send_monitor(&mut self, sink: Option<DryRunSink>) {
+        *self.monitor.lock().unwrap() = sink;
+    }
+
+    /// Sets the clock tempo; a running clock follows from its next tick.
+    pub fn set_bpm(&mut self, bpm: f32) {
+        self.clock.set_bpm(bpm);
+    }
+
+    pub fn swing(&self) -> u8 {
+        self.clock.swing()
+    }
+
+    /// Sets the swing (50-80%) of the clock sent out, and of the steps
+    /// the sequencer plays.
+    pub fn set_swing(&mut self, swing: u8) {
+        self.clock.set_swing(swing);
+        self.swing_steps();
+    }
+
+    /// The internal clock's pulses carry the swing; steps from an external
+    /// master's have to swing on their own.
+    fn swing_steps(&self) {
+        let swing = if self.following() { self.clock.swing() } else { MIN_SWING };
+        self.players.sequencer.lock().unwrap().set_swing(swing);
+    }
+
+    pub fn clock_running(&self) -> bool {
+        self.clock.is_running()
+    }
+
+    /// Whether the clock runs continuously, independent of Start/Stop.
+    pub fn free_clock(&self) -> bool {
+        self.free_clock
+    }
+
+    /// Runs the clock continuously, so devices synced to it can lock to
+    /// the tempo before Start; or, when turned off, stops it unless the
+    /// transport is playing.
+    pub fn set_free_clock(&mut self, on: bool) -> Result<()> {
+        if on && self.following() {
+            anyhow::bail!("Following an external clock");
+        }
+        self.free_clock = on;
+        if on && !self.clock.is_running() {
+            self.start_clock();
+        } else if !on && !self.transport.is_running() {
+            self.stop_clock();
+        }
+        Ok(())
+    }
+
+    pub fn transport(&self) -> &Transport {
+        &self.transport
+    }
+
+    /// The transport, for reading the position from another thread (e.g. a
+    /// display) as the clock or the external master advances it.
+    pub fn shared_transport(&self) -> Arc<Transport> {
+        self.transport.clone()
+    }
+
+    pub fn mtc_rate(&self) -> Option<FrameRate> {
+        self.mtc_rate
+    }
+
+    /// Enables MTC output at `rate`, or disables it with `None`. Takes
+    /// effect immediately if the clock is running.
+    pub fn set_mtc_rate(&mut self, rate: Option<FrameRate>) {
+        self.mtc_rate = rate;
+        self.mtc.stop();
+        if self.clock.is_running() {
+            self.start_mtc();
+        }
+    }
+
+    /// Elapsed song time at the transport position and current tempo.
+    fn transport_time(&self) -> Duration {
+        tick_period(self.clock.bpm()) * self.transport.ticks() as u32
+    }
+
+    pub fn clock_source(&self) -> ClockSource {
+        if self.external_clock.load(Ordering::Relaxed) {
+            ClockSource::External
+        } else {
+            ClockSource::Internal
+        }
+    }
+
+    /// Switches between generating clock and following the input port's
+    /// clock. Following requires an open input.
+    pub fn set_clock_source(&mut self, source: ClockSource) -> Result<()> {
+        match source {
+            ClockSource::External => {
+                if self.input.is_none() {
+                    anyhow::bail!("No input port open to follow");
+                }
+                self.stop_clock();
+                self.free_clock = false;
+                self.follower.lock().unwrap().reset();
+                self.external_clock.store(true, Ordering::Relaxed);
+            }
+            ClockSource::Internal => self.external_clock.store(false, Ordering::Relaxed),
+        }
+        self.swing_steps();
+        Ok(())
+    }
+
+    /// Settings for the device on the named output (the default profile if
+    /// unset).
+    pub fn device_profile(&self, port_name: &str) -> DeviceProfile {
+        self.profiles.get(port_name).copied().unwrap_or(self.default_profile)
+    }
+
+    /// Sets the profile of ports without their own, e.g. the defaults of
+    /// the device model in use.
+    pub fn set_default_profile(&mut self, profile: DeviceProfile) {
+        self.default_profile = profile;
+    }
+
+    pub fn set_device_profile(&mut self, port_name: &str, profile: DeviceProfile) {
+        self.profiles.insert(port_name.to_string(), profile);
+    }
+
+    pub fn sysex_delay(&self) -> Duration {
+        self.sysex_delay
+    }
+
+    /// Sets the pause inserted between consecutive SysEx packets.
+    pub fn set_sysex_delay(&mut self, delay: Duration) {
+        self.sysex_delay = delay;
+    }
+
+    /// Calls `f` for each targeted output along with its device profile.
+    fn for_each_target<F>(&mut self, mut f: F) -> Result<()>
+    where
+        F: FnMut(&mut dyn OutputConnection, DeviceProfile) -> Result<()>,
+    {
+        let mut outputs = self.outputs.lock().unwrap();
+        if outputs.is_empty() {
+            return Err(SendError::NotConnected.into());
+        }
+        let profile = |name: &str| self.device_profile(name);
+        match self.target {
+            PortTarget::All => {
+                for output in outputs.values_mut() {
+                    f(output.conn.as_mut(), profile(&output.name))
+                        .with_context(|| SendError::Failed(output.name.clone()))?;
+                }
+            }
+            PortTarget::Port(idx) => {
+                let output = outputs
+                    .get_mut(&idx)
+                    .ok_or(SendError::PortNotConnected(idx))?;
+                f(output.conn.as_mut(), profile(&output.name))
+                    .with_context(|| SendError::Failed(output.name.clone()))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn send_raw(&mut self, bytes: &[u8]) -> Result<()> {
+        self.for_each_target(|conn, _| conn.send(bytes))
+    }
+
+    /// Sends a transport command in whichever form each targeted device's
+    /// profile asks for, without touching the internal clock.
+    pub fn send_transport(&mut self, realtime: Message, mmc: MmcCommand) -> Result<()> {
+        let realtime = realtime.encode()?;
+        self.for_each_target(|conn, profile| {
+            if profile.transport.realtime() {
+                conn.send(&realtime)?;
+            }
+            if profile.transport.mmc() {
+                conn.send(&mmc.message(profile.mmc_device_id).encode()?)?;
+            }
+            Ok(())
+        })
+    }
+
+    /// Sends an MMC command to the targeted outputs regardless of their
+    /// transport protocol, using each profile's device ID.
+    pub fn mmc(&mut self, cmd: MmcCommand) -> Result<()> {
+        self.for_each_target(|conn, profile| {
+            conn.send(&cmd.message(profile.mmc_device_id).encode()?)
+        })
+    }
+
+    /// Encodes and sends a single message.
+    pub fn send(&mut self, msg: &Message) -> Result<()> {
+        let bytes = msg.encode()?;
+        self.send_raw(&bytes)?;
+        track(&self.state, std::slice::from_ref(msg));
+        Ok(())
+    }
+
+    /// Sends a burst of messages with one send per output where the backend
+    /// allows, using running status for devices whose profile enables it.
+    pub fn send_batch(&mut self, messages: &[Message]) -> Result<()> {
+        // Validate everything before anything goes out
+        let encoded = messages.iter().map(Message::encode).collect::<Result<Vec<_>>>()?;
+        self.for_each_target(|conn, profile| {
+            if PACKED_SENDS {
+                conn.send(&Message::encode_all(messages, profile.running_status)?)?;
+            } else {
+                for bytes in &encoded {
+                    conn.send(bytes)?;
+                }
+            }
+            Ok(())
+        })?;
+        track(&self.state, messages);
+        Ok(())
+    }
+
+    pub fn send_cc(&mut self, channel: Channel, controller: Controller, value: Value7) -> Result<()> {
+        self.send(&Message::ControlChange { channel, controller, value })
+    }
+
+    /// Sends a 14-bit NRPN value (0-16383).
+    pub fn send_nrpn(&mut self, channel: Channel, msb: Value7, lsb: Value7, value: u16) -> Result<()> {
+        self.send_batch(&Message::nrpn(channel, msb, lsb, value)?)
+    }
+
+    /// Sends a 7-bit parameter value using whichever encoding the parameter
+    /// is addressed by.
+    pub fn send_param(&mut self, channel: Channel, address: ParamAddress, value: Value7) -> Result<()> {
+        self.touch_automation(channel, address);
+        self.send_batch(&address.messages(channel, value)?)
+    }
+
+    /// Sends many parameter changes as one batch. Only the last value of
+    /// each parameter is sent; earlier ones would be overwritten anyway.
+    pub fn send_params(&mut self, params: &[(Channel, ParamAddress, Value7)]) -> Result<()> {
+        let mut latest: Vec<(Channel, ParamAddress, Value7)> = Vec::new();
+        for &(channel, address, value) in params {
+            latest.retain(|(c, a, _)| (*c, *a) != (channel, address));
+            latest.push((channel, address, value));
+        }
+        let mut messages = Vec::new();
+        for (channel, address, value) in latest {
+            self.touch_automation(channel, address);
+            messages.extend(address.messages(channel, value)?);
+        }
+        self.send_batch(&messages)
+    }
+
+    /// Re-sends a whole set of parameter values, e.g. after the device was
+    /// power-cycled, in bursts spaced out so its input buffer keeps up.
+    /// Returns how long until the last burst goes out.
+    pub fn sync_params(&mut self, params: &[(Channel, ParamAddress, Value7)]) -> Result<Duration> {
+        let mut bursts = Vec::new();
+        for chunk in params.chunks(SYNC_BURST) {
+            let mut messages = Vec::new();
+            for &(channel, address, value) in chunk {
+                messages.extend(address.messages(channel, value)?);
+            }
+            bursts.push(messages);
+        }
+        let mut delay = Duration::ZERO;
+        for (i, messages) in bursts.iter().enumerate() {
+            delay = SYNC_SPACING * i as u32;
+            if i == 0 {
+                self.send_batch(messages)?;
+            } else {
+                self.schedule_in(delay, messages)?;
+            }
+        }
+        Ok(delay)
+    }
+
+    pub fn note_on(&mut self, channel: Channel, note: Value7, velocity: Value7) -> Result<()> {
+        self.players.envelopes.lock().unwrap().open(Gate::Note(channel, note));
+        self.send(&Message::NoteOn { channel, note, velocity })
+    }
+
+    pub fn note_off(&mut self, channel: Channel, note: Value7) -> Result<()> {
+        self.players.envelopes.lock().unwrap().close(Gate::Note(channel, note));
+        self.send(&Message::NoteOff { channel, note, velocity: Value7::default() })
+    }
+
+    /// Sends Note Ons now and schedules the matching Note Offs after
+    /// `duration`.
+    pub fn play_notes(
+        &mut self,
+        channel: Channel,
+        notes: &[Value7],
+        velocity: Value7,
+        duration: Duration,
+    ) -> Result<JobId> {
+        let ons: Vec<Message> = notes
+            .iter()
+            .map(|&note| Message::NoteOn { channel, note, velocity })
+            .collect();
+        let offs: Vec<Message> = notes
+            .iter()
+            .map(|&note| Message::NoteOff { channel, note, velocity: Value7::default() })
+            .collect();
+        self.send_batch(&ons)?;
+        self.schedule_in(duration, &offs)
+    }
+
+    /// Bends by a signed offset (-8192..=8191, 0 = center).
+    pub fn pitch_bend(&mut self, channel: Channel, bend: i16) -> Result<()> {
+        self.send(&Message::pitch_bend(channel, bend)?)
+    }
+
+    pub fn channel_pressure(&mut self, channel: Channel, pressure: Value7) -> Result<()> {
+        self.send(&Message::ChannelPressure { channel, pressure })
+    }
+
+    pub fn poly_pressure(&mut self, channel: Channel, note: Value7, pressure: Value7) -> Result<()> {
+        self.send(&Message::PolyPressure { channel, note, pressure })
+    }
+
+    pub fn program_change(&mut self, channel: Channel, program: Value7) -> Result<()> {
+        self.send(&Message::ProgramChange { channel, program })
+    }
+
+    /// Switches pattern with Bank Select + Program Change.
+    pub fn select_pattern(&mut self, channel: Channel, pattern: Pattern) -> Result<()> {
+        self.send_batch(&pattern.messages(channel))
+    }
+
+    /// Sends each payload as its own SysEx message, pausing
+    /// [`sysex_delay`](Self::sysex_delay) between packets.
+    pub fn send_sysex(&mut self, payloads: &[Vec<u8>]) -> Result<()> {
+        for (i, payload) in payloads.iter().enumerate() {
+            if i > 0 {
+                thread::sleep(self.sysex_delay);
+            }
+            self.send(&Message::SysEx(payload.clone()))?;
+        }
+        Ok(())
+    }
+
+    /// Sends every message in a `.syx` file. Returns the number of packets sent.
+    pub fn send_sysex_file(&mut self, path: &Path) -> Result<usize> {
+        let payloads = sysex::read_file(path)?;
+        self.send_sysex(&payloads)?;
+        Ok(payloads.len())
+    }
+
+    /// Queues messages to be sent at `at` to the current target. Messages
+    /// are validated now, so a bad one fails here rather than later.
+    pub fn schedule_at(&mut self, at: Instant, messages: &[Message]) -> Result<JobId> {
+        let bytes = messages.iter().map(Message::encode).collect::<Result<Vec<_>>>()?;
+        let messages = messages.to_vec();
+        let outputs = self.outputs.clone();
+        let state = self.state.clone();
+        let target = self.target;
+        Ok(self.scheduler.handle().at(at, move || {
+            track(&state, &messages);
+            for (idx, output) in outputs.lock().unwrap().iter_mut() {
+                if target == PortTarget::All || target == PortTarget::Port(*idx) {
+                    for msg in &bytes {
+                        let _ = output.conn.send(msg);
+                    }
+                }
+            }
+        }))
+    }
+
+    pub fn schedule_in(&mut self, delay: Duration, messages: &[Message]) -> Result<JobId> {
+        self.schedule_at(Instant::now() + delay, messages)
+    }
+
+    /// When the `count`th upcoming boundary of `unit` clock ticks (e.g.
+    /// [`TICKS_PER_BAR`]) falls at the current tempo, if the transport is
+    /// running. Lands just ahead of that boundary's clock pulse, so
+    /// messages sent then apply on the beat.
+    pub fn boundary_at(&self, unit: u64, count: u64) -> Option<Instant> {
+        if !self.transport.is_running() || unit == 0 || count == 0 {
+            return None;
+        }
+        // `ticks` pulses have gone out; pulse number `ticks` is next
+        let ticks = self.transport.ticks();
+        let boundary = ticks + (unit - ticks % unit) % unit + (count - 1) * unit;
+        let period = tick_period(self.bpm());
+        let next_pulse = match self.transport.last_tick() {
+            Some(last) => last + period,
+            None => Instant::now(),
+        };
+        let at = next_pulse + period * (boundary - ticks) as u32;
+        Some(at.checked_sub(QUANTIZE_LEAD).unwrap_or(at).max(Instant::now()))
+    }
+
+    /// When the next bar starts at the current tempo, if the transport is
+    /// running.
+    pub fn next_bar_at(&self) -> Option<Instant> {
+        self.boundary_at(TICKS_PER_BAR, 1)
+    }
+
+    /// When a pattern change made now takes effect under `change`, with
+    /// patterns `bars` long; `None` for straight away (also while stopped).
+    pub fn pattern_change_at(&self, change: PatternChange, bars: u32) -> Option<Instant> {
+        self.boundary_at(change.unit(bars)?, 1)
+    }
+
+    /// Queues messages for the downbeat of the next bar.
+    pub fn schedule_next_bar(&mut self, messages: &[Message]) -> Result<JobId> {
+        let at = self
+            .next_bar_at()
+            .ok_or_else(|| anyhow::anyhow!("Transport is stopped; no next bar to wait for"))?;
+        self.schedule_at(at, messages)
+    }
+
+    /// Drops a queued send that has not gone out yet.
+    pub fn cancel_scheduled(&mut self, job: JobId) {
+        self.scheduler.handle().cancel(job);
+    }
+
+    /// Jobs waiting in the scheduler, including a running clock and MTC.
+    pub fn scheduled_count(&self) -> usize {
+        self.scheduler.handle().pending()
+    }
+
+    /// Queued one-off sends, i.e. [`scheduled_count`](Self::scheduled_count)
+    /// without the clock and MTC jobs.
+    pub fn pending_sends(&self) -> usize {
+        let running = self.clock.is_running() as usize
+            + self.mtc.is_running() as usize
+            + self.modulation_job.is_some() as usize;
+        self.scheduled_count().saturating_sub(running)
+    }
+
+    /// Notes sent On and not yet Off.
+    pub fn held_notes(&self) -> Vec<(Channel, Value7)> {
+        self.state.lock().unwrap().notes.iter().copied().collect()
+    }
+
+    /// Starts the clock job; ticks go to every open output. Does nothing
+    /// while following an external clock.
+    fn start_clock(&mut self) {
+        if self.following() {
+            return;
+        }
+        let outputs = self.outputs.clone();
+        let transport = self.transport.clone();
+        let (players, state) = (self.players.clone(), self.state.clone());
+        let tick = [Realtime::Clock.status()];
+        self.clock.start(self.transport.ticks(), move || {
+            for output in outputs.lock().unwrap().values_mut() {
+                // A vanished port is picked up by check_ports; keep ticking
+                let _ = output.conn.send(&tick);
+            }
+            clock_tick(&transport, &players, &outputs, &state);
+        });
+        self.start_mtc();
+    }
+
+    /// Starts quarter-frame output from the transport position, preceded
+    /// by a Full Frame so receivers lock on straight away.
+    fn start_mtc(&mut self) {
+        let Some(rate) = self.mtc_rate else {
+            return;
+        };
+        let from = Timecode::from_duration(self.transport_time(), rate);
+        let outputs = self.outputs.clone();
+        if let Ok(bytes) = mtc::full_frame(from).encode() {
+            for output in outputs.lock().unwrap().values_mut() {
+                let _ = output.conn.send(&bytes);
+            }
+        }
+        self.mtc.start(from, move |bytes| {
+            for output in outputs.lock().unwrap().values_mut() {
+                let _ = output.conn.send(bytes);
+            }
+        });
+    }
+
+    fn stop_clock(&mut self) {
+        self.clock.stop();
+        self.mtc.stop();
+    }
+
+    fn following(&self) -> bool {
+        self.clock_source() == ClockSource::External
+    }
+
+    /// Sends Start and runs the clock until [`stop`](Self::stop). When
+    /// following an external clock only the message is sent; the transport
+    /// is driven by the master.
+    pub fn start(&mut self) -> Result<()> {
+        self.cue_song(0);
+        self.send_transport(Message::Realtime(Realtime::Start), MmcCommand::Play)?;
+        if !self.following() {
+            self.release_sequence();
+            self.transport.start();
+            self.start_clock();
+            self.players.envelopes.lock().unwrap().open(Gate::Transport);
+        }
+        Ok(())
+    }
+
+    /// Stops the clock (unless it runs freely), then sends Stop.
+    pub fn stop(&mut self) -> Result<()> {
+        if !self.following() {
+            if self.free_clock {
+                self.mtc.stop();
+            } else {
+                self.stop_clock();
+            }
+            self.transport.stop();
+            self.release_sequence();
+            self.players.envelopes.lock().unwrap().close(Gate::Transport);
+        }
+        self.send_transport(Message::Realtime(Realtime::Stop), MmcCommand::Stop)
+    }
+
+    /// The sequencer's tracks.
+    pub fn sequence(&self) -> Vec<SeqTrack> {
+        self.players.sequencer.lock().unwrap().tracks().to_vec()
+    }
+
+    /// Replaces the sequencer's tracks, taking effect from the next step.
+    pub fn set_sequence(&mut self, tracks: Vec<SeqTrack>) {
+        self.players.sequencer.lock().unwrap().set_tracks(tracks);
+    }
+
+    pub fn sequencer_enabled(&self) -> bool {
+        self.players.sequencer.lock().unwrap().is_enabled()
+    }
+
+    /// Plays the sequence with the transport, or stops playing it.
+    pub fn set_sequencer_enabled(&mut self, on: bool) {
+        let messages = self.players.sequencer.lock().unwrap().set_enabled(on);
+        send_to_all(&self.outputs, &self.state, &messages);
+    }
+
+    /// Records notes played into the sequence's track `track` (by index)
+    /// while the transport runs, after a count-in; `None` stops recording.
+    pub fn set_recording(&mut self, track: Option<usize>, settings: Recording) {
+        self.players.sequencer.lock().unwrap().arm(track, settings);
+    }
+
+    /// The clock tick recording starts on once counted in, if recording.
+    pub fn recording_from(&self) -> Option<u64> {
+        self.players.sequencer.lock().unwrap().recording_from()
+    }
+
+    /// Records a note played now into the armed track. Returns whether a
+    /// step changed, e.g. to pass the sequence on.
+    pub fn record_note_on(&mut self, note: Value7, velocity: Value7) -> bool {
+        if !self.transport.is_running() {
+            return false;
+        }
+        let tick = self.transport.ticks().saturating_sub(1);
+        self.players.sequencer.lock().unwrap().record_note_on(tick, note, velocity)
+    }
+
+    /// Ends a note recorded with [`record_note_on`](Self::record_note_on),
+    /// setting its step's length.
+    pub fn record_note_off(&mut self, note: Value7) -> bool {
+        if !self.transport.is_running() {
+            return false;
+        }
+        let tick = self.transport.ticks().saturating_sub(1);
+        self.players.sequencer.lock().unwrap().record_note_off(tick, note)
+    }
+
+    /// Ends the notes the sequencer is playing.
+    fn release_sequence(&mut self) {
+        let messages = self.players.sequencer.lock().unwrap().release();
+        send_to_all(&self.outputs, &self.state, &messages);
+    }
+
+    /// Readies `song` to play with the transport, its patterns selected on
+    /// `channel`.
+    pub fn set_song(&mut self, song: &Song, channel: Channel) -> Result<()> {
+        self.players.song.lock().unwrap().load(song, channel)
+    }
+
+    pub fn song_enabled(&self) -> bool {
+        self.players.song.lock().unwrap().is_enabled()
+    }
+
+    /// Plays the song with the transport, or stops following it; the
+    /// device keeps the pattern it is on.
+    pub fn set_song_enabled(&mut self, on: bool) {
+        self.players.song.lock().unwrap().set_enabled(on);
+    }
+
+    /// Selects the song's pattern for playing from clock tick `tick`.
+    fn cue_song(&mut self, tick: u64) {
+        let messages = self.players.song.lock().unwrap().cue_in(tick);
+        send_to_all(&self.outputs, &self.state, &messages);
+    }
+
+    /// Readies automation lanes to play with the transport.
+    pub fn set_automation(&mut self, lanes: Vec<AutomationLane>) {
+        self.players.automation.lock().unwrap().set_lanes(lanes);
+    }
+
+    pub fn automation_enabled(&self) -> bool {
+        self.players.automation.lock().unwrap().is_enabled()
+    }
+
+    /// Plays the automation with the transport, or stops playing it.
+    pub fn set_automation_enabled(&mut self, on: bool) {
+        self.players.automation.lock().unwrap().set_enabled(on);
+    }
+
+    /// Holds off a parameter's automation while it is moved by hand.
+    fn touch_automation(&mut self, channel: Channel, address: ParamAddress) {
+        if self.transport.is_running() {
+            let tick = self.transport.ticks();
+            self.players.automation.lock().unwrap().touch(channel, address, tick);
+        }
+    }
+
+    /// Sets the software LFOs. Their values go to every open output, like
+    /// the clock, while any has a parameter to modulate.
+    pub fn set_lfos(&mut self, lfos: Vec<Lfo>) {
+        self.lfos.lock().unwrap().set_lfos(lfos);
+        self.run_modulation();
+    }
+
+    /// Sets the envelope generators, sent like the LFOs as their triggers
+    /// open and close.
+    pub fn set_envelopes(&mut self, envelopes: Vec<Envelope>) {
+        self.players.envelopes.lock().unwrap().set_envelopes(envelopes);
+        self.run_modulation();
+    }
+
+    /// Starts the job sending the LFOs' and envelopes' values while any
+    /// has a parameter, or cancels it.
+    fn run_modulation(&mut self) {
+        let active = self.lfos.lock().unwrap().is_active()
+            || self.players.envelopes.lock().unwrap().is_active();
+        match (active, self.modulation_job) {
+            (true, None) => {
+                let (lfos, transport) = (self.lfos.clone(), self.transport.clone());
+                let envelopes = self.players.envelopes.clone();
+                let (outputs, state) = (self.outputs.clone(), self.state.clone());
+                let (bpm, external) = (self.clock.shared_bpm(), self.external_clock.clone());
+                let follower = self.follower.clone();
+                let mut previous = Instant::now();
+                let job = self.scheduler.handle().repeating(previous, move |due| {
+                    let now = Instant::now();
+                    let beat = tick_period(tempo(&bpm, &external, &follower)) * PPQN;
+                    let mut messages = lfos.lock().unwrap().run(&transport, now, now - previous);
+                    messages.extend(envelopes.lock().unwrap().run(now - previous, beat));
+                    previous = now;
+                    send_to_all(&outputs, &state, &messages);
+                    Some(due + LFO_PERIOD)
+                });
+                self.modulation_job = Some(job);
+            }
+            (false, Some(job)) => {
+                self.scheduler.handle().cancel(job);
+                self.modulation_job = None;
+            }
+            _ => {}
+        }
+    }
+
+    pub fn note_repeat(&self) -> NoteRepeat {
+        self.players.repeater.lock().unwrap().settings()
+    }
+
+    /// Sets how held notes repeat; turning repeat off ends those held.
+    pub fn set_note_repeat(&mut self, settings: NoteRepeat) {
+        let messages = self.players.repeater.lock().unwrap().set_settings(settings);
+        send_to_all(&self.outputs, &self.state, &messages);
+    }
+
+    /// Plays `note` and retriggers it on the clock at the note repeat rate
+    /// until [`release_note`](Self::release_note). Goes to every open
+    /// output, like the clock.
+    pub fn hold_note(&mut self, channel: Channel, note: Value7, velocity: Value7) {
+        self.players.envelopes.lock().unwrap().open(Gate::Note(channel, note));
+        let messages = self.players.repeater.lock().unwrap().hold(channel, note, velocity);
+        send_to_all(&self.outputs, &self.state, &messages);
+    }
+
+    /// Stops repeating a held note and ends it.
+    pub fn release_note(&mut self, channel: Channel, note: Value7) {
+        self.players.envelopes.lock().unwrap().close(Gate::Note(channel, note));
+        let messages = self.players.repeater.lock().unwrap().release(channel, note);
+        send_to_all(&self.outputs, &self.state, &messages);
+    }
+
+    /// Sends Song Position Pointer (in MIDI beats, i.e. 16th notes) so the
+    /// next Continue resumes from there. MMC devices get a Locate to the
+    /// same point in time at the current tempo.
+    pub fn song_position(&mut self, midi_beats: u16) -> Result<()> {
+        let secs = midi_beats as f64 * 60.0 / (self.bpm().max(1.0) as f64 * 4.0);
+        let rate = self.mtc_rate.unwrap_or_default();
+        let locate = Timecode::from_duration(Duration::from_secs_f64(secs), rate);
+        self.send_transport(Message::SongPosition(midi_beats), MmcCommand::Locate(locate))?;
+        if !self.following() {
+            self.transport.locate(midi_beats as u64);
+        }
+        Ok(())
+    }
+
+    /// Resumes playback from the downbeat of `bar` (1-based): stops if
+    /// running, then sends Song Position Pointer followed by Continue.
+    pub fn continue_from_bar(&mut self, bar: u64) -> Result<()> {
+        let beats = Position::bar_to_midi_beats(bar);
+        let beats = u16::try_from(beats)
+            .ok()
+            .filter(|b| *b <= 0x3FFF)
+            .ok_or_else(|| anyhow::anyhow!("Bar {} is beyond the Song Position range", bar))?;
+        if self.transport.is_running() {
+            self.stop()?;
+        }
+        self.song_position(beats)?;
+        self.resume()
+    }
+
+    /// Sends Continue and restarts the clock from the current position.
+    pub fn resume(&mut self) -> Result<()> {
+        self.cue_song(self.transport.ticks());
+        self.send_transport(Message::Realtime(Realtime::Continue), MmcCommand::Play)?;
+        if !self.following() {
+            self.transport.resume();
+            self.start_clock();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::backend::MockBackend;
+
+    fn channel(n: u8) -> Channel {
+        Channel::new(n).unwrap()
+    }
+
+    fn value(v: u8) -> Value7 {
+        Value7::new(v).unwrap()
+    }
+
+    /// A controller on a mock with two outputs, both open.
+    fn connected() -> (MidiController, MockBackend) {
+        let mock = MockBackend::new(&["A", "B"], &["In"]);
+        let mut ctrl = MidiController::with_backend(channel(1), Arc::new(mock.clone()));
+        ctrl.connect(0).unwrap();
+        ctrl.connect(1).unwrap();
+        (ctrl, mock)
+    }
+
+    #[test]
+    fn param_values_by_address() {
+        let (mut ctrl, _mock) = connected();
+        let nrpn = ParamAddress::Nrpn { msb: 1, lsb: 20 };
+        ctrl.send_param(channel(2), nrpn, value(90)).unwrap();
+        ctrl.send_param(channel(2), ParamAddress::Cc(74), value(33)).unwrap();
+        assert_eq!(ctrl.param_value(channel(2), nrpn), Some(value(90)));
+        assert_eq!(ctrl.param_value(channel(2), ParamAddress::Cc(74)), Some(value(33)));
+        assert_eq!(ctrl.param_value(channel(1), nrpn), None);
+        // Data entry after an RPN select is not the NRPN's
+        let rpn = [(101, 0), (100, 0), (6, 12)].map(|(cc, v)| Message::ControlChange {
+            channel: channel(2),
+            controller: Controller::new(cc).unwrap(),
+            value: value(v),
+        });
+        ctrl.send_batch(&rpn).unwrap();
+        assert_eq!(ctrl.param_value(channel(2), nrpn), Some(value(90)));
+    }
+
+    #[test]
+    fn routes_by_port_target() {
+        let (mut ctrl, mock) = connected();
+        let cc = |v| [0xB0, 74, v];
+        ctrl.send_cc(channel(1), Controller::new(74).unwrap(), value(1)).unwrap();
+        let sent = mock.take_sent();
+        assert_eq!(sent, [("A".to_string(), cc(1).to_vec()), ("B".to_string(), cc(1).to_vec())]);
+
+        ctrl.set_target(PortTarget::Port(1));
+        ctrl.send_cc(channel(1), Controller::new(74).unwrap(), value(2)).unwrap();
+        assert_eq!(mock.take_sent(), [("B".to_string(), cc(2).to_vec())]);
+
+        ctrl.set_target(PortTarget::Port(5));
+        let e = ctrl.send_cc(channel(1), Controller::new(74).unwrap(), value(3)).unwrap_err();
+        assert!(matches!(e.downcast_ref(), Some(SendError::PortNotConnected(5))));
+        assert!(mock.take_sent().is_empty());
+    }
+
+    #[test]
+    fn not_connected() {
+        let mock = MockBackend::new(&["A"], &[]);
+        let mut ctrl = MidiController::with_backend(channel(1), Arc::new(mock.clone()));
+        let e = ctrl.note_off(channel(1), value(60)).unwrap_err();
+        assert!(matches!(e.downcast_ref(), Some(SendError::NotConnected)));
+        assert!(mock.sent().is_empty());
+    }
+
+    #[test]
+    fn batches_with_running_status_per_profile() {
+        let (mut ctrl, mock) = connected();
+        let running = DeviceProfile { running_status: true, ..DeviceProfile::default() };
+        ctrl.set_device_profile("B", running);
+        ctrl.send_nrpn(channel(1), value(1), value(2), 0x81).unwrap();
+        let full = [0xB0, 99, 1, 0xB0, 98, 2, 0xB0, 6, 1, 0xB0, 38, 1];
+        let sent = mock.take_sent();
+        if PACKED_SENDS {
+            let packed = [0xB0, 99, 1, 98, 2, 6, 1, 38, 1];
+            let expected = [("A".to_string(), full.to_vec()), ("B".to_string(), packed.to_vec())];
+            assert_eq!(sent, expected);
+        } else {
+            // One message per send, so running status never applies
+            let bytes: Vec<u8> = sent.iter().flat_map(|(_, bytes)| bytes.clone()).collect();
+            assert_eq!(sent.len(), 8);
+            assert_eq!(bytes, [full, full].concat());
+        }
+    }
+
+    #[test]
+    fn invalid_batch_sends_nothing() {
+        let (mut ctrl, mock) = connected();
+        let bend = Message::PitchBend { channel: channel(1), value: 0x4000 };
+        let note = Message::NoteOn { channel: channel(1), note: value(60), velocity: value(1) };
+        assert!(ctrl.send_batch(&[note, bend]).is_err());
+        assert!(mock.sent().is_empty());
+        assert!(ctrl.held_notes().is_empty());
+    }
+
+    #[test]
+    fn shutdown_ends_held_notes() {
+        let (mut ctrl, mock) = connected();
+        ctrl.note_on(channel(1), value(60), value(100)).unwrap();
+        ctrl.note_on(channel(3), value(64), value(100)).unwrap();
+        ctrl.note_off(channel(1), value(60)).unwrap();
+        ctrl.set_target(PortTarget::Port(0));
+        mock.take_sent();
+
+        ctrl.shutdown().unwrap();
+        let off = vec![0x82, 64, 0];
+        assert_eq!(mock.take_sent(), [("A".to_string(), off.clone()), ("B".to_string(), off)]);
+        assert!(ctrl.held_notes().is_empty());
+        assert!(!ctrl.is_connected());
+    }
+
+    #[test]
+    fn clock_ticks_between_start_and_stop() {
+        let (mut ctrl, mock) = connected();
+        ctrl.set_target(PortTarget::Port(0));
+        ctrl.set_bpm(250.0);
+        let started = Instant::now();
+        ctrl.start().unwrap();
+        thread::sleep(Duration::from_millis(200));
+        ctrl.stop().unwrap();
+        let elapsed = started.elapsed();
+        assert!(!ctrl.clock_running());
+        thread::sleep(Duration::from_millis(30));
+
+        // Every output gets the ticks; only the targeted one Start and Stop
+        let sent = mock.take_sent();
+        let a: Vec<u8> = sent.iter().filter(|s| s.0 == "A").flat_map(|s| s.1.clone()).collect();
+        assert_eq!(a.first(), Some(&0xFA));
+        assert_eq!(a.last(), Some(&0xFC));
+        let ticks = &a[1..a.len() - 1];
+        assert!(ticks.iter().all(|b| *b == 0xF8), "{:02X?}", a);
+        // 10 ms apart, the first straight away
+        let most = elapsed.as_millis() as usize / 10 + 1;
+        assert!((15..=most).contains(&ticks.len()), "{} ticks in {:?}", ticks.len(), elapsed);
+        let b: Vec<u8> = sent.iter().filter(|s| s.0 == "B").flat_map(|s| s.1.clone()).collect();
+        assert_eq!(b, ticks);
+    }
+}
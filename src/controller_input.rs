@@ -0,0 +1,223 @@
+//! External controller input for the GUI: CCs from a hardware controller
+//! drive bound parameters, with soft takeover.
+
+use anyhow::{Context, Result};
+use eframe::egui;
+use midi_ctrl::midi_in::MidiInputHandle;
+use midi_ctrl::{
+    input_port_index, input_port_names, Binding, Channel, Config, Controller, InputEvent, Message,
+    MidiMap, MidirBackend, ParamAddress, Takeover, TakeoverMode, Value7,
+};
+use std::collections::HashMap;
+use std::sync::mpsc::{self, Receiver};
+
+const MODES: [(TakeoverMode, &str); 3] = [
+    (TakeoverMode::Pickup, "Pickup"),
+    (TakeoverMode::Jump, "Jump"),
+    (TakeoverMode::Relative, "Relative"),
+];
+
+#[derive(Default)]
+pub struct ControllerInput {
+    input: Option<(MidiInputHandle, Receiver<InputEvent>)>,
+    /// Takeover state by binding index; reset whenever bindings change.
+    states: Vec<Takeover>,
+    /// Binding whose channel and CC are set by the next CC received.
+    learning: Option<usize>,
+    /// Notes received since [`take_notes`](Self::take_notes): the note and
+    /// its velocity, `None` for a release.
+    notes: Vec<(Value7, Option<Value7>)>,
+}
+
+impl ControllerInput {
+    /// Opens the input port called `name`, closing any open one. Received
+    /// messages wake the GUI through `ctx`.
+    pub fn open(&mut self, name: Option<&str>, ctx: &egui::Context) -> Result<()> {
+        if let Some((handle, _)) = self.input.take() {
+            handle.close();
+        }
+        let Some(name) = name else {
+            return Ok(());
+        };
+        let port = input_port_index(name)?
+            .with_context(|| format!("No input port named '{}'", name))?;
+        let (tx, rx) = mpsc::channel();
+        let ctx = ctx.clone();
+        let handle = MidiInputHandle::open(&MidirBackend, port, move |event| {
+            let _ = tx.send(event);
+            ctx.request_repaint();
+        })?;
+        self.input = Some((handle, rx));
+        Ok(())
+    }
+
+    /// Parameter changes from the CCs received since the last call, given
+    /// the parameters' current `values`. Learned bindings are updated in
+    /// place; returns true in the second field when that happened.
+    pub fn poll(
+        &mut self,
+        bindings: &mut [Binding],
+        midi_map: &MidiMap,
+        values: &HashMap<ParamAddress, u8>,
+    ) -> (Vec<(ParamAddress, u8)>, bool) {
+        let mut changes = Vec::new();
+        let mut learned = false;
+        let Some((_, rx)) = &self.input else {
+            return (changes, learned);
+        };
+        self.states.resize(bindings.len(), Takeover::default());
+        // Later CCs in the batch see the values earlier ones set
+        let mut values = values.clone();
+        for event in rx.try_iter() {
+            let (channel, controller, value) = match event.message {
+                Some(Message::ControlChange { channel, controller, value }) => {
+                    (channel, controller, value)
+                }
+                // Note On at velocity 0 is a release
+                Some(Message::NoteOn { note, velocity, .. }) => {
+                    self.notes.push((note, (velocity.get() > 0).then_some(velocity)));
+                    continue;
+                }
+                Some(Message::NoteOff { note, .. }) => {
+                    self.notes.push((note, None));
+                    continue;
+                }
+                _ => continue,
+            };
+            if let Some(binding) = self.learning.take().and_then(|i| bindings.get_mut(i)) {
+                binding.channel = channel;
+                binding.controller = controller;
+                learned = true;
+                self.states.fill(Takeover::default());
+                continue;
+            }
+            for (binding, state) in bindings.iter().zip(&mut self.states) {
+                if (binding.channel, binding.controller) != (channel, controller) {
+                    continue;
+                }
+                let Some(param) = midi_map.get_by_name(&binding.parameter) else {
+                    continue;
+                };
+                let current = values.get(&param.address).copied().unwrap_or(param.default);
+                // Knob travel is spread over the range; encoder steps are not
+                let incoming = match binding.mode {
+                    TakeoverMode::Relative => value.get(),
+                    _ => param.range.apply(value.get()),
+                };
+                let new = state.feed(binding.mode, incoming, current);
+                if let Some(new) = new.map(|v| param.range.clamp(v)) {
+                    values.insert(param.address, new);
+                    changes.push((param.address, new));
+                }
+            }
+        }
+        (changes, learned)
+    }
+
+    /// Notes received since the last call, collected by [`poll`](Self::poll).
+    pub fn take_notes(&mut self) -> Vec<(Value7, Option<Value7>)> {
+        std::mem::take(&mut self.notes)
+    }
+
+    /// Input port picker and binding editor. Returns true when the config
+    /// changed and should be saved, and an error if the port failed to open.
+    pub fn menu(
+        &mut self,
+        ui: &mut egui::Ui,
+        config: &mut Config,
+        midi_map: &MidiMap,
+    ) -> (bool, Option<String>) {
+        let mut changed = false;
+        let mut error = None;
+
+        ui.horizontal(|ui| {
+            ui.label("Input:");
+            let mut selected = config.controller_input.clone();
+            egui::ComboBox::from_id_source("controller_input")
+                .selected_text(selected.as_deref().unwrap_or("None"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut selected, None, "None");
+                    for name in input_port_names().unwrap_or_default() {
+                        ui.selectable_value(&mut selected, Some(name.clone()), name);
+                    }
+                });
+            if selected != config.controller_input {
+                if let Err(e) = self.open(selected.as_deref(), ui.ctx()) {
+                    error = Some(format!("Failed to open controller input: {:#}", e));
+                }
+                config.controller_input = selected;
+                changed = true;
+            }
+        });
+        ui.separator();
+
+        let params = midi_map.get_all_parameters();
+        let mut removed = None;
+        egui::Grid::new("bindings").striped(true).show(ui, |ui| {
+            ui.label("Ch");
+            ui.label("CC");
+            ui.label("Parameter");
+            ui.label("Mode");
+            ui.end_row();
+            for (i, binding) in config.bindings.iter_mut().enumerate() {
+                let mut channel = binding.channel.get();
+                if ui.add(egui::DragValue::new(&mut channel).clamp_range(1..=16)).changed() {
+                    binding.channel = Channel::new(channel).unwrap_or_default();
+                    changed = true;
+                }
+                let mut cc = binding.controller.get();
+                if ui.add(egui::DragValue::new(&mut cc).clamp_range(0..=127)).changed() {
+                    binding.controller = Controller::new(cc).unwrap_or_default();
+                    changed = true;
+                }
+                egui::ComboBox::from_id_source(("binding_param", i))
+                    .selected_text(binding.parameter.as_str())
+                    .show_ui(ui, |ui| {
+                        for param in &params {
+                            let name = param.name.clone();
+                            changed |= ui
+                                .selectable_value(&mut binding.parameter, name, &param.name)
+                                .changed();
+                        }
+                    });
+                let mode_label = MODES.iter().find(|(m, _)| *m == binding.mode).map_or("", |m| m.1);
+                egui::ComboBox::from_id_source(("binding_mode", i))
+                    .selected_text(mode_label)
+                    .show_ui(ui, |ui| {
+                        for (mode, label) in MODES {
+                            changed |= ui.selectable_value(&mut binding.mode, mode, label).changed();
+                        }
+                    });
+                let mut learning = self.learning == Some(i);
+                if ui.toggle_value(&mut learning, "Learn").on_hover_text("Move a knob").changed() {
+                    self.learning = learning.then_some(i);
+                }
+                if ui.button("✖").clicked() {
+                    removed = Some(i);
+                }
+                ui.end_row();
+            }
+        });
+        if let Some(i) = removed {
+            config.bindings.remove(i);
+            self.learning = None;
+            changed = true;
+        }
+        if ui.button("Add binding").clicked()
+            && let Some(param) = params.first()
+        {
+            config.bindings.push(Binding {
+                channel: Channel::default(),
+                controller: Controller::default(),
+                parameter: param.name.clone(),
+                mode: TakeoverMode::default(),
+            });
+            self.learning = Some(config.bindings.len() - 1);
+            changed = true;
+        }
+        if changed {
+            self.states.clear();
+        }
+        (changed, error)
+    }
+}
@@ -0,0 +1,122 @@
+//! Supported device models: which parameter map, how many tracks on which
+//! channels, and how transport is sent by default.
+
+use crate::config::DeviceProfile;
+use crate::identity::DeviceIdentity;
+use crate::mmc::TransportProtocol;
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DeviceModel {
+    #[default]
+    Digitakt,
+    DigitaktII,
+    Digitone,
+    Syntakt,
+    ModelSamples,
+    /// Any General MIDI-ish device.
+    Generic,
+}
+
+impl DeviceModel {
+    pub const ALL: [DeviceModel; 6] = [
+        DeviceModel::Digitakt,
+        DeviceModel::DigitaktII,
+        DeviceModel::Digitone,
+        DeviceModel::Syntakt,
+        DeviceModel::ModelSamples,
+        DeviceModel::Generic,
+    ];
+
+    /// Name as typed on the command line and stored in the config.
+    pub fn id(self) -> &'static str {
+        match self {
+            DeviceModel::Digitakt => "digitakt",
+            DeviceModel::DigitaktII => "digitakt-2",
+            DeviceModel::Digitone => "digitone",
+            DeviceModel::Syntakt => "syntakt",
+            DeviceModel::ModelSamples => "model-samples",
+            DeviceModel::Generic => "generic",
+        }
+    }
+
+    /// Tracks listening on channels 1 to this, the device's default.
+    pub fn tracks(self) -> u8 {
+        match self {
+            DeviceModel::Digitakt => 8,
+            DeviceModel::DigitaktII => 16,
+            DeviceModel::Digitone => 4,
+            DeviceModel::Syntakt => 12,
+            DeviceModel::ModelSamples => 6,
+            DeviceModel::Generic => 16,
+        }
+    }
+
+    /// The model a port name announces, e.g. `Elektron Digitone` or
+    /// `Digitakt II MIDI 1`.
+    pub fn from_port_name(name: &str) -> Option<DeviceModel> {
+        let name = name.to_lowercase();
+        // Longer names first: "digitakt ii" also contains "digitakt"
+        let patterns = [
+            ("digitakt ii", DeviceModel::DigitaktII),
+            ("digitakt 2", DeviceModel::DigitaktII),
+            ("digitakt", DeviceModel::Digitakt),
+            ("digitone", DeviceModel::Digitone),
+            ("syntakt", DeviceModel::Syntakt),
+            ("model:samples", DeviceModel::ModelSamples),
+            ("model samples", DeviceModel::ModelSamples),
+        ];
+        patterns.into_iter().find(|(pattern, _)| name.contains(pattern)).map(|(_, model)| model)
+    }
+
+    /// The model an identity reply names, for known Elektron products.
+    pub fn from_identity(identity: &DeviceIdentity) -> Option<DeviceModel> {
+        identity.model_name()?.parse().ok()
+    }
+
+    /// Settings for a port with this device and no saved profile. Elektron
+    /// boxes follow realtime Start/Stop; other gear may only know MMC.
+    pub fn default_profile(self) -> DeviceProfile {
+        let transport = match self {
+            DeviceModel::Generic => TransportProtocol::Both,
+            _ => TransportProtocol::Realtime,
+        };
+        DeviceProfile { transport, ..DeviceProfile::default() }
+    }
+}
+
+impl fmt::Display for DeviceModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            DeviceModel::Digitakt => "Digitakt",
+            DeviceModel::DigitaktII => "Digitakt II",
+            DeviceModel::Digitone => "Digitone",
+            DeviceModel::Syntakt => "Syntakt",
+            DeviceModel::ModelSamples => "Model:Samples",
+            DeviceModel::Generic => "Generic",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for DeviceModel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> anyhow::Result<Self> {
+        let mut s = s.trim().to_lowercase().replace([':', ' ', '_'], "-");
+        if s == "digitakt-ii" || s == "digitakt2" {
+            s = "digitakt-2".to_string();
+        }
+        match DeviceModel::ALL.into_iter().find(|model| model.id() == s) {
+            Some(model) => Ok(model),
+            None => {
+                let names: Vec<_> = DeviceModel::ALL.iter().map(|m| m.id()).collect();
+                bail!("Unknown device '{}' (expected {})", s, names.join(", "))
+            }
+        }
+    }
+}
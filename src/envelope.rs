@@ -0,0 +1,264 @@
+//! Envelope generators: attack, decay, sustain and release shapes sent as
+//! parameter changes, triggered by Start, by sequencer steps or by notes,
+//! e.g. to open the filter slowly over the first bars after Start.
+
+use crate::midi::Message;
+use crate::midi_map::{MidiMap, ParamAddress};
+use crate::note::Note;
+use crate::types::{Channel, Value7};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::time::Duration;
+
+/// Envelopes available.
+pub const MAX_ENVELOPES: usize = 4;
+
+/// What opens an envelope's gate, and what closes it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(tag = "on", rename_all = "snake_case")]
+pub enum EnvelopeTrigger {
+    /// Start, held until Stop.
+    #[default]
+    Start,
+    /// A sequencer step playing (numbered from 1, as shown); these run
+    /// through attack and decay, then release.
+    Step { track: usize, step: usize },
+    /// A Note On from the input or the pads, held until its Note Off;
+    /// any note or channel where unset.
+    Note {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        note: Option<Note>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        channel: Option<Channel>,
+    },
+}
+
+impl EnvelopeTrigger {
+    pub fn label(&self) -> &'static str {
+        match self {
+            EnvelopeTrigger::Start => "Start",
+            EnvelopeTrigger::Step { .. } => "Step",
+            EnvelopeTrigger::Note { .. } => "Note",
+        }
+    }
+}
+
+impl fmt::Display for EnvelopeTrigger {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EnvelopeTrigger::Start => write!(f, "Start"),
+            EnvelopeTrigger::Step { track, step } => write!(f, "track {} step {}", track, step),
+            EnvelopeTrigger::Note { note, channel } => {
+                match note {
+                    Some(note) => write!(f, "note {}", note)?,
+                    None => write!(f, "any note")?,
+                }
+                match channel {
+                    Some(channel) => write!(f, " on ch {}", channel),
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// A gate opening or closing, matched against envelopes' triggers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Gate {
+    Transport,
+    /// Track and step, numbered from 0.
+    Step(usize, usize),
+    Note(Channel, Value7),
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Envelope {
+    pub enabled: bool,
+    pub trigger: EnvelopeTrigger,
+    /// Stage times are in beats at the current tempo rather than seconds.
+    pub sync: bool,
+    pub attack: f32,
+    pub decay: f32,
+    /// Level held after the decay while the gate is open, 0-100%.
+    pub sustain: u8,
+    pub release: f32,
+    /// The value at rest, before the attack and after the release.
+    pub from: u8,
+    /// The value at the top of the attack.
+    pub peak: u8,
+    pub channel: Channel,
+    /// Name as `set` takes it; empty for none.
+    pub parameter: String,
+    /// Looked up by [`Envelope::resolve`]; envelopes without one send
+    /// nothing.
+    #[serde(skip)]
+    pub address: Option<ParamAddress>,
+}
+
+impl Default for Envelope {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            trigger: EnvelopeTrigger::default(),
+            sync: false,
+            attack: 0.5,
+            decay: 0.5,
+            sustain: 100,
+            release: 0.5,
+            from: 0,
+            peak: 127,
+            channel: Channel::default(),
+            parameter: String::new(),
+            address: None,
+        }
+    }
+}
+
+impl Envelope {
+    /// Looks up the parameter in `midi_map`, e.g. after loading or a
+    /// change of device model.
+    pub fn resolve(&mut self, midi_map: &MidiMap) {
+        self.address = midi_map.get_by_name(&self.parameter).map(|param| param.address);
+    }
+
+    fn opens_on(&self, gate: Gate) -> bool {
+        match (self.trigger, gate) {
+            (EnvelopeTrigger::Start, Gate::Transport) => true,
+            (EnvelopeTrigger::Step { track, step }, Gate::Step(t, s)) => {
+                (t + 1, s + 1) == (track, step)
+            }
+            (EnvelopeTrigger::Note { note, channel }, Gate::Note(c, n)) => {
+                note.is_none_or(|note| note.value() == n) && channel.is_none_or(|ch| ch == c)
+            }
+            _ => false,
+        }
+    }
+
+    /// The value sent at `level` (0-1) of the envelope.
+    fn value(&self, level: f64) -> u8 {
+        let (from, peak) = (self.from as f64, self.peak as f64);
+        (from + (peak - from) * level).round().clamp(0.0, 127.0) as u8
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum Stage {
+    #[default]
+    Idle,
+    Attack,
+    Decay,
+    Sustain,
+    Release,
+}
+
+/// Where an envelope is, and what it last sent.
+#[derive(Debug, Clone, Copy, Default)]
+struct Voice {
+    stage: Stage,
+    level: f64,
+    /// Level the release started from.
+    released: f64,
+    /// Released after the decay rather than by a closing gate.
+    one_shot: bool,
+    sent: Option<u8>,
+}
+
+/// Runs the envelopes as their gates open and close.
+#[derive(Debug, Default)]
+pub struct EnvelopeBank {
+    envelopes: Vec<Envelope>,
+    voices: Vec<Voice>,
+}
+
+impl EnvelopeBank {
+    /// Replaces the envelopes; those kept carry on where they were.
+    pub fn set_envelopes(&mut self, envelopes: Vec<Envelope>) {
+        self.voices.resize(envelopes.len(), Voice::default());
+        for (voice, envelope) in self.voices.iter_mut().zip(&envelopes) {
+            if !envelope.enabled {
+                *voice = Voice::default();
+            }
+        }
+        self.envelopes = envelopes;
+    }
+
+    /// Whether any envelope has a parameter to send to.
+    pub fn is_active(&self) -> bool {
+        self.envelopes.iter().any(|env| env.enabled && env.address.is_some())
+    }
+
+    /// Starts the attack of the envelopes `gate` triggers, from where they
+    /// are, so a retrigger does not jump.
+    pub fn open(&mut self, gate: Gate) {
+        for (voice, envelope) in self.voices.iter_mut().zip(&self.envelopes) {
+            if envelope.enabled && envelope.opens_on(gate) {
+                voice.stage = Stage::Attack;
+                voice.one_shot = matches!(gate, Gate::Step(..));
+            }
+        }
+    }
+
+    /// Releases the envelopes `gate` holds open.
+    pub fn close(&mut self, gate: Gate) {
+        for (voice, envelope) in self.voices.iter_mut().zip(&self.envelopes) {
+            if envelope.opens_on(gate) && voice.stage != Stage::Idle && !voice.one_shot {
+                voice.stage = Stage::Release;
+                voice.released = voice.level;
+            }
+        }
+    }
+
+    /// What to send `elapsed` after the previous run, with beats `beat`
+    /// long: each running envelope's value when it changed.
+    pub fn run(&mut self, elapsed: Duration, beat: Duration) -> Vec<Message> {
+        let mut messages = Vec::new();
+        for (voice, envelope) in self.voices.iter_mut().zip(&self.envelopes) {
+            let Some(address) = envelope.address.filter(|_| envelope.enabled) else {
+                continue;
+            };
+            if voice.stage == Stage::Idle {
+                continue;
+            }
+            let unit = if envelope.sync { beat.as_secs_f64() } else { 1.0 };
+            // The fraction of a stage `time` units long that has passed
+            let step = |time: f32| match time as f64 * unit {
+                secs if secs > 0.0 => elapsed.as_secs_f64() / secs,
+                _ => 1.0,
+            };
+            let sustain = envelope.sustain.min(100) as f64 / 100.0;
+            match voice.stage {
+                Stage::Attack => {
+                    voice.level += step(envelope.attack);
+                    if voice.level >= 1.0 {
+                        (voice.level, voice.stage) = (1.0, Stage::Decay);
+                    }
+                }
+                Stage::Decay => {
+                    voice.level -= step(envelope.decay) * (1.0 - sustain);
+                    if voice.level <= sustain {
+                        voice.level = sustain;
+                        voice.stage = Stage::Sustain;
+                    }
+                }
+                Stage::Sustain if voice.one_shot => {
+                    (voice.stage, voice.released) = (Stage::Release, voice.level);
+                }
+                Stage::Sustain | Stage::Idle => {}
+                Stage::Release => {
+                    voice.level -= step(envelope.release) * voice.released;
+                    if voice.level <= 0.0 {
+                        (voice.level, voice.stage) = (0.0, Stage::Idle);
+                    }
+                }
+            }
+            let value = envelope.value(voice.level);
+            if voice.sent != Some(value) {
+                voice.sent = Some(value);
+                let value = Value7::new(value).unwrap_or_default();
+                messages.extend(address.messages(envelope.channel, value).into_iter().flatten());
+            }
+        }
+        messages
+    }
+}
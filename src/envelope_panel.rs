@@ -0,0 +1,162 @@
+//! Envelopes page of the GUI: each envelope generator's trigger, stage
+//! times and levels, and the parameter it sends.
+
+use eframe::egui;
+use midi_ctrl::envelope::MAX_ENVELOPES;
+use midi_ctrl::{Channel, Envelope, EnvelopeTrigger, MidiMap, Note, Value7};
+
+/// Longest stage in the drags, in seconds or beats.
+const MAX_TIME: f32 = 64.0;
+
+/// The trigger's kind and settings. Returns true on change.
+fn trigger(ui: &mut egui::Ui, index: usize, trigger: &mut EnvelopeTrigger) -> bool {
+    let mut changed = false;
+    let kinds = [
+        EnvelopeTrigger::Start,
+        EnvelopeTrigger::Step { track: 1, step: 1 },
+        EnvelopeTrigger::Note { note: None, channel: None },
+    ];
+    egui::ComboBox::from_id_source(("env_trigger", index))
+        .width(60.0)
+        .selected_text(trigger.label())
+        .show_ui(ui, |ui| {
+            for kind in kinds {
+                let current = trigger.label() == kind.label();
+                if ui.selectable_label(current, kind.label()).clicked() && !current {
+                    *trigger = kind;
+                    changed = true;
+                }
+            }
+        })
+        .response
+        .on_hover_text("Start holds it open until Stop, a note until its Note Off");
+    match trigger {
+        EnvelopeTrigger::Start => {}
+        EnvelopeTrigger::Step { track, step } => {
+            let drag = egui::DragValue::new(track).clamp_range(1..=16).prefix("track ");
+            changed |= ui.add(drag).changed();
+            let drag = egui::DragValue::new(step).clamp_range(1..=64).prefix("step ");
+            changed |= ui.add(drag).changed();
+        }
+        EnvelopeTrigger::Note { note, channel } => {
+            let mut any = note.is_none();
+            if ui.checkbox(&mut any, "Any note").changed() {
+                *note = (!any).then(|| Note::new(Value7::new(60).unwrap_or_default()));
+                changed = true;
+            }
+            if let Some(note) = note {
+                let mut value = note.value().get();
+                let name = |n: f64| Note::from(Value7::new(n as u8).unwrap_or_default());
+                let drag = egui::DragValue::new(&mut value)
+                    .clamp_range(0..=127)
+                    .custom_formatter(|n, _| name(n).to_string());
+                if ui.add(drag).changed() {
+                    *note = Note::new(Value7::new(value).unwrap_or_default());
+                    changed = true;
+                }
+            }
+            let mut any = channel.is_none();
+            if ui.checkbox(&mut any, "Any ch").changed() {
+                *channel = (!any).then(Channel::default);
+                changed = true;
+            }
+            if let Some(channel) = channel {
+                let mut value = channel.get();
+                let drag = egui::DragValue::new(&mut value).clamp_range(1..=16).prefix("ch ");
+                if ui.add(drag).changed() {
+                    *channel = Channel::new(value).unwrap_or(*channel);
+                    changed = true;
+                }
+            }
+        }
+    }
+    changed
+}
+
+/// One envelope's controls. Returns true on change.
+fn envelope_row(ui: &mut egui::Ui, index: usize, env: &mut Envelope, midi_map: &MidiMap) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        changed |= ui.checkbox(&mut env.enabled, format!("Env {}", index + 1)).changed();
+        changed |= trigger(ui, index, &mut env.trigger);
+        let mut channel = env.channel.get();
+        let drag = egui::DragValue::new(&mut channel).clamp_range(1..=16).prefix("ch ");
+        if ui.add(drag).changed() {
+            env.channel = Channel::new(channel).unwrap_or(env.channel);
+            changed = true;
+        }
+        let selected = match env.parameter.as_str() {
+            "" => "Parameter…",
+            name => name,
+        };
+        egui::ComboBox::from_id_source(("env_parameter", index))
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for param in midi_map.get_all_parameters() {
+                    let current = env.address == Some(param.address);
+                    if ui.selectable_label(current, &param.name).clicked() {
+                        env.parameter = param.name.clone();
+                        env.address = Some(param.address);
+                        changed = true;
+                    }
+                }
+            });
+        if !env.parameter.is_empty() && env.address.is_none() {
+            ui.weak("(not in this device's map)");
+        }
+    });
+    ui.horizontal(|ui| {
+        let unit = if env.sync { " beats" } else { " s" };
+        let time = |time| {
+            egui::DragValue::new(time).clamp_range(0.0..=MAX_TIME).speed(0.05).suffix(unit)
+        };
+        ui.label("A");
+        changed |= ui.add(time(&mut env.attack)).changed();
+        ui.label("D");
+        changed |= ui.add(time(&mut env.decay)).changed();
+        ui.label("S");
+        let drag = egui::DragValue::new(&mut env.sustain).clamp_range(0..=100).suffix("%");
+        changed |= ui.add(drag).changed();
+        ui.label("R");
+        changed |= ui.add(time(&mut env.release)).changed();
+        let sync = ui.checkbox(&mut env.sync, "Beats").on_hover_text("Time the stages in beats");
+        changed |= sync.changed();
+        ui.label("From");
+        changed |= ui.add(egui::DragValue::new(&mut env.from).clamp_range(0..=127)).changed();
+        ui.label("Peak");
+        changed |= ui.add(egui::DragValue::new(&mut env.peak).clamp_range(0..=127)).changed();
+    });
+    changed
+}
+
+/// Draws the envelopes, with a button adding one on `channel` up to
+/// [`MAX_ENVELOPES`]. Returns true when they were edited and should be
+/// sent and saved.
+pub fn show(
+    ui: &mut egui::Ui,
+    envelopes: &mut Vec<Envelope>,
+    midi_map: &MidiMap,
+    channel: Channel,
+) -> bool {
+    let mut edited = false;
+    let mut removed = None;
+    for (index, env) in envelopes.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            ui.vertical(|ui| edited |= envelope_row(ui, index, env, midi_map));
+            if ui.small_button("🗑").on_hover_text("Remove envelope").clicked() {
+                removed = Some(index);
+            }
+        });
+        ui.separator();
+    }
+    if let Some(index) = removed {
+        envelopes.remove(index);
+        edited = true;
+    }
+    let add = egui::Button::new(format!("Add envelope on ch {}", channel));
+    if ui.add_enabled(envelopes.len() < MAX_ENVELOPES, add).clicked() {
+        envelopes.push(Envelope { channel, ..Envelope::default() });
+        edited = true;
+    }
+    edited
+}
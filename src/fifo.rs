@@ -0,0 +1,71 @@
+//! `--listen-fifo`: commands echoed into a named pipe by other programs,
+//! run alongside the ones typed at the prompt.
+
+use anyhow::Result;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use std::thread;
+
+/// Creates the pipe if needed and starts a thread passing each line
+/// written to it to `tx`, wrapped by `wrap`. The pipe is reopened after
+/// every writer closes, so `echo start > pipe` works any number of times.
+pub fn listen_fifo<T, F>(path: &Path, tx: Sender<T>, wrap: F) -> Result<()>
+where
+    T: Send + 'static,
+    F: Fn(String) -> T + Send + 'static,
+{
+    create_fifo(path)?;
+    let path = path.to_path_buf();
+    thread::spawn(move || {
+        loop {
+            // Blocks until a writer opens the other end
+            let file = match File::open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("✗ Stopped reading {}: {}", path.display(), e);
+                    return;
+                }
+            };
+            for line in BufReader::new(file).lines() {
+                let Ok(line) = line else {
+                    break;
+                };
+                if line.trim().is_empty() {
+                    continue;
+                }
+                if tx.send(wrap(line)).is_err() {
+                    return;
+                }
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_fifo(path: &Path) -> Result<()> {
+    use anyhow::Context;
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::metadata(path) {
+        Ok(meta) if meta.file_type().is_fifo() => Ok(()),
+        Ok(_) => anyhow::bail!("{} exists and is not a named pipe", path.display()),
+        Err(_) => {
+            let status = std::process::Command::new("mkfifo")
+                .arg(path)
+                .status()
+                .context("Failed to run mkfifo")?;
+            if !status.success() {
+                anyhow::bail!("mkfifo failed for {}", path.display());
+            }
+            Ok(())
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn create_fifo(path: &Path) -> Result<()> {
+    anyhow::bail!("Can't listen on {}: named pipes need Unix", path.display())
+}
@@ -1,433 +1,2923 @@
+use crate::activity::{Activity, ActivityLog, Direction};
+use crate::automation_editor;
+use crate::controller_input::ControllerInput;
+use crate::envelope_panel;
+use crate::keyboard::Keyboard;
+use crate::knob;
+use crate::lfo_panel;
+use crate::midi_tracks::MidiTracks;
+use crate::mixer::Mixer;
+use crate::morph::Morph;
+use crate::pads::Pads;
+use crate::song_editor::SongEditor;
+use crate::step_grid::StepGrid;
+use crate::xy_pad::XyPad;
 use anyhow::Result;
 use eframe::{egui, NativeOptions};
-use midir::{MidiOutput, MidiOutputConnection};
-use std::sync::mpsc::{self, Receiver, Sender};
+use midi_ctrl::clock::{MAX_SWING, MIN_SWING, PPQN};
+use midi_ctrl::harmony::KEYS;
+use midi_ctrl::pattern::{self, Pattern, PatternChange};
+use midi_ctrl::randomize::DEFAULT_AMOUNT;
+use midi_ctrl::recorder::MAX_COUNT_IN;
+use midi_ctrl::transport::TICKS_PER_BAR;
+use midi_ctrl::{
+    input_port_index, input_port_names, AutomationLane, AutomationRecorder, BEATS_PER_BAR, Channel,
+    ChordMode, ChordShape, ClockSource, Config, Curve, DeviceInstance, DeviceModel, DeviceProfile,
+    Envelope, FileWatch, FrameRate, GuiSettings, InputQuantize, Lfo, MapFile, MidiController,
+    MidiMap, MidiParameter, MmcCommand, Note, NoteRepeat, PanelLayout, ParamAddress, ParamRange,
+    PortEvent, PortTarget, Position, Randomizer, Recording, RepeatRate, Scale, SeqTrack, Snapshot,
+    Song, TapTempo, Theme, Transport, TransportProtocol, Value7, Voicing,
+};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
 use std::thread;
-use crate::midi_map::MidiMap;
+use std::time::{Duration, Instant};
+use tracing::{debug, error, info, trace, warn};
 
 #[derive(Debug, Clone)]
 pub enum MidiCommand {
-    Connect(Option<usize>, u8),
+    /// Opens the given output ports (added to any already open).
+    Connect(Vec<usize>, Channel),
     Disconnect,
-    SendCC { channel: u8, controller: u8, value: u8 },
+    SendParam { channel: Channel, address: ParamAddress, value: Value7 },
+    /// Re-sends a set of values in paced bursts, to bring the device back
+    /// in line with the controls.
+    SyncParams(Vec<(Channel, ParamAddress, Value7)>),
+    PitchBend { channel: Channel, bend: i16 },
+    NoteOn { channel: Channel, note: Value7, velocity: Value7 },
+    NoteOff { channel: Channel, note: Value7 },
+    /// Waits for the next bar or the end of a `bars` long pattern while
+    /// playing, per `change`.
+    SelectPattern { channel: Channel, pattern: Pattern, change: PatternChange, bars: u32 },
     Start,
     Stop,
     Continue,
+    /// Song Position Pointer to the start of a bar (1-based), then Continue.
+    Locate(u64),
+    Mmc(MmcCommand),
+    SetDeviceProfile { port_name: String, profile: DeviceProfile },
     QueryDevice,
     SetBpm(f32),
+    /// Runs the clock output continuously, independent of Start/Stop.
+    SetFreeClock(bool),
+    /// Swing in percent, for the clock and the sequencer's steps.
+    SetSwing(u8),
+    SetClockSource(ClockSource),
+    SetMtcRate(Option<FrameRate>),
+    /// Parameter sends per second at most (see `GuiSettings::cc_rate`).
+    SetCcRate(u32),
+    SetChordMode(ChordMode),
+    SetNoteRepeat(NoteRepeat),
+    SetSequence(Vec<SeqTrack>),
+    /// Plays the sequence with the transport, or stops playing it.
+    SetSequencer(bool),
+    /// Records notes played into the sequence's track `track` (by index);
+    /// `None` stops recording.
+    SetRecording { track: Option<usize>, settings: Recording },
+    SetSong { song: Song, channel: Channel },
+    /// Follows the song with the transport, or stops following it.
+    SetSongEnabled(bool),
+    SetAutomation(Vec<AutomationLane>),
+    /// Plays the automation with the transport, or stops playing it.
+    SetAutomationEnabled(bool),
+    SetLfos(Vec<Lfo>),
+    SetEnvelopes(Vec<Envelope>),
     Quit,
 }
 
+/// A command plus the output(s) it is sent to.
+#[derive(Debug, Clone)]
+pub struct Routed {
+    pub target: PortTarget,
+    pub cmd: MidiCommand,
+}
+
 #[derive(Debug, Clone)]
 pub enum DeviceState {
     Artist(String),
     Bpm(f32),
+    /// Names of selected outputs that vanished and are being retried.
+    LostPorts(Vec<String>),
+    /// The engine's transport, sent once so the GUI can read the position
+    /// every frame.
+    Transport(Arc<Transport>),
+    ClockSource(ClockSource),
+    FreeClock(bool),
+    /// Outcome of a Connect: the ports that opened, or why none did.
+    ConnectResult(Result<Vec<usize>, String>),
+    /// A failed connect or send, or a lost device, to show the user.
+    Error(String),
+    /// A message sent or received, for the activity log.
+    Activity(Activity),
+    /// The device model an output port's identity reply names.
+    Identified { port_name: String, model: DeviceModel },
+    /// The sequence after a note was recorded into it.
+    Sequence(Vec<SeqTrack>),
+    /// The clock tick recording starts on after the count-in, once placed.
+    RecordFrom(Option<u64>),
 }
 
-fn open_output(port_index: usize) -> Result<MidiOutputConnection> {
-    let midi_out = MidiOutput::new("midi_ctrl")?;
-    let ports = midi_out.ports();
-    let port = ports.get(port_index).ok_or_else(|| {
-        anyhow::anyhow!("No MIDI output port at index {}", port_index)
-    })?;
-    let port_name = midi_out
-        .port_name(port)
-        .unwrap_or_else(|_| "<unknown>".to_string());
-    let conn_out = midi_out
-        .connect(port, &format!("midi_ctrl-{}", port_name))?;
-    Ok(conn_out)
+/// How often the worker re-scans the port list.
+const HOTPLUG_POLL: Duration = Duration::from_secs(1);
+/// How often the config and map files are checked for outside edits.
+const FILE_POLL: Duration = Duration::from_secs(1);
+/// How often the worker reports transport state back to the GUI.
+const STATE_POLL: Duration = Duration::from_millis(100);
+/// Tempo change while a nudge button is held, as a fraction.
+const NUDGE: f32 = 0.03;
+/// How often the bar:beat:tick display refreshes while playing.
+const POSITION_REFRESH: Duration = Duration::from_millis(30);
+/// How long an error toast stays up.
+const TOAST_DURATION: Duration = Duration::from_secs(5);
+/// Toasts shown at once; older ones are dropped first.
+const MAX_TOASTS: usize = 4;
+
+/// Logs a worker failure and passes it to the GUI, where stderr is out
+/// of sight.
+fn report_error(state_tx: &Sender<DeviceState>, message: String) {
+    error!(target: "worker", "{}", message);
+    let _ = state_tx.send(DeviceState::Error(message));
 }
 
-fn send_realtime(conn: &mut MidiOutputConnection, byte: u8) -> Result<()> {
-    conn.send(&[byte])?;
-    Ok(())
+fn poll_hotplug(ctrl: &mut MidiController, state_tx: &Sender<DeviceState>) {
+    if !ctrl.is_connected() && ctrl.lost_ports().is_empty() {
+        return;
+    }
+    let events = match ctrl.check_ports() {
+        Ok(events) => events,
+        Err(e) => {
+            report_error(state_tx, format!("Failed to scan MIDI ports: {:#}", e));
+            return;
+        }
+    };
+    for event in &events {
+        match event {
+            PortEvent::Lost(name) => report_error(state_tx, format!("Lost port {}", name)),
+            PortEvent::Reconnected { name, port } => {
+                info!(target: "worker", "Reconnected {} (#{})", name, port);
+                let artist = identify_device(ctrl, *port, state_tx);
+                let _ = state_tx.send(DeviceState::Artist(artist));
+            }
+        }
+    }
+    if !events.is_empty() {
+        let lost = ctrl.lost_ports().into_iter().map(str::to_string).collect();
+        let _ = state_tx.send(DeviceState::LostPorts(lost));
+    }
 }
 
-fn send_cc(conn: &mut MidiOutputConnection, channel: u8, controller: u8, value: u8) -> Result<()> {
-    let status = 0xB0 | ((channel - 1) & 0x0F);
-    conn.send(&[status, controller, value])?;
-    Ok(())
+fn cc_interval(rate: u32) -> Duration {
+    match rate {
+        0 => Duration::ZERO,
+        rate => Duration::from_secs_f32(1.0 / rate as f32),
+    }
 }
 
-fn send_timing_clock(conn: &mut MidiOutputConnection, bpm: f32, ticks: u32) -> Result<()> {
-    // Send timing clock pulses at the given BPM
-    // MIDI clock = 24 pulses per quarter note
-    // Time between pulses = 60 / (BPM * 24) seconds
-    let ms_per_tick = (60.0 / (bpm * 24.0)) * 1000.0;
-    
-    for _ in 0..ticks {
-        send_realtime(conn, 0xF8)?; // Clock (0xF8)
-        let duration = std::time::Duration::from_millis(ms_per_tick as u64);
-        thread::sleep(duration);
+/// Sends the parameter changes held back by the rate limit, the latest
+/// value of each.
+fn flush_params(
+    ctrl: &mut MidiController,
+    target: PortTarget,
+    pending: &mut Vec<(Channel, ParamAddress, Value7)>,
+    state_tx: &Sender<DeviceState>,
+) {
+    let burst = std::mem::take(pending);
+    let Some(&(channel, address, value)) = burst.last() else {
+        return;
+    };
+    if !ctrl.is_connected() {
+        return;
+    }
+    ctrl.set_target(target);
+    match ctrl.send_params(&burst) {
+        Ok(()) => debug!(target: "worker", "{} = {} (ch {})", address, value, channel),
+        Err(e) => report_error(state_tx, format!("Failed to send {}: {:#}", address, e)),
     }
-    Ok(())
 }
 
-pub fn run_gui(_midi_out: MidiOutput, port_names: Vec<String>, initial_channel: u8) -> Result<()> {
-    let (tx, rx) = mpsc::channel::<MidiCommand>();
+/// Opens the input port paired with an open output (same name) and asks
+/// the device on that port who it is. Returns the label shown in the top panel.
+/// Everything received on the input is passed on for the activity log.
+fn identify_device(
+    ctrl: &mut MidiController,
+    port: usize,
+    state_tx: &Sender<DeviceState>,
+) -> String {
+    let Some(Ok(Some(input_idx))) = ctrl.port_name(port).as_deref().map(input_port_index) else {
+        warn!(target: "worker", "No input port matching the output; device identity unavailable");
+        return "Unknown".to_string();
+    };
+    let input_rx = match ctrl.connect_input(input_idx) {
+        Ok(rx) => rx,
+        Err(e) => {
+            error!(target: "worker", "Failed to open input port {}: {:?}", input_idx, e);
+            return "Unknown".to_string();
+        }
+    };
+    let activity_tx = state_tx.clone();
+    let port_name = input_port_names()
+        .ok()
+        .and_then(|names| names.get(input_idx).cloned())
+        .unwrap_or_else(|| format!("#{}", input_idx));
+    // Ends when the input is closed
+    thread::spawn(move || {
+        for event in input_rx {
+            let _ = activity_tx.send(DeviceState::Activity(Activity {
+                at: Instant::now(),
+                direction: Direction::Received,
+                port: port_name.clone(),
+                bytes: event.bytes,
+            }));
+        }
+    });
+    let previous_target = ctrl.target();
+    ctrl.set_target(PortTarget::Port(port));
+    let identity = ctrl.identify(Duration::from_millis(1000));
+    ctrl.set_target(previous_target);
+    match identity {
+        Ok(id) => {
+            info!(target: "worker", "Identified {}", id);
+            if let (Some(model), Some(port_name)) =
+                (DeviceModel::from_identity(&id), ctrl.port_name(port))
+            {
+                let _ = state_tx.send(DeviceState::Identified { port_name, model });
+            }
+            id.to_string()
+        }
+        Err(e) => {
+            warn!(target: "worker", "{}", e);
+            "Unknown".to_string()
+        }
+    }
+}
+
+pub fn run_gui(
+    port_names: Vec<String>,
+    initial_ports: Vec<usize>,
+    initial_channel: Channel,
+    config: Config,
+) -> Result<()> {
+    let (tx, rx) = mpsc::channel::<Routed>();
     let (state_tx, state_rx) = mpsc::channel::<DeviceState>();
 
-    // Background thread owns the MidiOutputConnection and performs sends.
-    thread::spawn(move || {
-        let mut conn: Option<MidiOutputConnection> = None;
-        let mut _current_port: Option<usize> = None;
-        let mut _current_channel: u8 = initial_channel;
-        let mut current_bpm: f32 = 120.0;
+    let profiles = config.port_profiles(&port_names);
+    let default_profile = config.default_model().default_profile();
+    let cc_rate = config.gui.cc_rate;
+    let mut chord_mode = config.chord_mode;
+    let note_repeat = config.note_repeat;
+
+    // Background thread owns the MidiController and performs sends.
+    let worker = thread::spawn(move || {
+        let mut ctrl = MidiController::new(initial_channel);
+        for (name, profile) in &profiles {
+            ctrl.set_device_profile(name, *profile);
+        }
+        ctrl.set_default_profile(default_profile);
+        ctrl.set_note_repeat(note_repeat);
+        let _ = state_tx.send(DeviceState::Transport(ctrl.shared_transport()));
+        let activity_tx = state_tx.clone();
+        ctrl.set_send_monitor(Some(Arc::new(move |port: &str, bytes: &[u8]| {
+            let _ = activity_tx.send(DeviceState::Activity(Activity {
+                at: Instant::now(),
+                direction: Direction::Sent,
+                port: port.to_string(),
+                bytes: bytes.to_vec(),
+            }));
+        })));
+        let mut last_scan = Instant::now();
+        let mut record_from = None;
+        // Notes sounding for each note played, chords in chord mode, and
+        // whether they repeat
+        let mut chords: HashMap<(Channel, Value7), (Vec<Value7>, bool)> = HashMap::new();
+        // Slider sweeps queue changes faster than the device wants them;
+        // they wait here and go out at most once per interval
+        let mut pending: Vec<(Channel, ParamAddress, Value7)> = Vec::new();
+        let mut pending_target = PortTarget::All;
+        let mut interval = cc_interval(cc_rate);
+        let mut last_flush = Instant::now();
+
+        loop {
+            let timeout = if pending.is_empty() {
+                STATE_POLL
+            } else {
+                interval.saturating_sub(last_flush.elapsed())
+            };
+            let routed = match rx.recv_timeout(timeout) {
+                Ok(routed) => Some(routed),
+                Err(RecvTimeoutError::Timeout) => None,
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+
+            // Anything but another change for the same outputs sends what
+            // is pending first, so commands keep their order
+            let more_params = matches!(
+                &routed,
+                Some(Routed { target, cmd: MidiCommand::SendParam { .. } })
+                    if *target == pending_target
+            );
+            let due = last_flush.elapsed() >= interval;
+            if !pending.is_empty() && if routed.is_some() { !more_params } else { due } {
+                flush_params(&mut ctrl, pending_target, &mut pending, &state_tx);
+                last_flush = Instant::now();
+            }
+
+            // Periodic work runs even while commands stream in
+            if last_scan.elapsed() >= HOTPLUG_POLL {
+                poll_hotplug(&mut ctrl, &state_tx);
+                last_scan = Instant::now();
+            }
+            if ctrl.clock_source() == ClockSource::External {
+                let _ = state_tx.send(DeviceState::Bpm(ctrl.bpm()));
+            }
+            if ctrl.recording_from() != record_from {
+                record_from = ctrl.recording_from();
+                let _ = state_tx.send(DeviceState::RecordFrom(record_from));
+            }
 
-        for cmd in rx {
+            let Some(Routed { target, cmd }) = routed else {
+                continue;
+            };
+            trace!(target: "worker", "{:?} to {:?}", cmd, target);
+            ctrl.set_target(target);
             match cmd {
-                MidiCommand::Connect(maybe_idx, ch) => {
-                    _current_channel = ch;
-                    if let Some(idx) = maybe_idx {
-                        match open_output(idx) {
-                            Ok(c) => {
-                                conn = Some(c);
-                                _current_port = Some(idx);
-                                eprintln!("✓ Connected to port {}", idx);
-                                // Broadcast device state on connect
-                                let _ = state_tx.send(DeviceState::Artist("Digitakt".to_string()));
-                                let _ = state_tx.send(DeviceState::Bpm(current_bpm));
+                MidiCommand::Connect(ports, ch) => {
+                    ctrl.set_channel(ch);
+                    let mut artists = Vec::new();
+                    let mut opened = Vec::new();
+                    let mut failures = Vec::new();
+                    for idx in ports {
+                        match ctrl.connect(idx) {
+                            Ok(()) => {
+                                info!(target: "worker", "Connected to port {}", idx);
+                                opened.push(idx);
+                                artists.push(identify_device(&mut ctrl, idx, &state_tx));
+                            }
+                            Err(e) => {
+                                let message = format!("Failed to connect port {}: {:#}", idx, e);
+                                error!(target: "worker", "{}", message);
+                                failures.push(message);
                             }
-                            Err(e) => eprintln!("✗ Failed to connect: {:?}", e),
                         }
                     }
+                    // Partly failed connects still count as connected; the
+                    // ports that failed are reported on their own
+                    let result = if !opened.is_empty() {
+                        for message in failures {
+                            let _ = state_tx.send(DeviceState::Error(message));
+                        }
+                        Ok(opened)
+                    } else if failures.is_empty() {
+                        Err("No port selected".to_string())
+                    } else {
+                        Err(failures.join("; "))
+                    };
+                    let _ = state_tx.send(DeviceState::ConnectResult(result));
+                    if !artists.is_empty() {
+                        // Broadcast device state on connect
+                        let _ = state_tx.send(DeviceState::Artist(artists.join(", ")));
+                        let _ = state_tx.send(DeviceState::Bpm(ctrl.bpm()));
+                    }
                 }
                 MidiCommand::Disconnect => {
-                    conn = None;
-                    _current_port = None;
-                    eprintln!("✓ Disconnected");
-                }
-                MidiCommand::SendCC { channel, controller, value } => {
-                    if let Some(ref mut c) = conn {
-                        if let Err(e) = send_cc(c, channel, controller, value) {
-                            eprintln!("✗ Failed to send CC {}: {:?}", controller, e);
+                    ctrl.disconnect_input();
+                    ctrl.disconnect();
+                    let _ = state_tx.send(DeviceState::ClockSource(ctrl.clock_source()));
+                    info!(target: "worker", "Disconnected");
+                }
+                MidiCommand::SendParam { channel, address, value } => {
+                    pending.push((channel, address, value));
+                    pending_target = target;
+                    // After a pause the first change goes out at once
+                    if last_flush.elapsed() >= interval {
+                        flush_params(&mut ctrl, pending_target, &mut pending, &state_tx);
+                        last_flush = Instant::now();
+                    }
+                }
+                MidiCommand::PitchBend { channel, bend } => {
+                    if ctrl.is_connected()
+                        && let Err(e) = ctrl.pitch_bend(channel, bend)
+                    {
+                        report_error(&state_tx, format!("Failed to send Pitch Bend: {:#}", e));
+                    }
+                }
+                MidiCommand::NoteOn { channel, note, velocity } => {
+                    let notes = match chord_mode.enabled {
+                        true => chord_mode.notes(note),
+                        false => vec![note],
+                    };
+                    let repeat = ctrl.note_repeat().enabled;
+                    if repeat {
+                        notes.iter().for_each(|&n| ctrl.hold_note(channel, n, velocity));
+                    } else if ctrl.is_connected() {
+                        let sent =
+                            notes.iter().try_for_each(|&n| ctrl.note_on(channel, n, velocity));
+                        if let Err(e) = sent {
+                            report_error(&state_tx, format!("Failed to send Note On: {:#}", e));
+                        }
+                    }
+                    chords.insert((channel, note), (notes, repeat));
+                    if ctrl.record_note_on(note, velocity) {
+                        let _ = state_tx.send(DeviceState::Sequence(ctrl.sequence()));
+                    }
+                }
+                MidiCommand::NoteOff { channel, note } => {
+                    // The notes it started, even if the modes changed since
+                    let (notes, repeat) =
+                        chords.remove(&(channel, note)).unwrap_or_else(|| (vec![note], false));
+                    if repeat {
+                        notes.iter().for_each(|&n| ctrl.release_note(channel, n));
+                    } else if ctrl.is_connected()
+                        && let Err(e) = notes.iter().try_for_each(|&n| ctrl.note_off(channel, n))
+                    {
+                        report_error(&state_tx, format!("Failed to send Note Off: {:#}", e));
+                    }
+                    if ctrl.record_note_off(note) {
+                        let _ = state_tx.send(DeviceState::Sequence(ctrl.sequence()));
+                    }
+                }
+                MidiCommand::SetChordMode(mode) => chord_mode = mode,
+                MidiCommand::SetNoteRepeat(settings) => ctrl.set_note_repeat(settings),
+                MidiCommand::SelectPattern { channel, pattern, change, bars } => {
+                    if ctrl.is_connected() {
+                        let result = match ctrl.pattern_change_at(change, bars) {
+                            Some(at) => {
+                                ctrl.schedule_at(at, &pattern.messages(channel)).map(|_| ())
+                            }
+                            None => ctrl.select_pattern(channel, pattern),
+                        };
+                        if let Err(e) = result {
+                            report_error(
+                                &state_tx,
+                                format!("Failed to select pattern {}: {:#}", pattern, e),
+                            );
                         } else {
-                            eprintln!("→ CC {} = {} (ch {})", controller, value, channel);
+                            info!(target: "worker", "Pattern {} (ch {})", pattern, channel);
                         }
                     }
                 }
                 MidiCommand::Start => {
-                    if let Some(ref mut c) = conn {
-                        if let Err(e) = send_realtime(c, 0xFA) {
-                            eprintln!("✗ Failed to send Start: {:?}", e);
+                    if ctrl.is_connected() {
+                        if let Err(e) = ctrl.start() {
+                            report_error(&state_tx, format!("Failed to send Start: {:#}", e));
                         } else {
-                            eprintln!("► Start");
-                            for _ in 0..6 {
-                                if let Err(e) = send_realtime(c, 0xF8) {
-                                    eprintln!("✗ Failed to send Clock tick: {:?}", e);
-                                }
-                                std::thread::sleep(std::time::Duration::from_millis(8));
-                            }
+                            info!(target: "worker", "Start");
                         }
                     }
                 }
                 MidiCommand::Stop => {
-                    if let Some(ref mut c) = conn {
-                        if let Err(e) = send_realtime(c, 0xFC) {
-                            eprintln!("✗ Failed to send Stop: {:?}", e);
+                    if ctrl.is_connected() {
+                        if let Err(e) = ctrl.stop() {
+                            report_error(&state_tx, format!("Failed to send Stop: {:#}", e));
                         } else {
-                            eprintln!("⏹ Stop");
+                            info!(target: "worker", "Stop");
                         }
                     }
                 }
                 MidiCommand::Continue => {
-                    if let Some(ref mut c) = conn {
-                        if let Err(e) = send_realtime(c, 0xFB) {
-                            eprintln!("✗ Failed to send Continue: {:?}", e);
+                    if ctrl.is_connected() {
+                        if let Err(e) = ctrl.resume() {
+                            report_error(&state_tx, format!("Failed to send Continue: {:#}", e));
+                        } else {
+                            info!(target: "worker", "Continue");
+                        }
+                    }
+                }
+                MidiCommand::Locate(bar) => {
+                    if ctrl.is_connected() {
+                        if let Err(e) = ctrl.continue_from_bar(bar) {
+                            let message = format!("Failed to locate to bar {}: {:#}", bar, e);
+                            report_error(&state_tx, message);
+                        } else {
+                            info!(target: "worker", "Continue from bar {}", bar);
+                        }
+                    }
+                }
+                MidiCommand::Mmc(mmc) => {
+                    if ctrl.is_connected() {
+                        if let Err(e) = ctrl.mmc(mmc) {
+                            report_error(&state_tx, format!("Failed to send {}: {:#}", mmc, e));
                         } else {
-                            eprintln!("→ Continue");
+                            info!(target: "worker", "{}", mmc);
+                        }
+                    }
+                }
+                MidiCommand::SetDeviceProfile { port_name, profile } => {
+                    ctrl.set_device_profile(&port_name, profile);
+                }
+                MidiCommand::SetSequence(tracks) => ctrl.set_sequence(tracks),
+                MidiCommand::SetSong { song, channel } => {
+                    if let Err(e) = ctrl.set_song(&song, channel) {
+                        report_error(&state_tx, format!("Failed to load the song: {:#}", e));
+                    }
+                }
+                MidiCommand::SetSongEnabled(on) => {
+                    ctrl.set_song_enabled(on);
+                    info!(target: "worker", "Song mode {}", if on { "on" } else { "off" });
+                }
+                MidiCommand::SetAutomation(lanes) => ctrl.set_automation(lanes),
+                MidiCommand::SetLfos(lfos) => ctrl.set_lfos(lfos),
+                MidiCommand::SetEnvelopes(envelopes) => ctrl.set_envelopes(envelopes),
+                MidiCommand::SetAutomationEnabled(on) => {
+                    ctrl.set_automation_enabled(on);
+                    info!(target: "worker", "Automation {}", if on { "on" } else { "off" });
+                }
+                MidiCommand::SetSequencer(on) => {
+                    ctrl.set_sequencer_enabled(on);
+                    info!(target: "worker", "Sequencer {}", if on { "on" } else { "off" });
+                }
+                MidiCommand::SetRecording { track, settings } => {
+                    ctrl.set_recording(track, settings);
+                    match track {
+                        Some(n) => info!(target: "worker", "Recording into track {}", n + 1),
+                        None => info!(target: "worker", "Recording off"),
+                    }
+                }
+                MidiCommand::SyncParams(params) => {
+                    if ctrl.is_connected() {
+                        match ctrl.sync_params(&params) {
+                            Ok(duration) => info!(
+                                target: "worker",
+                                "Resending {} parameter(s) over {:?}",
+                                params.len(),
+                                duration
+                            ),
+                            Err(e) => report_error(&state_tx, format!("Failed to sync: {:#}", e)),
                         }
                     }
                 }
                 MidiCommand::QueryDevice => {
                     // Broadcast current device state
-                    let _ = state_tx.send(DeviceState::Artist("Digitakt".to_string()));
-                    let _ = state_tx.send(DeviceState::Bpm(current_bpm));
+                    let artist = ctrl
+                        .device_identity()
+                        .map(|id| id.to_string())
+                        .unwrap_or_else(|| "Unknown".to_string());
+                    let _ = state_tx.send(DeviceState::Artist(artist));
+                    let _ = state_tx.send(DeviceState::Bpm(ctrl.bpm()));
                 }
                 MidiCommand::SetBpm(bpm) => {
-                    current_bpm = bpm;
-                    eprintln!("⏱ BPM set to {}", bpm);
+                    ctrl.set_bpm(bpm);
+                    info!(target: "worker", "BPM set to {}", bpm);
                     let _ = state_tx.send(DeviceState::Bpm(bpm));
                 }
+                MidiCommand::SetSwing(swing) => {
+                    ctrl.set_swing(swing);
+                    info!(target: "worker", "Swing {}%", ctrl.swing());
+                }
+                MidiCommand::SetFreeClock(on) => {
+                    if let Err(e) = ctrl.set_free_clock(on) {
+                        report_error(&state_tx, format!("Failed to switch clock: {:#}", e));
+                    } else {
+                        info!(target: "worker", "Clock {}", if on { "on" } else { "off" });
+                    }
+                    let _ = state_tx.send(DeviceState::FreeClock(ctrl.free_clock()));
+                }
+                MidiCommand::SetClockSource(source) => {
+                    match ctrl.set_clock_source(source) {
+                        Ok(()) => info!(target: "worker", "Clock source: {:?}", source),
+                        Err(e) => {
+                            report_error(&state_tx, format!("Failed to set clock source: {:#}", e))
+                        }
+                    }
+                    let _ = state_tx.send(DeviceState::ClockSource(ctrl.clock_source()));
+                }
+                MidiCommand::SetMtcRate(rate) => {
+                    ctrl.set_mtc_rate(rate);
+                    match rate {
+                        Some(rate) => info!(target: "worker", "MTC at {} fps", rate),
+                        None => info!(target: "worker", "MTC off"),
+                    }
+                }
+                MidiCommand::SetCcRate(rate) => {
+                    interval = cc_interval(rate);
+                    info!(target: "worker", "Parameter sends limited to {}/s", rate);
+                }
                 MidiCommand::Quit => {
                     break;
                 }
             }
         }
+
+        // Commands queued before Quit have been handled; silence the devices
+        if let Err(e) = ctrl.shutdown() {
+            error!(target: "worker", "Shutdown: {:?}", e);
+        }
+        info!(target: "worker", "Disconnected");
     });
+    let worker = Arc::new(Mutex::new(Some(worker)));
+    let quit_tx = tx.clone();
+    let stop_worker = move || {
+        let _ = quit_tx.send(Routed { target: PortTarget::All, cmd: MidiCommand::Quit });
+        if let Some(worker) = worker.lock().unwrap().take() {
+            let _ = worker.join();
+        }
+    };
+    let interrupt = stop_worker.clone();
+    ctrlc::set_handler(move || {
+        interrupt();
+        std::process::exit(0);
+    })?;
+    let _ = tx.send(Routed { target: PortTarget::All, cmd: MidiCommand::QueryDevice });
 
-    let app = MidiGuiApp::new(port_names, tx, state_rx, initial_channel);
+    let mut app = MidiGuiApp::new(port_names, tx, state_rx, initial_channel, config);
+    app.selected_ports.extend(initial_ports);
+    app.load_sequence();
+    app.load_song();
+    app.load_automation();
+    app.load_lfos();
+    app.load_envelopes();
+    if !app.config.instances.is_empty() {
+        app.select_instance(0);
+    }
+    let velocity = app.current_profile().velocity;
+    app.keyboard.set_velocity(velocity.get());
     let native_options = NativeOptions::default();
     eframe::run_native(
         "midi_ctrl - Digitakt MIDI controller",
         native_options,
-        Box::new(|_cc| Box::new(app)),
-    );
+        Box::new(move |cc| {
+            apply_theme(&cc.egui_ctx, &app.config.gui);
+            let name = app.config.controller_input.clone();
+            if let Err(e) = app.controller_input.open(name.as_deref(), &cc.egui_ctx) {
+                app.notify_error(format!("Failed to open controller input: {:#}", e));
+            }
+            Box::new(app)
+        }),
+    )
+    .map_err(|e| anyhow::anyhow!("GUI failed: {}", e))?;
 
+    stop_worker();
     Ok(())
 }
 
+/// Macro controls on the fullscreen performance view.
+const MACROS: usize = 8;
+/// Key toggling the fullscreen performance view.
+const PERFORM_KEY: egui::Key = egui::Key::F11;
+
+/// Interface zoom choices in the View menu.
+const SCALES: [f32; 5] = [0.75, 1.0, 1.25, 1.5, 2.0];
+
+/// Applies the theme, accent, touch spacing and scale from the settings.
+fn apply_theme(ctx: &egui::Context, settings: &GuiSettings) {
+    let mut style = (*ctx.style()).clone();
+    style.visuals = match settings.theme {
+        Theme::Dark => egui::Visuals::dark(),
+        Theme::Light => egui::Visuals::light(),
+    };
+    let [r, g, b] = settings.accent;
+    let accent = egui::Color32::from_rgb(r, g, b);
+    style.visuals.selection.bg_fill = accent;
+    style.visuals.hyperlink_color = accent;
+    style.spacing = egui::style::Spacing::default();
+    if settings.touch {
+        let spacing = &mut style.spacing;
+        spacing.interact_size = egui::vec2(48.0, 40.0);
+        spacing.button_padding = egui::vec2(12.0, 8.0);
+        spacing.item_spacing = egui::vec2(10.0, 8.0);
+        spacing.slider_width = 200.0;
+        spacing.icon_width = 24.0;
+        spacing.icon_width_inner = 14.0;
+    }
+    ctx.set_style(style);
+    // Hand-edited configs may hold anything
+    ctx.set_zoom_factor(settings.scale.clamp(0.5, 3.0));
+}
+
+/// Marks `center` on the rail of a 0-127 slider drawn in `rect`.
+fn center_tick(ui: &egui::Ui, rect: egui::Rect, center: u8) {
+    let height = ui.spacing().interact_size.y;
+    // The handle stops its radius short of the rail's ends
+    let handle_radius = height / 2.5;
+    let left = rect.left() + handle_radius;
+    let rail = left..=rect.left() + ui.spacing().slider_width - handle_radius;
+    let x = egui::lerp(rail, center as f32 / 127.0);
+    let y = rect.center().y;
+    let stroke = ui.visuals().widgets.noninteractive.fg_stroke;
+    let (top, bottom) = (egui::pos2(x, y - height / 2.0), egui::pos2(x, y + height / 2.0));
+    ui.painter().line_segment([top, bottom], stroke);
+}
+
+/// The Parameters page's groups, built once rather than every frame.
+/// Encoder pages come first, in the device's page and knob order; other
+/// categories follow alphabetically.
+fn category_layout(midi_map: &MidiMap) -> Vec<(String, Vec<ParamAddress>)> {
+    let mut categories: BTreeMap<String, Vec<MidiParameter>> = BTreeMap::new();
+    for param in midi_map.get_all_parameters() {
+        categories.entry(param.category.clone()).or_default().push(param);
+    }
+    let mut categories: Vec<_> = categories.into_iter().collect();
+    for (_, params) in &mut categories {
+        // Knobs in order; off-page extras after them by address
+        params.sort_by_key(|p| (p.position.is_none(), p.position, p.address));
+    }
+    // Stable, so categories without a page stay alphabetical
+    categories.sort_by_key(|(_, params)| {
+        params.iter().filter_map(|p| p.position).map(|p| p.page).min().unwrap_or(u8::MAX)
+    });
+    categories
+        .into_iter()
+        .map(|(category, params)| (category, params.iter().map(|p| p.address).collect()))
+        .collect()
+}
+
+/// Chord mode's switch, key, scale, chord shape and voicing. Returns true
+/// when any changed.
+fn chord_mode_controls(ui: &mut egui::Ui, mode: &mut ChordMode) -> bool {
+    let before = *mode;
+    ui.horizontal(|ui| {
+        let hover = "Play a chord in key from each note of the pads, keyboard and controller";
+        ui.checkbox(&mut mode.enabled, "Chords").on_hover_text(hover);
+        egui::ComboBox::from_id_source("chord_key")
+            .width(48.0)
+            .selected_text(KEYS[mode.key as usize % KEYS.len()])
+            .show_ui(ui, |ui| {
+                for (key, name) in KEYS.iter().enumerate() {
+                    ui.selectable_value(&mut mode.key, key as u8, *name);
+                }
+            });
+        egui::ComboBox::from_id_source("chord_scale")
+            .selected_text(mode.scale.label())
+            .show_ui(ui, |ui| {
+                for scale in Scale::ALL {
+                    ui.selectable_value(&mut mode.scale, scale, scale.label());
+                }
+            });
+        egui::ComboBox::from_id_source("chord_shape")
+            .selected_text(mode.shape.label())
+            .show_ui(ui, |ui| {
+                for shape in ChordShape::ALL {
+                    ui.selectable_value(&mut mode.shape, shape, shape.label());
+                }
+            });
+        egui::ComboBox::from_id_source("chord_voicing")
+            .selected_text(mode.voicing.label())
+            .show_ui(ui, |ui| {
+                for voicing in Voicing::ALL {
+                    ui.selectable_value(&mut mode.voicing, voicing, voicing.label());
+                }
+            });
+    });
+    *mode != before
+}
+
+/// Note repeat's switch, rate and velocity ramp. Returns true when any
+/// changed.
+fn note_repeat_controls(ui: &mut egui::Ui, repeat: &mut NoteRepeat) -> bool {
+    let before = *repeat;
+    ui.horizontal(|ui| {
+        let hover = "Retrigger held pads, keys and controller notes on the clock";
+        ui.checkbox(&mut repeat.enabled, "Repeat").on_hover_text(hover);
+        egui::ComboBox::from_id_source("repeat_rate")
+            .width(56.0)
+            .selected_text(repeat.rate.label())
+            .show_ui(ui, |ui| {
+                for rate in RepeatRate::ALL {
+                    ui.selectable_value(&mut repeat.rate, rate, rate.label());
+                }
+            });
+        ui.label("Ramp:");
+        ui.add(egui::DragValue::new(&mut repeat.ramp).clamp_range(-32..=32))
+            .on_hover_text("Velocity change on each repeat");
+    });
+    *repeat != before
+}
+
+/// Pad notes, velocity and note length of a device profile.
+fn note_defaults_menu(ui: &mut egui::Ui, profile: &mut DeviceProfile) {
+    let note_name = |number: f64| {
+        Value7::new(number as u8).map(Note::new).unwrap_or_default().to_string()
+    };
+    let mut chromatic = profile.pad_note.is_some();
+    let hover = "Pads count up from one note instead of using the pad layout's notes";
+    if ui.checkbox(&mut chromatic, "Chromatic pads").on_hover_text(hover).changed() {
+        // From C4, which Elektron tracks play unpitched
+        profile.pad_note = chromatic.then(|| Note::new(Value7::new(60).unwrap_or_default()));
+    }
+    if let Some(note) = profile.pad_note {
+        ui.horizontal(|ui| {
+            ui.label("First pad:");
+            let mut number = note.value().get();
+            let drag = egui::DragValue::new(&mut number)
+                .clamp_range(0..=127)
+                .custom_formatter(|n, _| note_name(n));
+            if ui.add(drag).changed()
+                && let Ok(value) = Value7::new(number)
+            {
+                profile.pad_note = Some(Note::new(value));
+            }
+        });
+    }
+    ui.horizontal(|ui| {
+        ui.label("Velocity:");
+        let mut velocity = profile.velocity.get();
+        if ui.add(egui::Slider::new(&mut velocity, 1..=127)).changed()
+            && let Ok(velocity) = Value7::new(velocity)
+        {
+            profile.velocity = velocity;
+        }
+    });
+    ui.horizontal(|ui| {
+        ui.label("Note length:");
+        let length = egui::DragValue::new(&mut profile.note_length)
+            .clamp_range(1..=10_000)
+            .suffix(" ms");
+        ui.add(length);
+    });
+}
+
+/// Watches the custom map file the config names, if any.
+fn map_watch(config: &Config) -> Option<FileWatch> {
+    let path = MapFile::resolve(config.map.as_deref()?).ok()?;
+    Some(FileWatch::new(path))
+}
+
+/// Width of a parameter category group, for fitting columns.
+const CATEGORY_WIDTH: f32 = 480.0;
+
+/// What the central panel, or a docked side panel, shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Page {
+    Parameters,
+    Performance,
+    Mixer,
+    Patterns,
+    Pads,
+    XyPad,
+    Morph,
+    MidiTracks,
+    Sequencer,
+    Song,
+    Automation,
+    Lfos,
+    Envelopes,
+}
+
+impl Page {
+    const ALL: [Page; 13] = [
+        Page::Parameters,
+        Page::Performance,
+        Page::Mixer,
+        Page::Patterns,
+        Page::Pads,
+        Page::XyPad,
+        Page::Morph,
+        Page::MidiTracks,
+        Page::Sequencer,
+        Page::Song,
+        Page::Automation,
+        Page::Lfos,
+        Page::Envelopes,
+    ];
+
+    /// Name in the config's panel layout.
+    fn id(self) -> &'static str {
+        match self {
+            Page::Parameters => "parameters",
+            Page::Performance => "performance",
+            Page::Mixer => "mixer",
+            Page::Patterns => "patterns",
+            Page::Pads => "pads",
+            Page::XyPad => "xy_pad",
+            Page::Morph => "morph",
+            Page::MidiTracks => "midi_tracks",
+            Page::Sequencer => "sequencer",
+            Page::Song => "song",
+            Page::Automation => "automation",
+            Page::Lfos => "lfos",
+            Page::Envelopes => "envelopes",
+        }
+    }
+
+    fn from_id(id: &str) -> Option<Page> {
+        Page::ALL.into_iter().find(|page| page.id() == id)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Page::Parameters => "Parameters",
+            Page::Performance => "Performance",
+            Page::Mixer => "Mixer",
+            Page::Patterns => "Patterns",
+            Page::Pads => "Pads",
+            Page::XyPad => "XY Pad",
+            Page::Morph => "Morph",
+            Page::MidiTracks => "MIDI Tracks",
+            Page::Sequencer => "Sequencer",
+            Page::Song => "Song",
+            Page::Automation => "Automation",
+            Page::Lfos => "LFOs",
+            Page::Envelopes => "Envelopes",
+        }
+    }
+}
+
 struct MidiGuiApp {
     port_names: Vec<String>,
-    tx: Sender<MidiCommand>,
+    config: Config,
+    tx: Sender<Routed>,
     state_rx: Receiver<DeviceState>,
-    selected_port: Option<usize>,
-    channel: u8,
-    cc_values: Vec<i32>,
+    selected_ports: BTreeSet<usize>,
+    target: PortTarget,
+    channel: Channel,
+    param_values: HashMap<ParamAddress, u8>,
     connected: bool,
-    last_sent_cc: Option<(u8, u8)>,
+    /// Connect was clicked and the worker has not answered yet.
+    connecting: bool,
+    last_sent: Option<(ParamAddress, Value7)>,
     last_sent_time: Option<std::time::Instant>,
+    /// Device model of the first targeted port, which `midi_map` is for.
+    model: DeviceModel,
+    /// Models named by identity replies, by output port name.
+    identified: HashMap<String, DeviceModel>,
     midi_map: MidiMap,
+    /// Parameter addresses grouped by category, sorted by category name.
+    categories: Vec<(String, Vec<ParamAddress>)>,
     device_artist: String,
     device_bpm: f32,
+    /// Tempo before the held nudge button changed it.
+    nudge_base: Option<f32>,
+    /// Clock output runs without Start.
+    free_clock: bool,
+    /// Swing in percent (50 is straight).
+    swing: u8,
+    tap_tempo: TapTempo,
+    lost_ports: Vec<String>,
+    transport: Option<Arc<Transport>>,
+    running: bool,
+    position: Position,
+    locate_bar: u64,
+    clock_source: ClockSource,
+    mtc_rate: Option<FrameRate>,
+    pitch_bend: i16,
+    selected_pattern: Option<Pattern>,
+    /// Pattern waiting to play, and the bar it starts in.
+    queued_pattern: Option<(Pattern, u64)>,
+    /// Recent errors and when they arrived, shown as toasts.
+    toasts: Vec<(String, Instant)>,
+    /// Kept in the status bar until cleared.
+    last_error: Option<String>,
+    page: Page,
+    mixer: Mixer,
+    pads: Pads,
+    midi_tracks: MidiTracks,
+    step_grid: StepGrid,
+    /// Whether the sequencer plays with the transport.
+    sequencer_on: bool,
+    /// The track notes record into, by index, and whether recording.
+    record_track: usize,
+    recording: bool,
+    /// The tick recording starts on after the count-in, once placed.
+    record_from: Option<u64>,
+    song_editor: SongEditor,
+    /// Whether the song's pattern changes follow the transport.
+    song_on: bool,
+    /// Whether the automation plays with the transport.
+    automation_on: bool,
+    /// Parameter moves are recorded into the automation while playing.
+    automation_recording: bool,
+    automation_recorder: AutomationRecorder,
+    /// Recorded moves not yet handed to the worker, and when it last got
+    /// them.
+    automation_changed: bool,
+    automation_sent: Instant,
+    xy_pad: XyPad,
+    morph: Morph,
+    randomizer: Randomizer,
+    /// Category the randomizer moves, or every parameter.
+    random_scope: Option<String>,
+    /// How far it moves them, in percent of their ranges.
+    random_amount: u8,
+    show_keyboard: bool,
+    keyboard: Keyboard,
+    show_activity: bool,
+    activity: ActivityLog,
+    /// The Performance page shows its reorder controls.
+    editing_performance: bool,
+    /// Parameters page filter: names, categories and aliases containing it.
+    param_search: String,
+    /// Fullscreen performance view instead of the editor.
+    performing: bool,
+    /// Macro slots show parameter pickers.
+    editing_macros: bool,
+    /// Saved snapshot names, read when the performance view opens.
+    snapshot_names: Vec<String>,
+    controller_input: ControllerInput,
+    /// A panel was resized; saved once the pointer is released.
+    layout_dirty: bool,
+    /// The config and custom map, reloaded when edited in a text editor.
+    config_watch: Option<FileWatch>,
+    map_watch: Option<FileWatch>,
+    last_file_poll: Instant,
+    /// The session instance whose tab is open, if instances are configured.
+    instance: Option<usize>,
+    /// Parameter values of the other channels, by instance tab, so each
+    /// track shows its own last-known values.
+    stashed_values: HashMap<(Option<usize>, Channel), HashMap<ParamAddress, u8>>,
+    /// Where each configured macro was last moved to, by name.
+    macro_positions: HashMap<String, u8>,
 }
 
 impl MidiGuiApp {
-    fn new(port_names: Vec<String>, tx: Sender<MidiCommand>, state_rx: Receiver<DeviceState>, initial_channel: u8) -> Self {
+    fn new(
+        port_names: Vec<String>,
+        tx: Sender<Routed>,
+        state_rx: Receiver<DeviceState>,
+        initial_channel: Channel,
+        config: Config,
+    ) -> Self {
+        let model = config.default_model();
+        let midi_map = config.midi_map(model);
+        let xy_pad = XyPad::new(&midi_map);
+        let categories = category_layout(&midi_map);
+        let config_watch = Config::path().map(FileWatch::new);
+        let map_watch = map_watch(&config);
         Self {
             port_names,
+            config,
             tx,
             state_rx,
-            selected_port: None,
+            selected_ports: BTreeSet::new(),
+            target: PortTarget::All,
             channel: initial_channel,
-            cc_values: vec![0i32; 128],
+            param_values: HashMap::new(),
             connected: false,
-            last_sent_cc: None,
+            connecting: false,
+            last_sent: None,
             last_sent_time: None,
-            midi_map: MidiMap::new(),
+            model,
+            identified: HashMap::new(),
+            midi_map,
+            categories,
             device_artist: "Unknown".to_string(),
             device_bpm: 120.0,
+            nudge_base: None,
+            free_clock: false,
+            swing: MIN_SWING,
+            tap_tempo: TapTempo::default(),
+            lost_ports: Vec::new(),
+            transport: None,
+            running: false,
+            position: Position::from_ticks(0),
+            locate_bar: 1,
+            clock_source: ClockSource::Internal,
+            mtc_rate: None,
+            pitch_bend: 0,
+            selected_pattern: None,
+            queued_pattern: None,
+            toasts: Vec::new(),
+            last_error: None,
+            page: Page::Parameters,
+            mixer: Mixer::default(),
+            pads: Pads::default(),
+            midi_tracks: MidiTracks::default(),
+            step_grid: StepGrid::default(),
+            sequencer_on: false,
+            record_track: 0,
+            recording: false,
+            record_from: None,
+            song_editor: SongEditor::default(),
+            song_on: false,
+            automation_on: false,
+            automation_recording: false,
+            automation_recorder: AutomationRecorder::default(),
+            automation_changed: false,
+            automation_sent: Instant::now(),
+            xy_pad,
+            morph: Morph::default(),
+            randomizer: Randomizer::default(),
+            random_scope: None,
+            random_amount: DEFAULT_AMOUNT,
+            show_keyboard: false,
+            keyboard: Keyboard::default(),
+            show_activity: false,
+            activity: ActivityLog::default(),
+            editing_performance: false,
+            param_search: String::new(),
+            performing: false,
+            editing_macros: false,
+            snapshot_names: Vec::new(),
+            controller_input: ControllerInput::default(),
+            layout_dirty: false,
+            config_watch,
+            map_watch,
+            last_file_poll: Instant::now(),
+            instance: None,
+            stashed_values: HashMap::new(),
+            macro_positions: HashMap::new(),
         }
     }
 
-    fn update_device_state(&mut self) {
-        // Drain all pending device state updates
-        while let Ok(state) = self.state_rx.try_recv() {
-            match state {
-                DeviceState::Artist(artist) => {
-                    self.device_artist = artist;
-                }
-                DeviceState::Bpm(bpm) => {
-                    self.device_bpm = bpm;
-                }
+    /// Hold − or + to play slower or faster for beat-matching; letting go
+    /// returns to the tempo before.
+    fn nudge_buttons(&mut self, ui: &mut egui::Ui, enabled: bool) {
+        let mut held = |label: &str, hover: &str| {
+            ui.add_enabled(enabled, egui::Button::new(label))
+                .on_hover_text(hover)
+                .is_pointer_button_down_on()
+        };
+        let slower = held("−", "Hold to nudge slower");
+        let faster = held("+", "Hold to nudge faster");
+        let factor = match (slower, faster) {
+            (true, false) => Some(1.0 - NUDGE),
+            (false, true) => Some(1.0 + NUDGE),
+            _ => None,
+        };
+        match (factor, self.nudge_base) {
+            (Some(factor), None) => {
+                self.nudge_base = Some(self.device_bpm);
+                self.send(MidiCommand::SetBpm(self.device_bpm * factor));
+            }
+            (None, Some(base)) => {
+                self.nudge_base = None;
+                self.send(MidiCommand::SetBpm(base));
             }
+            _ => {}
         }
     }
-}
 
-impl eframe::App for MidiGuiApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Update device state from background thread
-        self.update_device_state();
+    /// A light per beat of the bar, the current one lit and the downbeat
+    /// in the accent color.
+    fn beat_lights(&self, ui: &mut egui::Ui) {
+        let (rect, _) = ui.allocate_exact_size(
+            egui::vec2(BEATS_PER_BAR as f32 * 14.0, 14.0),
+            egui::Sense::hover(),
+        );
+        let painter = ui.painter();
+        for beat in 1..=BEATS_PER_BAR {
+            let center = rect.left_center() + egui::vec2(beat as f32 * 14.0 - 7.0, 0.0);
+            let color = match (self.running && beat == self.position.beat, beat) {
+                (true, 1) => ui.visuals().selection.bg_fill,
+                (true, _) => ui.visuals().strong_text_color(),
+                (false, _) => ui.visuals().widgets.inactive.bg_fill,
+            };
+            painter.circle_filled(center, 5.0, color);
+        }
+    }
 
-        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
-            ui.horizontal(|ui| {
-                ui.label("MIDI Port:");
-                if self.port_names.is_empty() {
-                    ui.label("No ports available");
-                } else {
-                    let mut selected_label = "None".to_string();
-                    if let Some(idx) = self.selected_port {
-                        if let Some(n) = self.port_names.get(idx) {
-                            selected_label = format!("{} (#{})", n, idx);
-                        }
+    /// Sends a command to the worker, routed to the currently chosen output(s).
+    fn send(&self, cmd: MidiCommand) {
+        let _ = self.tx.send(Routed { target: self.target, cmd });
+    }
+
+    /// Shows an error as a toast and in the status bar.
+    fn notify_error(&mut self, message: String) {
+        if self.toasts.len() == MAX_TOASTS {
+            self.toasts.remove(0);
+        }
+        self.toasts.push((message.clone(), Instant::now()));
+        self.last_error = Some(message);
+    }
+
+    fn save_config(&mut self) {
+        if let Err(e) = self.config.save() {
+            error!(target: "gui", "Failed to save config: {:#}", e);
+            self.notify_error(format!("Failed to save config: {:#}", e));
+        }
+        // Our own write is not an outside edit
+        if let Some(watch) = &mut self.config_watch {
+            watch.mark_seen();
+        }
+    }
+
+    /// Picks up edits to the config (device profiles and models, ranges,
+    /// which map, worker settings) and to the custom map file, rebuilding
+    /// the parameter map to match.
+    fn reload_edited_files(&mut self, ctx: &egui::Context) {
+        if self.last_file_poll.elapsed() < FILE_POLL {
+            return;
+        }
+        self.last_file_poll = Instant::now();
+
+        let config_changed = self.config_watch.as_mut().is_some_and(FileWatch::changed);
+        if config_changed {
+            match Config::load() {
+                Ok(config) => {
+                    info!(target: "gui", "Config edited; reloading");
+                    let forced_device = self.config.forced_device;
+                    self.config = Config { forced_device, ..config };
+                    for (name, profile) in self.config.port_profiles(&self.port_names) {
+                        self.send(MidiCommand::SetDeviceProfile { port_name: name, profile });
                     }
-                    egui::ComboBox::from_label("")
-                        .selected_text(selected_label)
-                        .show_ui(ui, |ui| {
-                            for (i, name) in self.port_names.iter().enumerate() {
-                                let label = format!("{} (#{})", name, i);
-                                if ui.selectable_value(&mut self.selected_port, Some(i), label).clicked() {
-                                }
-                            }
-                            if ui.selectable_value(&mut self.selected_port, None, "None").clicked() {
-                            }
-                        });
+                    // Settings the worker keeps its own copy of
+                    let cmd = MidiCommand::SetCcRate(self.config.gui.cc_rate);
+                    let _ = self.tx.send(Routed { target: PortTarget::All, cmd });
+                    self.send(MidiCommand::SetChordMode(self.config.chord_mode));
+                    self.send(MidiCommand::SetNoteRepeat(self.config.note_repeat));
+                    apply_theme(ctx, &self.config.gui);
+                    self.map_watch = map_watch(&self.config);
+                    self.load_song();
+                    self.follow_device_model();
+                }
+                // Likely mid-edit; keep what we have until it parses
+                Err(e) => {
+                    warn!(target: "gui", "{:#}", e);
+                    self.notify_error(format!("{:#}", e));
+                    return;
                 }
+            }
+        }
 
-                ui.label("Channel:");
-                ui.add(egui::DragValue::new(&mut self.channel).clamp_range(1..=16));
+        let map_changed = self.map_watch.as_mut().is_some_and(FileWatch::changed);
+        if map_changed && let Some(name) = self.config.map.as_deref() {
+            if let Err(e) = MapFile::load(name) {
+                warn!(target: "gui", "{:#}", e);
+                self.notify_error(format!("{:#}", e));
+                return;
+            }
+            info!(target: "gui", "Map '{}' edited; reloading", name);
+        }
+        if config_changed || map_changed {
+            self.reload_map();
+        }
+    }
 
-                if !self.connected {
-                    if ui.button("Connect").clicked() {
-                        let _ = self.tx.send(MidiCommand::Connect(self.selected_port, self.channel));
-                        self.connected = true;
+    /// Rebuilds the parameter map for the current model, and the layouts
+    /// built from it.
+    fn reload_map(&mut self) {
+        self.midi_map = self.config.midi_map(self.model);
+        self.categories = category_layout(&self.midi_map);
+        if let Some(scope) = &self.random_scope
+            && !self.categories.iter().any(|(category, _)| category == scope)
+        {
+            self.random_scope = None;
+        }
+        self.xy_pad.set_map(&self.midi_map);
+        self.load_sequence();
+        self.load_automation();
+        self.load_lfos();
+        self.load_envelopes();
+    }
+
+    /// Hands the sequence to the worker, its parameter locks looked up in
+    /// the current map.
+    fn load_sequence(&mut self) {
+        for track in &mut self.config.sequence {
+            track.resolve_locks(&self.midi_map);
+        }
+        self.send(MidiCommand::SetSequence(self.config.sequence.clone()));
+    }
+
+    /// Arming a track for recording, its quantization and count-in, and
+    /// where the recording is.
+    fn record_controls(&mut self, ui: &mut egui::Ui) {
+        let tracks = self.config.sequence.len();
+        if tracks == 0 {
+            self.recording = false;
+        }
+        self.record_track = self.record_track.min(tracks.saturating_sub(1));
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            let rec = egui::SelectableLabel::new(self.recording, "⏺ Rec");
+            let hover = "Record pads, keys and controller notes into the track as it plays";
+            if ui.add_enabled(tracks > 0, rec).on_hover_text(hover).clicked() {
+                self.recording = !self.recording;
+                changed = true;
+            }
+            let selected = format!("Track {}", self.record_track + 1);
+            egui::ComboBox::from_id_source("record_track")
+                .selected_text(selected)
+                .show_ui(ui, |ui| {
+                    for (index, track) in self.config.sequence.iter().enumerate() {
+                        let text = format!("Track {} (ch {})", index + 1, track.channel);
+                        let option = ui.selectable_value(&mut self.record_track, index, text);
+                        changed |= option.changed();
                     }
-                } else {
-                    ui.colored_label(egui::Color32::GREEN, "✓ Connected");
-                    if ui.button("Disconnect").clicked() {
-                        let _ = self.tx.send(MidiCommand::Disconnect);
-                        self.connected = false;
+                });
+            let settings = &mut self.config.recording;
+            ui.label("Quantize");
+            egui::ComboBox::from_id_source("record_quantize")
+                .width(48.0)
+                .selected_text(settings.quantize.label())
+                .show_ui(ui, |ui| {
+                    for quantize in InputQuantize::ALL {
+                        let option =
+                            ui.selectable_value(&mut settings.quantize, quantize, quantize.label());
+                        changed |= option.changed();
                     }
-                    
-                    // Show device info
-                    ui.separator();
-                    ui.label(format!("Artist: {}", self.device_artist));
-                    
-                    // BPM control
-                    ui.label("BPM:");
-                    let mut bpm_value = self.device_bpm;
-                    if ui.add(egui::Slider::new(&mut bpm_value, 20.0..=300.0).show_value(true)).changed() {
-                        let _ = self.tx.send(MidiCommand::SetBpm(bpm_value));
+                });
+            let drag = egui::DragValue::new(&mut settings.count_in)
+                .clamp_range(0..=MAX_COUNT_IN)
+                .prefix("count-in ")
+                .suffix(" bar(s)");
+            changed |= ui.add(drag).changed();
+            let tick = self.transport.as_ref().filter(|t| t.is_running()).map(|t| t.ticks());
+            if self.recording {
+                match (tick, self.record_from) {
+                    (Some(tick), Some(from)) if tick < from => {
+                        let beats = (from - tick).div_ceil(PPQN as u64);
+                        let amber = egui::Color32::from_rgb(230, 160, 0);
+                        ui.colored_label(amber, format!("{}…", beats)).on_hover_text("Count-in");
+                    }
+                    (Some(_), Some(_)) => {
+                        ui.colored_label(egui::Color32::from_rgb(220, 50, 50), "● Recording");
+                    }
+                    _ => {
+                        ui.label("Waiting for the transport");
                     }
                 }
+            }
+        });
+        if changed {
+            let track = self.recording.then_some(self.record_track);
+            let settings = self.config.recording;
+            self.send(MidiCommand::SetRecording { track, settings });
+            self.save_config();
+        }
+    }
 
-                ui.separator();
+    /// Hands the automation to the worker, its parameters looked up in the
+    /// current map.
+    fn load_automation(&mut self) {
+        self.config.automation.resolve(&self.midi_map);
+        self.send(MidiCommand::SetAutomation(self.config.automation.lanes.clone()));
+        self.automation_changed = false;
+        self.automation_sent = Instant::now();
+    }
 
-                if ui.button("▶ Start").clicked() {
-                    let _ = self.tx.send(MidiCommand::Start);
-                }
-                if ui.button("⏹ Stop").clicked() {
-                    let _ = self.tx.send(MidiCommand::Stop);
-                }
-                if ui.button("→ Continue").clicked() {
-                    let _ = self.tx.send(MidiCommand::Continue);
-                }
+    /// Hands the LFOs to the worker, their parameters looked up in the
+    /// current map.
+    fn load_lfos(&mut self) {
+        for lfo in &mut self.config.lfos {
+            lfo.resolve(&self.midi_map);
+        }
+        self.send(MidiCommand::SetLfos(self.config.lfos.clone()));
+    }
 
-                if let Some((cc, val)) = self.last_sent_cc {
-                    if let Some(time) = self.last_sent_time {
-                        let elapsed = time.elapsed().as_secs_f32();
-                        if elapsed < 2.0 {
-                            let param_name = self.midi_map.get_name(cc);
-                            ui.label(format!("Last: {} = {}", param_name, val));
-                        }
+    /// Hands the envelopes to the worker, their parameters looked up in
+    /// the current map.
+    fn load_envelopes(&mut self) {
+        for env in &mut self.config.envelopes {
+            env.resolve(&self.midi_map);
+        }
+        self.send(MidiCommand::SetEnvelopes(self.config.envelopes.clone()));
+    }
+
+    /// Hands the song to the worker, its patterns on the current channel.
+    fn load_song(&self) {
+        let (song, channel) = (self.config.song.clone(), self.channel);
+        self.send(MidiCommand::SetSong { song, channel });
+    }
+
+    /// Theme, accent, scale and touch settings, applied and saved on change.
+    fn view_menu(&mut self, ui: &mut egui::Ui) {
+        let settings = &mut self.config.gui;
+        let mut changed = false;
+        ui.horizontal(|ui| {
+            ui.label("Theme:");
+            changed |= ui.radio_value(&mut settings.theme, Theme::Dark, "Dark").changed();
+            changed |= ui.radio_value(&mut settings.theme, Theme::Light, "Light").changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Accent:");
+            changed |= ui.color_edit_button_srgb(&mut settings.accent).changed();
+        });
+        ui.horizontal(|ui| {
+            ui.label("Scale:");
+            for scale in SCALES {
+                let label = format!("{}%", (scale * 100.0) as u32);
+                changed |= ui.selectable_value(&mut settings.scale, scale, label).changed();
+            }
+        });
+        changed |= ui.checkbox(&mut settings.touch, "Touch (bigger controls)").changed();
+        ui.horizontal(|ui| {
+            ui.label("CC rate:");
+            let rate = egui::DragValue::new(&mut settings.cc_rate)
+                .clamp_range(0..=1000)
+                .suffix("/s");
+            if ui.add(rate).on_hover_text("Parameter sends per second; 0 is unlimited").changed() {
+                let cmd = MidiCommand::SetCcRate(settings.cc_rate);
+                let _ = self.tx.send(Routed { target: PortTarget::All, cmd });
+                changed = true;
+            }
+        });
+        ui.separator();
+        let layout = &mut settings.layout;
+        let docks = [("Left panel:", &mut layout.left), ("Right panel:", &mut layout.right)];
+        for (label, dock) in docks {
+            ui.horizontal(|ui| {
+                ui.label(label);
+                let selected = dock.as_deref().and_then(Page::from_id).map_or("None", Page::label);
+                egui::ComboBox::from_id_source(label).selected_text(selected).show_ui(ui, |ui| {
+                    changed |= ui.selectable_value(dock, None, "None").changed();
+                    for page in Page::ALL {
+                        let id = Some(page.id().to_string());
+                        changed |= ui.selectable_value(dock, id, page.label()).changed();
                     }
-                }
+                });
             });
+        }
+        // A page docked on both sides would share its widgets' state
+        if layout.left.is_some() && layout.left == layout.right {
+            layout.right = None;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Parameter columns:");
+            let columns = egui::DragValue::new(&mut layout.parameter_columns)
+                .clamp_range(0..=8)
+                .custom_formatter(|n, _| if n == 0.0 { "Fit".to_string() } else { n.to_string() });
+            changed |= ui.add(columns).changed();
         });
+        if changed {
+            apply_theme(ui.ctx(), &self.config.gui);
+            self.save_config();
+        }
+    }
 
-               egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading("Digitakt Parameters");
-            ui.label("Move sliders to send CC values to your Digitakt");
-            egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
-                let mut categories: std::collections::HashMap<String, Vec<u8>> = std::collections::HashMap::new();
-                
-                for cc in 0..128u8 {
-                    if let Some(param) = self.midi_map.get_parameter(cc) {
-                        categories.entry(param.category.clone())
-                            .or_insert_with(Vec::new)
-                            .push(cc);
-                    }
-                }
-
-                let mut sorted_categories: Vec<_> = categories.into_iter().collect();
-                sorted_categories.sort_by(|a, b| a.0.cmp(&b.0));
+    /// Records a panel's size after the user dragged its edge.
+    fn track_size(&mut self, size: f32, stored: fn(&mut PanelLayout) -> &mut f32) {
+        let stored = stored(&mut self.config.gui.layout);
+        if (*stored - size).abs() >= 1.0 {
+            *stored = size;
+            self.layout_dirty = true;
+        }
+    }
 
-                ui.horizontal(|ui| {
-                    // Left column
-                    ui.vertical(|ui| {
-                        let half = (sorted_categories.len() + 1) / 2;
-                        for (category, mut ccs) in sorted_categories.iter().take(half).cloned() {
-                            ccs.sort();
-                            
-                            ui.group(|ui| {
-                                ui.heading(&category);
-                                
-                                let cols = 2;
-                                for row in 0..((ccs.len() + cols - 1) / cols) {
-                                    ui.horizontal(|ui| {
-                                        for col in 0..cols {
-                                            let idx = row * cols + col;
-                                            if idx >= ccs.len() {
-                                                break;
-                                            }
-                                            
-                                            let cc = ccs[idx];
-                                            let param_name = self.midi_map.get_name(cc);
-                                            
-                                            ui.vertical(|ui| {
-                                                ui.label(&param_name);
-                                                
-                                                let slider_response = ui.add(
-                                                    egui::Slider::new(&mut self.cc_values[cc as usize], 0..=127)
-                                                        .show_value(true)
-                                                );
-                                                
-                                                if slider_response.changed() {
-                                                    let new_val = self.cc_values[cc as usize] as u8;
-                                                    let _ = self.tx.send(MidiCommand::SendCC {
-                                                        channel: self.channel,
-                                                        controller: cc,
-                                                        value: new_val,
-                                                    });
-                                                    self.last_sent_cc = Some((cc, new_val));
-                                                    self.last_sent_time = Some(std::time::Instant::now());
-                                                }
-                                                
-                                                ui.label(format!("Value: {}", self.cc_values[cc as usize]));
-                                            });
-                                            
-                                            ui.separator();
-                                        }
-                                    });
-                                }
-                            });
+    fn show_page(&mut self, ui: &mut egui::Ui, page: Page) {
+        match page {
+            Page::Parameters => self.parameters_page(ui),
+            Page::Performance => self.performance_page(ui),
+            Page::Patterns => self.patterns_page(ui),
+            Page::Pads => {
+                ui.heading("Pads");
+                self.chord_mode_row(ui);
+                let (tx, target) = (&self.tx, self.target);
+                let mut send = |cmd| {
+                    let _ = tx.send(Routed { target, cmd });
+                };
+                let first_note = self.current_profile().pad_note;
+                let pads = &mut self.config.pads;
+                let edited = self.pads.show(ui, pads, first_note, self.channel, &mut send);
+                if edited {
+                    self.save_config();
+                }
+            }
+            Page::XyPad => {
+                ui.heading("XY Pad");
+                let (tx, target) = (&self.tx, self.target);
+                self.xy_pad.show(ui, self.channel, &mut |cmd| {
+                    let _ = tx.send(Routed { target, cmd });
+                });
+            }
+            Page::Morph => {
+                ui.heading("Morph");
+                let (tx, target) = (&self.tx, self.target);
+                let mut send = |cmd| {
+                    let _ = tx.send(Routed { target, cmd });
+                };
+                let values = &mut self.param_values;
+                if let Some(error) = self.morph.show(ui, values, self.channel, &mut send) {
+                    self.notify_error(error);
+                }
+            }
+            Page::MidiTracks => {
+                ui.heading("MIDI Tracks");
+                let (tx, target) = (&self.tx, self.target);
+                let mut send = |cmd| {
+                    let _ = tx.send(Routed { target, cmd });
+                };
+                if self.midi_tracks.show(ui, &mut self.config, &mut send) {
+                    self.save_config();
+                }
+            }
+            Page::Sequencer => {
+                ui.heading("Sequencer");
+                let hover = "Play the steps while the transport runs, on its clock";
+                let toggle = ui.checkbox(&mut self.sequencer_on, "Play").on_hover_text(hover);
+                if toggle.changed() {
+                    self.send(MidiCommand::SetSequencer(self.sequencer_on));
+                }
+                self.record_controls(ui);
+                let tick = self.transport.as_ref().filter(|t| t.is_running()).map(|t| t.ticks());
+                let tracks = &mut self.config.sequence;
+                if self.step_grid.show(ui, tracks, tick, self.channel, &self.midi_map) {
+                    self.send(MidiCommand::SetSequence(self.config.sequence.clone()));
+                    self.save_config();
+                }
+            }
+            Page::Song => {
+                ui.heading("Song");
+                let hover = "Change patterns and recall snapshots as the transport runs";
+                let toggle = ui.checkbox(&mut self.song_on, "Play").on_hover_text(hover);
+                if toggle.changed() {
+                    self.send(MidiCommand::SetSongEnabled(self.song_on));
+                }
+                let running = self.transport.as_ref().filter(|t| t.is_running());
+                let bar = running.map(|t| t.ticks() / TICKS_PER_BAR);
+                let playing = bar.and_then(|bar| self.config.song.entry_at(bar)).map(|(i, _)| i);
+                if self.song_editor.show(ui, &mut self.config.song, playing) {
+                    self.load_song();
+                    self.save_config();
+                }
+            }
+            Page::Automation => {
+                ui.heading("Automation");
+                ui.horizontal(|ui| {
+                    let hover = "Replay the recorded moves as the transport runs";
+                    let toggle = ui.checkbox(&mut self.automation_on, "Play").on_hover_text(hover);
+                    if toggle.changed() {
+                        self.send(MidiCommand::SetAutomationEnabled(self.automation_on));
+                    }
+                    let rec = egui::SelectableLabel::new(self.automation_recording, "⏺ Rec");
+                    let hover = "Record parameter moves while the transport runs; \
+                                 a move replaces what its lane had there";
+                    if ui.add(rec).on_hover_text(hover).clicked() {
+                        self.automation_recording = !self.automation_recording;
+                        if !self.automation_recording {
+                            self.automation_recorder.stop();
+                            self.load_automation();
+                            self.save_config();
+                        }
+                    }
+                });
+                let tick = self.transport.as_ref().filter(|t| t.is_running()).map(|t| t.ticks());
+                if automation_editor::show(ui, &mut self.config.automation, tick) {
+                    self.load_automation();
+                    self.save_config();
+                }
+            }
+            Page::Lfos => {
+                ui.heading("LFOs");
+                let lfos = &mut self.config.lfos;
+                if lfo_panel::show(ui, lfos, &self.midi_map, self.channel) {
+                    self.send(MidiCommand::SetLfos(self.config.lfos.clone()));
+                    self.save_config();
+                }
+            }
+            Page::Envelopes => {
+                ui.heading("Envelopes");
+                let envelopes = &mut self.config.envelopes;
+                if envelope_panel::show(ui, envelopes, &self.midi_map, self.channel) {
+                    self.send(MidiCommand::SetEnvelopes(self.config.envelopes.clone()));
+                    self.save_config();
+                }
+            }
+            Page::Mixer => {
+                ui.heading("Mixer");
+                let (tx, target) = (&self.tx, self.target);
+                let (first_channel, tracks) = self.track_channels();
+                egui::ScrollArea::horizontal().show(ui, |ui| {
+                    self.mixer.show(ui, first_channel, tracks, &mut |cmd| {
+                        let _ = tx.send(Routed { target, cmd });
+                    });
+                });
+            }
+        }
+    }
+
+    /// Chord mode and note repeat controls, passing changes on to the
+    /// worker.
+    fn chord_mode_row(&mut self, ui: &mut egui::Ui) {
+        if chord_mode_controls(ui, &mut self.config.chord_mode) {
+            self.send(MidiCommand::SetChordMode(self.config.chord_mode));
+            self.save_config();
+        }
+        if note_repeat_controls(ui, &mut self.config.note_repeat) {
+            self.send(MidiCommand::SetNoteRepeat(self.config.note_repeat));
+            self.save_config();
+        }
+    }
+
+    /// Persists the selected ports by name for the next run.
+    fn remember_ports(&mut self) {
+        self.config.last_ports = self
+            .selected_ports
+            .iter()
+            .filter_map(|idx| self.port_names.get(*idx).cloned())
+            .collect();
+        self.save_config();
+    }
+
+    /// Names of the connected ports the current target sends to.
+    fn target_names(&self) -> Vec<String> {
+        self.selected_ports
+            .iter()
+            .filter(|idx| match self.target {
+                PortTarget::All => true,
+                PortTarget::Port(target) => **idx == target,
+            })
+            .filter_map(|idx| self.port_names.get(*idx).cloned())
+            .collect()
+    }
+
+    /// Device model on an output port, set for it or recognized.
+    fn model_for(&self, port_name: &str) -> DeviceModel {
+        self.config.model_for(port_name, self.identified.get(port_name).copied())
+    }
+
+    /// Opens a session instance's tab: sends go to its port and first
+    /// channel, with its parameter map and values.
+    fn select_instance(&mut self, index: usize) {
+        let Some(instance) = self.config.instances.get(index).cloned() else {
+            return;
+        };
+        let channel = Channel::new(instance.channel).unwrap_or(self.channel);
+        self.switch_values(Some(index), channel);
+        self.keyboard.set_velocity(instance.profile.velocity.get());
+        let Some(port) = instance.port_index(&self.port_names) else {
+            let message =
+                format!("No output port matching '{}' for {}", instance.port, instance.name);
+            warn!(target: "gui", "{}", message);
+            self.notify_error(message);
+            return;
+        };
+        self.target = PortTarget::Port(port);
+        if self.selected_ports.insert(port) && self.connected {
+            self.send(MidiCommand::Connect(vec![port], self.channel));
+        }
+        info!(target: "gui", "Switched to {}", instance.name);
+    }
+
+    /// Shows the values last sent on `channel` of `instance`, keeping the
+    /// current ones for when their channel comes back.
+    fn switch_values(&mut self, instance: Option<usize>, channel: Channel) {
+        if (instance, channel) == (self.instance, self.channel) {
+            return;
+        }
+        let values = self.stashed_values.remove(&(instance, channel)).unwrap_or_default();
+        let previous = std::mem::replace(&mut self.param_values, values);
+        self.stashed_values.insert((self.instance, self.channel), previous);
+        self.instance = instance;
+        self.channel = channel;
+    }
+
+    /// Tabs for the session's instances, when there are any.
+    fn instance_tabs(&mut self, ui: &mut egui::Ui) {
+        if self.config.instances.is_empty() {
+            return;
+        }
+        ui.horizontal(|ui| {
+            ui.label("Device:");
+            for index in 0..self.config.instances.len() {
+                let instance = &self.config.instances[index];
+                let model = self.instance_model(instance);
+                let tracks = instance.tracks(model);
+                let channels = match tracks {
+                    1 => format!("ch {}", instance.channel),
+                    _ => format!("ch {}-{}", instance.channel, instance.channel + tracks - 1),
+                };
+                let hover = format!("{} on {}, {}", model, instance.port, channels);
+                let open = self.instance == Some(index);
+                let tab = ui.selectable_label(open, &instance.name).on_hover_text(hover);
+                if tab.clicked() && !open {
+                    self.select_instance(index);
+                }
+            }
+        });
+        ui.separator();
+    }
+
+    /// An instance's model: its own, else its port's.
+    fn instance_model(&self, instance: &DeviceInstance) -> DeviceModel {
+        let port_name = instance.port_index(&self.port_names).map(|i| &self.port_names[i]);
+        instance.profile.model.unwrap_or_else(|| match port_name {
+            Some(name) => self.model_for(name),
+            None => self.config.default_model(),
+        })
+    }
+
+    /// The open instance's first channel and track count, or channel 1 and
+    /// the model's tracks.
+    fn track_channels(&self) -> (u8, u8) {
+        match self.instance.and_then(|i| self.config.instances.get(i)) {
+            Some(instance) => (instance.channel.clamp(1, 16), instance.tracks(self.model)),
+            None => (1, self.model.tracks()),
+        }
+    }
+
+    /// Switches the parameter map when the open instance or the first
+    /// targeted port has another device model, e.g. after connecting or
+    /// picking a model.
+    fn follow_device_model(&mut self) {
+        let instance = self.instance.and_then(|i| self.config.instances.get(i));
+        let model = match (instance, self.target_names().first()) {
+            (Some(instance), _) => self.instance_model(instance),
+            (None, Some(name)) => self.model_for(name),
+            (None, None) => self.config.default_model(),
+        };
+        if model == self.model {
+            return;
+        }
+        info!(target: "gui", "Parameter map for {}", model);
+        self.model = model;
+        self.reload_map();
+    }
+
+    /// Device profile controls for the targeted ports (shown for the first
+    /// of them); changes apply to all and are saved to the config.
+    fn device_profile_controls(&mut self, ui: &mut egui::Ui) {
+        let names = self.target_names();
+        let Some(first) = names.first() else {
+            return;
+        };
+        let fallback = self.config.default_model().default_profile();
+        let current = self.config.devices.get(first).copied().unwrap_or(fallback);
+        let mut edited = current;
+        ui.label("Device:");
+        let auto = format!("Auto ({})", self.model_for(first));
+        egui::ComboBox::from_id_source("device_model")
+            .selected_text(edited.model.map_or(auto, |m| m.to_string()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut edited.model, None, "Auto")
+                    .on_hover_text("Recognize by port name or identity reply");
+                for m in DeviceModel::ALL {
+                    ui.selectable_value(&mut edited.model, Some(m), m.to_string());
+                }
+            });
+        if edited.model != current.model
+            && let Some(model) = edited.model
+        {
+            // A new model brings its transport protocol along
+            edited.transport = model.default_profile().transport;
+        }
+        ui.label("Transport:");
+        egui::ComboBox::from_id_source("transport_protocol")
+            .selected_text(format!("{:?}", edited.transport))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut edited.transport, TransportProtocol::Realtime, "Realtime");
+                ui.selectable_value(&mut edited.transport, TransportProtocol::Mmc, "MMC");
+                ui.selectable_value(&mut edited.transport, TransportProtocol::Both, "Both");
+            });
+        ui.checkbox(&mut edited.running_status, "Running status")
+            .on_hover_text("Omit repeated status bytes in bursts");
+        ui.menu_button("Notes", |ui| note_defaults_menu(ui, &mut edited))
+            .response
+            .on_hover_text("Pad notes, velocity and note length for this device");
+        if edited == current {
+            return;
+        }
+        for name in names {
+            let profile = self.config.devices.entry(name.clone()).or_insert(fallback);
+            profile.model = edited.model;
+            profile.transport = edited.transport;
+            profile.running_status = edited.running_status;
+            profile.pad_note = edited.pad_note;
+            profile.velocity = edited.velocity;
+            profile.note_length = edited.note_length;
+            let profile = *profile;
+            self.send(MidiCommand::SetDeviceProfile { port_name: name, profile });
+        }
+        if edited.velocity != current.velocity {
+            self.keyboard.set_velocity(edited.velocity.get());
+        }
+        self.save_config();
+    }
+
+    /// The profile of the first targeted port, or the default model's.
+    fn current_profile(&self) -> DeviceProfile {
+        let fallback = self.config.default_model().default_profile();
+        match self.target_names().first() {
+            Some(name) => self.config.port_profiles(&self.port_names).get(name).copied(),
+            None => None,
+        }
+        .unwrap_or(fallback)
+    }
+
+    fn mtc_combo(&mut self, ui: &mut egui::Ui) {
+        let mut rate = self.mtc_rate;
+        ui.label("MTC:");
+        egui::ComboBox::from_id_source("mtc_rate")
+            .selected_text(rate.map_or("Off".to_string(), |r| r.to_string()))
+            .show_ui(ui, |ui| {
+                ui.selectable_value(&mut rate, None, "Off");
+                for r in FrameRate::ALL {
+                    ui.selectable_value(&mut rate, Some(r), r.to_string());
+                }
+            });
+        if rate != self.mtc_rate {
+            self.mtc_rate = rate;
+            self.send(MidiCommand::SetMtcRate(rate));
+        }
+    }
+
+    fn port_label(&self, idx: usize) -> String {
+        match self.port_names.get(idx) {
+            Some(name) => format!("{} (#{})", name, idx),
+            None => format!("#{}", idx),
+        }
+    }
+
+    fn category_group(&mut self, ui: &mut egui::Ui, category: &str, addresses: &[ParamAddress]) {
+        ui.group(|ui| {
+            let knobs = self.config.gui.knobs || self.config.gui.knob_categories.contains(category);
+            ui.horizontal(|ui| {
+                ui.heading(category);
+                if ui.small_button("⟲").on_hover_text("Reset to defaults").clicked() {
+                    for &address in addresses {
+                        self.set_param(address, self.midi_map.default_value(address));
+                    }
+                }
+                let mut on = knobs;
+                if !self.config.gui.knobs
+                    && ui.toggle_value(&mut on, "◎").on_hover_text("Show as knobs").changed()
+                {
+                    if on {
+                        self.config.gui.knob_categories.insert(category.to_string());
+                    } else {
+                        self.config.gui.knob_categories.remove(category);
+                    }
+                    self.save_config();
+                }
+            });
+
+            // Knobs are narrower, so more fit in a row
+            let cols = if knobs { 4 } else { 2 };
+            for row in addresses.chunks(cols) {
+                ui.horizontal(|ui| {
+                    for &address in row {
+                        self.parameter_control(ui, address, knobs);
+                        ui.separator();
+                    }
+                });
+            }
+        });
+    }
+
+    fn parameter_control(&mut self, ui: &mut egui::Ui, address: ParamAddress, knob: bool) {
+        let param_name = self.midi_map.get_address_name(address);
+        let knob_position = self.midi_map.get_by_address(address).and_then(|p| p.position);
+
+        ui.vertical(|ui| {
+            ui.horizontal(|ui| {
+                let label = ui.label(&param_name);
+                if let Some(knob_position) = knob_position {
+                    let letter = knob_position.knob_label();
+                    label.on_hover_text(format!("Knob {} on the device", letter));
+                }
+                let starred = self.config.gui.favorites.contains(&param_name);
+                let star = if starred { "★" } else { "☆" };
+                if ui.small_button(star).on_hover_text("Performance page").clicked() {
+                    if starred {
+                        self.config.gui.favorites.retain(|name| *name != param_name);
+                    } else {
+                        self.config.gui.favorites.push(param_name.clone());
+                    }
+                    self.save_config();
+                }
+            });
+
+            // Enumerations pick a setting by name
+            let options = self.midi_map.options(address);
+            if !options.is_empty() {
+                let value = self.param_values.get(&address).copied().unwrap_or(0);
+                let mut selected = value;
+                egui::ComboBox::from_id_source(("options", address))
+                    .selected_text(self.midi_map.format_value(address, value))
+                    .show_ui(ui, |ui| {
+                        for (option, label) in options {
+                            ui.selectable_value(&mut selected, option, label);
                         }
                     });
+                if selected != value {
+                    self.set_param(address, selected);
+                }
+                ui.label(address.to_string()).on_hover_text(value.to_string());
+                return;
+            }
 
-                    // Right column
+            // The control moves through the parameter's range and curve
+            let midi_map = &self.midi_map;
+            let range = midi_map.range(address);
+            let value = self.param_values.get(&address).copied().unwrap_or(0);
+            let mut position = range.position(value);
+            // Bipolar controls are drawn around their center
+            let center = midi_map.is_bipolar(address).then(|| range.position(64));
+            let slider_response = if knob {
+                knob::knob(ui, &mut position, center)
+            } else {
+                let response = ui.add(
+                    egui::Slider::new(&mut position, 0..=127)
+                        .show_value(true)
+                        .custom_formatter(|v, _| {
+                            midi_map.format_value(address, range.apply(v as u8))
+                        })
+                );
+                if let Some(center) = center {
+                    center_tick(ui, response.rect, center);
+                }
+                response
+            };
+            let slider_response = if range.is_full() {
+                slider_response
+            } else {
+                let limits = format!(
+                    "Limited to {}–{}",
+                    midi_map.format_value(address, range.min),
+                    midi_map.format_value(address, range.max)
+                );
+                slider_response.on_hover_text(limits)
+            };
+            let slider_response =
+                slider_response.context_menu(|ui| self.range_menu(ui, &param_name));
+
+            if slider_response.double_clicked() {
+                self.set_param(address, self.midi_map.default_value(address));
+            } else if slider_response.changed() {
+                self.set_param(address, range.apply(position));
+            }
+
+            let value = self.param_values.get(&address).copied().unwrap_or(0);
+            ui.label(format!("{}: {}", address, self.midi_map.format_value(address, value)))
+                .on_hover_text(value.to_string());
+        });
+    }
+
+    /// Range and curve of the parameter `name`, from a control's context
+    /// menu. Saved to the config as they change.
+    fn range_menu(&mut self, ui: &mut egui::Ui, name: &str) {
+        let current = self.config.ranges.get(name).copied().unwrap_or_default();
+        let mut range = current;
+        egui::Grid::new("range_menu").show(ui, |ui| {
+            ui.label("Min");
+            ui.add(egui::DragValue::new(&mut range.min).clamp_range(0..=range.max));
+            ui.end_row();
+            ui.label("Max");
+            ui.add(egui::DragValue::new(&mut range.max).clamp_range(range.min..=127));
+            ui.end_row();
+            ui.label("Curve");
+            egui::ComboBox::from_id_source("range_curve")
+                .selected_text(range.curve.label())
+                .show_ui(ui, |ui| {
+                    for curve in Curve::ALL {
+                        ui.selectable_value(&mut range.curve, curve, curve.label());
+                    }
+                });
+            ui.end_row();
+            if range.curve == Curve::Stepped {
+                ui.label("Steps");
+                ui.add(egui::DragValue::new(&mut range.steps).clamp_range(2..=128));
+                ui.end_row();
+            }
+        });
+        if ui.button("Full range").clicked() {
+            range = ParamRange::default();
+            ui.close_menu();
+        }
+        if range == current {
+            return;
+        }
+        if range.is_full() {
+            self.config.ranges.remove(name);
+        } else {
+            self.config.ranges.insert(name.to_string(), range);
+        }
+        self.midi_map.apply_ranges(&self.config.ranges);
+        self.save_config();
+    }
+
+    /// Shows `value` on the parameter's control and sends it, kept within
+    /// the parameter's range.
+    fn set_param(&mut self, address: ParamAddress, value: u8) {
+        let value = self.midi_map.range(address).clamp(value);
+        self.param_values.insert(address, value);
+        let Ok(value) = Value7::new(value) else {
+            return;
+        };
+        self.send(MidiCommand::SendParam { channel: self.channel, address, value });
+        self.last_sent = Some((address, value));
+        self.last_sent_time = Some(std::time::Instant::now());
+        let tick = self.transport.as_ref().filter(|t| t.is_running()).map(|t| t.ticks());
+        if self.automation_recording
+            && let Some(tick) = tick
+            && let Some(param) = self.midi_map.get_by_address(address)
+        {
+            let automation = &mut self.config.automation;
+            let (channel, value) = (self.channel, value.get());
+            self.automation_recorder.record(automation, channel, &param.name, address, tick, value);
+            self.automation_changed = true;
+        }
+    }
+
+    /// Resets the track's sound to the targeted device's init patch.
+    fn send_init(&mut self) {
+        let port_name = self.target_names().into_iter().next();
+        let patch = self.config.init_patch(port_name.as_deref(), &self.midi_map);
+        info!(target: "gui", "Init patch: {} parameters on ch {}", patch.len(), self.channel);
+        for (address, value) in patch {
+            self.set_param(address, value);
+        }
+    }
+
+    /// Moves the parameters in the randomizer's scope by its amount.
+    fn randomize(&mut self) {
+        let values = &self.param_values;
+        let scope = self.random_scope.as_deref();
+        let current = |address| values.get(&address).copied();
+        let (amount, instance, channel) = (self.random_amount, self.instance, self.channel);
+        let changes =
+            self.randomizer.randomize(&self.midi_map, scope, amount, instance, channel, current);
+        info!(
+            target: "gui",
+            "Randomized {} parameter(s) in {} by {}% on ch {}",
+            changes.len(),
+            scope.unwrap_or("all categories"),
+            self.random_amount,
+            self.channel
+        );
+        for (address, value) in changes {
+            self.set_param(address, value);
+        }
+    }
+
+    /// Sends the values the latest randomization replaced to the track it
+    /// randomized, even if another instance or channel is open by now.
+    fn undo_randomize(&mut self) {
+        let Some(undo) = self.randomizer.undo() else {
+            return;
+        };
+        let target = match undo.instance.and_then(|index| self.config.instances.get(index)) {
+            Some(instance) => match instance.port_index(&self.port_names) {
+                Some(port) => PortTarget::Port(port),
+                None => {
+                    let message = format!("No output port matching '{}'", instance.port);
+                    self.notify_error(message);
+                    return;
+                }
+            },
+            None => self.target,
+        };
+        let channel = undo.channel;
+        info!(target: "gui", "Restored {} parameter(s) on ch {}", undo.values.len(), channel);
+        let values = match (undo.instance, channel) == (self.instance, self.channel) {
+            true => &mut self.param_values,
+            false => self.stashed_values.entry((undo.instance, channel)).or_default(),
+        };
+        for (address, value) in undo.values {
+            values.insert(address, value);
+            let Ok(value) = Value7::new(value) else {
+                continue;
+            };
+            let cmd = MidiCommand::SendParam { channel, address, value };
+            let _ = self.tx.send(Routed { target, cmd });
+        }
+    }
+
+    /// Brings the device back in line with the sliders.
+    fn sync_to_device(&mut self) {
+        let mut params: Vec<_> = self
+            .param_values
+            .iter()
+            .filter_map(|(&address, &value)| Some((address, Value7::new(value).ok()?)))
+            .map(|(address, value)| (self.channel, address, value))
+            .collect();
+        params.sort_by_key(|&(_, address, _)| address);
+        info!(target: "gui", "Syncing {} parameter(s) on ch {}", params.len(), self.channel);
+        self.send(MidiCommand::SyncParams(params));
+    }
+
+    fn store_init(&mut self) {
+        let Some(port_name) = self.target_names().into_iter().next() else {
+            self.notify_error("Connect to a device to store its init patch".to_string());
+            return;
+        };
+        let values = self.param_values.clone();
+        self.config.set_init_patch(&port_name, &self.midi_map, values);
+        info!(target: "gui", "Stored the init patch for {}", port_name);
+        self.save_config();
+    }
+
+    /// A slider per configured macro, each moving all of its targets.
+    fn macro_sliders(&mut self, ui: &mut egui::Ui) {
+        if self.config.macro_controls.is_empty() {
+            return;
+        }
+        let mut changes = Vec::new();
+        ui.horizontal_wrapped(|ui| {
+            ui.label("Macros:");
+            for control in &self.config.macro_controls {
+                let position = self.macro_positions.entry(control.name.clone()).or_insert(0);
+                let targets: Vec<_> = control
+                    .targets
+                    .iter()
+                    .map(|t| format!("{}: {} → {}", t.parameter, t.from, t.to))
+                    .collect();
+                let slider = egui::Slider::new(position, 0..=127).text(&control.name);
+                if ui.add(slider).on_hover_text(targets.join("\n")).changed() {
+                    changes.extend(control.values(*position, &self.midi_map));
+                }
+            }
+        });
+        for (address, value) in changes {
+            self.set_param(address, value);
+        }
+    }
+
+    /// Spring-loaded bend: follows the drag and snaps back to center on release.
+    fn pitch_bend_slider(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Pitch Bend:");
+            let response = ui.add(
+                egui::Slider::new(&mut self.pitch_bend, -8192..=8191)
+                    .show_value(true)
+            );
+            let mut changed = response.changed();
+            if !response.dragged() && self.pitch_bend != 0 {
+                self.pitch_bend = 0;
+                changed = true;
+            }
+            if changed {
+                self.send(MidiCommand::PitchBend {
+                    channel: self.channel,
+                    bend: self.pitch_bend,
+                });
+            }
+        });
+    }
+
+    /// Banks A–H by 16 patterns. A pattern queued for the next bar or
+    /// pattern end is shown amber until it starts.
+    fn patterns_page(&mut self, ui: &mut egui::Ui) {
+        ui.heading("Patterns");
+        if let Some((pattern, bar)) = self.queued_pattern
+            && (!self.running || self.position.bar >= bar)
+        {
+            self.selected_pattern = Some(pattern);
+            self.queued_pattern = None;
+        }
+        ui.horizontal(|ui| {
+            let mut changed = false;
+            ui.label("Change");
+            egui::ComboBox::from_id_source("pattern_change")
+                .selected_text(self.config.pattern_change.label())
+                .show_ui(ui, |ui| {
+                    for change in PatternChange::ALL {
+                        let option = ui.selectable_value(
+                            &mut self.config.pattern_change,
+                            change,
+                            change.label(),
+                        );
+                        changed |= option.changed();
+                    }
+                });
+            if self.config.pattern_change == PatternChange::PatternEnd {
+                let mut bars = self.config.pattern_bars.unwrap_or(1);
+                let drag = egui::DragValue::new(&mut bars).clamp_range(1..=64).suffix(" bar(s)");
+                if ui.add(drag).on_hover_text("Length of the patterns").changed() {
+                    self.config.pattern_bars = Some(bars);
+                    changed = true;
+                }
+            }
+            if changed {
+                self.save_config();
+            }
+            ui.separator();
+            match (self.selected_pattern, self.queued_pattern) {
+                (_, Some((pattern, _))) => ui.label(format!("Queued: {}", pattern)),
+                (Some(pattern), None) => ui.label(format!("Selected: {}", pattern)),
+                (None, None) => ui.label("Selected: none"),
+            };
+        });
+        ui.add_space(8.0);
+        egui::Grid::new("pattern_grid").spacing([4.0, 4.0]).show(ui, |ui| {
+            for bank in 0..pattern::BANKS {
+                ui.strong(format!("{}", (b'A' + bank) as char));
+                for index in 0..pattern::PATTERNS_PER_BANK {
+                    let Ok(pattern) = Pattern::new(bank, index) else {
+                        continue;
+                    };
+                    let queued = matches!(self.queued_pattern, Some((p, _)) if p == pattern);
+                    let mut button = egui::Button::new(format!("{:02}", index + 1))
+                        .min_size(egui::vec2(36.0, 28.0))
+                        .selected(self.selected_pattern == Some(pattern));
+                    if queued {
+                        button = button.fill(egui::Color32::from_rgb(140, 100, 0));
+                    }
+                    if ui.add(button).clicked() {
+                        self.select_pattern(pattern);
+                    }
+                }
+                ui.end_row();
+            }
+        });
+    }
+
+    /// Sends the Program Change, deferred by the pattern change setting
+    /// while playing.
+    fn select_pattern(&mut self, pattern: Pattern) {
+        let (change, bars) = (self.config.pattern_change, self.config.pattern_bars.unwrap_or(1));
+        self.send(MidiCommand::SelectPattern { channel: self.channel, pattern, change, bars });
+        if change != PatternChange::Now && self.running {
+            let bar = change.effective_bar(self.position.bar, bars);
+            self.queued_pattern = Some((pattern, bar));
+        } else {
+            self.selected_pattern = Some(pattern);
+            self.queued_pattern = None;
+        }
+    }
+
+    /// Stacks unexpired toasts above the status bar, newest at the bottom.
+    fn show_toasts(&mut self, ctx: &egui::Context) {
+        self.toasts.retain(|(_, shown)| shown.elapsed() < TOAST_DURATION);
+        if self.toasts.is_empty() {
+            return;
+        }
+        let mut dismissed = None;
+        egui::Area::new("toasts")
+            .anchor(egui::Align2::RIGHT_BOTTOM, egui::vec2(-8.0, -40.0))
+            .show(ctx, |ui| {
+                for (i, (message, _)) in self.toasts.iter().enumerate() {
+                    egui::Frame::popup(ui.style()).show(ui, |ui| {
+                        ui.horizontal(|ui| {
+                            ui.colored_label(egui::Color32::RED, format!("✗ {}", message));
+                            if ui.small_button("✕").clicked() {
+                                dismissed = Some(i);
+                            }
+                        });
+                    });
+                }
+            });
+        if let Some(i) = dismissed {
+            self.toasts.remove(i);
+        }
+    }
+
+    /// Scope, amount and buttons of the parameter randomizer.
+    fn randomize_controls(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Randomize");
+            egui::ComboBox::from_id_source("random_scope")
+                .selected_text(self.random_scope.as_deref().unwrap_or("All categories"))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut self.random_scope, None, "All categories");
+                    for (category, _) in &self.categories {
+                        let option = Some(category.clone());
+                        ui.selectable_value(&mut self.random_scope, option, category);
+                    }
+                });
+            let drag = egui::DragValue::new(&mut self.random_amount)
+                .clamp_range(0..=100)
+                .suffix("%");
+            ui.add(drag).on_hover_text("How far values move, in percent of their ranges");
+            let hover = "Move each value by a random amount within its range and send it";
+            if ui.button("🎲 Randomize").on_hover_text(hover).clicked() {
+                self.randomize();
+            }
+            let undo = egui::Button::new("Undo");
+            let hover = "Send the values the last randomize replaced";
+            if ui.add_enabled(self.randomizer.can_undo(), undo).on_hover_text(hover).clicked() {
+                self.undo_randomize();
+            }
+        });
+    }
+
+    /// Parameter sliders by category, plus pitch bend.
+    fn parameters_page(&mut self, ui: &mut egui::Ui) {
+        ui.heading(format!("{} Parameters", self.model));
+        ui.horizontal(|ui| {
+            let device = match self.model {
+                DeviceModel::Generic => "device".to_string(),
+                model => model.to_string(),
+            };
+            ui.label(format!(
+                "Move sliders to send CC values to your {}; double-click to reset",
+                device
+            ));
+            if ui.checkbox(&mut self.config.gui.knobs, "Knobs").changed() {
+                self.save_config();
+            }
+            let hover = "Send every parameter's init value on this channel";
+            if ui.button("Send Init").on_hover_text(hover).clicked() {
+                self.send_init();
+            }
+            let hover = "Save the current values as this device's init patch";
+            if ui.button("Store Init").on_hover_text(hover).clicked() {
+                self.store_init();
+            }
+            let hover = "Resend every value shown, e.g. after power-cycling the device";
+            if ui.button("Sync to Device").on_hover_text(hover).clicked() {
+                self.sync_to_device();
+            }
+        });
+        self.randomize_controls(ui);
+        self.pitch_bend_slider(ui);
+        self.macro_sliders(ui);
+        ui.horizontal(|ui| {
+            ui.label("Search:");
+            let hover = "Names, categories and aliases, e.g. cutoff";
+            ui.text_edit_singleline(&mut self.param_search).on_hover_text(hover);
+            if !self.param_search.is_empty() && ui.small_button("✕").clicked() {
+                self.param_search.clear();
+            }
+        });
+        let search = self.param_search.trim();
+        let found: Option<BTreeSet<ParamAddress>> = (!search.is_empty())
+            .then(|| self.midi_map.search(search).iter().map(|p| p.address).collect());
+        egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+            // The groups borrow from self; take them out while drawing
+            let sorted_categories = std::mem::take(&mut self.categories);
+            let shown: Vec<(&str, Vec<ParamAddress>)> = sorted_categories
+                .iter()
+                .map(|(category, addresses)| {
+                    let addresses: Vec<ParamAddress> = addresses
+                        .iter()
+                        .copied()
+                        .filter(|a| found.as_ref().is_none_or(|found| found.contains(a)))
+                        .collect();
+                    (category.as_str(), addresses)
+                })
+                .filter(|(_, addresses)| !addresses.is_empty())
+                .collect();
+            let columns = match self.config.gui.layout.parameter_columns {
+                0 => (ui.available_width() / CATEGORY_WIDTH) as usize,
+                columns => columns as usize,
+            };
+            let per_column = shown.len().div_ceil(columns.max(1)).max(1);
+
+            ui.horizontal(|ui| {
+                for column in shown.chunks(per_column) {
                     ui.vertical(|ui| {
-                        let half = (sorted_categories.len() + 1) / 2;
-                        for (category, mut ccs) in sorted_categories.iter().skip(half).cloned() {
-                            ccs.sort();
-                            
-                            ui.group(|ui| {
-                                ui.heading(&category);
-                                
-                                let cols = 2;
-                                for row in 0..((ccs.len() + cols - 1) / cols) {
-                                    ui.horizontal(|ui| {
-                                        for col in 0..cols {
-                                            let idx = row * cols + col;
-                                            if idx >= ccs.len() {
-                                                break;
-                                            }
-                                            
-                                            let cc = ccs[idx];
-                                            let param_name = self.midi_map.get_name(cc);
-                                            
-                                            ui.vertical(|ui| {
-                                                ui.label(&param_name);
-                                                
-                                                let slider_response = ui.add(
-                                                    egui::Slider::new(&mut self.cc_values[cc as usize], 0..=127)
-                                                        .show_value(true)
-                                                );
-                                                
-                                                if slider_response.changed() {
-                                                    let new_val = self.cc_values[cc as usize] as u8;
-                                                    let _ = self.tx.send(MidiCommand::SendCC {
-                                                        channel: self.channel,
-                                                        controller: cc,
-                                                        value: new_val,
-                                                    });
-                                                    self.last_sent_cc = Some((cc, new_val));
-                                                    self.last_sent_time = Some(std::time::Instant::now());
-                                                }
-                                                
-                                                ui.label(format!("Value: {}", self.cc_values[cc as usize]));
-                                            });
-                                            
-                                            ui.separator();
-                                        }
-                                    });
-                                }
-                            });
+                        for (category, addresses) in column {
+                            self.category_group(ui, category, addresses);
                         }
                     });
+                }
+            });
+            self.categories = sorted_categories;
+        });
+    }
+
+    /// Starred parameters in the user's order, with controls to reorder
+    /// and remove them.
+    fn performance_page(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.heading("Performance");
+            ui.checkbox(&mut self.editing_performance, "Edit layout");
+        });
+        if self.config.gui.favorites.is_empty() {
+            ui.label("Star parameters (☆) on the Parameters page to add them here");
+            return;
+        }
+
+        if self.editing_performance {
+            let count = self.config.gui.favorites.len();
+            let mut moved = None;
+            let mut removed = None;
+            for (i, name) in self.config.gui.favorites.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(i > 0, egui::Button::new("▲")).clicked() {
+                        moved = Some((i, i - 1));
+                    }
+                    if ui.add_enabled(i + 1 < count, egui::Button::new("▼")).clicked() {
+                        moved = Some((i, i + 1));
+                    }
+                    if ui.button("✖").on_hover_text("Remove").clicked() {
+                        removed = Some(i);
+                    }
+                    ui.label(name);
                 });
+            }
+            if let Some((from, to)) = moved {
+                self.config.gui.favorites.swap(from, to);
+                self.save_config();
+            }
+            if let Some(i) = removed {
+                self.config.gui.favorites.remove(i);
+                self.save_config();
+            }
+            return;
+        }
+
+        // Names no longer in the map (e.g. renamed) are skipped, not dropped
+        let addresses: Vec<ParamAddress> = self
+            .config
+            .gui
+            .favorites
+            .iter()
+            .filter_map(|name| self.midi_map.get_by_name(name))
+            .map(|param| param.address)
+            .collect();
+        let knobs = self.config.gui.knobs;
+        let cols = if knobs { 6 } else { 3 };
+        egui::ScrollArea::vertical().auto_shrink([false; 2]).show(ui, |ui| {
+            for row in addresses.chunks(cols) {
+                ui.horizontal(|ui| {
+                    for &address in row {
+                        self.parameter_control(ui, address, knobs);
+                        ui.separator();
+                    }
+                });
+            }
+        });
+    }
+
+    fn set_performing(&mut self, ctx: &egui::Context, performing: bool) {
+        self.performing = performing;
+        if performing {
+            self.snapshot_names = Snapshot::list().unwrap_or_else(|e| {
+                self.notify_error(format!("Failed to list snapshots: {:#}", e));
+                Vec::new()
+            });
+        }
+        ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(performing));
+    }
+
+    /// Sends a saved snapshot and shows its values on each channel's controls.
+    fn recall_snapshot(&mut self, name: &str) {
+        let snapshot = match Snapshot::load(name) {
+            Ok(snapshot) => snapshot,
+            Err(e) => {
+                self.notify_error(format!("{:#}", e));
+                return;
+            }
+        };
+        for cc in snapshot.cc {
+            let address = ParamAddress::Cc(cc.controller.get());
+            let values = match cc.channel == self.channel {
+                true => &mut self.param_values,
+                false => self.stashed_values.entry((self.instance, cc.channel)).or_default(),
+            };
+            values.insert(address, cc.value.get());
+            self.send(MidiCommand::SendParam { channel: cc.channel, address, value: cc.value });
+        }
+    }
+
+    /// Big transport, tempo, macro controls and snapshot pads for playing live.
+    fn perform_view(&mut self, ctx: &egui::Context) {
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let big = |text: &str| egui::RichText::new(text).size(40.0);
+            let pad = egui::vec2(140.0, 90.0);
+            ui.horizontal(|ui| {
+                if ui.add_sized(pad, egui::Button::new(big("▶"))).clicked() {
+                    self.send(MidiCommand::Start);
+                }
+                if ui.add_sized(pad, egui::Button::new(big("⏹"))).clicked() {
+                    self.send(MidiCommand::Stop);
+                }
+                if ui.add_sized(pad, egui::Button::new(big("→"))).clicked() {
+                    self.send(MidiCommand::Continue);
+                }
+                ui.add_space(24.0);
+                ui.vertical(|ui| {
+                    ui.label(big(&format!("{:.1} BPM", self.device_bpm)));
+                    let position = egui::RichText::new(self.position.to_string()).size(24.0);
+                    if self.running {
+                        ui.colored_label(egui::Color32::GREEN, position);
+                    } else {
+                        ui.label(position);
+                    }
+                });
+                ui.with_layout(egui::Layout::right_to_left(egui::Align::Min), |ui| {
+                    if ui.button("Exit (F11)").clicked() {
+                        self.set_performing(ctx, false);
+                    }
+                    ui.checkbox(&mut self.editing_macros, "Edit macros");
+                });
+            });
+            ui.separator();
+
+            self.config.gui.macros.resize(MACROS, String::new());
+            let params = self.midi_map.get_all_parameters();
+            let mut assigned = false;
+            ui.horizontal(|ui| {
+                ui.spacing_mut().slider_width = 220.0;
+                for slot in 0..MACROS {
+                    ui.vertical(|ui| {
+                        ui.set_width(110.0);
+                        let name = self.config.gui.macros[slot].clone();
+                        if self.editing_macros {
+                            let shown = if name.is_empty() { "—" } else { name.as_str() };
+                            egui::ComboBox::from_id_source(("macro", slot))
+                                .selected_text(shown)
+                                .width(100.0)
+                                .show_ui(ui, |ui| {
+                                    let slot_name = &mut self.config.gui.macros[slot];
+                                    assigned |= ui
+                                        .selectable_value(slot_name, String::new(), "—")
+                                        .changed();
+                                    for param in &params {
+                                        let (name, label) = (param.name.clone(), &param.name);
+                                        assigned |=
+                                            ui.selectable_value(slot_name, name, label).changed();
+                                    }
+                                });
+                        } else {
+                            ui.strong(if name.is_empty() { "—" } else { name.as_str() });
+                        }
+                        let Some(param) = self.midi_map.get_by_name(&name) else {
+                            return;
+                        };
+                        let value = self.param_values.entry(param.address).or_insert(param.default);
+                        let slider = egui::Slider::new(value, 0..=127)
+                            .vertical()
+                            .custom_formatter(|v, _| param.format.format(v as u8));
+                        let response = ui.add(slider);
+                        if response.double_clicked() {
+                            self.set_param(param.address, param.default);
+                        } else if response.changed() {
+                            let value = *value;
+                            self.set_param(param.address, value);
+                        }
+                    });
+                }
+            });
+            if assigned {
+                self.save_config();
+            }
+            ui.separator();
+
+            if self.snapshot_names.is_empty() {
+                ui.label("Save snapshots (snap save <name> in the CLI) to get pads here");
+                return;
+            }
+            let mut recalled = None;
+            ui.horizontal_wrapped(|ui| {
+                for name in &self.snapshot_names {
+                    let button = egui::Button::new(egui::RichText::new(name).size(24.0));
+                    if ui.add_sized(pad, button).clicked() {
+                        recalled = Some(name.clone());
+                    }
+                }
+            });
+            if let Some(name) = recalled {
+                self.recall_snapshot(&name);
+            }
+        });
+    }
+
+    fn update_device_state(&mut self) {
+        // Drain all pending device state updates
+        while let Ok(state) = self.state_rx.try_recv() {
+            match state {
+                DeviceState::Artist(artist) => {
+                    self.device_artist = artist;
+                }
+                DeviceState::Identified { port_name, model } => {
+                    self.identified.insert(port_name, model);
+                }
+                DeviceState::Bpm(bpm) => {
+                    self.device_bpm = bpm;
+                }
+                DeviceState::LostPorts(ports) => {
+                    self.lost_ports = ports;
+                }
+                DeviceState::Transport(transport) => {
+                    self.transport = Some(transport);
+                }
+                DeviceState::ClockSource(source) => {
+                    self.clock_source = source;
+                }
+                DeviceState::FreeClock(on) => {
+                    self.free_clock = on;
+                }
+                DeviceState::ConnectResult(result) => {
+                    self.connecting = false;
+                    match result {
+                        Ok(_) => {
+                            self.connected = true;
+                            self.remember_ports();
+                        }
+                        Err(message) => {
+                            self.connected = false;
+                            self.notify_error(message);
+                        }
+                    }
+                }
+                DeviceState::Error(message) => {
+                    self.notify_error(message);
+                }
+                DeviceState::Activity(activity) => {
+                    self.activity.push(activity, &self.midi_map);
+                }
+                DeviceState::Sequence(tracks) => {
+                    self.config.sequence = tracks;
+                    self.save_config();
+                }
+                DeviceState::RecordFrom(from) => {
+                    self.record_from = from;
+                }
+            }
+        }
+    }
+}
+
+impl eframe::App for MidiGuiApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Update device state from background thread
+        self.update_device_state();
+        self.reload_edited_files(ctx);
+        self.follow_device_model();
+        // Worker updates (e.g. hotplug) arrive without user input
+        ctx.request_repaint_after(STATE_POLL);
+        if let Some(transport) = &self.transport {
+            self.running = transport.is_running();
+            self.position = transport.position();
+            if self.running {
+                ctx.request_repaint_after(POSITION_REFRESH);
+            }
+        }
+
+        let bindings = &mut self.config.bindings;
+        let (changes, learned) =
+            self.controller_input.poll(bindings, &self.midi_map, &self.param_values);
+        for (address, value) in changes {
+            self.set_param(address, value);
+        }
+        if learned {
+            self.save_config();
+        }
+        // The controller's notes play chords in chord mode, repeat, and
+        // record
+        let (chords, repeat) = (self.config.chord_mode.enabled, self.config.note_repeat.enabled);
+        for (note, velocity) in self.controller_input.take_notes() {
+            if !chords && !repeat && !self.recording {
+                continue;
+            }
+            let channel = self.channel;
+            self.send(match velocity {
+                Some(velocity) => MidiCommand::NoteOn { channel, note, velocity },
+                None => MidiCommand::NoteOff { channel, note },
+            });
+        }
+
+        // Recorded moves play back from the next pass of the loop
+        if self.automation_changed && self.automation_sent.elapsed() >= STATE_POLL {
+            self.load_automation();
+        }
+
+        if ctx.input(|i| i.key_pressed(PERFORM_KEY)) {
+            self.set_performing(ctx, !self.performing);
+        }
+        if self.performing {
+            self.perform_view(ctx);
+            self.show_toasts(ctx);
+            return;
+        }
+
+        egui::TopBottomPanel::top("top_panel").show(ctx, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("MIDI Port:");
+                if self.port_names.is_empty() {
+                    ui.label("No ports available");
+                } else {
+                    let selected_label = match self.selected_ports.len() {
+                        0 => "None".to_string(),
+                        1 => self.port_label(*self.selected_ports.first().unwrap_or(&0)),
+                        n => format!("{} ports", n),
+                    };
+                    egui::ComboBox::from_id_source("ports")
+                        .selected_text(selected_label)
+                        .show_ui(ui, |ui| {
+                            for i in 0..self.port_names.len() {
+                                let mut checked = self.selected_ports.contains(&i);
+                                if ui.checkbox(&mut checked, self.port_label(i)).changed() {
+                                    if checked {
+                                        self.selected_ports.insert(i);
+                                    } else {
+                                        self.selected_ports.remove(&i);
+                                    }
+                                }
+                            }
+                        });
+                }
+
+                ui.label("Channel:");
+                let mut channel = self.channel.get();
+                if ui.add(egui::DragValue::new(&mut channel).clamp_range(1..=16)).changed()
+                    && let Ok(channel) = Channel::new(channel)
+                {
+                    self.switch_values(self.instance, channel);
+                }
+
+                if self.connecting {
+                    ui.add_enabled(false, egui::Button::new("Connect"));
+                    ui.spinner();
+                    ui.label("Connecting…");
+                } else if !self.connected {
+                    if ui.button("Connect").clicked() {
+                        let ports = self.selected_ports.iter().copied().collect();
+                        self.send(MidiCommand::Connect(ports, self.channel));
+                        self.connecting = true;
+                    }
+                } else {
+                    if self.lost_ports.is_empty() {
+                        ui.colored_label(egui::Color32::GREEN, "✓ Connected");
+                    } else {
+                        ui.colored_label(
+                            egui::Color32::YELLOW,
+                            format!("⟳ Reconnecting: {}", self.lost_ports.join(", ")),
+                        );
+                    }
+                    if ui.button("Disconnect").clicked() {
+                        self.send(MidiCommand::Disconnect);
+                        self.connected = false;
+                        self.target = PortTarget::All;
+                        self.lost_ports.clear();
+                    }
+
+                    if self.selected_ports.len() > 1 {
+                        ui.label("Send to:");
+                        let target_label = match self.target {
+                            PortTarget::All => "All ports".to_string(),
+                            PortTarget::Port(idx) => self.port_label(idx),
+                        };
+                        let ports: Vec<usize> = self.selected_ports.iter().copied().collect();
+                        egui::ComboBox::from_id_source("target")
+                            .selected_text(target_label)
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(&mut self.target, PortTarget::All, "All ports");
+                                for idx in ports {
+                                    let label = self.port_label(idx);
+                                    let target = PortTarget::Port(idx);
+                                    ui.selectable_value(&mut self.target, target, label);
+                                }
+                            });
+                    }
+                    
+                    // Show device info
+                    ui.separator();
+                    ui.label(format!("Artist: {}", self.device_artist));
+                    
+                    // BPM control; follows the master in external sync
+                    ui.label("BPM:");
+                    let mut bpm_value = self.device_bpm;
+                    let internal = self.clock_source == ClockSource::Internal;
+                    let slider = egui::Slider::new(&mut bpm_value, 20.0..=300.0).show_value(true);
+                    if ui.add_enabled(internal, slider).changed() {
+                        self.send(MidiCommand::SetBpm(bpm_value));
+                    }
+                    if ui.add_enabled(internal, egui::Button::new("Tap")).clicked()
+                        && let Some(bpm) = self.tap_tempo.tap(Instant::now())
+                    {
+                        self.send(MidiCommand::SetBpm(bpm));
+                    }
+                    self.nudge_buttons(ui, internal);
+                    let mut free_clock = self.free_clock;
+                    let clock = egui::SelectableLabel::new(free_clock, "Clock");
+                    if ui
+                        .add_enabled(internal, clock)
+                        .on_hover_text("Send clock without Start")
+                        .clicked()
+                    {
+                        free_clock = !free_clock;
+                        self.send(MidiCommand::SetFreeClock(free_clock));
+                    }
+                    let swing = egui::DragValue::new(&mut self.swing)
+                        .clamp_range(MIN_SWING..=MAX_SWING)
+                        .prefix("Swing ")
+                        .suffix("%");
+                    let hover = "Delay off-beat 16ths of the clock and sequencer; 50% is straight";
+                    if ui.add(swing).on_hover_text(hover).changed() {
+                        self.send(MidiCommand::SetSwing(self.swing));
+                    }
+
+                    ui.label("Sync:");
+                    let mut source = self.clock_source;
+                    egui::ComboBox::from_id_source("clock_source")
+                        .selected_text(format!("{:?}", source))
+                        .show_ui(ui, |ui| {
+                            ui.selectable_value(&mut source, ClockSource::Internal, "Internal");
+                            ui.selectable_value(&mut source, ClockSource::External, "External");
+                        });
+                    if source != self.clock_source {
+                        self.send(MidiCommand::SetClockSource(source));
+                    }
+                }
+
+                self.device_profile_controls(ui);
+                self.mtc_combo(ui);
+
+                ui.separator();
+
+                if self.running {
+                    ui.colored_label(egui::Color32::GREEN, format!("▶ {}", self.position));
+                } else {
+                    ui.label(format!("⏹ {}", self.position));
+                }
+                self.beat_lights(ui);
+
+                if ui.button("▶ Start").clicked() {
+                    self.send(MidiCommand::Start);
+                }
+                if ui.button("⏹ Stop").clicked() {
+                    self.send(MidiCommand::Stop);
+                }
+                if ui.button("→ Continue").clicked() {
+                    self.send(MidiCommand::Continue);
+                }
+                ui.add(
+                    egui::DragValue::new(&mut self.locate_bar)
+                        .clamp_range(1..=1024)
+                        .prefix("Bar "),
+                );
+                if ui.button("⇥ Locate").clicked() {
+                    self.send(MidiCommand::Locate(self.locate_bar));
+                }
+                if ui.button("⏺ Rec").on_hover_text("MMC Record Strobe").clicked() {
+                    self.send(MidiCommand::Mmc(MmcCommand::RecordStrobe));
+                }
+
+                if let Some((address, val)) = self.last_sent
+                    && let Some(time) = self.last_sent_time
+                    && time.elapsed().as_secs_f32() < 2.0
+                {
+                    let param_name = self.midi_map.get_address_name(address);
+                    ui.label(format!("Last: {} = {}", param_name, val));
+                }
             });
         });
 
         egui::TopBottomPanel::bottom("bottom_panel").show(ctx, |ui| {
             ui.horizontal(|ui| {
+                if let Some(error) = &self.last_error {
+                    ui.colored_label(egui::Color32::RED, format!("✗ {}", error));
+                    if ui.small_button("Clear").clicked() {
+                        self.last_error = None;
+                    }
+                }
                 ui.with_layout(egui::Layout::right_to_left(egui::Align::Center), |ui| {
+                    // Closing the window hands off to run_gui's shutdown
                     if ui.button("Quit").clicked() {
-                        let _ = self.tx.send(MidiCommand::Quit);
-                        std::process::exit(0);
+                        ctx.send_viewport_cmd(egui::ViewportCommand::Close);
+                    }
+                    let perform = ui.button("⛶ Perform");
+                    if perform.on_hover_text("Fullscreen performance view (F11)").clicked() {
+                        self.set_performing(ctx, true);
+                    }
+                    ui.menu_button("🎨 View", |ui| self.view_menu(ui));
+                    ui.menu_button("🎛 Controller", |ui| {
+                        let (changed, error) =
+                            self.controller_input.menu(ui, &mut self.config, &self.midi_map);
+                        if let Some(error) = error {
+                            self.notify_error(error);
+                        }
+                        if changed {
+                            self.save_config();
+                        }
+                    });
+                    ui.toggle_value(&mut self.show_activity, "📜 Activity");
+                    if ui.toggle_value(&mut self.show_keyboard, "🎹 Keyboard").changed()
+                        && !self.show_keyboard
+                    {
+                        let (tx, target) = (&self.tx, self.target);
+                        self.keyboard.release(&mut |cmd| {
+                            let _ = tx.send(Routed { target, cmd });
+                        });
+                    }
+                });
+            });
+        });
+
+        if self.show_activity {
+            let panel = egui::TopBottomPanel::bottom("activity_panel")
+                .resizable(true)
+                .default_height(self.config.gui.layout.activity_height)
+                .show(ctx, |ui| {
+                    if let Some(error) = self.activity.show(ui) {
+                        self.notify_error(error);
                     }
                 });
+            self.track_size(panel.response.rect.height(), |l| &mut l.activity_height);
+        }
+
+        if self.show_keyboard {
+            egui::TopBottomPanel::bottom("keyboard_panel").show(ctx, |ui| {
+                self.chord_mode_row(ui);
+                let (tx, target) = (&self.tx, self.target);
+                self.keyboard.show(ui, self.channel, &mut |cmd| {
+                    let _ = tx.send(Routed { target, cmd });
+                });
             });
+        }
+
+        let layout = &self.config.gui.layout;
+        let left = layout.left.as_deref().and_then(Page::from_id);
+        let right = layout.right.as_deref().and_then(Page::from_id);
+        if let Some(page) = left {
+            let panel = egui::SidePanel::left("dock_left")
+                .resizable(true)
+                .default_width(layout.left_width)
+                .show(ctx, |ui| self.show_page(ui, page));
+            self.track_size(panel.response.rect.width(), |l| &mut l.left_width);
+        }
+        if let Some(page) = right {
+            let panel = egui::SidePanel::right("dock_right")
+                .resizable(true)
+                .default_width(self.config.gui.layout.right_width)
+                .show(ctx, |ui| self.show_page(ui, page));
+            self.track_size(panel.response.rect.width(), |l| &mut l.right_width);
+        }
+        if self.layout_dirty && !ctx.input(|i| i.pointer.any_down()) {
+            self.layout_dirty = false;
+            self.save_config();
+        }
+
+        let docked = |page: Page| Some(page) == left || Some(page) == right;
+        if docked(self.page)
+            && let Some(page) = Page::ALL.into_iter().find(|page| !docked(*page))
+        {
+            self.page = page;
+        }
+        egui::CentralPanel::default().show(ctx, |ui| {
+            self.instance_tabs(ui);
+            ui.horizontal(|ui| {
+                for page in Page::ALL.into_iter().filter(|page| !docked(*page)) {
+                    ui.selectable_value(&mut self.page, page, page.label());
+                }
+            });
+            ui.separator();
+            self.show_page(ui, self.page);
         });
+
+        self.show_toasts(ctx);
     }
-}
\ No newline at end of file
+}
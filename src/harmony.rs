@@ -0,0 +1,266 @@
+//! Chord mode: one note in, a chord out, built from the notes of a key's
+//! scale so whatever is played stays in key.
+
+use crate::types::Value7;
+use serde::{Deserialize, Serialize};
+
+/// Pitch class names, C = 0.
+pub const KEYS: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Scale {
+    #[default]
+    Major,
+    Minor,
+    HarmonicMinor,
+    Dorian,
+    Phrygian,
+    Lydian,
+    Mixolydian,
+    Locrian,
+}
+
+impl Scale {
+    pub const ALL: [Scale; 8] = [
+        Scale::Major,
+        Scale::Minor,
+        Scale::HarmonicMinor,
+        Scale::Dorian,
+        Scale::Phrygian,
+        Scale::Lydian,
+        Scale::Mixolydian,
+        Scale::Locrian,
+    ];
+
+    /// Semitones of each degree above the key note.
+    pub fn intervals(self) -> [u8; 7] {
+        match self {
+            Scale::Major => [0, 2, 4, 5, 7, 9, 11],
+            Scale::Minor => [0, 2, 3, 5, 7, 8, 10],
+            Scale::HarmonicMinor => [0, 2, 3, 5, 7, 8, 11],
+            Scale::Dorian => [0, 2, 3, 5, 7, 9, 10],
+            Scale::Phrygian => [0, 1, 3, 5, 7, 8, 10],
+            Scale::Lydian => [0, 2, 4, 6, 7, 9, 11],
+            Scale::Mixolydian => [0, 2, 4, 5, 7, 9, 10],
+            Scale::Locrian => [0, 1, 3, 5, 6, 8, 10],
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Scale::Major => "Major",
+            Scale::Minor => "Minor",
+            Scale::HarmonicMinor => "Harmonic minor",
+            Scale::Dorian => "Dorian",
+            Scale::Phrygian => "Phrygian",
+            Scale::Lydian => "Lydian",
+            Scale::Mixolydian => "Mixolydian",
+            Scale::Locrian => "Locrian",
+        }
+    }
+}
+
+/// Which scale degrees above the played one make the chord.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChordShape {
+    #[default]
+    Triad,
+    Seventh,
+    Ninth,
+    Sus2,
+    Sus4,
+    /// Root and fifth.
+    Power,
+}
+
+impl ChordShape {
+    pub const ALL: [ChordShape; 6] = [
+        ChordShape::Triad,
+        ChordShape::Seventh,
+        ChordShape::Ninth,
+        ChordShape::Sus2,
+        ChordShape::Sus4,
+        ChordShape::Power,
+    ];
+
+    /// Scale steps above the root, the root being 0.
+    fn degrees(self) -> &'static [usize] {
+        match self {
+            ChordShape::Triad => &[0, 2, 4],
+            ChordShape::Seventh => &[0, 2, 4, 6],
+            ChordShape::Ninth => &[0, 2, 4, 6, 8],
+            ChordShape::Sus2 => &[0, 1, 4],
+            ChordShape::Sus4 => &[0, 3, 4],
+            ChordShape::Power => &[0, 4],
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ChordShape::Triad => "Triad",
+            ChordShape::Seventh => "7th",
+            ChordShape::Ninth => "9th",
+            ChordShape::Sus2 => "Sus2",
+            ChordShape::Sus4 => "Sus4",
+            ChordShape::Power => "Power",
+        }
+    }
+}
+
+/// How the chord's notes are spread over octaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Voicing {
+    /// Stacked from the played note up.
+    #[default]
+    Close,
+    /// The root moved up an octave.
+    FirstInversion,
+    /// The root and the next note moved up an octave.
+    SecondInversion,
+    /// The second note moved up an octave, for a wider sound.
+    Open,
+    /// The second-highest note moved down an octave.
+    Drop2,
+}
+
+impl Voicing {
+    pub const ALL: [Voicing; 5] = [
+        Voicing::Close,
+        Voicing::FirstInversion,
+        Voicing::SecondInversion,
+        Voicing::Open,
+        Voicing::Drop2,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Voicing::Close => "Close",
+            Voicing::FirstInversion => "1st inversion",
+            Voicing::SecondInversion => "2nd inversion",
+            Voicing::Open => "Open",
+            Voicing::Drop2 => "Drop 2",
+        }
+    }
+
+    /// Moves `pitches` (lowest first) between octaves.
+    fn apply(self, pitches: &mut [i16]) {
+        let n = pitches.len();
+        match self {
+            Voicing::Close => {}
+            Voicing::FirstInversion if n > 1 => pitches[0] += 12,
+            Voicing::SecondInversion if n > 2 => {
+                pitches[0] += 12;
+                pitches[1] += 12;
+            }
+            Voicing::Open if n > 2 => pitches[1] += 12,
+            Voicing::Drop2 if n > 2 => pitches[n - 2] -= 12,
+            _ => {}
+        }
+        pitches.sort_unstable();
+    }
+}
+
+/// Settings for turning single notes into chords in a key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ChordMode {
+    pub enabled: bool,
+    /// Pitch class of the key note, 0 (C) to 11 (B).
+    pub key: u8,
+    pub scale: Scale,
+    pub shape: ChordShape,
+    pub voicing: Voicing,
+}
+
+impl ChordMode {
+    /// The chord for `note`: the note, moved down onto the scale if it is
+    /// not in it, with the shape's scale degrees stacked on top and
+    /// voiced. Notes beyond 0-127 are left out.
+    pub fn notes(&self, note: Value7) -> Vec<Value7> {
+        let intervals = self.scale.intervals();
+        let note = note.get() as i16;
+        let from_key = (note - self.key as i16).rem_euclid(12) as u8;
+        let degree = intervals.iter().rposition(|&i| i <= from_key).unwrap_or(0);
+        let root = note - (from_key - intervals[degree]) as i16;
+        let mut pitches: Vec<i16> = self
+            .shape
+            .degrees()
+            .iter()
+            .map(|step| {
+                let index = degree + step;
+                let octaves = (index / intervals.len()) as i16;
+                let above = intervals[index % intervals.len()] as i16 + 12 * octaves;
+                root + above - intervals[degree] as i16
+            })
+            .collect();
+        self.voicing.apply(&mut pitches);
+        pitches
+            .into_iter()
+            .filter_map(|pitch| u8::try_from(pitch).ok().and_then(|p| Value7::new(p).ok()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chord(mode: ChordMode, note: u8) -> Vec<u8> {
+        mode.notes(Value7::new(note).unwrap()).into_iter().map(Value7::get).collect()
+    }
+
+    fn mode(key: u8, scale: Scale, shape: ChordShape, voicing: Voicing) -> ChordMode {
+        ChordMode { enabled: true, key, scale, shape, voicing }
+    }
+
+    #[test]
+    fn chords_in_key() {
+        let c_major = |shape| mode(0, Scale::Major, shape, Voicing::Close);
+        assert_eq!(chord(c_major(ChordShape::Triad), 60), [60, 64, 67]);
+        assert_eq!(chord(c_major(ChordShape::Triad), 62), [62, 65, 69]);
+        assert_eq!(chord(c_major(ChordShape::Seventh), 60), [60, 64, 67, 71]);
+        assert_eq!(chord(c_major(ChordShape::Ninth), 60), [60, 64, 67, 71, 74]);
+        // From the 7th degree the 9th's steps wrap two octaves of the scale
+        assert_eq!(chord(c_major(ChordShape::Ninth), 71), [71, 74, 77, 81, 84]);
+        assert_eq!(chord(c_major(ChordShape::Sus4), 60), [60, 65, 67]);
+        assert_eq!(chord(c_major(ChordShape::Power), 60), [60, 67]);
+        let a_minor = mode(9, Scale::Minor, ChordShape::Triad, Voicing::Close);
+        assert_eq!(chord(a_minor, 57), [57, 60, 64]);
+    }
+
+    #[test]
+    fn notes_outside_the_scale_move_down_onto_it() {
+        let c_major = mode(0, Scale::Major, ChordShape::Triad, Voicing::Close);
+        assert_eq!(chord(c_major, 61), [60, 64, 67]);
+        let d_major = mode(2, Scale::Major, ChordShape::Triad, Voicing::Close);
+        assert_eq!(chord(d_major, 63), [62, 66, 69]);
+        // C is below D major's B, which is below note 0
+        assert_eq!(chord(d_major, 0), [2, 6]);
+    }
+
+    #[test]
+    fn voicings() {
+        let voiced = |shape, voicing| chord(mode(0, Scale::Major, shape, voicing), 60);
+        assert_eq!(voiced(ChordShape::Triad, Voicing::Close), [60, 64, 67]);
+        assert_eq!(voiced(ChordShape::Triad, Voicing::FirstInversion), [64, 67, 72]);
+        assert_eq!(voiced(ChordShape::Triad, Voicing::SecondInversion), [67, 72, 76]);
+        assert_eq!(voiced(ChordShape::Triad, Voicing::Open), [60, 67, 76]);
+        assert_eq!(voiced(ChordShape::Triad, Voicing::Drop2), [52, 60, 67]);
+        assert_eq!(voiced(ChordShape::Seventh, Voicing::Drop2), [55, 60, 64, 71]);
+        // Too few notes to move
+        assert_eq!(voiced(ChordShape::Power, Voicing::Drop2), [60, 67]);
+        assert_eq!(voiced(ChordShape::Power, Voicing::FirstInversion), [67, 72]);
+    }
+
+    #[test]
+    fn notes_beyond_the_range_are_left_out() {
+        let ninth = |voicing| mode(0, Scale::Major, ChordShape::Ninth, voicing);
+        assert_eq!(chord(ninth(Voicing::Close), 127), [127]);
+        assert_eq!(chord(ninth(Voicing::Drop2), 127), [125, 127]);
+        let triad = mode(0, Scale::Major, ChordShape::Triad, Voicing::Drop2);
+        assert_eq!(chord(triad, 0), [0, 7]);
+    }
+}
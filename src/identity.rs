@@ -0,0 +1,77 @@
+use std::fmt;
+
+/// Universal Non-Realtime Identity Request, sent to all devices (0x7F).
+pub const IDENTITY_REQUEST: [u8; 4] = [0x7E, 0x7F, 0x06, 0x01];
+
+const ELEKTRON_ID: [u8; 3] = [0x00, 0x20, 0x3C];
+
+/// Parsed Identity Reply.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeviceIdentity {
+    /// One-byte ID, or 0x00 followed by a two-byte extended ID.
+    pub manufacturer: Vec<u8>,
+    pub family: u16,
+    pub member: u16,
+    pub version: [u8; 4],
+}
+
+impl DeviceIdentity {
+    /// Parses an Identity Reply payload (framing bytes already stripped).
+    pub fn parse(payload: &[u8]) -> Option<Self> {
+        // 7E <device id> 06 02 <manufacturer> <family lsb msb> <member lsb msb> <version x4>
+        let [0x7E, _, 0x06, 0x02, rest @ ..] = payload else {
+            return None;
+        };
+        let id_len = if rest.first() == Some(&0x00) { 3 } else { 1 };
+        let (manufacturer, rest) = rest.split_at_checked(id_len)?;
+        let [f0, f1, m0, m1, v0, v1, v2, v3, ..] = *rest else {
+            return None;
+        };
+        Some(Self {
+            manufacturer: manufacturer.to_vec(),
+            family: f0 as u16 | ((f1 as u16) << 7),
+            member: m0 as u16 | ((m1 as u16) << 7),
+            version: [v0, v1, v2, v3],
+        })
+    }
+
+    pub fn is_elektron(&self) -> bool {
+        self.manufacturer == ELEKTRON_ID
+    }
+
+    pub fn manufacturer_name(&self) -> Option<&'static str> {
+        self.is_elektron().then_some("Elektron")
+    }
+
+    /// Product name for known Elektron family codes.
+    pub fn model_name(&self) -> Option<&'static str> {
+        if !self.is_elektron() {
+            return None;
+        }
+        match self.family {
+            0x0C => Some("Digitakt"),
+            0x0D => Some("Digitone"),
+            _ => None,
+        }
+    }
+
+    /// Firmware version as reported, e.g. "1.51".
+    pub fn firmware(&self) -> String {
+        let [major, minor, ..] = self.version;
+        format!("{}.{:02}", major, minor)
+    }
+}
+
+impl fmt::Display for DeviceIdentity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.manufacturer_name() {
+            Some(name) => write!(f, "{}", name)?,
+            None => write!(f, "Manufacturer {:02X?}", self.manufacturer)?,
+        }
+        match self.model_name() {
+            Some(model) => write!(f, " {}", model)?,
+            None => write!(f, " family 0x{:04X} model 0x{:04X}", self.family, self.member)?,
+        }
+        write!(f, " (OS {})", self.firmware())
+    }
+}
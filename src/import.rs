@@ -0,0 +1,176 @@
+//! Converters from other controller templates to [`MapFile`]s: Electra One
+//! presets (JSON) and TouchOSC layouts (`.tosc`, zlib-compressed XML).
+//! Only the controller numbers, names and pages come across; value labels
+//! and scaling are left behind.
+
+use crate::map_file::{MapEntry, MapFile};
+use anyhow::{bail, Context, Result};
+use flate2::read::ZlibDecoder;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::Read;
+use std::path::Path;
+
+/// Converts the template at `path`, choosing the format by extension.
+pub fn import_file(path: &Path) -> Result<MapFile> {
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let extension = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    match extension.as_deref() {
+        Some("json" | "epr") => {
+            let text = String::from_utf8(bytes).context("Preset is not UTF-8")?;
+            electra_one(&text)
+        }
+        Some("tosc" | "xml") => touchosc(&bytes),
+        _ => bail!("Unknown template type; expected .json (Electra One) or .tosc (TouchOSC)"),
+    }
+}
+
+/// An Electra One preset: each control value sending a CC, 14-bit CC or NRPN
+/// becomes a parameter, grouped by page.
+pub fn electra_one(json: &str) -> Result<MapFile> {
+    let preset: Value = serde_json::from_str(json).context("Invalid Electra One preset")?;
+    let Some(controls) = preset["controls"].as_array() else {
+        bail!("Not an Electra One preset: no controls");
+    };
+    let pages: HashMap<u64, &str> = preset["pages"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|page| Some((page["id"].as_u64()?, page["name"].as_str()?)))
+        .collect();
+
+    let mut map = MapFile {
+        description: preset["name"].as_str().unwrap_or("Electra One preset").to_string(),
+        ..MapFile::default()
+    };
+    for control in controls {
+        let name = control["name"].as_str().unwrap_or_default().trim();
+        let category = control["pageId"]
+            .as_u64()
+            .and_then(|id| pages.get(&id))
+            .copied()
+            .unwrap_or_default();
+        let values = control["values"].as_array().map(Vec::as_slice).unwrap_or_default();
+        for value in values {
+            let message = &value["message"];
+            let Some(number) = message["parameterNumber"].as_u64() else {
+                continue;
+            };
+            let (mut cc, mut cc14, mut nrpn) = (None, None, None);
+            match message["type"].as_str() {
+                Some("cc7") if number < 128 => cc = Some(number as u8),
+                Some("cc14") if number < 32 => cc14 = Some(number as u8),
+                Some("nrpn") if number < 16384 => {
+                    nrpn = Some([(number >> 7) as u8, (number & 0x7F) as u8]);
+                }
+                _ => continue,
+            }
+            // Multi-value controls (envelopes) name each value
+            let name = match (values.len(), value["id"].as_str()) {
+                (2.., Some(id)) => format!("{} {}", name, id),
+                _ if name.is_empty() => format!("CC {}", number),
+                _ => name.to_string(),
+            };
+            let default = value["defaultValue"].as_u64().map(|v| v.min(127) as u8);
+            // Electra One marks centered values with a negative minimum
+            let bipolar = value["min"].as_i64().is_some_and(|min| min < 0);
+            map.push(MapEntry {
+                name,
+                category: category.to_string(),
+                cc,
+                cc14,
+                nrpn,
+                default,
+                bipolar,
+                aliases: Vec::new(),
+            });
+        }
+    }
+    Ok(map)
+}
+
+/// A TouchOSC layout: each control with a MIDI Control Change message
+/// becomes a parameter, grouped by its enclosing named group or page.
+pub fn touchosc(bytes: &[u8]) -> Result<MapFile> {
+    let text = if bytes.starts_with(b"<") {
+        String::from_utf8(bytes.to_vec()).context("Layout is not UTF-8")?
+    } else {
+        let mut text = String::new();
+        ZlibDecoder::new(bytes)
+            .read_to_string(&mut text)
+            .context("Not a TouchOSC layout: failed to decompress")?;
+        text
+    };
+    let doc = roxmltree::Document::parse(&text).context("Invalid TouchOSC layout")?;
+    let mut map = MapFile { description: "TouchOSC layout".to_string(), ..MapFile::default() };
+    let root = doc.root_element();
+    for node in root.children().filter(|n| n.has_tag_name("node")) {
+        touchosc_node(node, "", &mut map);
+    }
+    if map.parameters.is_empty() {
+        bail!("No MIDI Control Change messages in the layout");
+    }
+    Ok(map)
+}
+
+fn child<'a, 'input>(
+    node: roxmltree::Node<'a, 'input>,
+    tag: &str,
+) -> Option<roxmltree::Node<'a, 'input>> {
+    node.children().find(|n| n.has_tag_name(tag))
+}
+
+fn child_text<'a>(node: roxmltree::Node<'a, '_>, tag: &str) -> Option<&'a str> {
+    child(node, tag).and_then(|n| n.text()).map(str::trim)
+}
+
+/// The value of the node's `name` property.
+fn touchosc_name<'a>(node: roxmltree::Node<'a, '_>) -> Option<&'a str> {
+    child(node, "properties")?
+        .children()
+        .filter(|p| p.has_tag_name("property"))
+        .find(|p| child_text(*p, "key") == Some("name"))
+        .and_then(|p| child_text(p, "value"))
+        .filter(|name| !name.is_empty())
+}
+
+fn touchosc_node(node: roxmltree::Node, category: &str, map: &mut MapFile) {
+    let name = touchosc_name(node);
+    let messages = child(node, "messages").into_iter().flat_map(|m| m.children());
+    for midi in messages.filter(|m| m.has_tag_name("midi")) {
+        let Some(message) = child(midi, "message") else {
+            continue;
+        };
+        if child_text(message, "type") != Some("CONTROLCHANGE") {
+            continue;
+        }
+        let Some(cc) = child_text(message, "data1").and_then(|d| d.parse::<u8>().ok()) else {
+            continue;
+        };
+        if cc > 127 {
+            continue;
+        }
+        let name = name.map_or_else(|| format!("CC {}", cc), str::to_string);
+        map.push(MapEntry {
+            name,
+            category: category.to_string(),
+            cc: Some(cc),
+            cc14: None,
+            nrpn: None,
+            default: None,
+            bipolar: false,
+            aliases: Vec::new(),
+        });
+    }
+
+    let is_group = matches!(node.attribute("type"), Some("GROUP" | "PAGER"));
+    let category = match name {
+        Some(name) if is_group => name,
+        _ => category,
+    };
+    if let Some(children) = child(node, "children") {
+        for child in children.children().filter(|n| n.has_tag_name("node")) {
+            touchosc_node(child, category, map);
+        }
+    }
+}
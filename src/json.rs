@@ -0,0 +1,289 @@
+//! `--json` mode: one JSON command per line on stdin, one JSON event per
+//! line on stdout, for driving midi_ctrl from another program.
+
+use crate::cli::{require_ports, Options};
+use crate::fifo;
+use crate::strict::{self, NoPort};
+use anyhow::{Context, Result};
+use midi_ctrl::{
+    output_port_names, Channel, Config, Controller, InputEvent, Message, MidiController, MidiMap,
+    Note, Pattern, PortEvent, Realtime, Value7,
+};
+use serde::{Deserialize, Serialize};
+use std::io::{self, BufRead};
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::Duration;
+
+/// A command line, e.g. `{"cmd":"cc","controller":74,"value":100}`.
+/// `id` is echoed back in the ack or error for matching replies.
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Option<serde_json::Value>,
+    #[serde(flatten)]
+    command: Command,
+}
+
+/// Channel messages take an optional `channel`, defaulting to the
+/// session's.
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "lowercase")]
+enum Command {
+    Cc { controller: Controller, value: Value7, channel: Option<Channel> },
+    Nrpn { msb: Value7, lsb: Value7, value: u16, channel: Option<Channel> },
+    NoteOn { note: Note, velocity: Value7, channel: Option<Channel> },
+    NoteOff { note: Note, channel: Option<Channel> },
+    Note { note: Note, velocity: Value7, duration_ms: u64, channel: Option<Channel> },
+    Pc { program: Value7, channel: Option<Channel> },
+    Pattern { pattern: String, channel: Option<Channel> },
+    Bend { value: i16, channel: Option<Channel> },
+    At { value: Value7, channel: Option<Channel> },
+    PolyAt { note: Note, value: Value7, channel: Option<Channel> },
+    /// Parameter by name, e.g. `filter-frequency`, kept within its
+    /// configured range.
+    Set { param: String, value: Value7, channel: Option<Channel> },
+    Start,
+    Stop,
+    Continue,
+    Spp { beat: u16 },
+    Bpm { bpm: f32 },
+    Quit,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event<'a> {
+    Ready { ports: Vec<String>, input: Option<&'a str> },
+    Ack { id: Option<serde_json::Value> },
+    Error { id: Option<serde_json::Value>, message: String },
+    Midi { timestamp_us: u64, bytes: &'a [u8], message: Option<String> },
+    PortLost { port: String },
+    PortReconnected { port: String, index: usize },
+}
+
+fn emit(event: &Event) {
+    // println! locks stdout per call, so lines from the input thread
+    // never interleave with ours
+    match serde_json::to_string(event) {
+        Ok(line) => println!("{}", line),
+        Err(e) => eprintln!("✗ {}", e),
+    }
+}
+
+/// What the main loop waits on: a line of input, Ctrl+C, or end of input.
+enum JsonInput {
+    Line(String),
+    Interrupt,
+    Eof,
+}
+
+fn spawn_event_printer(events: Receiver<InputEvent>) {
+    thread::spawn(move || {
+        for event in events {
+            // Clock and MTC would flood the stream
+            if let Some(Message::Realtime(Realtime::Clock)) | Some(Message::QuarterFrame { .. }) =
+                event.message
+            {
+                continue;
+            }
+            emit(&Event::Midi {
+                timestamp_us: event.timestamp_us,
+                bytes: &event.bytes,
+                message: event.message.as_ref().map(|m| m.to_string()),
+            });
+        }
+    });
+}
+
+/// Runs one command. Returns `Ok(false)` on `quit`.
+fn execute(ctrl: &mut MidiController, midi_map: &MidiMap, command: Command) -> Result<bool> {
+    let session_channel = ctrl.channel();
+    let messages = match command {
+        Command::Cc { controller, value, channel } => {
+            let channel = channel.unwrap_or(session_channel);
+            vec![Message::ControlChange { channel, controller, value }]
+        }
+        Command::Nrpn { msb, lsb, value, channel } => {
+            Message::nrpn(channel.unwrap_or(session_channel), msb, lsb, value)?
+        }
+        Command::NoteOn { note, velocity, channel } => {
+            let channel = channel.unwrap_or(session_channel);
+            vec![Message::NoteOn { channel, note: note.value(), velocity }]
+        }
+        Command::NoteOff { note, channel } => {
+            let channel = channel.unwrap_or(session_channel);
+            vec![Message::NoteOff { channel, note: note.value(), velocity: Value7::default() }]
+        }
+        Command::Note { note, velocity, duration_ms, channel } => {
+            let channel = channel.unwrap_or(session_channel);
+            let duration = Duration::from_millis(duration_ms);
+            ctrl.play_notes(channel, &[note.value()], velocity, duration)?;
+            return Ok(true);
+        }
+        Command::Pc { program, channel } => {
+            let channel = channel.unwrap_or(session_channel);
+            vec![Message::ProgramChange { channel, program }]
+        }
+        Command::Pattern { pattern, channel } => {
+            pattern.parse::<Pattern>()?.messages(channel.unwrap_or(session_channel))
+        }
+        Command::Bend { value, channel } => {
+            vec![Message::pitch_bend(channel.unwrap_or(session_channel), value)?]
+        }
+        Command::At { value, channel } => {
+            let channel = channel.unwrap_or(session_channel);
+            vec![Message::ChannelPressure { channel, pressure: value }]
+        }
+        Command::PolyAt { note, value, channel } => {
+            let channel = channel.unwrap_or(session_channel);
+            vec![Message::PolyPressure { channel, note: note.value(), pressure: value }]
+        }
+        Command::Set { param, value, channel } => {
+            let param = midi_map
+                .get_by_name(&param)
+                .ok_or_else(|| anyhow::anyhow!("Unknown parameter '{}'", param))?;
+            let value = Value7::new(param.range.clamp(value.get()))?;
+            ctrl.send_param(channel.unwrap_or(session_channel), param.address, value)?;
+            return Ok(true);
+        }
+        Command::Start => {
+            ctrl.start()?;
+            return Ok(true);
+        }
+        Command::Stop => {
+            ctrl.stop()?;
+            return Ok(true);
+        }
+        Command::Continue => {
+            ctrl.resume()?;
+            return Ok(true);
+        }
+        Command::Spp { beat } => {
+            ctrl.song_position(beat)?;
+            return Ok(true);
+        }
+        Command::Bpm { bpm } => {
+            if !(20.0..=300.0).contains(&bpm) {
+                anyhow::bail!("BPM {} out of range (20-300)", bpm);
+            }
+            ctrl.set_bpm(bpm);
+            return Ok(true);
+        }
+        Command::Quit => return Ok(false),
+    };
+    ctrl.send_batch(&messages)?;
+    Ok(true)
+}
+
+pub fn run_json(
+    ports: Vec<usize>,
+    input: Option<usize>,
+    channel: Channel,
+    config: Config,
+    options: &Options,
+) -> Result<()> {
+    require_ports(&ports)?;
+    let mut ctrl = MidiController::new(channel);
+    for (name, profile) in &config.port_profiles(&output_port_names()?) {
+        ctrl.set_device_profile(name, *profile);
+    }
+    ctrl.set_default_profile(config.default_model().default_profile());
+    for port in ports {
+        ctrl.connect(port).map_err(strict::port_error)?;
+    }
+    if let Some(input) = input {
+        spawn_event_printer(ctrl.connect_input(input)?);
+    }
+    emit(&Event::Ready {
+        ports: ctrl.ports().into_iter().map(|(_, name)| name).collect(),
+        input: ctrl.input_port_name(),
+    });
+
+    // Stdin, the pipe and Ctrl+C feed one channel so an interrupt can end
+    // the session while a read is blocked
+    let (input_tx, input_rx) = mpsc::channel();
+    if let Some(path) = &options.listen_fifo {
+        fifo::listen_fifo(path, input_tx.clone(), JsonInput::Line)?;
+    }
+    let ctrlc_tx = input_tx.clone();
+    ctrlc::set_handler(move || {
+        let _ = ctrlc_tx.send(JsonInput::Interrupt);
+    })
+    .context("Failed to install Ctrl+C handler")?;
+    thread::spawn(move || {
+        for line in io::stdin().lock().lines() {
+            let Ok(line) = line else {
+                break;
+            };
+            if input_tx.send(JsonInput::Line(line)).is_err() {
+                return;
+            }
+        }
+        let _ = input_tx.send(JsonInput::Eof);
+    });
+
+    let model = match ctrl.ports().first() {
+        Some((_, name)) => config.model_for(name, None),
+        None => config.default_model(),
+    };
+    let midi_map = config.midi_map(model);
+    // With --strict, the error that ended the session
+    let mut failure = None;
+    while let Ok(JsonInput::Line(line)) = input_rx.recv() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        if let Ok(events) = ctrl.check_ports() {
+            for event in events {
+                match event {
+                    PortEvent::Lost(port) => {
+                        if options.strict {
+                            failure = Some(NoPort(format!("Lost port {}", port)).into());
+                        }
+                        emit(&Event::PortLost { port })
+                    }
+                    PortEvent::Reconnected { name, port } => {
+                        emit(&Event::PortReconnected { port: name, index: port })
+                    }
+                }
+            }
+        }
+        if failure.is_some() {
+            break;
+        }
+        let request: Request = match serde_json::from_str(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                // Still echo the id if the line was JSON at all
+                let id = serde_json::from_str::<serde_json::Value>(&line)
+                    .ok()
+                    .and_then(|v| v.get("id").cloned());
+                emit(&Event::Error { id, message: e.to_string() });
+                if options.strict {
+                    failure = Some(e.into());
+                    break;
+                }
+                continue;
+            }
+        };
+        match execute(&mut ctrl, &midi_map, request.command) {
+            Ok(keep_going) => {
+                emit(&Event::Ack { id: request.id });
+                if !keep_going {
+                    break;
+                }
+            }
+            Err(e) => {
+                emit(&Event::Error { id: request.id, message: format!("{:#}", e) });
+                if options.strict {
+                    failure = Some(e);
+                    break;
+                }
+            }
+        }
+    }
+
+    ctrl.shutdown()?;
+    failure.map_or(Ok(()), Err)
+}
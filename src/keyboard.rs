@@ -0,0 +1,152 @@
+//! On-screen piano keyboard for the GUI: all 128 notes in a scrolling
+//! strip, played on the active channel.
+
+use crate::gui::MidiCommand;
+use eframe::egui;
+use midi_ctrl::{Channel, Note, Value7};
+
+const WHITE_WIDTH: f32 = 22.0;
+const WHITE_HEIGHT: f32 = 90.0;
+const BLACK_WIDTH: f32 = 14.0;
+const BLACK_HEIGHT: f32 = 56.0;
+/// White keys among the 128 MIDI notes.
+const WHITE_KEYS: f32 = 75.0;
+
+fn is_black(note: u8) -> bool {
+    matches!(note % 12, 1 | 3 | 6 | 8 | 10)
+}
+
+/// Key outlines for every note, relative to `origin`.
+fn key_rects(origin: egui::Pos2) -> Vec<(u8, egui::Rect)> {
+    let mut whites = 0.0;
+    let mut keys = Vec::with_capacity(128);
+    for note in 0..=Value7::MAX {
+        let left = origin.x + whites * WHITE_WIDTH;
+        let (min, size) = if is_black(note) {
+            (egui::pos2(left - BLACK_WIDTH / 2.0, origin.y), egui::vec2(BLACK_WIDTH, BLACK_HEIGHT))
+        } else {
+            whites += 1.0;
+            (egui::pos2(left, origin.y), egui::vec2(WHITE_WIDTH, WHITE_HEIGHT))
+        };
+        keys.push((note, egui::Rect::from_min_size(min, size)));
+    }
+    keys
+}
+
+/// The key under `pos`; black keys sit on top of white ones.
+fn note_at(keys: &[(u8, egui::Rect)], pos: egui::Pos2) -> Option<u8> {
+    let hit = |black: bool| {
+        keys.iter()
+            .find(|(note, rect)| is_black(*note) == black && rect.contains(pos))
+            .map(|(note, _)| *note)
+    };
+    hit(true).or_else(|| hit(false))
+}
+
+pub struct Keyboard {
+    /// Octave scrolled to, -1 to 9.
+    octave: i8,
+    velocity: u8,
+    /// The sounding key and the channel it was played on.
+    held: Option<(Channel, u8)>,
+    /// Scroll to `octave` on the next frame.
+    scroll: bool,
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self { octave: 3, velocity: 100, held: None, scroll: true }
+    }
+}
+
+impl Keyboard {
+    /// Sets the velocity of the notes played from now on.
+    pub fn set_velocity(&mut self, velocity: u8) {
+        self.velocity = velocity.clamp(1, 127);
+    }
+
+    /// Ends the sounding note, if any.
+    pub fn release(&mut self, send: &mut dyn FnMut(MidiCommand)) {
+        if let Some((channel, note)) = self.held.take()
+            && let Ok(note) = Value7::new(note)
+        {
+            send(MidiCommand::NoteOff { channel, note });
+        }
+    }
+
+    /// Draws the controls and keys. Dragging across keys glides from note
+    /// to note; letting go releases.
+    pub fn show(&mut self, ui: &mut egui::Ui, channel: Channel, send: &mut dyn FnMut(MidiCommand)) {
+        ui.horizontal(|ui| {
+            if ui.button("◀").on_hover_text("Octave down").clicked() && self.octave > -1 {
+                self.octave -= 1;
+                self.scroll = true;
+            }
+            ui.label(format!("C{}", self.octave));
+            if ui.button("▶").on_hover_text("Octave up").clicked() && self.octave < 9 {
+                self.octave += 1;
+                self.scroll = true;
+            }
+            ui.separator();
+            ui.label("Velocity:");
+            ui.add(egui::Slider::new(&mut self.velocity, 1..=127));
+        });
+
+        // Dragging plays across the keys rather than scrolling
+        let mut scroll_area =
+            egui::ScrollArea::horizontal().id_source("keyboard").drag_to_scroll(false);
+        if self.scroll {
+            let first_c = (self.octave as f32 + 1.0) * 7.0 * WHITE_WIDTH;
+            scroll_area = scroll_area.horizontal_scroll_offset(first_c);
+            self.scroll = false;
+        }
+        scroll_area.show(ui, |ui| {
+            let size = egui::vec2(WHITE_KEYS * WHITE_WIDTH, WHITE_HEIGHT);
+            let (rect, response) = ui.allocate_exact_size(size, egui::Sense::drag());
+            let keys = key_rects(rect.min);
+
+            let pressed = response.is_pointer_button_down_on() || response.dragged();
+            let under = if pressed {
+                response.interact_pointer_pos().and_then(|pos| note_at(&keys, pos))
+            } else {
+                None
+            };
+            if self.held.map(|(_, note)| note) != under {
+                self.release(send);
+                let velocity = Value7::new(self.velocity);
+                if let Some(note) = under
+                    && let (Ok(value), Ok(velocity)) = (Value7::new(note), velocity)
+                {
+                    send(MidiCommand::NoteOn { channel, note: value, velocity });
+                    self.held = Some((channel, note));
+                }
+            }
+
+            let painter = ui.painter_at(rect);
+            let held = self.held.map(|(_, note)| note);
+            let accent = ui.visuals().selection.bg_fill;
+            let stroke = egui::Stroke::new(1.0, egui::Color32::DARK_GRAY);
+            for black in [false, true] {
+                for (note, key) in keys.iter().filter(|(note, _)| is_black(*note) == black) {
+                    let fill = match (held == Some(*note), black) {
+                        (true, _) => accent,
+                        (false, true) => egui::Color32::BLACK,
+                        (false, false) => egui::Color32::WHITE,
+                    };
+                    painter.rect(*key, 2.0, fill, stroke);
+                    if *note % 12 == 0
+                        && let Ok(value) = Value7::new(*note)
+                    {
+                        painter.text(
+                            key.center_bottom() - egui::vec2(0.0, 4.0),
+                            egui::Align2::CENTER_BOTTOM,
+                            Note::new(value).to_string(),
+                            egui::FontId::proportional(10.0),
+                            egui::Color32::DARK_GRAY,
+                        );
+                    }
+                }
+            }
+        });
+    }
+}
@@ -0,0 +1,73 @@
+//! Rotary knob widget for 0-127 parameters, like the Digitakt's encoders.
+
+use eframe::egui;
+use std::f32::consts::PI;
+
+const SIZE: f32 = 48.0;
+/// Vertical drag per step; Shift slows it down for fine changes.
+const PIXELS_PER_STEP: f32 = 2.0;
+const FINE_PIXELS_PER_STEP: f32 = 8.0;
+/// The dial runs clockwise from bottom-left to bottom-right.
+const START_ANGLE: f32 = 0.75 * PI;
+const SWEEP: f32 = 1.5 * PI;
+
+fn point_at(center: egui::Pos2, radius: f32, angle: f32) -> egui::Pos2 {
+    center + radius * egui::vec2(angle.cos(), angle.sin())
+}
+
+fn arc(center: egui::Pos2, radius: f32, from: f32, to: f32) -> Vec<egui::Pos2> {
+    let segments = ((to - from).abs() / SWEEP * 32.0).ceil().max(1.0) as usize;
+    (0..=segments)
+        .map(|i| point_at(center, radius, from + (to - from) * i as f32 / segments as f32))
+        .collect()
+}
+
+/// Drag up to turn up. Marks the response changed when the value moves;
+/// double-clicks are left to the caller. Bipolar knobs light up from
+/// `center_value` instead of the start.
+pub fn knob(ui: &mut egui::Ui, value: &mut u8, center_value: Option<u8>) -> egui::Response {
+    let (rect, mut response) =
+        ui.allocate_exact_size(egui::vec2(SIZE, SIZE), egui::Sense::click_and_drag());
+    let id = response.id;
+
+    if response.dragged() {
+        let per_step = if ui.input(|i| i.modifiers.shift) {
+            FINE_PIXELS_PER_STEP
+        } else {
+            PIXELS_PER_STEP
+        };
+        // Movement short of a whole step carries over to the next frame
+        let carried: f32 = ui.data(|d| d.get_temp(id)).unwrap_or(0.0);
+        let total = carried - response.drag_delta().y / per_step;
+        let steps = total.trunc();
+        ui.data_mut(|d| d.insert_temp(id, total - steps));
+        let turned = (*value as f32 + steps).clamp(0.0, 127.0) as u8;
+        if turned != *value {
+            *value = turned;
+            response.mark_changed();
+        }
+    }
+    if response.drag_released() {
+        ui.data_mut(|d| d.remove::<f32>(id));
+    }
+
+    let visuals = ui.style().interact(&response);
+    let center = rect.center();
+    let radius = SIZE / 2.0 - 4.0;
+    let angle_of = |value: u8| START_ANGLE + SWEEP * value as f32 / 127.0;
+    let angle = angle_of(*value);
+    let from = center_value.map_or(START_ANGLE, angle_of);
+    let painter = ui.painter();
+    painter.circle_filled(center, radius - 4.0, visuals.bg_fill);
+    let track = egui::Stroke::new(3.0, ui.visuals().extreme_bg_color);
+    painter.add(egui::Shape::line(arc(center, radius, START_ANGLE, START_ANGLE + SWEEP), track));
+    let level = egui::Stroke::new(3.0, ui.visuals().selection.bg_fill);
+    painter.add(egui::Shape::line(arc(center, radius, from, angle), level));
+    if center_value.is_some() {
+        let tick = [point_at(center, radius + 2.0, from), point_at(center, radius - 3.0, from)];
+        painter.line_segment(tick, visuals.fg_stroke);
+    }
+    painter.line_segment([center, point_at(center, radius - 6.0, angle)], visuals.fg_stroke);
+
+    response.on_hover_text(format!("{} (Shift-drag for fine steps)", value))
+}
@@ -0,0 +1,292 @@
+//! Software LFOs: modulation worked out here and sent as parameter changes,
+//! on top of the device's own LFO per track. Each runs freely in Hz or
+//! synced to the transport's clock over a note length, sweeping one
+//! parameter around an offset.
+
+use crate::clock::PPQN;
+use crate::midi::Message;
+use crate::midi_map::{MidiMap, ParamAddress};
+use crate::rng::Rng;
+use crate::transport::Transport;
+use crate::types::{Channel, Value7};
+use serde::{Deserialize, Serialize};
+use std::f64::consts::TAU;
+use std::time::{Duration, Instant};
+
+/// LFOs available.
+pub const MAX_LFOS: usize = 4;
+/// How often LFO values are worked out and sent.
+pub const LFO_PERIOD: Duration = Duration::from_millis(20);
+/// Fastest free-running rate; faster than the sends could follow.
+pub const MAX_HZ: f32 = 10.0;
+pub const MIN_HZ: f32 = 0.01;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LfoShape {
+    #[default]
+    Sine,
+    Triangle,
+    Saw,
+    Square,
+    /// A random value held for each cycle.
+    SampleHold,
+}
+
+impl LfoShape {
+    pub const ALL: [LfoShape; 5] = [
+        LfoShape::Sine,
+        LfoShape::Triangle,
+        LfoShape::Saw,
+        LfoShape::Square,
+        LfoShape::SampleHold,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LfoShape::Sine => "Sine",
+            LfoShape::Triangle => "Triangle",
+            LfoShape::Saw => "Saw",
+            LfoShape::Square => "Square",
+            LfoShape::SampleHold => "S&H",
+        }
+    }
+
+    /// The wave at `phase` (0-1 through the cycle), from -1 to 1; `held`
+    /// is the cycle's random value.
+    fn wave(self, phase: f64, held: f64) -> f64 {
+        match self {
+            LfoShape::Sine => (phase * TAU).sin(),
+            LfoShape::Triangle => match phase {
+                p if p < 0.25 => 4.0 * p,
+                p if p < 0.75 => 2.0 - 4.0 * p,
+                p => 4.0 * p - 4.0,
+            },
+            LfoShape::Saw => 2.0 * phase - 1.0,
+            LfoShape::Square if phase < 0.5 => 1.0,
+            LfoShape::Square => -1.0,
+            LfoShape::SampleHold => held,
+        }
+    }
+}
+
+/// Cycle length of an LFO synced to the clock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LfoLength {
+    #[serde(rename = "1/16")]
+    Sixteenth,
+    #[serde(rename = "1/8")]
+    Eighth,
+    #[serde(rename = "1/4")]
+    Quarter,
+    #[serde(rename = "1/2")]
+    Half,
+    #[serde(rename = "1")]
+    Bar,
+    #[serde(rename = "2")]
+    TwoBars,
+    #[serde(rename = "4")]
+    FourBars,
+    #[serde(rename = "8")]
+    EightBars,
+}
+
+impl LfoLength {
+    pub const ALL: [LfoLength; 8] = [
+        LfoLength::Sixteenth,
+        LfoLength::Eighth,
+        LfoLength::Quarter,
+        LfoLength::Half,
+        LfoLength::Bar,
+        LfoLength::TwoBars,
+        LfoLength::FourBars,
+        LfoLength::EightBars,
+    ];
+
+    /// Clock ticks in one cycle.
+    pub fn ticks(self) -> u64 {
+        let quarter = PPQN as u64;
+        match self {
+            LfoLength::Sixteenth => quarter / 4,
+            LfoLength::Eighth => quarter / 2,
+            LfoLength::Quarter => quarter,
+            LfoLength::Half => quarter * 2,
+            LfoLength::Bar => quarter * 4,
+            LfoLength::TwoBars => quarter * 8,
+            LfoLength::FourBars => quarter * 16,
+            LfoLength::EightBars => quarter * 32,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LfoLength::Sixteenth => "1/16",
+            LfoLength::Eighth => "1/8",
+            LfoLength::Quarter => "1/4",
+            LfoLength::Half => "1/2",
+            LfoLength::Bar => "1 bar",
+            LfoLength::TwoBars => "2 bars",
+            LfoLength::FourBars => "4 bars",
+            LfoLength::EightBars => "8 bars",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Lfo {
+    pub enabled: bool,
+    pub shape: LfoShape,
+    /// Cycles per second when running freely.
+    pub hz: f32,
+    /// Cycle length on the clock; runs freely at `hz` if unset. A synced
+    /// LFO holds while the transport is stopped.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync: Option<LfoLength>,
+    /// Width of the sweep, 0-127 from bottom to top.
+    pub depth: u8,
+    /// The value the sweep centers on.
+    pub offset: u8,
+    pub channel: Channel,
+    /// Name as `set` takes it; empty for none.
+    pub parameter: String,
+    /// Looked up by [`Lfo::resolve`]; LFOs without one send nothing.
+    #[serde(skip)]
+    pub address: Option<ParamAddress>,
+}
+
+impl Default for Lfo {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            shape: LfoShape::default(),
+            hz: 1.0,
+            sync: None,
+            depth: 64,
+            offset: 64,
+            channel: Channel::default(),
+            parameter: String::new(),
+            address: None,
+        }
+    }
+}
+
+impl Lfo {
+    /// Looks up the parameter in `midi_map`, e.g. after loading or a
+    /// change of device model.
+    pub fn resolve(&mut self, midi_map: &MidiMap) {
+        self.address = midi_map.get_by_name(&self.parameter).map(|param| param.address);
+    }
+
+    /// The value sent for the wave at `level` (-1 to 1).
+    fn value(&self, level: f64) -> u8 {
+        let value = self.offset as f64 + level * self.depth as f64 / 2.0;
+        value.round().clamp(0.0, 127.0) as u8
+    }
+}
+
+/// Where an LFO is in its cycle, and what it last sent.
+#[derive(Debug, Clone, Copy, Default)]
+struct Voice {
+    phase: f64,
+    /// Cycles completed, for a new random value on each.
+    cycle: u64,
+    held: f64,
+    sent: Option<u8>,
+}
+
+/// Works out the LFOs' values, sent every [`LFO_PERIOD`].
+#[derive(Debug, Default)]
+pub struct LfoBank {
+    lfos: Vec<Lfo>,
+    voices: Vec<Voice>,
+    rng: Rng,
+    /// The transport's tick count and when its latest tick came, as of
+    /// the previous run, to place synced LFOs between ticks.
+    last_tick: Option<(u64, Instant)>,
+    tick_period: Option<Duration>,
+}
+
+impl LfoBank {
+    /// Replaces the LFOs; those kept run on from where they were.
+    pub fn set_lfos(&mut self, lfos: Vec<Lfo>) {
+        self.voices.resize(lfos.len(), Voice::default());
+        for (voice, lfo) in self.voices.iter_mut().zip(&lfos) {
+            if !lfo.enabled {
+                voice.sent = None;
+            }
+        }
+        self.lfos = lfos;
+    }
+
+    /// Whether any LFO has something to send.
+    pub fn is_active(&self) -> bool {
+        self.lfos.iter().any(|lfo| lfo.enabled && lfo.address.is_some())
+    }
+
+    /// The transport's position in ticks at `now`, between ticks by the
+    /// pace they have been coming at; `None` while stopped.
+    fn clock_position(&mut self, transport: &Transport, now: Instant) -> Option<f64> {
+        let (ticks, last) = (transport.ticks(), transport.last_tick());
+        let (Some(last), true) = (last, transport.is_running()) else {
+            self.last_tick = None;
+            return None;
+        };
+        if let Some((before, at)) = self.last_tick
+            && ticks > before
+            && last > at
+        {
+            self.tick_period = Some((last - at) / (ticks - before) as u32);
+        }
+        self.last_tick = Some((ticks, last));
+        let since = now.saturating_duration_since(last).as_secs_f64();
+        let since = match self.tick_period {
+            Some(period) => (since / period.as_secs_f64()).min(1.0),
+            None => 0.0,
+        };
+        // `ticks` pulses have gone out, the latest at `last`
+        Some(ticks.saturating_sub(1) as f64 + since)
+    }
+
+    /// What to send at `now`, `elapsed` after the previous run: each LFO's
+    /// value when it changed.
+    pub fn run(&mut self, transport: &Transport, now: Instant, elapsed: Duration) -> Vec<Message> {
+        let position = self.clock_position(transport, now);
+        let mut messages = Vec::new();
+        for index in 0..self.lfos.len() {
+            let lfo = &self.lfos[index];
+            let (Some(address), true) = (lfo.address, lfo.enabled) else {
+                continue;
+            };
+            let (channel, shape, voice) = (lfo.channel, lfo.shape, self.voices[index]);
+            let (phase, cycle) = match lfo.sync {
+                Some(length) => {
+                    let Some(position) = position else {
+                        continue;
+                    };
+                    let cycles = position / length.ticks() as f64;
+                    (cycles.fract(), cycles as u64)
+                }
+                None => {
+                    let hz = lfo.hz.clamp(MIN_HZ, MAX_HZ) as f64;
+                    let phase = voice.phase + hz * elapsed.as_secs_f64();
+                    (phase.fract(), voice.cycle + phase as u64)
+                }
+            };
+            let held = if cycle != voice.cycle || voice.sent.is_none() {
+                self.rng.signed()
+            } else {
+                voice.held
+            };
+            let value = self.lfos[index].value(shape.wave(phase, held));
+            let voice = &mut self.voices[index];
+            (voice.phase, voice.cycle, voice.held) = (phase, cycle, held);
+            if voice.sent != Some(value) {
+                voice.sent = Some(value);
+                let value = Value7::new(value).unwrap_or_default();
+                messages.extend(address.messages(channel, value).into_iter().flatten());
+            }
+        }
+        messages
+    }
+}
@@ -0,0 +1,97 @@
+//! LFOs page of the GUI: each software LFO's shape, rate, depth, offset
+//! and the parameter it modulates.
+
+use eframe::egui;
+use midi_ctrl::lfo::{MAX_HZ, MAX_LFOS, MIN_HZ};
+use midi_ctrl::{Channel, Lfo, LfoLength, LfoShape, MidiMap};
+
+/// One LFO's controls. Returns true on change.
+fn lfo_row(ui: &mut egui::Ui, index: usize, lfo: &mut Lfo, midi_map: &MidiMap) -> bool {
+    let mut changed = false;
+    ui.horizontal(|ui| {
+        changed |= ui.checkbox(&mut lfo.enabled, format!("LFO {}", index + 1)).changed();
+        egui::ComboBox::from_id_source(("lfo_shape", index))
+            .width(72.0)
+            .selected_text(lfo.shape.label())
+            .show_ui(ui, |ui| {
+                for shape in LfoShape::ALL {
+                    changed |= ui.selectable_value(&mut lfo.shape, shape, shape.label()).changed();
+                }
+            });
+        egui::ComboBox::from_id_source(("lfo_sync", index))
+            .width(60.0)
+            .selected_text(lfo.sync.map_or("Free", LfoLength::label))
+            .show_ui(ui, |ui| {
+                changed |= ui.selectable_value(&mut lfo.sync, None, "Free").changed();
+                for length in LfoLength::ALL {
+                    let option = ui.selectable_value(&mut lfo.sync, Some(length), length.label());
+                    changed |= option.changed();
+                }
+            })
+            .response
+            .on_hover_text("Free-running, or a cycle per note length on the clock");
+        if lfo.sync.is_none() {
+            let drag = egui::DragValue::new(&mut lfo.hz)
+                .clamp_range(MIN_HZ..=MAX_HZ)
+                .speed(0.01)
+                .suffix(" Hz");
+            changed |= ui.add(drag).changed();
+        }
+        ui.label("Depth");
+        changed |= ui.add(egui::DragValue::new(&mut lfo.depth).clamp_range(0..=127)).changed();
+        ui.label("Offset");
+        changed |= ui.add(egui::DragValue::new(&mut lfo.offset).clamp_range(0..=127)).changed();
+        let mut channel = lfo.channel.get();
+        let drag = egui::DragValue::new(&mut channel).clamp_range(1..=16).prefix("ch ");
+        if ui.add(drag).changed() {
+            lfo.channel = Channel::new(channel).unwrap_or(lfo.channel);
+            changed = true;
+        }
+        let selected = match lfo.parameter.as_str() {
+            "" => "Parameter…",
+            name => name,
+        };
+        egui::ComboBox::from_id_source(("lfo_parameter", index))
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for param in midi_map.get_all_parameters() {
+                    let current = lfo.address == Some(param.address);
+                    if ui.selectable_label(current, &param.name).clicked() {
+                        lfo.parameter = param.name.clone();
+                        lfo.address = Some(param.address);
+                        changed = true;
+                    }
+                }
+            });
+        if !lfo.parameter.is_empty() && lfo.address.is_none() {
+            ui.weak("(not in this device's map)");
+        }
+    });
+    changed
+}
+
+/// Draws the LFOs, with a button adding one on `channel` up to
+/// [`MAX_LFOS`]. Returns true when they were edited and should be sent
+/// and saved.
+pub fn show(ui: &mut egui::Ui, lfos: &mut Vec<Lfo>, midi_map: &MidiMap, channel: Channel) -> bool {
+    let mut edited = false;
+    let mut removed = None;
+    for (index, lfo) in lfos.iter_mut().enumerate() {
+        ui.horizontal(|ui| {
+            edited |= lfo_row(ui, index, lfo, midi_map);
+            if ui.small_button("🗑").on_hover_text("Remove LFO").clicked() {
+                removed = Some(index);
+            }
+        });
+    }
+    if let Some(index) = removed {
+        lfos.remove(index);
+        edited = true;
+    }
+    let add = egui::Button::new(format!("Add LFO on ch {}", channel));
+    if ui.add_enabled(lfos.len() < MAX_LFOS, add).clicked() {
+        lfos.push(Lfo { channel, ..Lfo::default() });
+        edited = true;
+    }
+    edited
+}
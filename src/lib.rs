@@ -0,0 +1,79 @@
+//! Digitakt MIDI control engine.
+//!
+//! The `midi_ctrl` binary is a thin GUI/CLI frontend over this crate; other
+//! programs can embed [`MidiController`] directly.
+
+pub mod automation;
+pub mod backend;
+pub mod chord;
+pub mod clock;
+pub mod config;
+pub mod controller;
+pub mod device;
+pub mod envelope;
+pub mod harmony;
+pub mod identity;
+pub mod lfo;
+pub mod import;
+pub mod macro_control;
+pub mod midi;
+pub mod midi_in;
+pub mod map_file;
+pub mod midi_map;
+pub mod midi_track;
+pub mod mmc;
+pub mod mtc;
+pub mod note;
+pub mod note_repeat;
+pub mod pattern;
+#[cfg(feature = "profile-repo")]
+pub mod profile_repo;
+pub mod randomize;
+pub mod recorder;
+pub mod rng;
+pub mod scheduler;
+pub mod schema;
+pub mod sequencer;
+pub mod snapshot;
+pub mod song;
+pub mod sysex;
+pub mod takeover;
+pub mod timecode;
+pub mod transport;
+pub mod types;
+pub mod watch;
+
+pub use automation::{Automation, AutomationLane, AutomationPlayer, AutomationRecorder};
+pub use backend::{MidiBackend, MidirBackend, MockBackend};
+pub use chord::Chord;
+pub use clock::TapTempo;
+pub use config::{Config, DeviceInstance, DeviceProfile, GuiSettings, PadLayout, PanelLayout, Theme};
+pub use controller::{find_output_port, DryRunSink, output_port_names, MidiController, PortEvent, PortTarget, SendError};
+pub use device::DeviceModel;
+pub use envelope::{Envelope, EnvelopeBank, EnvelopeTrigger};
+pub use harmony::{ChordMode, ChordShape, Scale, Voicing};
+pub use identity::DeviceIdentity;
+pub use lfo::{Lfo, LfoBank, LfoLength, LfoShape};
+pub use midi::{Message, Realtime};
+pub use midi_in::{find_input_port, input_port_index, input_port_names, InputEvent};
+pub use macro_control::{MacroControl, MacroTarget};
+pub use map_file::{MapChange, MapEntry, MapFile, MapIssue, Severity};
+pub use midi_map::{
+    Curve, MidiMap, MidiParameter, PagePosition, ParamAddress, ParamRange, ValueFormat,
+};
+pub use midi_track::{MidiTrack, MidiTrackKnob};
+pub use mmc::{MmcCommand, TransportProtocol};
+pub use note::Note;
+pub use note_repeat::{NoteRepeat, RepeatRate, Repeater};
+pub use pattern::{Pattern, PatternChange};
+pub use randomize::Randomizer;
+pub use recorder::{InputQuantize, Recorder, Recording};
+pub use sequencer::{ParamLock, SeqTrack, Sequencer, Step};
+pub use snapshot::Snapshot;
+pub use song::{Song, SongEntry, SongPlayer};
+pub use scheduler::{JobId, Scheduler, SchedulerHandle};
+pub use takeover::{Binding, Takeover, TakeoverMode};
+pub use timecode::{FrameRate, Timecode};
+pub use transport::{ClockSource, Position, Transport, BEATS_PER_BAR};
+pub use types::{Channel, Controller, Value7};
+pub use watch::FileWatch;
@@ -0,0 +1,68 @@
+//! User-defined macros: one 0-127 control moving several parameters, each
+//! between its own end values along its own curve, e.g. a "Build-up" that
+//! opens the filter while drying out the reverb.
+
+use crate::midi_map::{Curve, MidiMap, ParamAddress, ParamRange};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MacroControl {
+    pub name: String,
+    #[serde(default, rename = "target")]
+    pub targets: Vec<MacroTarget>,
+}
+
+/// A parameter a macro moves.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MacroTarget {
+    /// Name or slug, as `set` takes it.
+    pub parameter: String,
+    /// Values at the bottom and top of the macro's travel; `from` above
+    /// `to` lowers the parameter as the macro rises.
+    pub from: u8,
+    pub to: u8,
+    pub curve: Curve,
+    /// Values a stepped curve snaps to, ends included.
+    pub steps: u8,
+}
+
+impl Default for MacroTarget {
+    fn default() -> Self {
+        Self { parameter: String::new(), from: 0, to: 127, curve: Curve::Linear, steps: 8 }
+    }
+}
+
+impl MacroTarget {
+    /// The parameter's value with the macro at `position` (0-127).
+    pub fn value(&self, position: u8) -> u8 {
+        let shape = ParamRange { curve: self.curve, steps: self.steps, ..ParamRange::default() };
+        let t = shape.apply(position) as f32 / 127.0;
+        let (from, to) = (self.from.min(127) as f32, self.to.min(127) as f32);
+        (from + t * (to - from)).round() as u8
+    }
+}
+
+impl MacroControl {
+    /// The name as one lowercase word, like [`crate::MidiParameter::slug`].
+    pub fn slug(&self) -> String {
+        self.name.to_lowercase().replace(' ', "-")
+    }
+
+    /// Whether `name` (any case, or the slug) names this macro.
+    pub fn is_named(&self, name: &str) -> bool {
+        name.trim().to_lowercase().replace(' ', "-") == self.slug()
+    }
+
+    /// The targets' values with the macro at `position`, kept within each
+    /// parameter's range. Targets not in `midi_map` are left out.
+    pub fn values(&self, position: u8, midi_map: &MidiMap) -> Vec<(ParamAddress, u8)> {
+        self.targets
+            .iter()
+            .filter_map(|target| {
+                let param = midi_map.get_by_name(&target.parameter)?;
+                Some((param.address, param.range.clamp(target.value(position))))
+            })
+            .collect()
+    }
+}
@@ -1,36 +1,247 @@
-use anyhow::Result;
-use clap::Parser;
-use midir::MidiOutput;
+use anyhow::{Context, Result};
+use clap::{ArgAction, Parser, Subcommand};
+use midi_ctrl::{find_output_port, Channel, Config, DeviceModel};
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::level_filters::LevelFilter;
+use tracing_subscriber::filter::Targets;
+use tracing_subscriber::fmt::writer::BoxMakeWriter;
+use tracing_subscriber::prelude::*;
 
+mod activity;
+mod automation_editor;
+mod cli;
+mod controller_input;
+mod envelope_panel;
+mod fifo;
 mod gui;
-mod midi_map;
+mod json;
+mod keyboard;
+mod knob;
+mod lfo_panel;
+mod midi_tracks;
+mod mixer;
+mod monitor;
+mod morph;
+mod pads;
+mod script;
+mod song_editor;
+mod step_grid;
+mod strict;
+mod xy_pad;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about = "Digitakt MIDI controller")]
 struct Args {
     /// MIDI channel (1-16). Defaults to 1.
-    #[arg(short, long, default_value_t = 1)]
-    channel: u8,
+    #[arg(short, long, global = true, default_value_t = Channel::default())]
+    channel: Channel,
+
+    /// Run the interactive command line instead of the GUI.
+    #[arg(long)]
+    cli: bool,
+
+    /// Read JSON commands from stdin and write JSON events to stdout,
+    /// for driving midi_ctrl from another program.
+    #[arg(long)]
+    json: bool,
+
+    /// Device model: digitakt, digitakt-2, digitone, syntakt, model-samples
+    /// or generic. Ports with a model in their profile keep it; without
+    /// this, the others are recognized by name or identity reply.
+    #[arg(long, global = true)]
+    device: Option<DeviceModel>,
+
+    /// MIDI output port index (CLI mode). Repeat to open several ports.
+    #[arg(short, long, global = true)]
+    port: Vec<usize>,
+
+    /// Open the first output port whose name contains this text
+    /// (case-insensitive). Repeatable.
+    #[arg(long, global = true)]
+    port_name: Vec<String>,
+
+    /// MIDI input port index to receive from (CLI mode).
+    #[arg(short, long, global = true)]
+    input: Option<usize>,
+
+    /// Print every message that would be sent, decoded and as hex,
+    /// without sending anything (CLI mode and send). Works without a port.
+    #[arg(long, global = true)]
+    dry_run: bool,
+
+    /// Also read commands from this named pipe (created if missing), e.g.
+    /// `echo start > /tmp/midi_ctrl.fifo`. CLI and JSON modes.
+    #[arg(long, global = true)]
+    listen_fifo: Option<PathBuf>,
+
+    /// End at the first failed send, bad argument or missing port, with a
+    /// JSON error line on stderr and exit code 2 (command), 3 (port) or
+    /// 4 (send).
+    #[arg(long, global = true)]
+    strict: bool,
+
+    /// Log more: -v for debug, -vv for trace (including clock timing).
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
+
+    /// Append log output to this file instead of stderr.
+    #[arg(long, global = true)]
+    log_file: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Send one message to the selected port(s) and exit.
+    #[command(subcommand)]
+    Send(cli::SendCommand),
+    /// Print decoded messages arriving on an input port (--input or
+    /// --port-name).
+    Monitor(monitor::MonitorArgs),
+    /// Convert an Electra One preset or TouchOSC layout into a saved
+    /// parameter map.
+    Import(cli::ImportArgs),
+    /// Work with the parameter map of the device in use.
+    #[command(subcommand)]
+    Map(cli::MapCommand),
+    /// Install community parameter maps from the profile repository.
+    #[cfg(feature = "profile-repo")]
+    #[command(subcommand)]
+    Profile(cli::ProfileCommand),
+}
+
+/// Log targets of our own subsystems; everything else (egui, winit)
+/// stays at warnings.
+const LOG_TARGETS: &[&str] = &["midi_ctrl", "clock", "worker", "gui"];
+
+fn init_logging(verbose: u8, log_file: Option<&Path>) -> Result<()> {
+    let level = match verbose {
+        0 => LevelFilter::INFO,
+        1 => LevelFilter::DEBUG,
+        _ => LevelFilter::TRACE,
+    };
+    let filter = LOG_TARGETS
+        .iter()
+        .fold(Targets::new().with_default(LevelFilter::WARN), |filter, target| {
+            filter.with_target(*target, level)
+        });
+    let (writer, ansi) = match log_file {
+        Some(path) => {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .with_context(|| format!("Failed to open log file {}", path.display()))?;
+            (BoxMakeWriter::new(Mutex::new(file)), false)
+        }
+        None => (BoxMakeWriter::new(std::io::stderr), true),
+    };
+    tracing_subscriber::registry()
+        .with(tracing_subscriber::fmt::layer().with_writer(writer).with_ansi(ansi))
+        .with(filter)
+        .init();
+    Ok(())
+}
+
+/// Ports given on the command line, or else the session's instances' and
+/// the ones used last time (matched by name, since indices change between
+/// boots).
+fn resolve_ports(args: &Args, port_names: &[String], config: &Config) -> Result<Vec<usize>> {
+    let mut ports = args.port.clone();
+    for pattern in &args.port_name {
+        match find_output_port(pattern)? {
+            Some(idx) => ports.push(idx),
+            None => {
+                let message = format!("No MIDI output port matching '{}'", pattern);
+                return Err(strict::NoPort(message).into());
+            }
+        }
+    }
+    if ports.is_empty() {
+        ports = config
+            .last_ports
+            .iter()
+            .filter_map(|name| port_names.iter().position(|n| n == name))
+            .collect();
+        ports.extend(config.instances.iter().filter_map(|i| i.port_index(port_names)));
+    }
+    ports.sort_unstable();
+    ports.dedup();
+    Ok(ports)
 }
 
 fn main() -> Result<()> {
     let args = Args::parse();
+    let strict = args.strict;
+    let result = run(args);
+    if strict && let Err(e) = &result {
+        strict::exit_with(e);
+    }
+    result
+}
+
+fn run(args: Args) -> Result<()> {
+    let strict = args.strict;
+    init_logging(args.verbose, args.log_file.as_deref())?;
+
+    let mut config = Config::load().unwrap_or_else(|e| {
+        eprintln!("✗ {:#}", e);
+        Config::default()
+    });
+    config.forced_device = args.device;
+
+    if let Some(Command::Import(import_args)) = args.command {
+        return cli::run_import(config, import_args);
+    }
+    #[cfg(feature = "profile-repo")]
+    if let Some(Command::Profile(command)) = args.command {
+        return cli::run_profile(&config, command);
+    }
+
+    // Monitoring needs no output ports
+    if let Some(Command::Monitor(monitor_args)) = args.command {
+        let port_names = &args.port_name;
+        let model = config.default_model();
+        return monitor::run_monitor(args.input, port_names, model, monitor_args);
+    }
 
-    let midi_out = MidiOutput::new("midi_ctrl")?;
-    
     // List available MIDI ports
-    let ports = midi_out.ports();
-    let mut port_names = Vec::new();
-    for p in ports.iter() {
-        let name = midi_out
-            .port_name(p)
-            .map(|s| s.to_string())
-            .unwrap_or_else(|_| "Unknown".to_string());
-        port_names.push(name);
+    let port_names = midi_ctrl::output_port_names()?;
+    let ports = resolve_ports(&args, &port_names, &config)?;
+
+    if let Some(Command::Send(command)) = args.command {
+        return cli::run_send(ports, args.channel, config, command, args.dry_run);
+    }
+
+    if let Some(Command::Map(command)) = args.command {
+        // The map of the first port given or used last, as a session would
+        let model = match ports.first().and_then(|&idx| port_names.get(idx)) {
+            Some(name) => config.model_for(name, None),
+            None => config.default_model(),
+        };
+        return cli::run_map(&config, model, command);
+    }
+
+    let options = cli::Options {
+        dry_run: args.dry_run,
+        listen_fifo: args.listen_fifo,
+        strict,
+    };
+
+    if args.json {
+        return json::run_json(ports, args.input, args.channel, config, &options);
+    }
+
+    if args.cli {
+        return cli::run_cli(ports, args.input, args.channel, config, &options);
     }
 
     // Launch GUI
-    gui::run_gui(midi_out, port_names, args.channel)?;
-    
+    gui::run_gui(port_names, ports, args.channel, config)?;
+
     Ok(())
-}
\ No newline at end of file
+}
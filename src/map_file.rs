@@ -0,0 +1,538 @@
+//! Parameter maps stored as TOML under the config directory
+//! (`maps/<name>.toml`), e.g. converted from another controller's template.
+
+use crate::config::Config;
+use crate::midi_map::{MidiMap, MidiParameter, ParamAddress, ParamRange, ValueFormat};
+use crate::schema::{self, MAP_MIGRATIONS, MAP_VERSION};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// One parameter, addressed by one of a CC, a 14-bit CC pair (the MSB's
+/// controller, 0-31) or an NRPN (MSB, LSB).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapEntry {
+    pub name: String,
+    #[serde(default)]
+    pub category: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cc: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cc14: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub nrpn: Option<[u8; 2]>,
+    /// Without one, bipolar parameters default to their center.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default: Option<u8>,
+    /// Centered at 64 and shown as -64..+63.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bipolar: bool,
+    /// Other words to find it by, e.g. `["cutoff", "vcf"]`.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+impl MapEntry {
+    pub fn address(&self) -> Option<ParamAddress> {
+        match (self.cc, self.cc14, self.nrpn) {
+            (Some(cc), _, _) => Some(ParamAddress::Cc(cc)),
+            (None, Some(cc), _) => Some(ParamAddress::Cc14(cc)),
+            (None, None, Some([msb, lsb])) => Some(ParamAddress::Nrpn { msb, lsb }),
+            (None, None, None) => None,
+        }
+    }
+}
+
+/// Errors leave a parameter unusable or ambiguous; warnings are likely
+/// mistakes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found by [`MapFile::check`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MapIssue {
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl MapIssue {
+    pub fn error(message: String) -> Self {
+        Self { severity: Severity::Error, message }
+    }
+
+    pub fn warning(message: String) -> Self {
+        Self { severity: Severity::Warning, message }
+    }
+}
+
+impl fmt::Display for MapIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// A difference between two maps, from [`MapFile::diff`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapChange {
+    Added { name: String, address: ParamAddress },
+    Removed { name: String, address: ParamAddress },
+    /// Same address under another name.
+    Renamed { from: String, to: String, address: ParamAddress },
+    /// Same name at another address.
+    Moved { name: String, from: ParamAddress, to: ParamAddress },
+}
+
+impl fmt::Display for MapChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MapChange::Added { name, address } => write!(f, "+ {} ({})", name, address),
+            MapChange::Removed { name, address } => write!(f, "- {} ({})", name, address),
+            MapChange::Renamed { from, to, address } => {
+                write!(f, "~ {} renamed {} ({})", from, to, address)
+            }
+            MapChange::Moved { name, from, to } => write!(f, "~ {}: {} → {}", name, from, to),
+        }
+    }
+}
+
+/// The name as `set` matches it.
+fn slug(name: &str) -> String {
+    name.trim().to_lowercase().replace(' ', "-")
+}
+
+/// CCs the MIDI spec gives a meaning of their own: bank select, data
+/// entry, (N)RPN selection and channel mode messages.
+fn is_reserved_cc(cc: u8) -> bool {
+    matches!(cc, 0 | 6 | 32 | 38 | 96..=101 | 120..=127)
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapFile {
+    /// Where the map came from, e.g. the template's name.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub description: String,
+    #[serde(default, rename = "parameter")]
+    pub parameters: Vec<MapEntry>,
+}
+
+impl MapFile {
+    /// Adds a parameter unless its address is already mapped. Returns
+    /// whether it was added.
+    pub fn push(&mut self, entry: MapEntry) -> bool {
+        let Some(address) = entry.address() else {
+            return false;
+        };
+        if self.parameters.iter().any(|p| p.address() == Some(address)) {
+            return false;
+        }
+        self.parameters.push(entry);
+        true
+    }
+
+    /// The built-in or loaded `midi_map` as entries, e.g. to check it.
+    pub fn from_midi_map(midi_map: &MidiMap) -> Self {
+        let parameters = midi_map
+            .get_all_parameters()
+            .into_iter()
+            .map(|param| {
+                let (mut cc, mut cc14, mut nrpn) = (None, None, None);
+                match param.address {
+                    ParamAddress::Cc(n) => cc = Some(n),
+                    ParamAddress::Cc14(n) => cc14 = Some(n),
+                    ParamAddress::Nrpn { msb, lsb } => nrpn = Some([msb, lsb]),
+                }
+                MapEntry {
+                    bipolar: param.is_bipolar(),
+                    name: param.name,
+                    category: param.category,
+                    cc,
+                    cc14,
+                    nrpn,
+                    default: Some(param.default),
+                    aliases: param.aliases,
+                }
+            })
+            .collect();
+        Self { description: String::new(), parameters }
+    }
+
+    /// Problems with the map: values out of range, parameters sharing an
+    /// address or a name, and missing defaults. A map's categories are the
+    /// ones its parameters name, so none can be empty; settings naming a
+    /// category or parameter the map lacks are [`Config::check_map`]'s.
+    pub fn check(&self) -> Vec<MapIssue> {
+        let mut issues = Vec::new();
+        // Which parameter holds each CC and NRPN, for spotting clashes
+        let mut ccs: HashMap<u8, &str> = HashMap::new();
+        let mut nrpns: HashMap<[u8; 2], &str> = HashMap::new();
+        let mut names: HashMap<String, &str> = HashMap::new();
+        let mut aliases: HashMap<String, &str> = HashMap::new();
+        for entry in &self.parameters {
+            let name = entry.name.as_str();
+            if name.trim().is_empty() {
+                issues.push(MapIssue::error("A parameter has no name".to_string()));
+            }
+            let addresses = [entry.cc.is_some(), entry.cc14.is_some(), entry.nrpn.is_some()];
+            match addresses.iter().filter(|set| **set).count() {
+                0 => issues.push(MapIssue::error(format!("'{}' has no cc, cc14 or nrpn", name))),
+                1 => {}
+                _ => {
+                    let used = entry.address().map(|a| a.to_string()).unwrap_or_default();
+                    let message =
+                        format!("'{}' has several addresses; only {} is used", name, used);
+                    issues.push(MapIssue::warning(message));
+                }
+            }
+
+            let mut out_of_range = |what: &str, value: u16, max: u16| {
+                if value > max {
+                    let message =
+                        format!("'{}': {} {} out of range (0-{})", name, what, value, max);
+                    issues.push(MapIssue::error(message));
+                }
+            };
+            if let Some(cc) = entry.cc {
+                out_of_range("cc", cc.into(), 127);
+            }
+            if let Some(cc) = entry.cc14 {
+                out_of_range("cc14", cc.into(), 31);
+            }
+            if let Some([msb, lsb]) = entry.nrpn {
+                out_of_range("nrpn MSB", msb.into(), 127);
+                out_of_range("nrpn LSB", lsb.into(), 127);
+            }
+            if let Some(default) = entry.default {
+                out_of_range("default", default.into(), 127);
+            } else if !entry.bipolar {
+                let message = format!("'{}' has no default; double-click resets it to 0", name);
+                issues.push(MapIssue::warning(message));
+            }
+
+            // A 14-bit pair also takes the LSB's controller, 32 up
+            let taken: Vec<u8> = match entry.address() {
+                Some(ParamAddress::Cc(cc)) => vec![cc],
+                Some(ParamAddress::Cc14(cc)) if cc < 32 => vec![cc, cc + 32],
+                Some(ParamAddress::Cc14(cc)) => vec![cc],
+                _ => Vec::new(),
+            };
+            for cc in taken {
+                if is_reserved_cc(cc) {
+                    let message = format!("'{}': CC {} is reserved by the MIDI spec", name, cc);
+                    issues.push(MapIssue::warning(message));
+                }
+                if let Some(other) = ccs.insert(cc, name) {
+                    let message =
+                        format!("CC {} is assigned to both '{}' and '{}'", cc, other, name);
+                    issues.push(MapIssue::error(message));
+                }
+            }
+            if let Some(ParamAddress::Nrpn { msb, lsb }) = entry.address()
+                && let Some(other) = nrpns.insert([msb, lsb], name)
+            {
+                let message = format!(
+                    "NRPN {}:{} is assigned to both '{}' and '{}'",
+                    msb, lsb, other, name
+                );
+                issues.push(MapIssue::error(message));
+            }
+
+            if let Some(other) = names.insert(slug(name), name) {
+                let message =
+                    format!("'{}' and '{}' share a name; `set` finds only one", other, name);
+                issues.push(MapIssue::warning(message));
+            }
+            for alias in &entry.aliases {
+                if let Some(other) = aliases.insert(slug(alias), name)
+                    && other != name
+                {
+                    let message = format!(
+                        "'{}' and '{}' share the alias '{}'; `set` finds only one",
+                        other, name, alias
+                    );
+                    issues.push(MapIssue::warning(message));
+                }
+            }
+        }
+        issues.sort_by_key(|issue| issue.severity);
+        issues
+    }
+
+    /// What changed from this map to `other`: parameters are matched by
+    /// name, then those left over by address as renames (the first of
+    /// several at one address). One both renamed and moved matches
+    /// neither way, so shows as removed and added.
+    pub fn diff(&self, other: &MapFile) -> Vec<MapChange> {
+        let addressed = |map: &MapFile| -> Vec<(String, ParamAddress)> {
+            map.parameters
+                .iter()
+                .filter_map(|entry| Some((entry.name.clone(), entry.address()?)))
+                .collect()
+        };
+        let (old, new) = (addressed(self), addressed(other));
+        let mut changes = Vec::new();
+        let mut removed = Vec::new();
+        let is_new = |name: &str| !old.iter().any(|(o, _)| slug(o) == slug(name));
+        let mut added: Vec<&(String, ParamAddress)> =
+            new.iter().filter(|(name, _)| is_new(name)).collect();
+        for (name, address) in &old {
+            match new.iter().find(|(n, _)| slug(n) == slug(name)) {
+                Some((_, to)) if to != address => changes.push(MapChange::Moved {
+                    name: name.clone(),
+                    from: *address,
+                    to: *to,
+                }),
+                Some(_) => {}
+                None => match added.iter().position(|(_, a)| a == address) {
+                    Some(index) => {
+                        let (to, _) = added.remove(index);
+                        let (from, to) = (name.clone(), to.clone());
+                        changes.push(MapChange::Renamed { from, to, address: *address });
+                    }
+                    None => {
+                        removed.push(MapChange::Removed { name: name.clone(), address: *address })
+                    }
+                },
+            }
+        }
+        changes.extend(removed);
+        changes.extend(added.into_iter().map(|(name, address)| MapChange::Added {
+            name: name.clone(),
+            address: *address,
+        }));
+        changes
+    }
+
+    /// The map's parameters as a [`MidiMap`].
+    pub fn midi_map(&self) -> MidiMap {
+        let mut midi_map = MidiMap::empty();
+        for entry in &self.parameters {
+            let Some(address) = entry.address() else {
+                continue;
+            };
+            let category = match entry.category.as_str() {
+                "" => "Parameters",
+                category => category,
+            };
+            let (format, center) = match entry.bipolar {
+                true => (ValueFormat::Bipolar, 64),
+                false => (ValueFormat::Raw, 0),
+            };
+            midi_map.insert(MidiParameter {
+                name: entry.name.clone(),
+                address,
+                category: category.to_string(),
+                default: entry.default.unwrap_or(center),
+                format,
+                range: ParamRange::default(),
+                position: None,
+                aliases: entry.aliases.clone(),
+            });
+        }
+        midi_map
+    }
+
+    pub fn dir() -> Option<PathBuf> {
+        Config::dir().map(|d| d.join("maps"))
+    }
+
+    fn path(name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            bail!("Invalid map name '{}'", name);
+        }
+        let dir = Self::dir().ok_or_else(|| anyhow::anyhow!("No config directory"))?;
+        Ok(dir.join(format!("{}.toml", name)))
+    }
+
+    /// Where the map `name` is: the saved map, or `name` itself if it is a
+    /// path.
+    pub fn resolve(name: &str) -> Result<PathBuf> {
+        match Path::new(name).extension() {
+            Some(_) => Ok(PathBuf::from(name)),
+            None => Self::path(name),
+        }
+    }
+
+    /// Loads the saved map `name`, or the file at `name` if it is a path.
+    pub fn load(name: &str) -> Result<Self> {
+        let path = Self::resolve(name)?;
+        if !path.exists() {
+            bail!("No map named '{}'", name);
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let what = path.display().to_string();
+        schema::parse(&text, &MAP_MIGRATIONS, &what)
+            .with_context(|| format!("Invalid map {}", what))
+    }
+
+    /// Saves the map as `name`, returning where it went.
+    pub fn save(&self, name: &str) -> Result<PathBuf> {
+        let path = Self::path(name)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, schema::to_string(self, MAP_VERSION)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str) -> MapEntry {
+        MapEntry {
+            name: name.to_string(),
+            category: String::new(),
+            cc: None,
+            cc14: None,
+            nrpn: None,
+            default: Some(0),
+            bipolar: false,
+            aliases: Vec::new(),
+        }
+    }
+
+    fn cc(name: &str, cc: u8) -> MapEntry {
+        MapEntry { cc: Some(cc), ..entry(name) }
+    }
+
+    fn map(parameters: Vec<MapEntry>) -> MapFile {
+        MapFile { description: String::new(), parameters }
+    }
+
+    fn messages(map: &MapFile) -> Vec<(Severity, String)> {
+        map.check().into_iter().map(|issue| (issue.severity, issue.message)).collect()
+    }
+
+    #[test]
+    fn cc14_takes_the_lsb_controller() {
+        let fine = MapEntry { cc14: Some(5), ..entry("Fine") };
+        let issues = messages(&map(vec![fine, cc("Other", 37), cc("Free", 36)]));
+        let clash = "CC 37 is assigned to both 'Fine' and 'Other'".to_string();
+        assert_eq!(issues, [(Severity::Error, clash)]);
+    }
+
+    #[test]
+    fn reserved_ccs() {
+        let data_entry = MapEntry { cc14: Some(6), ..entry("Data") };
+        let issues = messages(&map(vec![cc("Bank", 0), data_entry, cc("Mode", 123)]));
+        let reserved = |name: &str, cc: u8| {
+            let message = format!("'{}': CC {} is reserved by the MIDI spec", name, cc);
+            (Severity::Warning, message)
+        };
+        let expected = [("Bank", 0), ("Data", 6), ("Data", 38), ("Mode", 123)];
+        assert_eq!(issues, expected.map(|(name, cc)| reserved(name, cc)));
+        assert!(messages(&map(vec![cc("Cutoff", 74), cc("Fine", 31)])).is_empty());
+    }
+
+    #[test]
+    fn duplicate_addresses_names_and_aliases() {
+        let nrpn = |name| MapEntry { nrpn: Some([1, 2]), ..entry(name) };
+        let aliased = |name, aliases: &[&str]| MapEntry {
+            aliases: aliases.iter().map(|a| a.to_string()).collect(),
+            ..cc(name, 20)
+        };
+        let map = map(vec![
+            nrpn("Level"),
+            nrpn("Volume"),
+            aliased("Filter Freq", &["cutoff", "Cutoff"]),
+            MapEntry { cc: Some(21), ..aliased("filter-freq", &[]) },
+            MapEntry { cc: Some(22), ..aliased("Res", &["cutoff"]) },
+        ]);
+        let expected = [
+            (Severity::Error, "NRPN 1:2 is assigned to both 'Level' and 'Volume'"),
+            (
+                Severity::Warning,
+                "'Filter Freq' and 'filter-freq' share a name; `set` finds only one",
+            ),
+            (
+                Severity::Warning,
+                "'Filter Freq' and 'Res' share the alias 'cutoff'; `set` finds only one",
+            ),
+        ];
+        assert_eq!(messages(&map), expected.map(|(severity, m)| (severity, m.to_string())));
+    }
+
+    #[test]
+    fn missing_and_out_of_range_values() {
+        let nameless = cc(" ", 20);
+        let unaddressed = entry("Nowhere");
+        let wide = MapEntry { cc14: Some(40), default: None, ..entry("Wide") };
+        let pan = MapEntry { default: None, bipolar: true, ..cc("Pan", 10) };
+        let issues: Vec<String> = messages(&map(vec![nameless, unaddressed, wide, pan]))
+            .into_iter()
+            .map(|(_, message)| message)
+            .collect();
+        let expected = [
+            "A parameter has no name",
+            "'Nowhere' has no cc, cc14 or nrpn",
+            "'Wide': cc14 40 out of range (0-31)",
+            "'Wide' has no default; double-click resets it to 0",
+        ];
+        assert_eq!(issues, expected);
+    }
+
+    #[test]
+    fn diff_matches_by_name_then_address() {
+        let nrpn = |name| MapEntry { nrpn: Some([0, 1]), ..entry(name) };
+        let old = map(vec![
+            cc("Cutoff", 74),
+            cc("Res", 71),
+            cc("Drive", 20),
+            cc("Gone", 30),
+            nrpn("Tune"),
+        ]);
+        let new = map(vec![
+            cc("cutoff", 74),
+            cc("Res", 72),
+            cc("Overdrive", 20),
+            cc("Fresh", 40),
+            nrpn("Pitch"),
+            cc("Detune", 21),
+        ]);
+        let cc = ParamAddress::Cc;
+        let renamed = |from: &str, to: &str, address| {
+            MapChange::Renamed { from: from.to_string(), to: to.to_string(), address }
+        };
+        let expected = [
+            MapChange::Moved { name: "Res".to_string(), from: cc(71), to: cc(72) },
+            renamed("Drive", "Overdrive", cc(20)),
+            renamed("Tune", "Pitch", ParamAddress::Nrpn { msb: 0, lsb: 1 }),
+            MapChange::Removed { name: "Gone".to_string(), address: cc(30) },
+            MapChange::Added { name: "Fresh".to_string(), address: cc(40) },
+            MapChange::Added { name: "Detune".to_string(), address: cc(21) },
+        ];
+        assert_eq!(old.diff(&new), expected);
+        assert!(old.diff(&old).is_empty());
+    }
+
+    #[test]
+    fn diff_of_a_renamed_and_moved_parameter() {
+        let old = map(vec![cc("Drive", 20)]);
+        let new = map(vec![cc("Overdrive", 21)]);
+        let expected = [
+            MapChange::Removed { name: "Drive".to_string(), address: ParamAddress::Cc(20) },
+            MapChange::Added { name: "Overdrive".to_string(), address: ParamAddress::Cc(21) },
+        ];
+        assert_eq!(old.diff(&new), expected);
+        // Of two new names at a removed one's address, the first is the rename
+        let new = map(vec![cc("Overdrive", 20), cc("Saturation", 20)]);
+        let expected = [
+            MapChange::Renamed {
+                from: "Drive".to_string(),
+                to: "Overdrive".to_string(),
+                address: ParamAddress::Cc(20),
+            },
+            MapChange::Added { name: "Saturation".to_string(), address: ParamAddress::Cc(20) },
+        ];
+        assert_eq!(old.diff(&new), expected);
+    }
+}
@@ -0,0 +1,383 @@
+use crate::note::Note;
+use crate::types::{Channel, Controller, Value7};
+use anyhow::{bail, Result};
+use std::fmt;
+
+/// System realtime messages (single status byte, no data).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Realtime {
+    Clock,
+    Start,
+    Continue,
+    Stop,
+    ActiveSensing,
+    Reset,
+}
+
+impl Realtime {
+    pub fn status(self) -> u8 {
+        match self {
+            Realtime::Clock => 0xF8,
+            Realtime::Start => 0xFA,
+            Realtime::Continue => 0xFB,
+            Realtime::Stop => 0xFC,
+            Realtime::ActiveSensing => 0xFE,
+            Realtime::Reset => 0xFF,
+        }
+    }
+
+    pub fn from_status(status: u8) -> Option<Self> {
+        match status {
+            0xF8 => Some(Realtime::Clock),
+            0xFA => Some(Realtime::Start),
+            0xFB => Some(Realtime::Continue),
+            0xFC => Some(Realtime::Stop),
+            0xFE => Some(Realtime::ActiveSensing),
+            0xFF => Some(Realtime::Reset),
+            _ => None,
+        }
+    }
+}
+
+/// A MIDI message. Channel message fields are validated newtypes, so they
+/// cannot hold out-of-range values.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    NoteOn { channel: Channel, note: Value7, velocity: Value7 },
+    NoteOff { channel: Channel, note: Value7, velocity: Value7 },
+    ControlChange { channel: Channel, controller: Controller, value: Value7 },
+    ProgramChange { channel: Channel, program: Value7 },
+    /// 14-bit bend, 0-16383 with 8192 as center.
+    PitchBend { channel: Channel, value: u16 },
+    ChannelPressure { channel: Channel, pressure: Value7 },
+    PolyPressure { channel: Channel, note: Value7, pressure: Value7 },
+    /// MTC quarter frame: one 4-bit `value` of the 8-`piece` timecode.
+    QuarterFrame { piece: u8, value: u8 },
+    /// Song Position Pointer in MIDI beats (16th notes, 6 clocks each).
+    SongPosition(u16),
+    /// Payload between the 0xF0/0xF7 framing bytes.
+    SysEx(Vec<u8>),
+    Realtime(Realtime),
+}
+
+impl Message {
+    /// Encodes the message to wire bytes, rejecting out-of-range fields
+    /// instead of masking them.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let bytes = match *self {
+            Message::NoteOn { channel, note, velocity } => {
+                vec![0x90 | channel.index(), note.get(), velocity.get()]
+            }
+            Message::NoteOff { channel, note, velocity } => {
+                vec![0x80 | channel.index(), note.get(), velocity.get()]
+            }
+            Message::ControlChange { channel, controller, value } => {
+                vec![0xB0 | channel.index(), controller.get(), value.get()]
+            }
+            Message::ProgramChange { channel, program } => vec![0xC0 | channel.index(), program.get()],
+            Message::PitchBend { channel, value } => {
+                if value > 0x3FFF {
+                    bail!("Pitch bend {} out of range (0-16383)", value);
+                }
+                vec![
+                    0xE0 | channel.index(),
+                    (value & 0x7F) as u8,
+                    (value >> 7) as u8,
+                ]
+            }
+            Message::ChannelPressure { channel, pressure } => {
+                vec![0xD0 | channel.index(), pressure.get()]
+            }
+            Message::PolyPressure { channel, note, pressure } => {
+                vec![0xA0 | channel.index(), note.get(), pressure.get()]
+            }
+            Message::QuarterFrame { piece, value } => {
+                if piece > 7 || value > 0x0F {
+                    bail!("Invalid MTC quarter frame {}:{}", piece, value);
+                }
+                vec![0xF1, piece << 4 | value]
+            }
+            Message::SongPosition(beats) => {
+                if beats > 0x3FFF {
+                    bail!("Song position {} out of range (0-16383)", beats);
+                }
+                vec![0xF2, (beats & 0x7F) as u8, (beats >> 7) as u8]
+            }
+            Message::SysEx(ref data) => {
+                if let Some(b) = data.iter().find(|b| **b > 0x7F) {
+                    bail!("SysEx payload contains status byte 0x{:02X}", b);
+                }
+                let mut bytes = Vec::with_capacity(data.len() + 2);
+                bytes.push(0xF0);
+                bytes.extend_from_slice(data);
+                bytes.push(0xF7);
+                bytes
+            }
+            Message::Realtime(rt) => vec![rt.status()],
+        };
+        Ok(bytes)
+    }
+
+    /// Encodes several messages into one byte stream. With `running_status`
+    /// a channel message's status byte is left out when it repeats the
+    /// previous one; SysEx and system common messages cancel it.
+    pub fn encode_all(messages: &[Message], running_status: bool) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        let mut last_status = None;
+        for msg in messages {
+            let encoded = msg.encode()?;
+            let status = encoded[0];
+            match status {
+                0x80..=0xEF => {
+                    let skip = running_status && last_status == Some(status);
+                    bytes.extend_from_slice(if skip { &encoded[1..] } else { &encoded });
+                    last_status = Some(status);
+                }
+                // Realtime may be interleaved without disturbing running status
+                0xF8..=0xFF => bytes.extend_from_slice(&encoded),
+                _ => {
+                    bytes.extend_from_slice(&encoded);
+                    last_status = None;
+                }
+            }
+        }
+        Ok(bytes)
+    }
+
+    /// Pitch bend from a signed offset (-8192..=8191, 0 = center).
+    pub fn pitch_bend(channel: Channel, bend: i16) -> Result<Message> {
+        if !(-8192..=8191).contains(&bend) {
+            bail!("Pitch bend {} out of range (-8192..8191)", bend);
+        }
+        Ok(Message::PitchBend { channel, value: (bend as i32 + 8192) as u16 })
+    }
+
+    /// Expands an NRPN write into its CC sequence: parameter number
+    /// (CC 99/98) followed by a 14-bit data entry (CC 6/38).
+    pub fn nrpn(channel: Channel, msb: Value7, lsb: Value7, value: u16) -> Result<Vec<Message>> {
+        if value > 0x3FFF {
+            bail!("NRPN value {} out of range (0-16383)", value);
+        }
+        let cc = |controller, value| Message::ControlChange {
+            channel,
+            controller: Controller::from_low_bits(controller),
+            value,
+        };
+        Ok(vec![
+            cc(99, msb),
+            cc(98, lsb),
+            cc(6, Value7::from_low_bits(value >> 7)),
+            cc(38, Value7::from_low_bits(value)),
+        ])
+    }
+
+    /// Decodes one complete message as delivered by the input backend.
+    /// Returns `None` for malformed or unsupported messages.
+    pub fn decode(bytes: &[u8]) -> Option<Message> {
+        let (&status, data) = bytes.split_first()?;
+        if status >= 0xF8 {
+            return Realtime::from_status(status).map(Message::Realtime);
+        }
+        // System common data bytes, like channel ones below, are 7-bit
+        if status > 0xF0 && data.iter().any(|b| *b > 0x7F) {
+            return None;
+        }
+        if status == 0xF1 {
+            let [data] = *data else {
+                return None;
+            };
+            return Some(Message::QuarterFrame { piece: data >> 4, value: data & 0x0F });
+        }
+        if status == 0xF2 {
+            let [lsb, msb] = *data else {
+                return None;
+            };
+            return Some(Message::SongPosition(lsb as u16 | ((msb as u16) << 7)));
+        }
+        if status == 0xF0 {
+            let payload = data.strip_suffix(&[0xF7]).unwrap_or(data);
+            if payload.iter().any(|b| *b > 0x7F) {
+                return None;
+            }
+            return Some(Message::SysEx(payload.to_vec()));
+        }
+        let channel = Channel::from_index(status);
+        let raw1 = *data.first()?;
+        let d1 = Value7::new(raw1).ok()?;
+        let d2 = data.get(1).and_then(|d| Value7::new(*d).ok());
+        let msg = match status & 0xF0 {
+            // Note On with velocity 0 is a Note Off by convention
+            0x90 if d2?.get() == 0 => Message::NoteOff { channel, note: d1, velocity: d2? },
+            0x90 => Message::NoteOn { channel, note: d1, velocity: d2? },
+            0x80 => Message::NoteOff { channel, note: d1, velocity: d2? },
+            0xB0 => Message::ControlChange {
+                channel,
+                controller: Controller::new(raw1).ok()?,
+                value: d2?,
+            },
+            0xA0 => Message::PolyPressure { channel, note: d1, pressure: d2? },
+            0xC0 => Message::ProgramChange { channel, program: d1 },
+            0xD0 => Message::ChannelPressure { channel, pressure: d1 },
+            0xE0 => Message::PitchBend { channel, value: (raw1 as u16) | ((d2?.get() as u16) << 7) },
+            _ => return None,
+        };
+        Some(msg)
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Message::NoteOn { channel, note, velocity } => {
+                write!(f, "Note On {} vel {} (ch {})", Note::from(*note), velocity, channel)
+            }
+            Message::NoteOff { channel, note, .. } => {
+                write!(f, "Note Off {} (ch {})", Note::from(*note), channel)
+            }
+            Message::ControlChange { channel, controller, value } => {
+                write!(f, "CC {} = {} (ch {})", controller, value, channel)
+            }
+            Message::ProgramChange { channel, program } => write!(f, "PC {} (ch {})", program, channel),
+            Message::PitchBend { channel, value } => {
+                write!(f, "Pitch Bend {:+} (ch {})", *value as i32 - 8192, channel)
+            }
+            Message::ChannelPressure { channel, pressure } => {
+                write!(f, "Aftertouch {} (ch {})", pressure, channel)
+            }
+            Message::PolyPressure { channel, note, pressure } => {
+                write!(f, "Poly Aftertouch {} = {} (ch {})", Note::from(*note), pressure, channel)
+            }
+            Message::QuarterFrame { piece, value } => write!(f, "MTC Quarter Frame {}:{:X}", piece, value),
+            Message::SongPosition(beats) => write!(f, "Song Position {}", beats),
+            Message::SysEx(data) => write!(f, "SysEx ({} bytes)", data.len()),
+            Message::Realtime(rt) => write!(f, "{:?}", rt),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn channel(n: u8) -> Channel {
+        Channel::new(n).unwrap()
+    }
+
+    fn value(v: u8) -> Value7 {
+        Value7::new(v).unwrap()
+    }
+
+    #[test]
+    fn round_trips_each_kind() {
+        let ch = channel(10);
+        let (note, velocity) = (value(60), value(100));
+        let cc = Controller::new(74).unwrap();
+        let messages = [
+            (Message::NoteOn { channel: ch, note, velocity }, vec![0x99, 60, 100]),
+            (Message::NoteOff { channel: ch, note, velocity }, vec![0x89, 60, 100]),
+            (
+                Message::ControlChange { channel: ch, controller: cc, value: note },
+                vec![0xB9, 74, 60],
+            ),
+            (Message::ProgramChange { channel: ch, program: value(5) }, vec![0xC9, 5]),
+            (Message::PitchBend { channel: ch, value: 0x3FFF }, vec![0xE9, 0x7F, 0x7F]),
+            (Message::PitchBend { channel: ch, value: 8192 }, vec![0xE9, 0, 64]),
+            (Message::ChannelPressure { channel: ch, pressure: value(33) }, vec![0xD9, 33]),
+            (Message::PolyPressure { channel: ch, note, pressure: value(2) }, vec![0xA9, 60, 2]),
+            (Message::QuarterFrame { piece: 7, value: 0x0F }, vec![0xF1, 0x7F]),
+            (Message::SongPosition(0x3FFF), vec![0xF2, 0x7F, 0x7F]),
+            (Message::SysEx(vec![0x7E, 0x7F, 0x06]), vec![0xF0, 0x7E, 0x7F, 0x06, 0xF7]),
+            (Message::SysEx(Vec::new()), vec![0xF0, 0xF7]),
+            (Message::Realtime(Realtime::Clock), vec![0xF8]),
+            (Message::Realtime(Realtime::Start), vec![0xFA]),
+            (Message::Realtime(Realtime::Continue), vec![0xFB]),
+            (Message::Realtime(Realtime::Stop), vec![0xFC]),
+            (Message::Realtime(Realtime::ActiveSensing), vec![0xFE]),
+            (Message::Realtime(Realtime::Reset), vec![0xFF]),
+        ];
+        for (msg, bytes) in messages {
+            assert_eq!(msg.encode().unwrap(), bytes, "{:?}", msg);
+            assert_eq!(Message::decode(&bytes), Some(msg));
+        }
+    }
+
+    #[test]
+    fn note_on_without_velocity_is_note_off() {
+        let off = Message::NoteOff { channel: channel(1), note: value(60), velocity: value(0) };
+        assert_eq!(Message::decode(&[0x90, 60, 0]), Some(off));
+    }
+
+    #[test]
+    fn encode_rejects_out_of_range() {
+        let ch = channel(1);
+        for msg in [
+            Message::PitchBend { channel: ch, value: 0x4000 },
+            Message::QuarterFrame { piece: 8, value: 0 },
+            Message::QuarterFrame { piece: 0, value: 0x10 },
+            Message::SongPosition(0x4000),
+            Message::SysEx(vec![0x41, 0xF7]),
+        ] {
+            assert!(msg.encode().is_err(), "{:?} encoded", msg);
+        }
+        let messages = [Message::Realtime(Realtime::Clock), Message::SongPosition(0x4000)];
+        assert!(Message::encode_all(&messages, true).is_err());
+    }
+
+    #[test]
+    fn decode_rejects_bad_data_and_truncation() {
+        let malformed: [&[u8]; 16] = [
+            &[],
+            &[0x90, 60],
+            &[0x90],
+            &[0xB0, 7],
+            &[0xC0],
+            &[0xE0, 0],
+            &[0x90, 0x80, 100],
+            &[0x90, 60, 0x80],
+            &[0xB0, 0x80, 1],
+            &[0xE0, 0, 0x80],
+            &[0xF1],
+            &[0xF1, 0x80],
+            &[0xF2, 1],
+            &[0xF2, 0x80, 0],
+            &[0xF0, 0x41, 0x90, 0xF7],
+            &[0x40, 1, 2],
+        ];
+        for bytes in malformed {
+            assert_eq!(Message::decode(bytes), None, "{:02X?} decoded", bytes);
+        }
+        assert_eq!(Message::decode(&[0xF9]), None);
+    }
+
+    #[test]
+    fn encode_all_with_running_status() {
+        let ch = channel(1);
+        let cc = |controller, v| Message::ControlChange {
+            channel: ch,
+            controller: Controller::new(controller).unwrap(),
+            value: value(v),
+        };
+        let messages = [
+            cc(1, 10),
+            cc(2, 20),
+            Message::Realtime(Realtime::Clock),
+            cc(3, 30),
+            Message::NoteOn { channel: ch, note: value(60), velocity: value(1) },
+            Message::NoteOn { channel: ch, note: value(62), velocity: value(2) },
+            Message::SongPosition(0),
+            Message::NoteOn { channel: ch, note: value(64), velocity: value(3) },
+            Message::SysEx(vec![1]),
+            Message::NoteOn { channel: ch, note: value(65), velocity: value(4) },
+        ];
+        let running = [
+            0xB0, 1, 10, 2, 20, 0xF8, 3, 30, 0x90, 60, 1, 62, 2, 0xF2, 0, 0, 0x90, 64, 3, 0xF0, 1,
+            0xF7, 0x90, 65, 4,
+        ];
+        assert_eq!(Message::encode_all(&messages, true).unwrap(), running);
+        let full: Vec<u8> = messages.iter().flat_map(|m| m.encode().unwrap()).collect();
+        assert_eq!(Message::encode_all(&messages, false).unwrap(), full);
+        let other_channel = [cc(1, 10), Message::ProgramChange { channel: ch, program: value(2) }];
+        let expected = [0xB0, 1, 10, 0xC0, 2];
+        assert_eq!(Message::encode_all(&other_channel, true).unwrap(), expected);
+    }
+}
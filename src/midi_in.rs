@@ -0,0 +1,67 @@
+use crate::backend::{InputConnection, MidiBackend, MidirBackend};
+use crate::midi::Message;
+use anyhow::Result;
+
+/// A message received on an input port.
+#[derive(Debug, Clone)]
+pub struct InputEvent {
+    /// Backend timestamp in microseconds (monotonic, arbitrary origin).
+    pub timestamp_us: u64,
+    pub bytes: Vec<u8>,
+    /// Decoded form of `bytes`, if it is a message we understand.
+    pub message: Option<Message>,
+}
+
+/// Lists the names of all available MIDI input ports, in port index order.
+pub fn input_port_names() -> Result<Vec<String>> {
+    MidirBackend.input_port_names()
+}
+
+/// Index of the first input port whose name contains `pattern`
+/// (case-insensitive).
+pub fn find_input_port(pattern: &str) -> Result<Option<usize>> {
+    let pattern = pattern.to_lowercase();
+    Ok(input_port_names()?
+        .iter()
+        .position(|name| name.to_lowercase().contains(&pattern)))
+}
+
+/// Index of the input port with exactly this name, if any. Devices usually
+/// expose input and output ports under the same name.
+pub fn input_port_index(name: &str) -> Result<Option<usize>> {
+    Ok(input_port_names()?.iter().position(|n| n == name))
+}
+
+/// An open input port. Incoming messages are passed to the handler given
+/// to [`MidiInputHandle::open`] until the handle is dropped or closed.
+pub struct MidiInputHandle {
+    conn: Box<dyn InputConnection>,
+    port_name: String,
+}
+
+impl MidiInputHandle {
+    pub fn open<F>(backend: &dyn MidiBackend, port_index: usize, mut handler: F) -> Result<Self>
+    where
+        F: FnMut(InputEvent) + Send + 'static,
+    {
+        let (conn, port_name) = backend.open_input(
+            port_index,
+            Box::new(move |timestamp_us, bytes| {
+                handler(InputEvent {
+                    timestamp_us,
+                    bytes: bytes.to_vec(),
+                    message: Message::decode(bytes),
+                })
+            }),
+        )?;
+        Ok(Self { conn, port_name })
+    }
+
+    pub fn port_name(&self) -> &str {
+        &self.port_name
+    }
+
+    pub fn close(self) {
+        self.conn.close();
+    }
+}
@@ -1,19 +1,357 @@
-use std::collections::HashMap;
+use crate::device::DeviceModel;
+use crate::midi::Message;
+use crate::types::{Channel, Controller, Value7};
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// How a parameter is addressed on the wire.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ParamAddress {
+    Cc(u8),
+    /// A 14-bit pair: this controller (0-31) carries the MSB and the one 32
+    /// above it the LSB.
+    Cc14(u8),
+    Nrpn { msb: u8, lsb: u8 },
+}
+
+impl ParamAddress {
+    /// Messages setting this parameter to a 7-bit value. NRPN values go in
+    /// the data entry MSB.
+    pub fn messages(&self, channel: Channel, value: Value7) -> Result<Vec<Message>> {
+        match *self {
+            ParamAddress::Cc(cc) => {
+                let controller = Controller::new(cc)?;
+                Ok(vec![Message::ControlChange { channel, controller, value }])
+            }
+            ParamAddress::Cc14(cc) => {
+                if cc >= 32 {
+                    anyhow::bail!("14-bit CC {} out of range (0-31)", cc);
+                }
+                Ok(vec![
+                    Message::ControlChange { channel, controller: Controller::new(cc)?, value },
+                    Message::ControlChange {
+                        channel,
+                        controller: Controller::new(cc + 32)?,
+                        value: Value7::default(),
+                    },
+                ])
+            }
+            ParamAddress::Nrpn { msb, lsb } => Message::nrpn(
+                channel,
+                Value7::new(msb)?,
+                Value7::new(lsb)?,
+                (value.get() as u16) << 7,
+            ),
+        }
+    }
+}
+
+impl fmt::Display for ParamAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParamAddress::Cc(cc) => write!(f, "CC {}", cc),
+            ParamAddress::Cc14(cc) => write!(f, "CC {}/{}", cc, cc + 32),
+            ParamAddress::Nrpn { msb, lsb } => write!(f, "NRPN {}:{}", msb, lsb),
+        }
+    }
+}
+
+/// How a parameter's 0-127 value reads on the device.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ValueFormat {
+    Raw,
+    /// Signed offset from 64, e.g. `+23`.
+    Bipolar,
+    /// Left/center/right around 64.
+    Pan,
+    /// Exponential from 20 Hz to 20 kHz, as the filter cutoff sweeps.
+    Frequency,
+    /// Gain in dB with 100 as unity; an approximation of the device's curve.
+    Decibels,
+    /// The value range split evenly between named settings.
+    Names(&'static [&'static str]),
+}
+
+impl ValueFormat {
+    pub fn format(&self, value: u8) -> String {
+        let offset = value as i32 - 64;
+        match *self {
+            ValueFormat::Raw => value.to_string(),
+            ValueFormat::Bipolar => format!("{:+}", offset),
+            ValueFormat::Pan => match offset {
+                0 => "C".to_string(),
+                offset if offset < 0 => format!("L{}", -offset),
+                offset => format!("R{}", offset),
+            },
+            ValueFormat::Frequency => {
+                let hz = 20.0 * 1000f32.powf(value as f32 / 127.0);
+                if hz >= 1000.0 {
+                    format!("{:.1} kHz", hz / 1000.0)
+                } else {
+                    format!("{:.0} Hz", hz)
+                }
+            }
+            ValueFormat::Decibels => match value {
+                0 => "-inf dB".to_string(),
+                value => format!("{:+.1} dB", 20.0 * (value as f32 / 100.0).log10()),
+            },
+            ValueFormat::Names(names) => {
+                let index = value as usize * names.len() / 128;
+                names.get(index).map_or_else(|| value.to_string(), |name| name.to_string())
+            }
+        }
+    }
+
+    /// The named settings with the lowest value selecting each; empty for
+    /// formats without names.
+    pub fn options(&self) -> Vec<(u8, &'static str)> {
+        match *self {
+            ValueFormat::Names(names) => names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| ((i * 128).div_ceil(names.len()) as u8, *name))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    /// The value selecting the setting called `label` (any case, `-` for
+    /// spaces), e.g. `reverse-loop`.
+    pub fn value_of(&self, label: &str) -> Option<u8> {
+        let slug = |s: &str| s.trim().to_lowercase().replace(' ', "-");
+        let label = slug(label);
+        self.options().into_iter().find(|(_, name)| slug(name) == label).map(|(value, _)| value)
+    }
+}
+
+const OFF_ON: &[&str] = &["Off", "On"];
+const LFO_WAVEFORMS: &[&str] =
+    &["Triangle", "Sine", "Square", "Saw", "Exponential", "Ramp", "Random"];
+const LFO_TRIG_MODES: &[&str] = &["Free", "Trig", "Hold", "One Shot", "Half"];
+
+/// How a control's travel spreads over a parameter's range.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Curve {
+    #[default]
+    Linear,
+    /// Fine control at the low end.
+    Exp,
+    /// Fine control at the high end.
+    Log,
+    /// Snaps to evenly spaced values.
+    Stepped,
+}
+
+impl Curve {
+    pub const ALL: [Curve; 4] = [Curve::Linear, Curve::Exp, Curve::Log, Curve::Stepped];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Curve::Linear => "Linear",
+            Curve::Exp => "Exp",
+            Curve::Log => "Log",
+            Curve::Stepped => "Stepped",
+        }
+    }
+}
+
+/// Limits within which a slider or controller moves a parameter, and how.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ParamRange {
+    pub min: u8,
+    pub max: u8,
+    pub curve: Curve,
+    /// Values a stepped curve snaps to, ends included.
+    pub steps: u8,
+}
+
+impl Default for ParamRange {
+    fn default() -> Self {
+        Self { min: 0, max: 127, curve: Curve::Linear, steps: 8 }
+    }
+}
+
+impl ParamRange {
+    /// True for the full range with a linear curve, which changes nothing.
+    pub fn is_full(&self) -> bool {
+        self.min == 0 && self.max == 127 && self.curve == Curve::Linear
+    }
+
+    /// The value for a control at `position` (0-127).
+    pub fn apply(&self, position: u8) -> u8 {
+        let t = position.min(127) as f32 / 127.0;
+        let t = match self.curve {
+            Curve::Linear => t,
+            Curve::Exp => t * t,
+            Curve::Log => t.sqrt(),
+            Curve::Stepped => {
+                let intervals = self.steps.max(2) as f32 - 1.0;
+                (t * intervals).round() / intervals
+            }
+        };
+        let (min, max) = (self.min.min(self.max) as f32, self.max.max(self.min) as f32);
+        (min + t * (max - min)).round() as u8
+    }
+
+    /// The control position that gives `value`, or the nearest one.
+    pub fn position(&self, value: u8) -> u8 {
+        (0..=127u8).min_by_key(|&p| self.apply(p).abs_diff(value)).unwrap_or(0)
+    }
+
+    /// `value` moved inside the limits.
+    pub fn clamp(&self, value: u8) -> u8 {
+        value.clamp(self.min.min(self.max), self.max.max(self.min))
+    }
+}
+
+/// Where a parameter sits on the device's screens: the encoder page, counted
+/// in the order the page buttons step through, and the knob (0-7 for A-H).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct PagePosition {
+    pub page: u8,
+    pub knob: u8,
+}
+
+impl PagePosition {
+    /// The knob's letter as printed on the device.
+    pub fn knob_label(&self) -> char {
+        (b'A' + self.knob.min(25)) as char
+    }
+}
 
 #[derive(Clone, Debug)]
 pub struct MidiParameter {
     pub name: String,
-    pub cc: u8,
+    pub address: ParamAddress,
     pub category: String,
+    /// The value a fresh track has, e.g. 64 for centered pan.
+    pub default: u8,
+    pub format: ValueFormat,
+    pub range: ParamRange,
+    /// For parameters on one of the device's encoder pages.
+    pub position: Option<PagePosition>,
+    /// Other words for the parameter, e.g. "cutoff", found by name lookups
+    /// and searches like the name.
+    pub aliases: Vec<String>,
+}
+
+/// `name` as one lowercase word, like [`MidiParameter::slug`].
+fn slug(name: &str) -> String {
+    name.trim().to_lowercase().replace(' ', "-")
+}
+
+impl MidiParameter {
+    /// The CC number (the MSB's for 14-bit pairs), for parameters addressed
+    /// by CC.
+    pub fn cc(&self) -> Option<u8> {
+        match self.address {
+            ParamAddress::Cc(cc) | ParamAddress::Cc14(cc) => Some(cc),
+            ParamAddress::Nrpn { .. } => None,
+        }
+    }
+
+    /// Centered at 64 on the device, e.g. tune, pan and LFO depth.
+    pub fn is_bipolar(&self) -> bool {
+        matches!(self.format, ValueFormat::Bipolar | ValueFormat::Pan)
+    }
+
+    /// The name as one lowercase word, e.g. `filter-frequency`, for typing
+    /// on the command line.
+    pub fn slug(&self) -> String {
+        self.name.to_lowercase().replace(' ', "-")
+    }
+
+    /// The aliases as slugs.
+    pub fn alias_slugs(&self) -> impl Iterator<Item = String> + '_ {
+        self.aliases.iter().map(|alias| slug(alias))
+    }
+
+    /// Whether `text` (lowercase) is in the name, category or an alias.
+    fn matches(&self, text: &str) -> bool {
+        self.name.to_lowercase().contains(text)
+            || self.category.to_lowercase().contains(text)
+            || self.aliases.iter().any(|alias| alias.to_lowercase().contains(text))
+    }
 }
 
 pub struct MidiMap {
-    params_by_cc: HashMap<u8, MidiParameter>,
+    params: HashMap<ParamAddress, MidiParameter>,
+}
+
+impl Default for MidiMap {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl MidiMap {
+    /// The Digitakt's map.
     pub fn new() -> Self {
-        let mut params_by_cc = HashMap::new();
+        Self::for_device(DeviceModel::Digitakt)
+    }
+
+    /// A map without parameters, to fill with [`MidiMap::insert`].
+    pub fn empty() -> Self {
+        MidiMap { params: HashMap::new() }
+    }
+
+    /// The parameters `model` responds to.
+    pub fn for_device(model: DeviceModel) -> Self {
+        let mut map = MidiMap { params: HashMap::new() };
+        match model {
+            DeviceModel::Generic => map.insert_general_midi(),
+            _ => map.insert_elektron(model),
+        }
+        map
+    }
+
+    /// General MIDI controllers most synths understand.
+    fn insert_general_midi(&mut self) {
+        let performance_params = vec![
+            (1, "Modulation"),
+            (2, "Breath"),
+            (11, "Expression"),
+            (64, "Sustain"),
+        ];
+        self.insert_cc_group("Performance", performance_params);
+
+        let mix_params = vec![(7, "Volume"), (10, "Pan"), (91, "Reverb Send"), (93, "Chorus Send")];
+        self.insert_cc_group("Mix", mix_params);
+
+        let sound_params = vec![
+            (71, "Resonance"),
+            (72, "Release Time"),
+            (73, "Attack Time"),
+            (74, "Brightness"),
+        ];
+        self.insert_cc_group("Sound", sound_params);
+
+        for (cc, default, format) in [
+            (7, 100, ValueFormat::Raw),
+            (10, 64, ValueFormat::Pan),
+            (11, 127, ValueFormat::Raw),
+            (64, 0, ValueFormat::Names(OFF_ON)),
+            (71, 64, ValueFormat::Bipolar),
+            (72, 64, ValueFormat::Bipolar),
+            (73, 64, ValueFormat::Bipolar),
+            (74, 64, ValueFormat::Bipolar),
+        ] {
+            if let Some(param) = self.params.get_mut(&ParamAddress::Cc(cc)) {
+                param.default = default;
+                param.format = format;
+            }
+        }
+        self.set_aliases(&[(74, &["cutoff", "filter"]), (71, &["reso", "q"])]);
+    }
+
+    /// The Elektron boxes share most of the Digitakt's CC layout and differ
+    /// in their sound source page.
+    fn insert_elektron(&mut self, model: DeviceModel) {
+        let map = self;
 
         // Track parameters
         let track_params = vec![
@@ -22,13 +360,7 @@ impl MidiMap {
             (110, "Pattern Mute"),
             (95, "Track Level"),
         ];
-        for (cc, name) in track_params {
-            params_by_cc.insert(cc, MidiParameter {
-                name: name.to_string(),
-                cc,
-                category: "Track".to_string(),
-            });
-        }
+        map.insert_cc_group("Track", track_params);
 
         // Trig parameters
         let trig_params = vec![
@@ -38,51 +370,51 @@ impl MidiMap {
             (13, "Filter Trig"),
             (14, "LFO Trig"),
         ];
-        for (cc, name) in trig_params {
-            params_by_cc.insert(cc, MidiParameter {
-                name: name.to_string(),
-                cc,
-                category: "Trig".to_string(),
-            });
-        }
+        map.insert_cc_page("Trig", trig_params);
 
-        // Source parameters
-        let source_params = vec![
-            (16, "Source Tune"),
-            (17, "Source Play Mode"),
-            (18, "Source Bit Reduction"),
-            (19, "Source Sample Slot"),
-            (20, "Source Start"),
-            (21, "Source Length"),
-            (22, "Source Loop Position"),
-            (23, "Source Sample Level"),
-        ];
-        for (cc, name) in source_params {
-            params_by_cc.insert(cc, MidiParameter {
-                name: name.to_string(),
-                cc,
-                category: "Source".to_string(),
-            });
+        // Sound source parameters
+        match model {
+            DeviceModel::Digitone => {
+                let synth_params = vec![
+                    (40, "Synth Algorithm"),
+                    (41, "Synth Ratio C"),
+                    (42, "Synth Ratio A"),
+                    (43, "Synth Ratio B"),
+                    (44, "Synth Harmonics"),
+                    (45, "Synth Detune"),
+                    (46, "Synth Feedback"),
+                    (47, "Synth Mix"),
+                ];
+                map.insert_cc_page("Synth", synth_params);
+            }
+            DeviceModel::Syntakt => {
+                let machine_params = vec![
+                    (16, "Machine Param 1"),
+                    (17, "Machine Param 2"),
+                    (18, "Machine Param 3"),
+                    (19, "Machine Param 4"),
+                    (20, "Machine Param 5"),
+                    (21, "Machine Param 6"),
+                    (22, "Machine Param 7"),
+                    (23, "Machine Param 8"),
+                ];
+                map.insert_cc_page("Machine", machine_params);
+            }
+            _ => map.insert_source_group(),
         }
 
-        // Filter parameters
+        // Filter parameters, envelope first as on the FLTR page
         let filter_params = vec![
-            (74, "Filter Frequency"),
-            (75, "Resonance"),
-            (76, "Filter Type"),
             (70, "Filter Attack Time"),
             (71, "Filter Decay Time"),
             (72, "Filter Sustain Level"),
             (73, "Filter Release Time"),
+            (74, "Filter Frequency"),
+            (75, "Resonance"),
+            (76, "Filter Type"),
             (77, "Filter Env Depth"),
         ];
-        for (cc, name) in filter_params {
-            params_by_cc.insert(cc, MidiParameter {
-                name: name.to_string(),
-                cc,
-                category: "Filter".to_string(),
-            });
-        }
+        map.insert_cc_page("Filter", filter_params);
 
         // Amp parameters
         let amp_params = vec![
@@ -95,13 +427,7 @@ impl MidiMap {
             (10, "Amp Pan"),
             (7, "Amp Volume"),
         ];
-        for (cc, name) in amp_params {
-            params_by_cc.insert(cc, MidiParameter {
-                name: name.to_string(),
-                cc,
-                category: "Amp".to_string(),
-            });
-        }
+        map.insert_cc_page("Amp", amp_params);
 
         // LFO parameters
         let lfo_params = vec![
@@ -114,12 +440,12 @@ impl MidiMap {
             (108, "LFO Trig Mode"),
             (109, "LFO Depth"),
         ];
-        for (cc, name) in lfo_params {
-            params_by_cc.insert(cc, MidiParameter {
-                name: name.to_string(),
-                cc,
-                category: "LFO".to_string(),
-            });
+        map.insert_cc_page("LFO", lfo_params);
+
+        if model == DeviceModel::ModelSamples {
+            // No delay and reverb pages
+            map.apply_elektron_tables();
+            return;
         }
 
         // FX Delay parameters
@@ -133,49 +459,339 @@ impl MidiMap {
             (91, "FX Reverb Send"),
             (92, "FX Mix Volume"),
         ];
-        for (cc, name) in fx_delay_params {
-            params_by_cc.insert(cc, MidiParameter {
-                name: name.to_string(),
-                cc,
-                category: "FX Delay".to_string(),
-            });
-        }
+        map.insert_cc_page("FX Delay", fx_delay_params);
 
         // FX Reverb parameters
         let fx_reverb_params = vec![
             (24, "FX Reverb Predelay"),
             (25, "FX Reverb Decay Time"),
-            (2, "FX Reverb Shelving Freq"),
+            (26, "FX Reverb Shelving Freq"),
             (27, "FX Reverb Shelving Gain"),
             (28, "FX Reverb Highpass Filter"),
             (29, "FX Reverb Lowpass Filter"),
             (31, "FX Reverb Mix Volume"),
         ];
-        for (cc, name) in fx_reverb_params {
-            params_by_cc.insert(cc, MidiParameter {
+        map.insert_cc_page("FX Reverb", fx_reverb_params);
+        if model == DeviceModel::DigitaktII {
+            map.insert_digitakt_ii_groups();
+        }
+        map.apply_elektron_tables();
+    }
+
+    /// What the Digitakt II adds: filter machines, a second LFO per track
+    /// and more effects. Beyond the CC range, so all NRPN (MSB 1).
+    fn insert_digitakt_ii_groups(&mut self) {
+        let filter_params = vec![
+            (0, "Filter Machine"),
+            (1, "Filter Key Tracking"),
+            (2, "Filter Env Delay"),
+            (3, "Filter Base"),
+            (4, "Filter Width"),
+        ];
+        self.insert_nrpn_group("Filter", 1, filter_params);
+
+        let lfo2_params = vec![
+            (16, "LFO 2 Speed"),
+            (17, "LFO 2 Multiplier"),
+            (18, "LFO 2 Fade In/Out"),
+            (19, "LFO 2 Destination"),
+            (20, "LFO 2 Waveform"),
+            (21, "LFO 2 Start Phase"),
+            (22, "LFO 2 Trig Mode"),
+            (23, "LFO 2 Depth"),
+        ];
+        self.insert_nrpn_group("LFO 2", 1, lfo2_params);
+        self.set_page((16..=23).map(|lsb| ParamAddress::Nrpn { msb: 1, lsb }).collect());
+
+        let fx_params = vec![
+            (32, "FX Chorus Send"),
+            (33, "FX Chorus Depth"),
+            (34, "FX Chorus Speed"),
+            (35, "FX Chorus Width"),
+            (36, "FX Chorus Mix Volume"),
+            (40, "FX Compressor Threshold"),
+            (41, "FX Compressor Ratio"),
+            (42, "FX Compressor Attack"),
+            (43, "FX Compressor Release"),
+            (44, "FX Compressor Makeup Gain"),
+            (45, "FX Compressor Dry/Wet"),
+        ];
+        self.insert_nrpn_group("FX Extra", 1, fx_params);
+
+        let nrpn = |lsb| ParamAddress::Nrpn { msb: 1, lsb };
+        for (lsb, default, format) in [
+            (
+                0,
+                0,
+                ValueFormat::Names(&[
+                    "Multimode", "Lowpass 4", "Legacy", "Comb-", "Comb+", "Equalizer",
+                ]),
+            ),
+            (3, 0, ValueFormat::Frequency),
+            (4, 127, ValueFormat::Raw),
+            (23, 64, ValueFormat::Bipolar),
+            (20, 0, ValueFormat::Names(LFO_WAVEFORMS)),
+            (22, 0, ValueFormat::Names(LFO_TRIG_MODES)),
+            (36, 64, ValueFormat::Decibels),
+            (45, 127, ValueFormat::Raw),
+        ] {
+            if let Some(param) = self.params.get_mut(&nrpn(lsb)) {
+                param.default = default;
+                param.format = format;
+            }
+        }
+    }
+
+    /// Sample playback parameters of the Digitakt and Model:Samples.
+    fn insert_source_group(&mut self) {
+        let source_params = vec![
+            (16, "Source Tune"),
+            (17, "Source Play Mode"),
+            (18, "Source Bit Reduction"),
+            (19, "Source Sample Slot"),
+            (20, "Source Start"),
+            (21, "Source Length"),
+            (22, "Source Loop Position"),
+            (23, "Source Sample Level"),
+        ];
+        self.insert_cc_page("Source", source_params);
+    }
+
+    /// Defaults and display formats of the Elektron CCs present in the map.
+    fn apply_elektron_tables(&mut self) {
+        let map = self;
+
+        // Defaults other than 0
+        let defaults = [
+            (95, 100),
+            (3, 64),
+            (4, 100),
+            (16, 64),
+            (21, 127),
+            (23, 100),
+            (74, 127),
+            (77, 64),
+            (10, 64),
+            (7, 100),
+            (109, 64),
+            (92, 64),
+            (31, 64),
+        ];
+        for (cc, default) in defaults {
+            if let Some(param) = map.params.get_mut(&ParamAddress::Cc(cc)) {
+                param.default = default;
+            }
+        }
+
+        // How values read on the device's screen
+        let formats = [
+            (93, ValueFormat::Names(OFF_ON)),
+            (94, ValueFormat::Names(OFF_ON)),
+            (110, ValueFormat::Names(OFF_ON)),
+            (95, ValueFormat::Decibels),
+            (3, ValueFormat::Bipolar),
+            (13, ValueFormat::Names(OFF_ON)),
+            (14, ValueFormat::Names(OFF_ON)),
+            (16, ValueFormat::Bipolar),
+            (
+                17,
+                ValueFormat::Names(&["Forward", "Reverse", "Forward Loop", "Reverse Loop"]),
+            ),
+            (74, ValueFormat::Frequency),
+            (
+                76,
+                ValueFormat::Names(&[
+                    "Lowpass 2",
+                    "Lowpass 1",
+                    "Bandpass",
+                    "Highpass 1",
+                    "Highpass 2",
+                    "Band Stop",
+                    "Peak",
+                ]),
+            ),
+            (77, ValueFormat::Bipolar),
+            (10, ValueFormat::Pan),
+            (7, ValueFormat::Decibels),
+            (
+                103,
+                ValueFormat::Names(&[
+                    "×1", "×2", "×4", "×8", "×16", "×32", "×64", "×128", "×256", "×512", "×1k",
+                    "×2k",
+                ]),
+            ),
+            (106, ValueFormat::Names(LFO_WAVEFORMS)),
+            (108, ValueFormat::Names(LFO_TRIG_MODES)),
+            (109, ValueFormat::Bipolar),
+            (89, ValueFormat::Frequency),
+            (90, ValueFormat::Frequency),
+            (92, ValueFormat::Decibels),
+            (28, ValueFormat::Frequency),
+            (29, ValueFormat::Frequency),
+            (31, ValueFormat::Decibels),
+        ];
+        for (cc, format) in formats {
+            if let Some(param) = map.params.get_mut(&ParamAddress::Cc(cc)) {
+                param.format = format;
+            }
+        }
+
+        map.set_aliases(&[
+            (74, &["cutoff", "freq", "vcf"]),
+            (75, &["reso", "q"]),
+            (77, &["env-amount"]),
+            (81, &["drive", "distortion"]),
+            (7, &["volume"]),
+            (102, &["rate"]),
+        ]);
+    }
+
+    /// Adds the words users commonly type for CC parameters.
+    fn set_aliases(&mut self, aliases: &[(u8, &[&str])]) {
+        for (cc, words) in aliases {
+            if let Some(param) = self.params.get_mut(&ParamAddress::Cc(*cc)) {
+                param.aliases.extend(words.iter().map(|word| word.to_string()));
+            }
+        }
+    }
+
+    pub fn insert(&mut self, param: MidiParameter) {
+        self.params.insert(param.address, param);
+    }
+
+    fn insert_cc_group(&mut self, category: &str, params: Vec<(u8, &str)>) {
+        for (cc, name) in params {
+            self.insert(MidiParameter {
                 name: name.to_string(),
-                cc,
-                category: "FX Reverb".to_string(),
+                address: ParamAddress::Cc(cc),
+                category: category.to_string(),
+                default: 0,
+                format: ValueFormat::Raw,
+                range: ParamRange::default(),
+                position: None,
+                aliases: Vec::new(),
             });
         }
+    }
+
+    /// Inserts an encoder page after those already in the map, with the
+    /// parameters in knob order.
+    fn insert_cc_page(&mut self, category: &str, params: Vec<(u8, &str)>) {
+        let knobs = params.iter().map(|&(cc, _)| ParamAddress::Cc(cc)).collect();
+        self.insert_cc_group(category, params);
+        self.set_page(knobs);
+    }
 
-        MidiMap { params_by_cc }
+    /// Places the parameters at `knobs` on a new page, knob A first.
+    fn set_page(&mut self, knobs: Vec<ParamAddress>) {
+        let page = self.params.values().filter_map(|p| p.position).map(|p| p.page + 1).max();
+        let page = page.unwrap_or(0);
+        for (knob, address) in knobs.into_iter().enumerate() {
+            if let Some(param) = self.params.get_mut(&address) {
+                param.position = Some(PagePosition { page, knob: knob as u8 });
+            }
+        }
+    }
+
+    fn insert_nrpn_group(&mut self, category: &str, msb: u8, params: Vec<(u8, &str)>) {
+        for (lsb, name) in params {
+            self.insert(MidiParameter {
+                name: name.to_string(),
+                address: ParamAddress::Nrpn { msb, lsb },
+                category: category.to_string(),
+                default: 0,
+                format: ValueFormat::Raw,
+                range: ParamRange::default(),
+                position: None,
+                aliases: Vec::new(),
+            });
+        }
     }
 
     pub fn get_parameter(&self, cc: u8) -> Option<MidiParameter> {
-        self.params_by_cc.get(&cc).cloned()
+        self.get_by_address(ParamAddress::Cc(cc))
+    }
+
+    pub fn get_by_address(&self, address: ParamAddress) -> Option<MidiParameter> {
+        self.params.get(&address).cloned()
+    }
+
+    /// Looks a parameter up by name (`Filter Frequency`, any case) or by
+    /// its [`MidiParameter::slug`], else by one of its aliases.
+    pub fn get_by_name(&self, name: &str) -> Option<MidiParameter> {
+        let slug = slug(name);
+        let by_alias = || {
+            // The lowest address when parameters share an alias, to be predictable
+            let params = self.params.values().filter(|p| p.alias_slugs().any(|a| a == slug));
+            params.min_by_key(|p| p.address)
+        };
+        self.params.values().find(|p| p.slug() == slug).or_else(by_alias).cloned()
+    }
+
+    /// Parameters whose name, category or an alias contains `text`
+    /// (case-insensitive), in address order.
+    pub fn search(&self, text: &str) -> Vec<MidiParameter> {
+        let text = text.to_lowercase();
+        let mut params: Vec<_> =
+            self.params.values().filter(|p| p.matches(&text)).cloned().collect();
+        params.sort_by_key(|p| p.address);
+        params
     }
 
     pub fn get_name(&self, cc: u8) -> String {
-        self.params_by_cc
-            .get(&cc)
+        self.get_address_name(ParamAddress::Cc(cc))
+    }
+
+    pub fn get_address_name(&self, address: ParamAddress) -> String {
+        self.params
+            .get(&address)
             .map(|p| p.name.clone())
-            .unwrap_or_else(|| format!("CC {}", cc))
+            .unwrap_or_else(|| address.to_string())
+    }
+
+    /// Default value of a parameter; 0 for addresses not in the map.
+    pub fn default_value(&self, address: ParamAddress) -> u8 {
+        self.params.get(&address).map_or(0, |p| p.default)
+    }
+
+    /// `value` as it reads on the device, or the number for addresses not
+    /// in the map.
+    pub fn format_value(&self, address: ParamAddress, value: u8) -> String {
+        self.params.get(&address).map_or_else(|| value.to_string(), |p| p.format.format(value))
+    }
+
+    /// Sets the range of each parameter named in `ranges` (by name or
+    /// slug); the others get the full range.
+    pub fn apply_ranges(&mut self, ranges: &BTreeMap<String, ParamRange>) {
+        for param in self.params.values_mut() {
+            param.range = ranges
+                .get(&param.name)
+                .or_else(|| ranges.get(&param.slug()))
+                .copied()
+                .unwrap_or_default();
+        }
+    }
+
+    /// The named settings of the parameter at `address` and their values,
+    /// if it is an enumeration.
+    pub fn options(&self, address: ParamAddress) -> Vec<(u8, &'static str)> {
+        self.params.get(&address).map_or_else(Vec::new, |p| p.format.options())
+    }
+
+    /// Whether the parameter at `address` is centered at 64.
+    pub fn is_bipolar(&self, address: ParamAddress) -> bool {
+        self.params.get(&address).is_some_and(MidiParameter::is_bipolar)
+    }
+
+    /// The range of the parameter at `address`; full for addresses not in
+    /// the map.
+    pub fn range(&self, address: ParamAddress) -> ParamRange {
+        self.params.get(&address).map_or_else(ParamRange::default, |p| p.range)
     }
 
     pub fn get_all_parameters(&self) -> Vec<MidiParameter> {
-        let mut params: Vec<_> = self.params_by_cc.values().cloned().collect();
-        params.sort_by_key(|p| p.cc);
+        let mut params: Vec<_> = self.params.values().cloned().collect();
+        params.sort_by_key(|p| p.address);
         params
     }
 }
\ No newline at end of file
@@ -0,0 +1,71 @@
+//! What the Digitakt's MIDI tracks' eight assignable CC knobs (A-H) mean for
+//! the synths they drive, so they can be named and set from here.
+
+use crate::midi_map::ParamAddress;
+use crate::types::Channel;
+use serde::{Deserialize, Serialize};
+
+/// MIDI tracks per Digitakt pattern.
+pub const MIDI_TRACKS: u8 = 8;
+/// CC VAL knobs per MIDI track, A to H.
+pub const KNOBS: usize = 8;
+/// The Digitakt's CC VAL A-H parameters, on the MIDI track's own channel;
+/// the track passes them on as the CCs assigned on its CC SEL page.
+const VALUE_CCS: [u8; KNOBS] = [70, 71, 72, 73, 74, 75, 76, 77];
+
+/// One of a MIDI track's CC knobs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MidiTrackKnob {
+    /// What the knob does on the downstream synth, e.g. "Cutoff".
+    pub name: String,
+    /// The CC the knob is assigned on the device, for reference.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cc: Option<u8>,
+    pub default: u8,
+}
+
+/// The knobs of one MIDI track, A-H in order.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MidiTrack {
+    /// 1-8.
+    pub track: u8,
+    /// The synth the track drives.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub name: String,
+    /// Channel the Digitakt listens on for the track. Defaults to the one
+    /// after the audio tracks', 9-16.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub channel: Option<Channel>,
+    #[serde(default, rename = "knob")]
+    pub knobs: Vec<MidiTrackKnob>,
+}
+
+/// "A" to "H".
+pub fn knob_letter(index: usize) -> char {
+    (b'A' + index.min(KNOBS - 1) as u8) as char
+}
+
+impl MidiTrack {
+    pub fn new(track: u8) -> Self {
+        Self { track, ..Self::default() }
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel
+            .unwrap_or_else(|| Channel::new(MIDI_TRACKS + self.track).unwrap_or_default())
+    }
+
+    /// The parameter setting knob `index` (0 for A).
+    pub fn address(index: usize) -> ParamAddress {
+        ParamAddress::Cc(VALUE_CCS[index.min(KNOBS - 1)])
+    }
+
+    /// Knob `index`'s name, or its letter when it has none.
+    pub fn knob_name(&self, index: usize) -> String {
+        match self.knobs.get(index) {
+            Some(knob) if !knob.name.trim().is_empty() => knob.name.clone(),
+            _ => format!("CC {}", knob_letter(index)),
+        }
+    }
+}
@@ -0,0 +1,136 @@
+//! MIDI Tracks page of the GUI: the Digitakt MIDI tracks' CC knobs A-H,
+//! named after what they do on the synths the tracks drive.
+
+use crate::gui::MidiCommand;
+use crate::knob;
+use eframe::egui;
+use midi_ctrl::midi_track::{knob_letter, KNOBS, MIDI_TRACKS};
+use midi_ctrl::{Channel, Config, MidiTrack, MidiTrackKnob, Value7};
+use std::collections::HashMap;
+
+/// The selected track and what was last sent per knob. The device is not
+/// read back, so knobs start at their defaults.
+#[derive(Debug)]
+pub struct MidiTracks {
+    track: u8,
+    values: HashMap<(u8, usize), u8>,
+    editing: bool,
+}
+
+impl Default for MidiTracks {
+    fn default() -> Self {
+        Self { track: 1, values: HashMap::new(), editing: false }
+    }
+}
+
+/// The names, assigned CCs and defaults of `track`'s knobs. Returns true
+/// when something changed.
+fn edit_track(ui: &mut egui::Ui, track: &mut MidiTrack) -> bool {
+    let mut edited = false;
+    track.knobs.resize_with(KNOBS, MidiTrackKnob::default);
+    egui::Grid::new("midi_track_knobs").spacing([12.0, 4.0]).show(ui, |ui| {
+        ui.label("Synth:");
+        edited |= ui.text_edit_singleline(&mut track.name).changed();
+        ui.end_row();
+        ui.label("Channel:");
+        let mut channel = track.channel().get();
+        if ui.add(egui::DragValue::new(&mut channel).clamp_range(1..=16)).changed() {
+            track.channel = Channel::new(channel).ok();
+            edited = true;
+        }
+        ui.end_row();
+
+        ui.strong("Knob");
+        ui.strong("Name");
+        ui.strong("CC");
+        ui.strong("Default");
+        ui.end_row();
+        for (index, knob) in track.knobs.iter_mut().enumerate() {
+            ui.label(knob_letter(index).to_string());
+            edited |= ui.text_edit_singleline(&mut knob.name).changed();
+            let mut cc = knob.cc.unwrap_or(0);
+            let response = ui
+                .add(egui::DragValue::new(&mut cc).clamp_range(0..=127))
+                .on_hover_text("The CC the knob is assigned on the device");
+            if response.changed() {
+                knob.cc = Some(cc);
+                edited = true;
+            }
+            edited |= ui
+                .add(egui::DragValue::new(&mut knob.default).clamp_range(0..=127))
+                .changed();
+            ui.end_row();
+        }
+    });
+    edited
+}
+
+impl MidiTracks {
+    /// Draws the track tabs and the selected track's knobs, or their
+    /// editor. Returns true when the config was edited and should be saved.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        config: &mut Config,
+        send: &mut dyn FnMut(MidiCommand),
+    ) -> bool {
+        ui.horizontal(|ui| {
+            for track in 1..=MIDI_TRACKS {
+                let synth = config
+                    .midi_tracks
+                    .iter()
+                    .find(|t| t.track == track && !t.name.is_empty())
+                    .map(|t| t.name.as_str());
+                let label = match synth {
+                    Some(synth) => format!("{}: {}", track, synth),
+                    None => format!("MIDI {}", track),
+                };
+                ui.selectable_value(&mut self.track, track, label);
+            }
+            ui.separator();
+            ui.checkbox(&mut self.editing, "Edit knobs");
+        });
+        ui.add_space(8.0);
+
+        if self.editing {
+            return edit_track(ui, config.midi_track_mut(self.track));
+        }
+        let track = config
+            .midi_tracks
+            .iter()
+            .find(|t| t.track == self.track)
+            .cloned()
+            .unwrap_or_else(|| MidiTrack::new(self.track));
+        self.knobs(ui, &track, send);
+        false
+    }
+
+    fn knobs(&mut self, ui: &mut egui::Ui, track: &MidiTrack, send: &mut dyn FnMut(MidiCommand)) {
+        let channel = track.channel();
+        ui.label(format!("Channel {}", channel));
+        ui.horizontal(|ui| {
+            for index in 0..KNOBS {
+                let knob = track.knobs.get(index);
+                let default = knob.map_or(0, |k| k.default);
+                let value = self.values.entry((track.track, index)).or_insert(default);
+                ui.vertical(|ui| {
+                    let mut response = knob::knob(ui, value, None);
+                    if response.double_clicked() && *value != default {
+                        *value = default;
+                        response.mark_changed();
+                    }
+                    let label = ui.label(track.knob_name(index));
+                    if let Some(cc) = knob.and_then(|k| k.cc) {
+                        label.on_hover_text(format!("Sent on as CC {}", cc));
+                    }
+                    if response.changed()
+                        && let Ok(value) = Value7::new(*value)
+                    {
+                        let address = MidiTrack::address(index);
+                        send(MidiCommand::SendParam { channel, address, value });
+                    }
+                });
+            }
+        });
+    }
+}
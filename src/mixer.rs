@@ -0,0 +1,134 @@
+//! Mixer page of the GUI: a strip per device track with level, pan,
+//! delay and reverb sends, mute and solo.
+
+use crate::gui::MidiCommand;
+use eframe::egui;
+use midi_ctrl::{Channel, ParamAddress, Value7};
+
+/// Tracks on 16 channels at most.
+const MAX_TRACKS: u8 = 16;
+
+const LEVEL_CC: u8 = 95;
+const PAN_CC: u8 = 10;
+const DELAY_SEND_CC: u8 = 82;
+const REVERB_SEND_CC: u8 = 83;
+const MUTE_CC: u8 = 94;
+const SOLO_CC: u8 = 93;
+
+/// Pan value for center.
+const PAN_CENTER: u8 = 64;
+
+#[derive(Debug, Clone, Copy)]
+struct Strip {
+    level: u8,
+    pan: u8,
+    delay_send: u8,
+    reverb_send: u8,
+    mute: bool,
+    solo: bool,
+}
+
+impl Default for Strip {
+    fn default() -> Self {
+        Self {
+            level: 100,
+            pan: PAN_CENTER,
+            delay_send: 0,
+            reverb_send: 0,
+            mute: false,
+            solo: false,
+        }
+    }
+}
+
+/// What the mixer last sent per channel. The device is not read back, so
+/// strips start at the Digitakt's defaults.
+#[derive(Debug, Default)]
+pub struct Mixer {
+    strips: [Strip; MAX_TRACKS as usize],
+}
+
+/// Tracks listen on consecutive channels; the Elektron default starts at 1.
+fn track_channel(first_channel: u8, track: u8) -> Channel {
+    Channel::new(first_channel + track).unwrap_or_default()
+}
+
+fn pan_label(pan: f64) -> String {
+    match pan as i32 - PAN_CENTER as i32 {
+        0 => "C".to_string(),
+        offset if offset < 0 => format!("L{}", -offset),
+        offset => format!("R{}", offset),
+    }
+}
+
+impl Mixer {
+    /// Draws strips for `tracks` tracks from `first_channel` side by side;
+    /// each change is passed to `send` as a parameter change on the strip's
+    /// track channel.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        first_channel: u8,
+        tracks: u8,
+        send: &mut dyn FnMut(MidiCommand),
+    ) {
+        ui.horizontal(|ui| {
+            let first_channel = first_channel.clamp(1, MAX_TRACKS);
+            let tracks = tracks.min(MAX_TRACKS + 1 - first_channel);
+            let strips = self.strips.iter_mut().skip(first_channel as usize - 1);
+            for (track, strip) in (0..tracks).zip(strips) {
+                ui.group(|ui| {
+                    ui.vertical_centered(|ui| {
+                        let mut changes = Vec::new();
+                        ui.strong(format!("T{}", track + 1));
+                        ui.label(format!("ch {}", track_channel(first_channel, track)));
+
+                        let pan = egui::Slider::new(&mut strip.pan, 0..=127)
+                            .custom_formatter(|v, _| pan_label(v));
+                        if ui.add(pan).on_hover_text("Pan").changed() {
+                            changes.push((PAN_CC, strip.pan));
+                        }
+                        if ui
+                            .add(egui::Slider::new(&mut strip.delay_send, 0..=127).text("Dly"))
+                            .changed()
+                        {
+                            changes.push((DELAY_SEND_CC, strip.delay_send));
+                        }
+                        if ui
+                            .add(egui::Slider::new(&mut strip.reverb_send, 0..=127).text("Rev"))
+                            .changed()
+                        {
+                            changes.push((REVERB_SEND_CC, strip.reverb_send));
+                        }
+                        if ui
+                            .add(egui::Slider::new(&mut strip.level, 0..=127).vertical())
+                            .on_hover_text("Track level")
+                            .changed()
+                        {
+                            changes.push((LEVEL_CC, strip.level));
+                        }
+
+                        ui.horizontal(|ui| {
+                            if ui.toggle_value(&mut strip.mute, "M").on_hover_text("Mute").changed() {
+                                changes.push((MUTE_CC, if strip.mute { 127 } else { 0 }));
+                            }
+                            if ui.toggle_value(&mut strip.solo, "S").on_hover_text("Solo").changed() {
+                                changes.push((SOLO_CC, if strip.solo { 127 } else { 0 }));
+                            }
+                        });
+
+                        for (cc, value) in changes {
+                            if let Ok(value) = Value7::new(value) {
+                                send(MidiCommand::SendParam {
+                                    channel: track_channel(first_channel, track),
+                                    address: ParamAddress::Cc(cc),
+                                    value,
+                                });
+                            }
+                        }
+                    });
+                });
+            }
+        });
+    }
+}
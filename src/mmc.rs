@@ -0,0 +1,91 @@
+use crate::midi::Message;
+use crate::timecode::Timecode;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Device ID that addresses every MMC receiver on the port.
+pub const ALL_DEVICES: u8 = 0x7F;
+
+/// How transport commands reach a device: System Realtime Start/Stop/
+/// Continue, MIDI Machine Control SysEx, or both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransportProtocol {
+    #[default]
+    Realtime,
+    Mmc,
+    Both,
+}
+
+impl TransportProtocol {
+    pub fn realtime(self) -> bool {
+        matches!(self, TransportProtocol::Realtime | TransportProtocol::Both)
+    }
+
+    pub fn mmc(self) -> bool {
+        matches!(self, TransportProtocol::Mmc | TransportProtocol::Both)
+    }
+}
+
+/// MIDI Machine Control commands (`F0 7F <device> 06 <command> ... F7`).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MmcCommand {
+    Stop,
+    Play,
+    DeferredPlay,
+    FastForward,
+    Rewind,
+    /// Punch in.
+    RecordStrobe,
+    /// Punch out.
+    RecordExit,
+    Pause,
+    Locate(Timecode),
+}
+
+impl MmcCommand {
+    fn code(self) -> u8 {
+        match self {
+            MmcCommand::Stop => 0x01,
+            MmcCommand::Play => 0x02,
+            MmcCommand::DeferredPlay => 0x03,
+            MmcCommand::FastForward => 0x04,
+            MmcCommand::Rewind => 0x05,
+            MmcCommand::RecordStrobe => 0x06,
+            MmcCommand::RecordExit => 0x07,
+            MmcCommand::Pause => 0x09,
+            MmcCommand::Locate(_) => 0x44,
+        }
+    }
+
+    /// SysEx payload (without the F0/F7 framing) addressed to `device_id`.
+    pub fn payload(self, device_id: u8) -> Vec<u8> {
+        let mut payload = vec![0x7F, device_id & 0x7F, 0x06, self.code()];
+        if let MmcCommand::Locate(tc) = self {
+            // Locate [TARGET], with the standard 5-byte time field
+            payload.extend_from_slice(&[
+                0x06,
+                0x01,
+                tc.rate.code() << 5 | tc.hours,
+                tc.minutes,
+                tc.seconds,
+                tc.frames,
+                0x00,
+            ]);
+        }
+        payload
+    }
+
+    pub fn message(self, device_id: u8) -> Message {
+        Message::SysEx(self.payload(device_id))
+    }
+}
+
+impl fmt::Display for MmcCommand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MmcCommand::Locate(tc) => write!(f, "MMC Locate {}", tc),
+            other => write!(f, "MMC {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,118 @@
+//! `midi_ctrl monitor`: prints everything arriving on an input port.
+
+use anyhow::{Context, Result};
+use clap::Args;
+use midi_ctrl::midi_in::MidiInputHandle;
+use midi_ctrl::transport::ClockFollower;
+use midi_ctrl::{find_input_port, input_port_names, DeviceModel, InputEvent, Message, MidiMap, MidirBackend, ParamAddress, Realtime};
+use std::sync::mpsc;
+
+#[derive(Args, Debug)]
+pub struct MonitorArgs {
+    /// Hide clock ticks and the tempo estimate.
+    #[arg(long)]
+    hide_clock: bool,
+
+    /// Hide Active Sensing.
+    #[arg(long)]
+    hide_sensing: bool,
+
+    /// Hide MIDI Time Code quarter frames.
+    #[arg(long)]
+    hide_mtc: bool,
+}
+
+/// Tempo changes smaller than this are not reported again.
+const BPM_REPORT_STEP: f32 = 0.5;
+
+enum MonitorInput {
+    Event(InputEvent),
+    Interrupt,
+}
+
+/// One line describing `msg`, with parameter names for known CCs.
+pub fn describe(msg: &Message, midi_map: &MidiMap) -> String {
+    match msg {
+        Message::ControlChange { channel, controller, value } => {
+            match midi_map.get_by_address(ParamAddress::Cc(controller.get())) {
+                Some(param) => format!("CC {} {} = {} (ch {})", controller, param.name, value, channel),
+                None => msg.to_string(),
+            }
+        }
+        _ => msg.to_string(),
+    }
+}
+
+/// Opens the input given by `--input`, or else the first whose name
+/// matches one of the `--port-name` patterns.
+fn resolve_input(input: Option<usize>, port_names: &[String]) -> Result<usize> {
+    if let Some(input) = input {
+        return Ok(input);
+    }
+    for pattern in port_names {
+        if let Some(idx) = find_input_port(pattern)? {
+            return Ok(idx);
+        }
+    }
+    eprintln!("Available MIDI input ports:");
+    for (i, name) in input_port_names()?.iter().enumerate() {
+        eprintln!("  #{}: {}", i, name);
+    }
+    anyhow::bail!("No input selected; pass --input <index> or --port-name <name>");
+}
+
+pub fn run_monitor(
+    input: Option<usize>,
+    port_names: &[String],
+    model: DeviceModel,
+    args: MonitorArgs,
+) -> Result<()> {
+    let port = resolve_input(input, port_names)?;
+    let (tx, rx) = mpsc::channel();
+    let event_tx = tx.clone();
+    let handle = MidiInputHandle::open(&MidirBackend, port, move |event| {
+        let _ = event_tx.send(MonitorInput::Event(event));
+    })?;
+    ctrlc::set_handler(move || {
+        let _ = tx.send(MonitorInput::Interrupt);
+    })
+    .context("Failed to install Ctrl+C handler")?;
+    println!("✓ Monitoring {} (#{}), Ctrl+C to stop", handle.port_name(), port);
+
+    let midi_map = MidiMap::for_device(model);
+    let mut follower = ClockFollower::default();
+    let mut reported_bpm: Option<f32> = None;
+    let mut first_us = None;
+
+    while let Ok(MonitorInput::Event(event)) = rx.recv() {
+        let elapsed = event.timestamp_us - *first_us.get_or_insert(event.timestamp_us);
+        let time = format!("[{:>9.3}]", elapsed as f64 / 1_000_000.0);
+        match &event.message {
+            Some(Message::Realtime(Realtime::Clock)) => {
+                if args.hide_clock {
+                    continue;
+                }
+                // Report the tempo rather than every tick
+                let bpm = follower.tick(event.timestamp_us);
+                if let Some(bpm) = bpm
+                    && reported_bpm.is_none_or(|r| (r - bpm).abs() >= BPM_REPORT_STEP)
+                {
+                    println!("{} ⏱ Clock {:.1} BPM", time, bpm);
+                    reported_bpm = Some(bpm);
+                }
+            }
+            Some(Message::Realtime(Realtime::ActiveSensing)) if args.hide_sensing => {}
+            Some(Message::QuarterFrame { .. }) if args.hide_mtc => {}
+            Some(Message::Realtime(rt @ (Realtime::Start | Realtime::Stop | Realtime::Continue))) => {
+                follower.reset();
+                reported_bpm = None;
+                println!("{} ← {:?}", time, rt);
+            }
+            Some(msg) => println!("{} ← {}", time, describe(msg, &midi_map)),
+            None => println!("{} ← {:02X?}", time, event.bytes),
+        }
+    }
+
+    handle.close();
+    Ok(())
+}
@@ -0,0 +1,120 @@
+//! Morph page of the GUI: two snapshot slots and a slider that streams the
+//! controller values between them.
+
+use crate::gui::MidiCommand;
+use eframe::egui;
+use midi_ctrl::{Channel, Controller, ParamAddress, Snapshot, Value7};
+use std::collections::{BTreeMap, HashMap};
+
+#[derive(Debug, Default)]
+pub struct Morph {
+    a: Option<Snapshot>,
+    b: Option<Snapshot>,
+    /// 0.0 is A, 1.0 is B.
+    amount: f32,
+    /// What the morph last sent, so only changed values go out.
+    sent: BTreeMap<(Channel, Controller), Value7>,
+}
+
+/// The GUI's current values on `channel` as a snapshot.
+fn capture(values: &HashMap<ParamAddress, u8>, channel: Channel) -> Snapshot {
+    let mut ccs: Vec<_> = values
+        .iter()
+        .filter_map(|(address, value)| match address {
+            ParamAddress::Cc(cc) => {
+                Some((channel, Controller::new(*cc).ok()?, Value7::new(*value).ok()?))
+            }
+            ParamAddress::Cc14(_) | ParamAddress::Nrpn { .. } => None,
+        })
+        .collect();
+    ccs.sort();
+    Snapshot::from_values(&ccs)
+}
+
+impl Morph {
+    fn slot(
+        ui: &mut egui::Ui,
+        label: &str,
+        slot: &mut Option<Snapshot>,
+        values: &HashMap<ParamAddress, u8>,
+        channel: Channel,
+        filled: &mut bool,
+    ) -> Option<String> {
+        let mut error = None;
+        ui.horizontal(|ui| {
+            ui.strong(label);
+            if ui.button("Store current").clicked() {
+                *slot = Some(capture(values, channel));
+                *filled = true;
+            }
+            egui::ComboBox::from_id_source(("morph_slot", label))
+                .selected_text("Load snapshot")
+                .show_ui(ui, |ui| {
+                    for name in Snapshot::list().unwrap_or_default() {
+                        if ui.selectable_label(false, &name).clicked() {
+                            match Snapshot::load(&name) {
+                                Ok(snapshot) => {
+                                    *slot = Some(snapshot);
+                                    *filled = true;
+                                }
+                                Err(e) => error = Some(format!("{:#}", e)),
+                            }
+                        }
+                    }
+                });
+            match slot {
+                Some(snapshot) => ui.label(format!("{} controller value(s)", snapshot.cc.len())),
+                None => ui.weak("Empty"),
+            };
+        });
+        error
+    }
+
+    /// Draws the slots and the morph slider. Moving the slider sends the
+    /// values that changed; values on `channel` are mirrored into `values`
+    /// so the parameter sliders follow. Returns an error message if a
+    /// snapshot failed to load.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        values: &mut HashMap<ParamAddress, u8>,
+        channel: Channel,
+        send: &mut dyn FnMut(MidiCommand),
+    ) -> Option<String> {
+        let mut filled = false;
+        let mut error = Self::slot(ui, "A", &mut self.a, values, channel, &mut filled);
+        error = error.or(Self::slot(ui, "B", &mut self.b, values, channel, &mut filled));
+        if filled {
+            // New end points: send everything on the next move
+            self.sent.clear();
+        }
+        ui.add_space(8.0);
+
+        let (Some(a), Some(b)) = (&self.a, &self.b) else {
+            ui.label("Fill both slots to morph between them");
+            return error;
+        };
+        ui.horizontal(|ui| {
+            ui.label("A");
+            let slider = egui::Slider::new(&mut self.amount, 0.0..=1.0).show_value(false);
+            let changed = ui.add_sized([ui.available_width() - 24.0, 24.0], slider).changed();
+            ui.label("B");
+            if !changed {
+                return;
+            }
+            for cc in a.morph(b, self.amount).cc {
+                let key = (cc.channel, cc.controller);
+                if self.sent.get(&key) == Some(&cc.value) {
+                    continue;
+                }
+                self.sent.insert(key, cc.value);
+                let address = ParamAddress::Cc(cc.controller.get());
+                if cc.channel == channel {
+                    values.insert(address, cc.value.get());
+                }
+                send(MidiCommand::SendParam { channel: cc.channel, address, value: cc.value });
+            }
+        });
+        error
+    }
+}
@@ -0,0 +1,94 @@
+use crate::midi::Message;
+use crate::scheduler::{JobId, SchedulerHandle};
+use crate::timecode::Timecode;
+use std::time::{Duration, Instant};
+
+/// The 4-bit nibble carried by quarter-frame `piece` (0-7) of `tc`.
+pub fn quarter_frame(tc: Timecode, piece: u8) -> Message {
+    let value = match piece {
+        0 => tc.frames & 0x0F,
+        1 => tc.frames >> 4,
+        2 => tc.seconds & 0x0F,
+        3 => tc.seconds >> 4,
+        4 => tc.minutes & 0x0F,
+        5 => tc.minutes >> 4,
+        6 => tc.hours & 0x0F,
+        _ => tc.hours >> 4 | tc.rate.code() << 1,
+    };
+    Message::QuarterFrame { piece: piece & 0x07, value }
+}
+
+/// Full Frame message, sent when locating so receivers jump immediately
+/// instead of waiting for two frames of quarter-frame messages.
+pub fn full_frame(tc: Timecode) -> Message {
+    Message::SysEx(vec![
+        0x7F,
+        0x7F,
+        0x01,
+        0x01,
+        tc.rate.code() << 5 | tc.hours,
+        tc.minutes,
+        tc.seconds,
+        tc.frames,
+    ])
+}
+
+/// MIDI Time Code generator emitting quarter frames from the scheduler.
+///
+/// A full timecode takes eight quarter frames spanning two frames, so the
+/// position sent advances two frames per cycle.
+pub struct MtcGenerator {
+    scheduler: SchedulerHandle,
+    job: Option<JobId>,
+}
+
+impl MtcGenerator {
+    pub fn new(scheduler: SchedulerHandle) -> Self {
+        Self { scheduler, job: None }
+    }
+
+    pub fn is_running(&self) -> bool {
+        self.job.is_some()
+    }
+
+    /// Starts sending quarter frames from `from`, passing each encoded
+    /// message to `send`. Restarts the generator if already running.
+    pub fn start<F>(&mut self, from: Timecode, mut send: F)
+    where
+        F: FnMut(&[u8]) + Send + 'static,
+    {
+        self.stop();
+
+        let rate = from.rate;
+        let period = Duration::from_secs_f64(1.0 / (rate.per_second() * 4.0));
+        let mut frame = from.to_frames();
+        let mut tc = from;
+        let mut piece = 0;
+        self.job = Some(self.scheduler.repeating(Instant::now(), move |due| {
+            if piece == 0 {
+                tc = Timecode::from_frames(frame, rate);
+            }
+            if let Ok(bytes) = quarter_frame(tc, piece).encode() {
+                send(&bytes);
+            }
+            piece += 1;
+            if piece == 8 {
+                piece = 0;
+                frame += 2;
+            }
+            Some(due + period)
+        }));
+    }
+
+    pub fn stop(&mut self) {
+        if let Some(job) = self.job.take() {
+            self.scheduler.cancel(job);
+        }
+    }
+}
+
+impl Drop for MtcGenerator {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
@@ -0,0 +1,114 @@
+//! Note names, with C4 as middle C (MIDI note 60) like the Digitakt's
+//! keyboard display. The lowest note, 0, is C-1.
+
+use crate::types::Value7;
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+const NAMES: [&str; 12] = ["C", "C#", "D", "D#", "E", "F", "F#", "G", "G#", "A", "A#", "B"];
+
+/// A MIDI note number that parses from and prints as a note name
+/// (`C3`, `F#4`, `Bb2`); plain numbers parse too.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+#[derive(Serialize, Deserialize)]
+#[serde(try_from = "NoteRepr", into = "String")]
+pub struct Note(Value7);
+
+/// A note as written in config or JSON: a number or a name.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum NoteRepr {
+    Number(u8),
+    Name(String),
+}
+
+impl TryFrom<NoteRepr> for Note {
+    type Error = anyhow::Error;
+
+    fn try_from(repr: NoteRepr) -> Result<Self> {
+        match repr {
+            NoteRepr::Number(number) => Ok(Self(Value7::new(number)?)),
+            NoteRepr::Name(name) => name.parse(),
+        }
+    }
+}
+
+impl Note {
+    pub fn new(value: Value7) -> Self {
+        Self(value)
+    }
+
+    pub fn value(self) -> Value7 {
+        self.0
+    }
+
+    /// Octave number, -1 to 9.
+    pub fn octave(self) -> i8 {
+        (self.0.get() / 12) as i8 - 1
+    }
+}
+
+impl From<Value7> for Note {
+    fn from(value: Value7) -> Self {
+        Self(value)
+    }
+}
+
+impl From<Note> for Value7 {
+    fn from(note: Note) -> Self {
+        note.0
+    }
+}
+
+impl From<Note> for String {
+    fn from(note: Note) -> Self {
+        note.to_string()
+    }
+}
+
+impl FromStr for Note {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        if s.starts_with(|c: char| c.is_ascii_digit()) {
+            return Ok(Self(s.parse()?));
+        }
+
+        let mut chars = s.chars();
+        let semitone: i32 = match chars.next().map(|c| c.to_ascii_uppercase()) {
+            Some('C') => 0,
+            Some('D') => 2,
+            Some('E') => 4,
+            Some('F') => 5,
+            Some('G') => 7,
+            Some('A') => 9,
+            Some('B') => 11,
+            _ => bail!("Invalid note '{}'", s),
+        };
+        let rest = chars.as_str();
+        let (semitone, octave) = if let Some(octave) = rest.strip_prefix('#') {
+            (semitone + 1, octave)
+        } else if let Some(octave) = rest.strip_prefix('b') {
+            (semitone - 1, octave)
+        } else {
+            (semitone, rest)
+        };
+        let octave = octave
+            .parse::<i32>()
+            .with_context(|| format!("Invalid note '{}' (expected e.g. C3, F#4, Bb2)", s))?;
+
+        let number = (octave + 1) * 12 + semitone;
+        if !(0..=Value7::MAX as i32).contains(&number) {
+            bail!("Note {} out of range (C-1 to G9)", s);
+        }
+        Ok(Self(Value7::new(number as u8)?))
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", NAMES[(self.0.get() % 12) as usize], self.octave())
+    }
+}
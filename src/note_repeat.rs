@@ -0,0 +1,263 @@
+//! Note repeat: a held pad or key retriggers on the clock at a set rate,
+//! optionally getting louder or softer with each hit, for drum rolls.
+//! Repeats need clock pulses, internal or external; without a running
+//! clock a held note just sounds once.
+
+use crate::clock::PPQN;
+use crate::midi::Message;
+use crate::types::{Channel, Value7};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum RepeatRate {
+    #[serde(rename = "1/4")]
+    Quarter,
+    #[serde(rename = "1/8")]
+    Eighth,
+    #[default]
+    #[serde(rename = "1/16")]
+    Sixteenth,
+    #[serde(rename = "1/32")]
+    ThirtySecond,
+    #[serde(rename = "1/4t")]
+    QuarterTriplet,
+    #[serde(rename = "1/8t")]
+    EighthTriplet,
+    #[serde(rename = "1/16t")]
+    SixteenthTriplet,
+    #[serde(rename = "1/32t")]
+    ThirtySecondTriplet,
+}
+
+impl RepeatRate {
+    pub const ALL: [RepeatRate; 8] = [
+        RepeatRate::Quarter,
+        RepeatRate::Eighth,
+        RepeatRate::Sixteenth,
+        RepeatRate::ThirtySecond,
+        RepeatRate::QuarterTriplet,
+        RepeatRate::EighthTriplet,
+        RepeatRate::SixteenthTriplet,
+        RepeatRate::ThirtySecondTriplet,
+    ];
+
+    /// Clock ticks between repeats.
+    pub fn ticks(self) -> u64 {
+        let quarter = PPQN as u64;
+        match self {
+            RepeatRate::Quarter => quarter,
+            RepeatRate::Eighth => quarter / 2,
+            RepeatRate::Sixteenth => quarter / 4,
+            RepeatRate::ThirtySecond => quarter / 8,
+            RepeatRate::QuarterTriplet => quarter * 2 / 3,
+            RepeatRate::EighthTriplet => quarter / 3,
+            RepeatRate::SixteenthTriplet => quarter / 6,
+            RepeatRate::ThirtySecondTriplet => quarter / 12,
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            RepeatRate::Quarter => "1/4",
+            RepeatRate::Eighth => "1/8",
+            RepeatRate::Sixteenth => "1/16",
+            RepeatRate::ThirtySecond => "1/32",
+            RepeatRate::QuarterTriplet => "1/4T",
+            RepeatRate::EighthTriplet => "1/8T",
+            RepeatRate::SixteenthTriplet => "1/16T",
+            RepeatRate::ThirtySecondTriplet => "1/32T",
+        }
+    }
+}
+
+/// Settings for repeating held notes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NoteRepeat {
+    pub enabled: bool,
+    pub rate: RepeatRate,
+    /// Velocity added on each repeat, negative to fade out.
+    pub ramp: i8,
+}
+
+/// A note being held down.
+#[derive(Debug, Clone, Copy)]
+struct Held {
+    channel: Channel,
+    note: Value7,
+    /// Velocity of the latest hit.
+    velocity: Value7,
+    sounding: bool,
+}
+
+/// Retriggers held notes as the clock pulses.
+#[derive(Debug, Default)]
+pub struct Repeater {
+    settings: NoteRepeat,
+    held: Vec<Held>,
+    /// Pulses counted while the transport is stopped.
+    free_ticks: u64,
+}
+
+fn note_off(channel: Channel, note: Value7) -> Message {
+    Message::NoteOff { channel, note, velocity: Value7::default() }
+}
+
+impl Repeater {
+    pub fn settings(&self) -> NoteRepeat {
+        self.settings
+    }
+
+    /// Changes the settings; turning repeat off ends the held notes.
+    pub fn set_settings(&mut self, settings: NoteRepeat) -> Vec<Message> {
+        self.settings = settings;
+        if settings.enabled { Vec::new() } else { self.release_all() }
+    }
+
+    /// Plays `note` now and repeats it until [`release`](Self::release).
+    pub fn hold(&mut self, channel: Channel, note: Value7, velocity: Value7) -> Vec<Message> {
+        let mut messages = self.release(channel, note);
+        messages.push(Message::NoteOn { channel, note, velocity });
+        self.held.push(Held { channel, note, velocity, sounding: true });
+        messages
+    }
+
+    /// Stops repeating `note`, ending it if it sounds.
+    pub fn release(&mut self, channel: Channel, note: Value7) -> Vec<Message> {
+        let mut messages = Vec::new();
+        self.held.retain(|held| {
+            let found = (held.channel, held.note) == (channel, note);
+            if found && held.sounding {
+                messages.push(note_off(channel, note));
+            }
+            !found
+        });
+        messages
+    }
+
+    /// Note Offs for every held note, which stop repeating.
+    pub fn release_all(&mut self) -> Vec<Message> {
+        self.held
+            .drain(..)
+            .filter(|held| held.sounding)
+            .map(|held| note_off(held.channel, held.note))
+            .collect()
+    }
+
+    /// What to send on a clock pulse. `tick` is the transport's position
+    /// while it runs, so repeats land on its grid; with `None` the
+    /// repeater counts the pulses of a free-running clock itself. Each
+    /// note ends halfway to the next repeat.
+    pub fn pulse(&mut self, tick: Option<u64>) -> Vec<Message> {
+        let tick = tick.unwrap_or(self.free_ticks);
+        self.free_ticks = tick + 1;
+        let interval = self.settings.rate.ticks().max(2);
+        let phase = tick % interval;
+        if self.held.is_empty() || (phase != 0 && phase != interval / 2) {
+            return Vec::new();
+        }
+        let mut messages = Vec::new();
+        for held in &mut self.held {
+            if held.sounding {
+                messages.push(note_off(held.channel, held.note));
+                held.sounding = false;
+            }
+            if phase == 0 {
+                let velocity = held.velocity.get() as i16 + self.settings.ramp as i16;
+                held.velocity = Value7::new(velocity.clamp(1, 127) as u8).unwrap_or(held.velocity);
+                messages.push(Message::NoteOn {
+                    channel: held.channel,
+                    note: held.note,
+                    velocity: held.velocity,
+                });
+                held.sounding = true;
+            }
+        }
+        messages
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(v: u8) -> Value7 {
+        Value7::new(v).unwrap()
+    }
+
+    fn on(velocity: u8) -> Message {
+        Message::NoteOn { channel: Channel::default(), note: value(36), velocity: value(velocity) }
+    }
+
+    fn off() -> Message {
+        note_off(Channel::default(), value(36))
+    }
+
+    /// A repeater holding note 36 at velocity 100.
+    fn holding(rate: RepeatRate, ramp: i8) -> Repeater {
+        let mut repeater = Repeater::default();
+        repeater.set_settings(NoteRepeat { enabled: true, rate, ramp });
+        assert_eq!(repeater.hold(Channel::default(), value(36), value(100)), [on(100)]);
+        repeater
+    }
+
+    /// The pulses that send something, with what they send.
+    fn run<I>(repeater: &mut Repeater, ticks: I) -> Vec<Vec<Message>>
+    where
+        I: IntoIterator<Item = Option<u64>>,
+    {
+        ticks.into_iter().map(|tick| repeater.pulse(tick)).filter(|m| !m.is_empty()).collect()
+    }
+
+    #[test]
+    fn intervals() {
+        let ticks = RepeatRate::ALL.map(RepeatRate::ticks);
+        assert_eq!(ticks, [24, 12, 6, 3, 16, 8, 4, 2]);
+    }
+
+    #[test]
+    fn sixteenths_end_halfway() {
+        let mut repeater = holding(RepeatRate::Sixteenth, 10);
+        let mut sent = Vec::new();
+        for tick in 1..=12 {
+            let messages = repeater.pulse(Some(tick));
+            if !messages.is_empty() {
+                sent.push((tick, messages));
+            }
+        }
+        let expected = [(3, off()), (6, on(110)), (9, off()), (12, on(120))];
+        assert_eq!(sent, expected.map(|(tick, message)| (tick, vec![message])));
+        assert_eq!(repeater.release(Channel::default(), value(36)), [off()]);
+        assert!(run(&mut repeater, (13..=24).map(Some)).is_empty());
+    }
+
+    #[test]
+    fn thirty_second_triplets_ramp_down_to_one() {
+        // Two ticks apart: on, off, on, ...
+        let mut repeater = holding(RepeatRate::ThirtySecondTriplet, -50);
+        let sent = run(&mut repeater, [None; 4]);
+        let expected = [vec![off(), on(50)], vec![off()], vec![on(1)], vec![off()]];
+        assert_eq!(sent, expected);
+        // Released between hits there is nothing to end
+        assert!(repeater.release(Channel::default(), value(36)).is_empty());
+    }
+
+    #[test]
+    fn ramp_up_stops_at_127() {
+        let mut repeater = holding(RepeatRate::ThirtySecondTriplet, 100);
+        let sent = run(&mut repeater, [Some(1), Some(2)]);
+        assert_eq!(sent, [vec![off()], vec![on(127)]]);
+    }
+
+    #[test]
+    fn free_running_carries_on_from_the_transport() {
+        let mut repeater = holding(RepeatRate::Sixteenth, 0);
+        assert!(run(&mut repeater, [Some(10), None]).is_empty());
+        // Tick 12, the next 1/16 after the transport's tick 10
+        assert_eq!(run(&mut repeater, [None]), [vec![off(), on(100)]]);
+        assert_eq!(run(&mut repeater, [None; 3]), [vec![off()]]);
+        let stopped = repeater.set_settings(NoteRepeat::default());
+        assert!(stopped.is_empty());
+        assert!(run(&mut repeater, [None; 12]).is_empty());
+    }
+}
@@ -0,0 +1,190 @@
+//! Drum pad page of the GUI: a grid of pads playing notes on the active
+//! channel, with velocity from where the pad is hit.
+
+use crate::gui::MidiCommand;
+use eframe::egui;
+use midi_ctrl::{Channel, Note, PadLayout, Value7};
+use std::collections::btree_map::{BTreeMap, Entry};
+
+const PAD_SIZE: f32 = 72.0;
+/// Grid size limits for the layout editor.
+const MAX_ROWS: u8 = 8;
+const MAX_COLUMNS: u8 = 8;
+
+/// Pads currently sounding and the page's switches.
+#[derive(Debug, Default)]
+pub struct Pads {
+    /// Sounding pads and the note each was started with, so editing a
+    /// held pad's note still releases the right one.
+    held: BTreeMap<usize, (Channel, Value7)>,
+    /// Pads toggle on click and keep sounding until clicked again.
+    latch: bool,
+    editing: bool,
+}
+
+fn default_velocity() -> Value7 {
+    Value7::new(100).unwrap_or_default()
+}
+
+/// Pad `pad` counting up chromatically from `first`.
+fn chromatic(first: Note, pad: usize) -> Note {
+    let number = (first.value().get() as usize + pad).min(Value7::MAX as usize) as u8;
+    Value7::new(number).map(Note::new).unwrap_or_default()
+}
+
+/// Top of the pad plays at full velocity, the bottom edge softest.
+fn velocity_at(rect: egui::Rect, pos: egui::Pos2) -> Value7 {
+    let height = ((rect.bottom() - pos.y) / rect.height()).clamp(0.0, 1.0);
+    Value7::new(1 + (height * 126.0).round() as u8).unwrap_or_default()
+}
+
+fn note_name(number: f64) -> String {
+    Value7::new(number as u8).map_or(String::new(), |v| Note::new(v).to_string())
+}
+
+impl Pads {
+    fn release(&mut self, pad: usize, send: &mut dyn FnMut(MidiCommand)) {
+        if let Some((channel, note)) = self.held.remove(&pad) {
+            send(MidiCommand::NoteOff { channel, note });
+        }
+    }
+
+    fn release_all(&mut self, send: &mut dyn FnMut(MidiCommand)) {
+        let pads: Vec<usize> = self.held.keys().copied().collect();
+        for pad in pads {
+            self.release(pad, send);
+        }
+    }
+
+    /// Draws the grid and its options. With `first_note` (from the device
+    /// profile), pads play chromatically from it instead of the layout's
+    /// notes. Returns true when the layout was edited and should be saved.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        layout: &mut PadLayout,
+        first_note: Option<Note>,
+        channel: Channel,
+        send: &mut dyn FnMut(MidiCommand),
+    ) -> bool {
+        let mut edited = false;
+
+        // A release outside any pad (or on another page) still ends the note
+        if !self.latch && !self.held.is_empty() && !ui.input(|i| i.pointer.any_down()) {
+            self.release_all(send);
+        }
+
+        ui.horizontal(|ui| {
+            if ui.checkbox(&mut self.latch, "Latch").changed() && !self.latch {
+                self.release_all(send);
+            }
+            let mut fixed = layout.fixed_velocity.is_some();
+            if ui.checkbox(&mut fixed, "Fixed velocity").changed() {
+                layout.fixed_velocity = fixed.then(default_velocity);
+                edited = true;
+            }
+            if let Some(velocity) = layout.fixed_velocity {
+                let mut value = velocity.get();
+                if ui.add(egui::Slider::new(&mut value, 1..=127)).changed() {
+                    layout.fixed_velocity = Value7::new(value).ok();
+                    edited = true;
+                }
+            }
+            ui.separator();
+            ui.checkbox(&mut self.editing, "Edit layout");
+            if let Some(first) = first_note {
+                ui.label(format!("Notes from {} (device profile)", first));
+            }
+        });
+
+        if self.editing {
+            ui.horizontal(|ui| {
+                ui.label("Rows:");
+                edited |= ui
+                    .add(egui::DragValue::new(&mut layout.rows).clamp_range(1..=MAX_ROWS))
+                    .changed();
+                ui.label("Columns:");
+                edited |= ui
+                    .add(egui::DragValue::new(&mut layout.columns).clamp_range(1..=MAX_COLUMNS))
+                    .changed();
+            });
+        }
+        ui.add_space(8.0);
+
+        egui::Grid::new("pads").spacing([6.0, 6.0]).show(ui, |ui| {
+            for row in 0..layout.rows as usize {
+                for column in 0..layout.columns as usize {
+                    let pad = row * layout.columns as usize + column;
+                    if self.editing && first_note.is_none() {
+                        let mut number = layout.note(pad).value().get();
+                        let drag = egui::DragValue::new(&mut number)
+                            .clamp_range(0..=127)
+                            .custom_formatter(|n, _| note_name(n));
+                        let response = ui.add_sized([PAD_SIZE, PAD_SIZE / 2.0], drag);
+                        if response.changed()
+                            && let Ok(value) = Value7::new(number)
+                        {
+                            layout.set_note(pad, Note::new(value));
+                            edited = true;
+                        }
+                        continue;
+                    }
+                    let note = first_note.map_or_else(|| layout.note(pad), |n| chromatic(n, pad));
+                    self.pad(ui, pad, note, layout, channel, send);
+                }
+                ui.end_row();
+            }
+        });
+        edited
+    }
+
+    fn pad(
+        &mut self,
+        ui: &mut egui::Ui,
+        pad: usize,
+        note: Note,
+        layout: &PadLayout,
+        channel: Channel,
+        send: &mut dyn FnMut(MidiCommand),
+    ) {
+        let size = egui::vec2(PAD_SIZE, PAD_SIZE);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::drag());
+
+        if response.drag_started() {
+            match self.held.entry(pad) {
+                Entry::Occupied(_) if self.latch => self.release(pad, send),
+                Entry::Occupied(_) => {}
+                Entry::Vacant(entry) => {
+                    let velocity = layout.fixed_velocity.unwrap_or_else(|| {
+                        response
+                            .interact_pointer_pos()
+                            .map_or(default_velocity(), |pos| velocity_at(rect, pos))
+                    });
+                    send(MidiCommand::NoteOn { channel, note: note.value(), velocity });
+                    entry.insert((channel, note.value()));
+                }
+            }
+        }
+        if response.drag_released() && !self.latch {
+            self.release(pad, send);
+        }
+
+        let visuals = ui.visuals();
+        let fill = if self.held.contains_key(&pad) {
+            visuals.selection.bg_fill
+        } else if response.hovered() {
+            visuals.widgets.hovered.bg_fill
+        } else {
+            visuals.widgets.inactive.bg_fill
+        };
+        let painter = ui.painter();
+        painter.rect_filled(rect, 6.0, fill);
+        painter.text(
+            rect.center(),
+            egui::Align2::CENTER_CENTER,
+            note.to_string(),
+            egui::FontId::proportional(16.0),
+            visuals.strong_text_color(),
+        );
+    }
+}
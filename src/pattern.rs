@@ -0,0 +1,145 @@
+use crate::midi::Message;
+use crate::transport::TICKS_PER_BAR;
+use crate::types::{Channel, Controller, Value7};
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+pub const BANKS: u8 = 8;
+pub const PATTERNS_PER_BANK: u8 = 16;
+
+/// When a pattern change made while the transport runs takes effect;
+/// stopped, changes always go out straight away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PatternChange {
+    #[default]
+    Now,
+    NextBar,
+    /// When the pattern playing ends, by the bar counter from Start.
+    PatternEnd,
+}
+
+impl PatternChange {
+    pub const ALL: [PatternChange; 3] =
+        [PatternChange::Now, PatternChange::NextBar, PatternChange::PatternEnd];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            PatternChange::Now => "now",
+            PatternChange::NextBar => "next bar",
+            PatternChange::PatternEnd => "pattern end",
+        }
+    }
+
+    /// Clock ticks between the points a change can happen on, with
+    /// patterns `bars` long; `None` for straight away.
+    pub fn unit(self, bars: u32) -> Option<u64> {
+        match self {
+            PatternChange::Now => None,
+            PatternChange::NextBar => Some(TICKS_PER_BAR),
+            PatternChange::PatternEnd => Some(TICKS_PER_BAR * bars.max(1) as u64),
+        }
+    }
+
+    /// The bar (1-based) a change made during `bar` takes effect in.
+    pub fn effective_bar(self, bar: u64, bars: u32) -> u64 {
+        let length = match self {
+            PatternChange::Now => return bar,
+            PatternChange::NextBar => 1,
+            PatternChange::PatternEnd => bars.max(1) as u64,
+        };
+        (bar.saturating_sub(1) / length + 1) * length + 1
+    }
+}
+
+/// A Digitakt pattern slot such as `B07`: bank A–H, pattern 1–16.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Pattern {
+    bank: u8,
+    index: u8,
+}
+
+impl Pattern {
+    /// `bank` and `index` are zero-based (bank 0 = A, index 0 = pattern 01).
+    pub fn new(bank: u8, index: u8) -> Result<Self> {
+        if bank >= BANKS {
+            bail!("Bank {} out of range (A-{})", bank, (b'A' + BANKS - 1) as char);
+        }
+        if index >= PATTERNS_PER_BANK {
+            bail!("Pattern {} out of range (1-{})", index + 1, PATTERNS_PER_BANK);
+        }
+        Ok(Self { bank, index })
+    }
+
+    pub fn bank(&self) -> u8 {
+        self.bank
+    }
+
+    pub fn index(&self) -> u8 {
+        self.index
+    }
+
+    pub fn bank_letter(&self) -> char {
+        (b'A' + self.bank) as char
+    }
+
+    /// Absolute program number across all banks (A01 = 0).
+    pub fn program(&self) -> u16 {
+        self.bank as u16 * PATTERNS_PER_BANK as u16 + self.index as u16
+    }
+
+    /// Bank Select MSB followed by the Program Change within that bank of 128.
+    pub fn messages(&self, channel: Channel) -> Vec<Message> {
+        let program = self.program();
+        vec![
+            Message::ControlChange {
+                channel,
+                controller: Controller::default(),
+                value: Value7::from_low_bits(program >> 7),
+            },
+            Message::ProgramChange { channel, program: Value7::from_low_bits(program) },
+        ]
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let mut chars = s.trim().chars();
+        let Some(letter) = chars.next().filter(|c| c.is_ascii_alphabetic()) else {
+            bail!("Invalid pattern '{}' (expected e.g. B07)", s);
+        };
+        let number: u8 = chars
+            .as_str()
+            .parse()
+            .map_err(|_| anyhow::anyhow!("Invalid pattern '{}' (expected e.g. B07)", s))?;
+        if number == 0 {
+            bail!("Pattern numbers start at 01");
+        }
+        Pattern::new(letter.to_ascii_uppercase() as u8 - b'A', number - 1)
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{:02}", self.bank_letter(), self.index + 1)
+    }
+}
+
+impl TryFrom<String> for Pattern {
+    type Error = anyhow::Error;
+
+    fn try_from(text: String) -> Result<Self> {
+        text.parse()
+    }
+}
+
+impl From<Pattern> for String {
+    fn from(pattern: Pattern) -> Self {
+        pattern.to_string()
+    }
+}
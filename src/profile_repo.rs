@@ -0,0 +1,206 @@
+//! Community parameter maps from a profile repository (`profile_repo` in
+//! the config): an HTTP(S) base URL or a git repository whose top holds an
+//! `index.toml` listing each profile's file and SHA-256 checksum:
+//!
+//! ```toml
+//! [[profile]]
+//! name = "digitone"
+//! file = "maps/digitone.toml"
+//! sha256 = "9f86d081884c7d65…"
+//! ```
+//!
+//! Downloads are kept in the cache directory once they check out, so
+//! profiles fetched before still install offline.
+
+use crate::map_file::MapFile;
+use crate::schema::{self, MAP_MIGRATIONS};
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+use tracing::warn;
+
+const TIMEOUT: Duration = Duration::from_secs(15);
+
+/// A profile the repository offers.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProfileEntry {
+    pub name: String,
+    #[serde(default)]
+    pub description: String,
+    /// Path from the repository's top.
+    pub file: String,
+    /// Hex SHA-256 of the file.
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct ProfileIndex {
+    #[serde(default, rename = "profile")]
+    pub profiles: Vec<ProfileEntry>,
+}
+
+impl ProfileIndex {
+    /// The profile called `name` (any case).
+    pub fn find(&self, name: &str) -> Option<&ProfileEntry> {
+        self.profiles.iter().find(|p| p.name.eq_ignore_ascii_case(name.trim()))
+    }
+}
+
+/// A profile repository, read over the network where it can be and from
+/// the cache where it can't.
+pub struct ProfileRepo {
+    url: String,
+    /// Cached downloads, or the clone of a git repository.
+    cache: PathBuf,
+    offline: bool,
+}
+
+fn is_git(url: &str) -> bool {
+    url.ends_with(".git") || url.starts_with("git@")
+}
+
+/// Runs git, failing with its error output.
+fn git(args: &[&str]) -> Result<()> {
+    let output = Command::new("git").args(args).output().context("Failed to run git")?;
+    if !output.status.success() {
+        bail!("git {}: {}", args[0], String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+fn sha256_hex(bytes: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(bytes))
+}
+
+fn parse_index(bytes: &[u8]) -> Result<ProfileIndex> {
+    let text = std::str::from_utf8(bytes).context("Profile index is not UTF-8")?;
+    toml::from_str(text).context("Invalid profile index")
+}
+
+impl ProfileRepo {
+    /// The repository at `url`; a git repository is cloned, or updated if
+    /// it was before.
+    pub fn open(url: &str) -> Result<Self> {
+        let slug: String =
+            url.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect();
+        let cache = dirs::cache_dir()
+            .ok_or_else(|| anyhow::anyhow!("No cache directory"))?
+            .join("midi_ctrl")
+            .join("profiles")
+            .join(slug);
+        let mut repo = Self { url: url.trim_end_matches('/').to_string(), cache, offline: false };
+        if is_git(url) {
+            let clone = repo.cache.to_string_lossy().to_string();
+            let synced = if repo.cache.join(".git").exists() {
+                git(&["-C", &clone, "pull", "--ff-only", "--quiet"])
+            } else {
+                if let Some(dir) = repo.cache.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                git(&["clone", "--depth", "1", "--quiet", url, &clone])
+            };
+            if let Err(e) = synced {
+                if !repo.cache.join(".git").exists() {
+                    return Err(e.context(format!("Failed to clone {}", url)));
+                }
+                warn!("Failed to update {}, using the last copy: {:#}", url, e);
+                repo.offline = true;
+            }
+        }
+        Ok(repo)
+    }
+
+    /// Whether anything was read from the cache for lack of a connection
+    /// or of a download that checked out.
+    pub fn offline(&self) -> bool {
+        self.offline
+    }
+
+    /// The file at `path` in the repository, once `check` accepts it. A
+    /// download replaces the cached copy only then; one that fails falls
+    /// back to the cached copy if that passes.
+    fn read(&mut self, path: &str, check: impl Fn(&[u8]) -> Result<()>) -> Result<Vec<u8>> {
+        let relative = Path::new(path);
+        if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+            bail!("Invalid path '{}' in the profile index", path);
+        }
+        let cached = self.cache.join(relative);
+        if is_git(&self.url) {
+            let bytes = fs::read(&cached).with_context(|| format!("No {} in {}", path, self.url))?;
+            check(&bytes)?;
+            return Ok(bytes);
+        }
+        let url = format!("{}/{}", self.url, path);
+        let fetched = ureq::AgentBuilder::new()
+            .timeout(TIMEOUT)
+            .build()
+            .get(&url)
+            .call()
+            .map_err(anyhow::Error::from)
+            .and_then(|response| {
+                let mut bytes = Vec::new();
+                response.into_reader().read_to_end(&mut bytes)?;
+                Ok(bytes)
+            })
+            .with_context(|| format!("Failed to fetch {}", url))
+            .and_then(|bytes| check(&bytes).map(|()| bytes));
+        match fetched {
+            Ok(bytes) => {
+                if let Some(dir) = cached.parent() {
+                    fs::create_dir_all(dir)?;
+                }
+                fs::write(&cached, &bytes)
+                    .with_context(|| format!("Failed to write {}", cached.display()))?;
+                Ok(bytes)
+            }
+            Err(e) if cached.exists() => {
+                let bytes = fs::read(&cached)?;
+                // An outdated cache is no use either; report the download
+                if check(&bytes).is_err() {
+                    return Err(e);
+                }
+                warn!("Using the cached copy of {}: {:#}", path, e);
+                self.offline = true;
+                Ok(bytes)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn index(&mut self) -> Result<ProfileIndex> {
+        let bytes = self.read("index.toml", |bytes| parse_index(bytes).map(drop))?;
+        parse_index(&bytes)
+    }
+
+    /// Fetches the profile called `name`, checks it against the index's
+    /// checksum and saves it as a map under its own name. Returns where it
+    /// went.
+    pub fn install(&mut self, name: &str) -> Result<PathBuf> {
+        let index = self.index()?;
+        let Some(entry) = index.find(name) else {
+            let names: Vec<&str> = index.profiles.iter().map(|p| p.name.as_str()).collect();
+            bail!("No profile '{}' in {} (it has: {})", name, self.url, names.join(", "));
+        };
+        let bytes = self.read(&entry.file, |bytes| {
+            let sha256 = sha256_hex(bytes);
+            if !sha256.eq_ignore_ascii_case(entry.sha256.trim()) {
+                bail!(
+                    "Checksum mismatch for {}: expected {}, got {}",
+                    entry.file,
+                    entry.sha256,
+                    sha256
+                );
+            }
+            Ok(())
+        })?;
+        let text = String::from_utf8(bytes).context("Profile is not UTF-8")?;
+        let map: MapFile = schema::parse(&text, &MAP_MIGRATIONS, &entry.file)
+            .with_context(|| format!("Invalid profile {}", entry.file))?;
+        map.save(&entry.name)
+    }
+}
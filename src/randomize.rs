@@ -0,0 +1,141 @@
+//! Parameter randomizer: moves every parameter in a category, or the whole
+//! map, by a random share of its allowed range, with undo back to the
+//! values before.
+
+use crate::midi_map::{MidiMap, ParamAddress};
+use crate::rng::Rng;
+use crate::types::Channel;
+
+/// How far parameters move unless told otherwise, in percent of their
+/// ranges.
+pub const DEFAULT_AMOUNT: u8 = 25;
+/// Randomizations that can be undone.
+pub const MAX_UNDO: usize = 16;
+
+/// The values a randomization replaced, and the track they were sent to,
+/// so undo restores them there whatever is selected by then.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Undo {
+    /// The session instance (by index) the values were sent to, if any.
+    pub instance: Option<usize>,
+    pub channel: Channel,
+    pub values: Vec<(ParamAddress, u8)>,
+}
+
+/// Works out random values and remembers what they replaced.
+#[derive(Debug, Default)]
+pub struct Randomizer {
+    rng: Rng,
+    /// Latest last.
+    undo: Vec<Undo>,
+}
+
+impl Randomizer {
+    /// New values for the parameters in `category` (any case; all of them
+    /// if `None`), each moved from its current value (its default where
+    /// `current` has none) by up to `amount` percent of its range and kept
+    /// within it. Parameters with named settings switch to a random one
+    /// with a chance of `amount` percent. Only values that change are
+    /// returned, and the ones they replace are kept for [`undo`](Self::undo)
+    /// along with the `instance` and `channel` they are sent to.
+    pub fn randomize(
+        &mut self,
+        midi_map: &MidiMap,
+        category: Option<&str>,
+        amount: u8,
+        instance: Option<usize>,
+        channel: Channel,
+        current: impl Fn(ParamAddress) -> Option<u8>,
+    ) -> Vec<(ParamAddress, u8)> {
+        let amount = amount.min(100) as f64 / 100.0;
+        let mut changes = Vec::new();
+        let mut previous = Vec::new();
+        let in_scope = |name: &str| category.is_none_or(|c| name.eq_ignore_ascii_case(c));
+        for param in midi_map.get_all_parameters().into_iter().filter(|p| in_scope(&p.category)) {
+            let range = param.range;
+            let (min, max) = (range.min.min(range.max), range.max.max(range.min));
+            let value = range.clamp(current(param.address).unwrap_or(param.default));
+            let options = param.format.options();
+            let random = if !options.is_empty() {
+                if self.rng.unit() >= amount {
+                    continue;
+                }
+                let index = (self.rng.unit() * options.len() as f64) as usize;
+                range.clamp(options[index.min(options.len() - 1)].0)
+            } else {
+                let offset = self.rng.signed() * amount * (max - min) as f64;
+                let moved = (value as f64 + offset).round().clamp(min as f64, max as f64) as u8;
+                // Onto the values the range's curve gives, e.g. its steps,
+                // leaving a value that did not move as it is
+                match moved == value {
+                    true => value,
+                    false => range.apply(range.position(moved)),
+                }
+            };
+            if random != value {
+                changes.push((param.address, random));
+                previous.push((param.address, value));
+            }
+        }
+        if !previous.is_empty() {
+            if self.undo.len() >= MAX_UNDO {
+                self.undo.remove(0);
+            }
+            self.undo.push(Undo { instance, channel, values: previous });
+        }
+        changes
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    /// The values the latest randomization replaced, to send again to
+    /// the track they came from.
+    pub fn undo(&mut self) -> Option<Undo> {
+        self.undo.pop()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::device::DeviceModel;
+
+    #[test]
+    fn stays_in_range_and_undoes_to_its_track() {
+        let map = MidiMap::for_device(DeviceModel::Digitakt);
+        let channel = Channel::new(3).unwrap();
+        let mut randomizer = Randomizer::default();
+        let changes = randomizer.randomize(&map, None, 100, Some(1), channel, |_| None);
+        assert!(!changes.is_empty());
+        for &(address, value) in &changes {
+            let range = map.range(address);
+            assert_eq!(range.clamp(value), value, "{} out of its range", address);
+        }
+        let undo = randomizer.undo().unwrap();
+        assert_eq!((undo.instance, undo.channel), (Some(1), channel));
+        let addresses = |values: &[(ParamAddress, u8)]| -> Vec<ParamAddress> {
+            values.iter().map(|v| v.0).collect()
+        };
+        assert_eq!(addresses(&undo.values), addresses(&changes));
+        for (address, value) in undo.values {
+            assert_eq!(value, map.range(address).clamp(map.default_value(address)));
+        }
+        assert!(!randomizer.can_undo());
+    }
+
+    #[test]
+    fn category_scope_and_no_amount() {
+        let map = MidiMap::for_device(DeviceModel::Digitakt);
+        let category = map.get_all_parameters()[0].category.clone();
+        let mut randomizer = Randomizer::default();
+        let channel = Channel::default();
+        let changes = randomizer.randomize(&map, Some(&category), 100, None, channel, |_| None);
+        for (address, _) in changes {
+            let param = map.get_by_address(address).unwrap();
+            assert!(param.category.eq_ignore_ascii_case(&category));
+        }
+        assert!(randomizer.randomize(&map, None, 0, None, channel, |_| None).is_empty());
+    }
+}
@@ -0,0 +1,181 @@
+//! Live recording into the sequencer: notes played while the transport
+//! runs land on the steps of the armed track after a count-in, their timing
+//! optionally quantized. Recording overdubs; steps not played over keep
+//! what they had.
+
+use crate::note::Note;
+use crate::sequencer::{SeqTrack, TICKS_PER_STEP};
+use crate::transport::TICKS_PER_BAR;
+use crate::types::Value7;
+use serde::{Deserialize, Serialize};
+
+/// Longest count-in, in bars.
+pub const MAX_COUNT_IN: u8 = 4;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum InputQuantize {
+    /// Notes land on the step they were played in.
+    #[serde(rename = "off")]
+    Off,
+    #[default]
+    #[serde(rename = "1/16")]
+    Sixteenth,
+    #[serde(rename = "1/8")]
+    Eighth,
+}
+
+impl InputQuantize {
+    pub const ALL: [InputQuantize; 3] =
+        [InputQuantize::Off, InputQuantize::Sixteenth, InputQuantize::Eighth];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            InputQuantize::Off => "off",
+            InputQuantize::Sixteenth => "1/16",
+            InputQuantize::Eighth => "1/8",
+        }
+    }
+
+    /// Clock ticks between the points notes are pulled to; `None` when off.
+    fn grid(self) -> Option<u64> {
+        match self {
+            InputQuantize::Off => None,
+            InputQuantize::Sixteenth => Some(TICKS_PER_STEP),
+            InputQuantize::Eighth => Some(TICKS_PER_STEP * 2),
+        }
+    }
+}
+
+/// Settings for recording notes into the sequencer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Recording {
+    pub quantize: InputQuantize,
+    /// Bars the transport plays before notes record.
+    pub count_in: u8,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self { quantize: InputQuantize::default(), count_in: 1 }
+    }
+}
+
+/// A note recorded and still held, by the step it went on and the tick
+/// it was played.
+#[derive(Debug, Clone, Copy)]
+struct Held {
+    note: Value7,
+    step: usize,
+    played: u64,
+}
+
+/// Writes notes played into a sequencer track.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    settings: Recording,
+    /// The armed track, by index.
+    track: Option<usize>,
+    /// The tick recording starts on, once the count-in is placed.
+    from: Option<u64>,
+    held: Vec<Held>,
+}
+
+impl Recorder {
+    pub fn settings(&self) -> Recording {
+        self.settings
+    }
+
+    pub fn track(&self) -> Option<usize> {
+        self.track
+    }
+
+    /// Records into track `track`, or stops recording with `None`. Arming
+    /// another track counts in again.
+    pub fn arm(&mut self, track: Option<usize>, settings: Recording) {
+        if track != self.track {
+            self.restart();
+        }
+        self.track = track;
+        self.settings = settings;
+    }
+
+    /// The tick recording starts on, once the transport has run since the
+    /// track was armed.
+    pub fn from(&self) -> Option<u64> {
+        self.from.filter(|_| self.track.is_some())
+    }
+
+    /// Counts in again when the transport next runs, e.g. after Stop.
+    pub fn restart(&mut self) {
+        self.from = None;
+        self.held.clear();
+    }
+
+    /// Places the count-in on the first tick the transport plays after
+    /// arming: it starts on the next downbeat.
+    pub fn tick(&mut self, tick: u64) {
+        if self.track.is_none() || self.from.is_some() {
+            return;
+        }
+        let count_in = self.settings.count_in.min(MAX_COUNT_IN) as u64;
+        self.from = Some(match count_in {
+            0 => tick,
+            bars => tick.next_multiple_of(TICKS_PER_BAR) + bars * TICKS_PER_BAR,
+        });
+    }
+
+    /// Writes `note`, played at `tick`, onto the step it lands on. Returns
+    /// whether a step changed; notes during the count-in are left out.
+    pub fn note_on(
+        &mut self,
+        tracks: &mut [SeqTrack],
+        tick: u64,
+        note: Value7,
+        velocity: Value7,
+    ) -> bool {
+        let (Some(from), Some(track)) = (self.from, self.track.and_then(|i| tracks.get_mut(i)))
+        else {
+            return false;
+        };
+        if track.steps.is_empty() {
+            return false;
+        }
+        // Steps coarser than the grid pull notes to the nearest step
+        let per_step = track.ticks_per_step();
+        let placed = match self.settings.quantize.grid() {
+            Some(grid) => {
+                let grid = grid.max(per_step);
+                (tick + grid / 2) / grid * grid
+            }
+            None => tick - tick % per_step,
+        };
+        if placed < from {
+            return false;
+        }
+        let index = track.step_at(placed);
+        let step = &mut track.steps[index];
+        step.on = true;
+        step.note = Note::new(note);
+        step.velocity = velocity;
+        step.length = per_step.min(u8::MAX as u64) as u8;
+        self.held.retain(|held| held.note != note);
+        self.held.push(Held { note, step: index, played: tick });
+        true
+    }
+
+    /// Sets the length of the step `note` went on to how long it was held.
+    /// Returns whether a step changed.
+    pub fn note_off(&mut self, tracks: &mut [SeqTrack], tick: u64, note: Value7) -> bool {
+        let Some(index) = self.held.iter().position(|held| held.note == note) else {
+            return false;
+        };
+        let held = self.held.remove(index);
+        let track = self.track.and_then(|i| tracks.get_mut(i));
+        let Some(step) = track.and_then(|track| track.steps.get_mut(held.step)) else {
+            return false;
+        };
+        step.length = tick.saturating_sub(held.played).clamp(1, u8::MAX as u64) as u8;
+        true
+    }
+}
@@ -0,0 +1,327 @@
+use std::cmp::Ordering;
+use std::collections::BinaryHeap;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+/// Below this much remaining time the scheduler spins instead of sleeping;
+/// OS sleeps routinely overshoot by a millisecond or more.
+const SPIN_THRESHOLD: Duration = Duration::from_millis(2);
+
+/// Identifies a scheduled job so it can be cancelled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct JobId(u64);
+
+/// A job runs at its deadline and returns the deadline of its next run,
+/// or `None` when it is done.
+type Job = Box<dyn FnMut(Instant) -> Option<Instant> + Send>;
+
+struct Entry {
+    at: Instant,
+    id: u64,
+    job: Job,
+}
+
+// Min-heap on (deadline, insertion order) so equal deadlines run FIFO
+impl Ord for Entry {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.at, other.id).cmp(&(self.at, self.id))
+    }
+}
+
+impl PartialOrd for Entry {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl PartialEq for Entry {
+    fn eq(&self, other: &Self) -> bool {
+        self.id == other.id
+    }
+}
+
+impl Eq for Entry {}
+
+#[derive(Default)]
+struct Queue {
+    heap: BinaryHeap<Entry>,
+    next_id: u64,
+    /// Job being run right now (it is out of the heap meanwhile).
+    current: Option<u64>,
+    /// Set when the current job is cancelled while running.
+    cancel_current: bool,
+    shutdown: bool,
+}
+
+struct Shared {
+    queue: Mutex<Queue>,
+    wakeup: Condvar,
+    /// Held while a job runs, so `cancel` can wait for it to finish.
+    running: Mutex<()>,
+}
+
+/// Cloneable handle for queueing jobs on a [`Scheduler`].
+#[derive(Clone)]
+pub struct SchedulerHandle {
+    shared: Arc<Shared>,
+}
+
+impl SchedulerHandle {
+    /// Runs `job` once at `at` (immediately if that is in the past).
+    pub fn at<F>(&self, at: Instant, job: F) -> JobId
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        let mut job = Some(job);
+        self.repeating(at, move |_| {
+            if let Some(job) = job.take() {
+                job();
+            }
+            None
+        })
+    }
+
+    /// Runs `job` once after `delay`.
+    pub fn after<F>(&self, delay: Duration, job: F) -> JobId
+    where
+        F: FnOnce() + Send + 'static,
+    {
+        self.at(Instant::now() + delay, job)
+    }
+
+    /// Runs `job` at `first`, then again at each deadline it returns until
+    /// it returns `None`. The job is passed the deadline it was due at.
+    pub fn repeating<F>(&self, first: Instant, job: F) -> JobId
+    where
+        F: FnMut(Instant) -> Option<Instant> + Send + 'static,
+    {
+        let mut queue = self.shared.queue.lock().unwrap();
+        let id = queue.next_id;
+        queue.next_id += 1;
+        queue.heap.push(Entry { at: first, id, job: Box::new(job) });
+        self.shared.wakeup.notify_one();
+        JobId(id)
+    }
+
+    /// Removes a job. If it is running right now, waits for that run to
+    /// finish, so the job never runs after `cancel` returns. Must not be
+    /// called from inside a job.
+    pub fn cancel(&self, id: JobId) {
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            if queue.current == Some(id.0) {
+                queue.cancel_current = true;
+            } else {
+                let heap = std::mem::take(&mut queue.heap);
+                queue.heap = heap.into_iter().filter(|e| e.id != id.0).collect();
+            }
+        }
+        drop(self.shared.running.lock().unwrap());
+    }
+
+    /// Drops every queued job and waits for a running one to finish.
+    pub fn clear(&self) {
+        {
+            let mut queue = self.shared.queue.lock().unwrap();
+            queue.heap.clear();
+            if queue.current.is_some() {
+                queue.cancel_current = true;
+            }
+        }
+        drop(self.shared.running.lock().unwrap());
+    }
+
+    /// Number of jobs waiting to run.
+    pub fn pending(&self) -> usize {
+        self.shared.queue.lock().unwrap().heap.len()
+    }
+}
+
+/// Single time-ordered queue that every timed feature (clock, MTC,
+/// delayed sends) runs through, on one high-priority thread.
+///
+/// Jobs are scheduled against absolute deadlines; the thread sleeps until
+/// shortly before the earliest one and spins the rest of the way.
+pub struct Scheduler {
+    handle: SchedulerHandle,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        let shared = Arc::new(Shared {
+            queue: Mutex::new(Queue::default()),
+            wakeup: Condvar::new(),
+            running: Mutex::new(()),
+        });
+        let thread_shared = shared.clone();
+        let thread = thread::Builder::new()
+            .name("midi_ctrl-scheduler".to_string())
+            .spawn(move || run(&thread_shared))
+            .expect("failed to spawn scheduler thread");
+        Self {
+            handle: SchedulerHandle { shared },
+            thread: Some(thread),
+        }
+    }
+
+    pub fn handle(&self) -> SchedulerHandle {
+        self.handle.clone()
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for Scheduler {
+    fn drop(&mut self) {
+        self.handle.shared.queue.lock().unwrap().shutdown = true;
+        self.handle.shared.wakeup.notify_one();
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+fn run(shared: &Shared) {
+    let mut queue = shared.queue.lock().unwrap();
+    loop {
+        if queue.shutdown {
+            return;
+        }
+        let Some(next_at) = queue.heap.peek().map(|e| e.at) else {
+            queue = shared.wakeup.wait(queue).unwrap();
+            continue;
+        };
+        let now = Instant::now();
+        if next_at > now {
+            let remaining = next_at - now;
+            if remaining > SPIN_THRESHOLD {
+                // Woken early if something sooner gets queued
+                queue = shared
+                    .wakeup
+                    .wait_timeout(queue, remaining - SPIN_THRESHOLD / 2)
+                    .unwrap()
+                    .0;
+            } else {
+                drop(queue);
+                thread::yield_now();
+                queue = shared.queue.lock().unwrap();
+            }
+            continue;
+        }
+
+        let Some(mut entry) = queue.heap.pop() else {
+            continue;
+        };
+        queue.current = Some(entry.id);
+        queue.cancel_current = false;
+        let running = shared.running.lock().unwrap();
+        drop(queue);
+        let next = (entry.job)(entry.at);
+        queue = shared.queue.lock().unwrap();
+        drop(running);
+        queue.current = None;
+        if let Some(at) = next
+            && !queue.cancel_current
+        {
+            entry.at = at;
+            queue.heap.push(entry);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn runs_in_due_time_order() {
+        let scheduler = Scheduler::new();
+        let handle = scheduler.handle();
+        let order = Arc::new(Mutex::new(Vec::new()));
+        let start = Instant::now() + ms(20);
+        // Equal deadlines run in the order they were queued
+        for (name, at) in [("d", 30), ("a", 0), ("b1", 10), ("c", 20), ("b2", 10)] {
+            let order = order.clone();
+            handle.at(start + ms(at), move || order.lock().unwrap().push(name));
+        }
+        assert_eq!(handle.pending(), 5);
+        thread::sleep(ms(100));
+        assert_eq!(*order.lock().unwrap(), ["a", "b1", "b2", "c", "d"]);
+        assert_eq!(handle.pending(), 0);
+    }
+
+    #[test]
+    fn repeats_from_the_deadline_it_returns() {
+        let scheduler = Scheduler::new();
+        let runs = Arc::new(Mutex::new(Vec::new()));
+        let (first, recorded) = (Instant::now(), runs.clone());
+        scheduler.handle().repeating(first, move |due| {
+            let mut runs = recorded.lock().unwrap();
+            runs.push(due);
+            (runs.len() < 3).then_some(due + ms(5))
+        });
+        thread::sleep(ms(50));
+        assert_eq!(*runs.lock().unwrap(), [first, first + ms(5), first + ms(10)]);
+    }
+
+    #[test]
+    fn cancel_and_clear_drop_pending_jobs() {
+        let scheduler = Scheduler::new();
+        let handle = scheduler.handle();
+        let runs = Arc::new(AtomicUsize::new(0));
+        let job = |runs: &Arc<AtomicUsize>| {
+            let runs = runs.clone();
+            move || {
+                runs.fetch_add(1, Ordering::SeqCst);
+            }
+        };
+        let cancelled = handle.after(ms(20), job(&runs));
+        handle.after(ms(30), job(&runs));
+        handle.cancel(cancelled);
+        assert_eq!(handle.pending(), 1);
+        thread::sleep(ms(60));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+
+        for delay in [10, 20, 30] {
+            handle.after(ms(delay), job(&runs));
+        }
+        handle.clear();
+        assert_eq!(handle.pending(), 0);
+        thread::sleep(ms(50));
+        assert_eq!(runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn cancel_waits_for_a_running_job() {
+        let scheduler = Scheduler::new();
+        let handle = scheduler.handle();
+        let (started, finished) = (Arc::new(AtomicBool::new(false)), Arc::new(AtomicUsize::new(0)));
+        let (s, f) = (started.clone(), finished.clone());
+        let id = handle.repeating(Instant::now(), move |due| {
+            s.store(true, Ordering::SeqCst);
+            thread::sleep(ms(30));
+            f.fetch_add(1, Ordering::SeqCst);
+            Some(due + ms(1))
+        });
+        while !started.load(Ordering::SeqCst) {
+            thread::yield_now();
+        }
+        handle.cancel(id);
+        let runs = finished.load(Ordering::SeqCst);
+        assert!(runs >= 1, "cancel returned mid-run");
+        thread::sleep(ms(50));
+        assert_eq!(finished.load(Ordering::SeqCst), runs);
+        assert_eq!(handle.pending(), 0);
+    }
+}
@@ -0,0 +1,106 @@
+//! Format versions of the TOML files midi_ctrl keeps (config, maps), so
+//! files from older versions are brought up to date on load and files
+//! from newer ones are refused instead of losing what this one can't read.
+
+use anyhow::{bail, Result};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::info;
+
+/// Upgrades a file's table from one version to the next.
+pub type Migration = fn(&mut toml::Table);
+
+/// Files written before versioning, which count as version 0.
+fn unversioned(_: &mut toml::Table) {}
+
+/// Config format version, and migrations from each older one in order.
+pub const CONFIG_VERSION: u32 = 1;
+pub const CONFIG_MIGRATIONS: [Migration; CONFIG_VERSION as usize] = [unversioned];
+
+/// Map file format version, and migrations from each older one in order.
+pub const MAP_VERSION: u32 = 1;
+pub const MAP_MIGRATIONS: [Migration; MAP_VERSION as usize] = [unversioned];
+
+/// Reads `text` as version `migrations.len()` of a format, migrating it
+/// first if it is older. `what` names the file in the log.
+pub fn parse<T: DeserializeOwned>(text: &str, migrations: &[Migration], what: &str) -> Result<T> {
+    let mut table: toml::Table = toml::from_str(text)?;
+    let current = migrations.len();
+    let version = match table.remove("version") {
+        None => 0,
+        Some(toml::Value::Integer(version)) if version >= 0 => version as usize,
+        Some(version) => bail!("Invalid version {}", version),
+    };
+    if version > current {
+        bail!(
+            "Format version {} is newer than this midi_ctrl reads ({}); update midi_ctrl",
+            version,
+            current
+        );
+    }
+    if version < current {
+        info!("Migrating {} from format version {} to {}", what, version, current);
+        for migrate in &migrations[version..] {
+            migrate(&mut table);
+        }
+    }
+    Ok(toml::Value::Table(table).try_into()?)
+}
+
+/// `value` as TOML, headed by its format `version`.
+pub fn to_string<T: Serialize>(value: &T, version: u32) -> Result<String> {
+    Ok(format!("version = {}\n\n{}", version, toml::to_string_pretty(value)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde::Deserialize;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    struct Named {
+        name: String,
+    }
+
+    /// Version 1 renamed `title` to `name`.
+    fn rename_title(table: &mut toml::Table) {
+        if let Some(title) = table.remove("title") {
+            table.insert("name".to_string(), title);
+        }
+    }
+
+    const MIGRATIONS: [Migration; 1] = [rename_title];
+
+    fn named(name: &str) -> Named {
+        Named { name: name.to_string() }
+    }
+
+    #[test]
+    fn migrates_older_files() {
+        let parsed: Named = parse("title = 'bass'", &MIGRATIONS, "test").unwrap();
+        assert_eq!(parsed, named("bass"));
+        let parsed: Named = parse("version = 0\ntitle = 'bass'", &MIGRATIONS, "test").unwrap();
+        assert_eq!(parsed, named("bass"));
+    }
+
+    #[test]
+    fn current_files_load_without_their_version() {
+        let parsed: Named = parse("version = 1\nname = 'lead'", &MIGRATIONS, "test").unwrap();
+        assert_eq!(parsed, named("lead"));
+        let text = to_string(&named("pad"), 1).unwrap();
+        assert!(text.starts_with("version = 1\n"));
+        assert_eq!(parse::<Named>(&text, &MIGRATIONS, "test").unwrap(), named("pad"));
+    }
+
+    #[test]
+    fn refuses_newer_and_invalid_versions() {
+        let e = parse::<Named>("version = 2\nname = 'x'", &MIGRATIONS, "test").unwrap_err();
+        let newer = "Format version 2 is newer than this midi_ctrl reads (1); update midi_ctrl";
+        assert_eq!(e.to_string(), newer);
+        let e = parse::<Named>("version = -1\nname = 'x'", &MIGRATIONS, "test").unwrap_err();
+        assert_eq!(e.to_string(), "Invalid version -1");
+        let e = parse::<Named>("version = '1'\nname = 'x'", &MIGRATIONS, "test").unwrap_err();
+        assert_eq!(e.to_string(), "Invalid version \"1\"");
+    }
+}
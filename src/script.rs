@@ -0,0 +1,155 @@
+//! Command scripts for the CLI's `run`: one command per line, `#` starts a
+//! comment, and `repeat N { ... }` blocks may nest. Also expands aliases
+//! and reads CSV event lists.
+
+use anyhow::{bail, Context, Result};
+use midi_ctrl::{Channel, Message, Note, Realtime, Value7};
+use std::time::Duration;
+
+/// One step of a parsed script.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Step {
+    /// A CLI command line, with its 1-based line number in the file.
+    Command { line: usize, text: String },
+    Repeat { count: u32, body: Vec<Step> },
+}
+
+pub fn parse(source: &str) -> Result<Vec<Step>> {
+    // Open blocks: (repeat count, line it opened on, steps so far)
+    let mut stack: Vec<(u32, usize, Vec<Step>)> = Vec::new();
+    let mut steps = Vec::new();
+
+    for (idx, raw) in source.lines().enumerate() {
+        let line = idx + 1;
+        let text = raw.split('#').next().unwrap_or_default().trim();
+        if text.is_empty() {
+            continue;
+        }
+        let current = stack.last_mut().map_or(&mut steps, |(_, _, body)| body);
+
+        if text == "}" {
+            let Some((count, _, body)) = stack.pop() else {
+                bail!("line {}: '}}' without a matching repeat", line);
+            };
+            let parent = stack.last_mut().map_or(&mut steps, |(_, _, body)| body);
+            parent.push(Step::Repeat { count, body });
+        } else if let Some(rest) = text.strip_prefix("repeat ") {
+            let Some(count) = rest.trim().strip_suffix('{') else {
+                bail!("line {}: expected 'repeat <count> {{'", line);
+            };
+            let count = count
+                .trim()
+                .parse::<u32>()
+                .with_context(|| format!("line {}: invalid repeat count '{}'", line, count.trim()))?;
+            stack.push((count, line, Vec::new()));
+        } else {
+            current.push(Step::Command { line, text: text.to_string() });
+        }
+    }
+
+    if let Some((_, line, _)) = stack.last() {
+        bail!("line {}: repeat block is never closed", line);
+    }
+    Ok(steps)
+}
+
+/// Expands an alias body such as `cc 74 $1; start` into its command lines,
+/// substituting `$1`..`$9` with `args`.
+pub fn expand_alias(body: &str, args: &[&str]) -> Result<Vec<String>> {
+    let mut used = 0;
+    let mut commands = Vec::new();
+    for command in body.split(';').map(str::trim).filter(|c| !c.is_empty()) {
+        let mut words = Vec::new();
+        for word in command.split_whitespace() {
+            let arg = word
+                .strip_prefix('$')
+                .and_then(|n| n.parse::<usize>().ok())
+                .filter(|n| (1..=9).contains(n));
+            match arg {
+                Some(n) => {
+                    let value = args
+                        .get(n - 1)
+                        .ok_or_else(|| anyhow::anyhow!("Missing argument ${}", n))?;
+                    words.push(*value);
+                    used = used.max(n);
+                }
+                None => words.push(word),
+            }
+        }
+        commands.push(words.join(" "));
+    }
+    if args.len() > used {
+        bail!("Too many arguments (expected {})", used);
+    }
+    Ok(commands)
+}
+
+/// Parses an event list for `load`: one `delay_ms,type,channel,data1,data2`
+/// row per line, each delay counted from the previous row. Types are
+/// `noteon`, `noteoff`, `cc`, `pc`, `bend`, `at`, `polyat`, `start`, `stop`
+/// and `continue`; unused fields may be left empty, and an empty channel
+/// means `default_channel`. A header row and `#` comments are skipped.
+pub fn parse_events(source: &str, default_channel: Channel) -> Result<Vec<(Duration, Message)>> {
+    let mut at = Duration::ZERO;
+    let mut events = Vec::new();
+
+    for (idx, raw) in source.lines().enumerate() {
+        let line = idx + 1;
+        let text = raw.split('#').next().unwrap_or_default().trim();
+        if text.is_empty() || (events.is_empty() && text.starts_with(char::is_alphabetic)) {
+            continue;
+        }
+        let fields: Vec<&str> = text.split(',').map(str::trim).collect();
+        let field = |i: usize| fields.get(i).copied().filter(|f| !f.is_empty());
+        let event = || -> Result<(u64, Message)> {
+            let delay = field(0)
+                .ok_or_else(|| anyhow::anyhow!("Missing delay"))?
+                .parse::<u64>()
+                .context("Invalid delay")?;
+            let kind = field(1).ok_or_else(|| anyhow::anyhow!("Missing type"))?;
+            let channel = match field(2) {
+                Some(ch) => ch.parse()?,
+                None => default_channel,
+            };
+            let data1 = || field(3).ok_or_else(|| anyhow::anyhow!("Missing data1"));
+            let data2 = || field(4).ok_or_else(|| anyhow::anyhow!("Missing data2"));
+            let message = match kind.to_lowercase().as_str() {
+                "noteon" => Message::NoteOn {
+                    channel,
+                    note: data1()?.parse::<Note>()?.value(),
+                    velocity: data2()?.parse()?,
+                },
+                "noteoff" => Message::NoteOff {
+                    channel,
+                    note: data1()?.parse::<Note>()?.value(),
+                    velocity: field(4).map_or(Ok(Value7::default()), str::parse)?,
+                },
+                "cc" => Message::ControlChange {
+                    channel,
+                    controller: data1()?.parse()?,
+                    value: data2()?.parse()?,
+                },
+                "pc" => Message::ProgramChange { channel, program: data1()?.parse()? },
+                "bend" => {
+                    let bend = data1()?.parse::<i16>().context("Invalid bend")?;
+                    Message::pitch_bend(channel, bend)?
+                }
+                "at" => Message::ChannelPressure { channel, pressure: data1()?.parse()? },
+                "polyat" => Message::PolyPressure {
+                    channel,
+                    note: data1()?.parse::<Note>()?.value(),
+                    pressure: data2()?.parse()?,
+                },
+                "start" => Message::Realtime(Realtime::Start),
+                "stop" => Message::Realtime(Realtime::Stop),
+                "continue" => Message::Realtime(Realtime::Continue),
+                other => bail!("Unknown event type '{}'", other),
+            };
+            Ok((delay, message))
+        };
+        let (delay, message) = event().with_context(|| format!("line {}", line))?;
+        at += Duration::from_millis(delay);
+        events.push((at, message));
+    }
+    Ok(events)
+}
@@ -0,0 +1,262 @@
+//! Step sequencer playing notes from the transport's clock, internal or
+//! external, e.g. to drive the Digitakt's MIDI tracks. A step is a 16th
+//! note by default; each track loops on its own length and can run slower
+//! by a clock division, so tracks of different lengths phase.
+//! Steps can carry parameter locks, values sent just before they play.
+//! Notes played live can be recorded into a track (see [`Recorder`]).
+
+use crate::midi::Message;
+use crate::midi_map::{MidiMap, ParamAddress};
+use crate::note::Note;
+use crate::recorder::{Recorder, Recording};
+use crate::transport::TICKS_PER_MIDI_BEAT;
+use crate::types::{Channel, Value7};
+use serde::{Deserialize, Serialize};
+
+/// Clock ticks per step, a 16th note.
+pub const TICKS_PER_STEP: u64 = TICKS_PER_MIDI_BEAT;
+pub const DEFAULT_STEPS: usize = 16;
+/// Four bars of 16ths.
+pub const MAX_STEPS: usize = 64;
+/// Slowest clock division, a step per bar.
+pub const MAX_DIVISION: u8 = 16;
+
+/// A parameter value a step sends before its note, like an Elektron
+/// parameter lock. The value stays until something else changes it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ParamLock {
+    /// Name as `set` takes it.
+    pub parameter: String,
+    pub value: u8,
+    /// Looked up by [`SeqTrack::resolve_locks`]; locks without one are
+    /// not sent.
+    #[serde(skip)]
+    pub address: Option<ParamAddress>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Step {
+    pub on: bool,
+    pub note: Note,
+    pub velocity: Value7,
+    /// Clock ticks the note sounds, 6 to a step.
+    pub length: u8,
+    #[serde(rename = "lock", skip_serializing_if = "Vec::is_empty")]
+    pub locks: Vec<ParamLock>,
+}
+
+impl Default for Step {
+    fn default() -> Self {
+        Self {
+            on: false,
+            note: Note::new(Value7::new(60).unwrap_or_default()),
+            velocity: Value7::new(100).unwrap_or_default(),
+            length: TICKS_PER_STEP as u8,
+            locks: Vec::new(),
+        }
+    }
+}
+
+impl Step {
+    /// Locks `parameter` (at `address`) to `value` on this step, replacing
+    /// any lock it had.
+    pub fn set_lock(&mut self, parameter: &str, address: ParamAddress, value: u8) {
+        self.locks.retain(|lock| lock.address != Some(address) && lock.parameter != parameter);
+        let parameter = parameter.to_string();
+        self.locks.push(ParamLock { parameter, value, address: Some(address) });
+    }
+}
+
+/// The steps played on one channel.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SeqTrack {
+    pub channel: Channel,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub mute: bool,
+    /// 16ths per step, e.g. 2 for a track of 8ths.
+    #[serde(default = "no_division", skip_serializing_if = "is_undivided")]
+    pub division: u8,
+    #[serde(rename = "step")]
+    pub steps: Vec<Step>,
+}
+
+fn no_division() -> u8 {
+    1
+}
+
+fn is_undivided(division: &u8) -> bool {
+    *division == 1
+}
+
+impl SeqTrack {
+    pub fn new(channel: Channel) -> Self {
+        Self { channel, mute: false, division: 1, steps: vec![Step::default(); DEFAULT_STEPS] }
+    }
+
+    /// Clock ticks per step, with the division.
+    pub fn ticks_per_step(&self) -> u64 {
+        TICKS_PER_STEP * self.division.clamp(1, MAX_DIVISION) as u64
+    }
+
+    /// Sets the number of steps (1-64); new steps start off.
+    pub fn set_length(&mut self, steps: usize) {
+        self.steps.resize(steps.clamp(1, MAX_STEPS), Step::default());
+    }
+
+    /// The step playing at clock tick `tick` (counted from Start).
+    pub fn step_at(&self, tick: u64) -> usize {
+        (tick / self.ticks_per_step()) as usize % self.steps.len().max(1)
+    }
+
+    /// Looks up the locked parameters in `midi_map`, e.g. after loading
+    /// or a change of device model, keeping values within their ranges.
+    pub fn resolve_locks(&mut self, midi_map: &MidiMap) {
+        for lock in self.steps.iter_mut().flat_map(|step| step.locks.iter_mut()) {
+            let param = midi_map.get_by_name(&lock.parameter);
+            lock.address = param.as_ref().map(|p| p.address);
+            if let Some(param) = param {
+                lock.value = param.range.clamp(lock.value);
+            }
+        }
+    }
+}
+
+/// Plays the tracks as the clock ticks, ending each note after its length.
+#[derive(Debug, Default)]
+pub struct Sequencer {
+    tracks: Vec<SeqTrack>,
+    enabled: bool,
+    /// Notes playing and the tick each ends on.
+    sounding: Vec<(u64, Channel, Value7)>,
+    /// Clock ticks off-beat 16ths play late.
+    swing: u64,
+    recorder: Recorder,
+    /// Tracks and steps played on the latest tick, by index.
+    played: Vec<(usize, usize)>,
+}
+
+/// The tick the step `track` plays on `tick` was due, if one plays: steps
+/// start on the track's own division, off-beat 16ths `swing` ticks late.
+fn step_start(track: &SeqTrack, tick: u64, swing: u64) -> Option<u64> {
+    let due = |start: &u64| start.is_multiple_of(track.ticks_per_step());
+    let off_beat = |start: &u64| (start / TICKS_PER_STEP) % 2 == 1;
+    let on_beat = Some(tick).filter(|start| !off_beat(start) && due(start));
+    on_beat.or(tick.checked_sub(swing).filter(|start| off_beat(start) && due(start)))
+}
+
+impl Sequencer {
+    pub fn tracks(&self) -> &[SeqTrack] {
+        &self.tracks
+    }
+
+    /// Replaces the tracks; notes already playing end as they would have.
+    pub fn set_tracks(&mut self, tracks: Vec<SeqTrack>) {
+        self.tracks = tracks;
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Swings the steps by `percent` (50 is straight) when the clock
+    /// driving them is not swung itself, e.g. an external master: off-beat
+    /// 16ths play late by the nearest whole clock tick.
+    pub fn set_swing(&mut self, percent: u8) {
+        let off_beat = (TICKS_PER_STEP * 2) as f64 * percent as f64 / 100.0;
+        self.swing = (off_beat.round() as u64).saturating_sub(TICKS_PER_STEP);
+    }
+
+    /// The armed track, by index, and the recording settings.
+    pub fn recording(&self) -> (Option<usize>, Recording) {
+        (self.recorder.track(), self.recorder.settings())
+    }
+
+    /// Records notes into track `track`, or stops recording with `None`.
+    pub fn arm(&mut self, track: Option<usize>, settings: Recording) {
+        self.recorder.arm(track, settings);
+    }
+
+    /// The tick recording starts on after the count-in, once placed.
+    pub fn recording_from(&self) -> Option<u64> {
+        self.recorder.from()
+    }
+
+    /// Records `note` played at `tick` into the armed track. Returns
+    /// whether a step changed.
+    pub fn record_note_on(&mut self, tick: u64, note: Value7, velocity: Value7) -> bool {
+        self.recorder.note_on(&mut self.tracks, tick, note, velocity)
+    }
+
+    /// Ends a recorded note at `tick`. Returns whether a step changed.
+    pub fn record_note_off(&mut self, tick: u64, note: Value7) -> bool {
+        self.recorder.note_off(&mut self.tracks, tick, note)
+    }
+
+    /// Turns playback on or off; turning it off ends the notes playing.
+    pub fn set_enabled(&mut self, on: bool) -> Vec<Message> {
+        self.enabled = on;
+        if on { Vec::new() } else { self.release() }
+    }
+
+    /// What to send on clock tick `tick` (counted from Start): the notes
+    /// ending on it, then those of steps starting on it, each preceded by
+    /// its step's parameter locks.
+    pub fn tick(&mut self, tick: u64) -> Vec<Message> {
+        self.recorder.tick(tick);
+        self.played.clear();
+        let mut messages = Vec::new();
+        self.sounding.retain(|&(end, channel, note)| {
+            let ended = end <= tick;
+            if ended {
+                messages.push(Message::NoteOff { channel, note, velocity: Value7::default() });
+            }
+            !ended
+        });
+        if !self.enabled {
+            return messages;
+        }
+        let swing = self.swing;
+        let tracks = self.tracks.iter().enumerate();
+        for (index, track) in tracks.filter(|(_, t)| !t.mute && !t.steps.is_empty()) {
+            let Some(start) = step_start(track, tick, swing) else {
+                continue;
+            };
+            let step = &track.steps[track.step_at(start)];
+            if !step.on {
+                continue;
+            }
+            self.played.push((index, track.step_at(start)));
+            let (channel, note) = (track.channel, step.note.value());
+            // A retrigger cuts the note still playing
+            if let Some(index) = self.sounding.iter().position(|s| (s.1, s.2) == (channel, note)) {
+                self.sounding.remove(index);
+                messages.push(Message::NoteOff { channel, note, velocity: Value7::default() });
+            }
+            for lock in &step.locks {
+                let value = Value7::new(lock.value.min(127)).unwrap_or_default();
+                let sent = lock.address.and_then(|a| a.messages(channel, value).ok());
+                messages.extend(sent.into_iter().flatten());
+            }
+            messages.push(Message::NoteOn { channel, note, velocity: step.velocity });
+            self.sounding.push((tick + step.length.max(1) as u64, channel, note));
+        }
+        messages
+    }
+
+    /// Tracks and steps played on the latest tick, by index, e.g. to
+    /// trigger envelopes.
+    pub fn played(&self) -> &[(usize, usize)] {
+        &self.played
+    }
+
+    /// Note Offs for every note playing, e.g. on Start or Stop; a
+    /// recording counts in again when the transport next runs.
+    pub fn release(&mut self) -> Vec<Message> {
+        self.recorder.restart();
+        self.sounding
+            .drain(..)
+            .map(|(_, channel, note)| Message::NoteOff { channel, note, velocity: Value7::default() })
+            .collect()
+    }
+}
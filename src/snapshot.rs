@@ -0,0 +1,124 @@
+//! Named snapshots of controller values, stored as TOML under the config
+//! directory (`snapshots/<name>.toml`).
+
+use crate::config::Config;
+use crate::midi::Message;
+use crate::types::{Channel, Controller, Value7};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CcValue {
+    pub channel: Channel,
+    pub controller: Controller,
+    pub value: Value7,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Snapshot {
+    #[serde(default)]
+    pub cc: Vec<CcValue>,
+}
+
+impl Snapshot {
+    pub fn from_values(values: &[(Channel, Controller, Value7)]) -> Self {
+        Self {
+            cc: values
+                .iter()
+                .map(|&(channel, controller, value)| CcValue { channel, controller, value })
+                .collect(),
+        }
+    }
+
+    /// The Control Changes that restore this snapshot.
+    pub fn messages(&self) -> Vec<Message> {
+        self.cc
+            .iter()
+            .map(|cc| Message::ControlChange {
+                channel: cc.channel,
+                controller: cc.controller,
+                value: cc.value,
+            })
+            .collect()
+    }
+
+    /// The values `amount` of the way from `self` (0.0) to `other` (1.0),
+    /// rounded. Controllers only one side has keep that side's value.
+    pub fn morph(&self, other: &Snapshot, amount: f32) -> Snapshot {
+        let amount = amount.clamp(0.0, 1.0);
+        let mut values: BTreeMap<(Channel, Controller), f32> = self
+            .cc
+            .iter()
+            .map(|cc| ((cc.channel, cc.controller), cc.value.get() as f32))
+            .collect();
+        for cc in &other.cc {
+            let to = cc.value.get() as f32;
+            values
+                .entry((cc.channel, cc.controller))
+                .and_modify(|from| *from += (to - *from) * amount)
+                .or_insert(to);
+        }
+        Self {
+            cc: values
+                .into_iter()
+                .map(|((channel, controller), value)| CcValue {
+                    channel,
+                    controller,
+                    value: Value7::from_low_bits(value.round() as u16),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn dir() -> Option<PathBuf> {
+        Config::dir().map(|d| d.join("snapshots"))
+    }
+
+    fn path(name: &str) -> Result<PathBuf> {
+        if name.is_empty() || name.contains(['/', '\\']) || name.starts_with('.') {
+            bail!("Invalid snapshot name '{}'", name);
+        }
+        let dir = Self::dir().ok_or_else(|| anyhow::anyhow!("No config directory"))?;
+        Ok(dir.join(format!("{}.toml", name)))
+    }
+
+    pub fn load(name: &str) -> Result<Self> {
+        let path = Self::path(name)?;
+        if !path.exists() {
+            bail!("No snapshot named '{}'", name);
+        }
+        let text = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        toml::from_str(&text).with_context(|| format!("Invalid snapshot {}", path.display()))
+    }
+
+    pub fn save(&self, name: &str) -> Result<()> {
+        let path = Self::path(name)?;
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&path, toml::to_string_pretty(self)?)
+            .with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Names of the saved snapshots, sorted.
+    pub fn list() -> Result<Vec<String>> {
+        let Some(dir) = Self::dir().filter(|d| d.exists()) else {
+            return Ok(Vec::new());
+        };
+        let mut names = Vec::new();
+        for entry in fs::read_dir(&dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|e| e == "toml")
+                && let Some(stem) = path.file_stem()
+            {
+                names.push(stem.to_string_lossy().into_owned());
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+}
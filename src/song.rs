@@ -0,0 +1,179 @@
+//! Song mode: an arrangement of patterns, each played for its length in
+//! bars and a number of repeats, optionally recalling a snapshot as it
+//! starts. While the transport runs, the Program Changes and snapshots go
+//! out at the right bars.
+
+use crate::midi::Message;
+use crate::pattern::Pattern;
+use crate::snapshot::Snapshot;
+use crate::transport::TICKS_PER_BAR;
+use crate::types::Channel;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+fn one() -> u32 {
+    1
+}
+
+fn is_one(value: &u32) -> bool {
+    *value == 1
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SongEntry {
+    pub pattern: Pattern,
+    /// The pattern's length in bars.
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub bars: u32,
+    /// Times the pattern plays before the next entry.
+    #[serde(default = "one", skip_serializing_if = "is_one")]
+    pub repeat: u32,
+    /// Snapshot recalled as the entry starts.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub snapshot: Option<String>,
+}
+
+impl SongEntry {
+    pub fn new(pattern: Pattern) -> Self {
+        Self { pattern, bars: 1, repeat: 1, snapshot: None }
+    }
+
+    /// Bars the entry lasts, repeats included.
+    pub fn length(&self) -> u64 {
+        self.bars.max(1) as u64 * self.repeat.max(1) as u64
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Song {
+    #[serde(default, rename = "entry")]
+    pub entries: Vec<SongEntry>,
+    /// Starts over after the last entry.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub looped: bool,
+}
+
+impl Song {
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Bars in one pass of the song.
+    pub fn length(&self) -> u64 {
+        self.entries.iter().map(SongEntry::length).sum()
+    }
+
+    /// The entry playing in bar `bar` (counted from 0 at Start) and the bar
+    /// it started in; `None` once a song that doesn't loop has ended.
+    pub fn entry_at(&self, bar: u64) -> Option<(usize, u64)> {
+        let length = self.length();
+        let bar = if self.looped && length > 0 { bar % length } else { bar };
+        let mut start = 0;
+        for (index, entry) in self.entries.iter().enumerate() {
+            if bar < start + entry.length() {
+                return Some((index, start));
+            }
+            start += entry.length();
+        }
+        None
+    }
+}
+
+/// An entry ready to play: where it starts, and what it sends.
+#[derive(Debug, Clone)]
+struct Cue {
+    start: u64,
+    end: u64,
+    /// Bank Select and Program Change.
+    program: Vec<Message>,
+    snapshot: Vec<Message>,
+}
+
+/// Plays a [`Song`] as the clock ticks. Elektron devices change pattern
+/// when the one playing ends, so each Program Change goes out half a bar
+/// ahead; snapshots go out on the downbeat.
+#[derive(Debug, Default)]
+pub struct SongPlayer {
+    enabled: bool,
+    cues: Vec<Cue>,
+    looped: bool,
+}
+
+impl SongPlayer {
+    /// Readies `song` to play on `channel`, loading its snapshots.
+    pub fn load(&mut self, song: &Song, channel: Channel) -> Result<()> {
+        let mut cues = Vec::new();
+        let mut start = 0;
+        for (number, entry) in song.entries.iter().enumerate() {
+            let snapshot = match &entry.snapshot {
+                Some(name) => Snapshot::load(name)
+                    .with_context(|| format!("Song entry {}", number + 1))?
+                    .messages(),
+                None => Vec::new(),
+            };
+            let end = start + entry.length() * TICKS_PER_BAR;
+            cues.push(Cue { start, end, program: entry.pattern.messages(channel), snapshot });
+            start = end;
+        }
+        self.cues = cues;
+        self.looped = song.looped;
+        Ok(())
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn set_enabled(&mut self, on: bool) {
+        self.enabled = on;
+    }
+
+    /// Tick `tick` within one pass of the song.
+    fn in_song(&self, tick: u64) -> u64 {
+        match self.cues.last() {
+            Some(last) if self.looped && last.end > 0 => tick % last.end,
+            _ => tick,
+        }
+    }
+
+    /// What to send before playing from clock tick `tick`, e.g. ahead of
+    /// Start or Continue: the program of the entry playing there, and its
+    /// snapshot if it started before.
+    pub fn cue_in(&self, tick: u64) -> Vec<Message> {
+        let tick = self.in_song(tick);
+        let cue = self.cues.iter().find(|cue| cue.start <= tick && tick < cue.end);
+        let Some(cue) = cue.filter(|_| self.enabled) else {
+            return Vec::new();
+        };
+        let mut messages = cue.program.clone();
+        if cue.start < tick {
+            messages.extend(cue.snapshot.iter().cloned());
+        }
+        messages
+    }
+
+    /// What to send on clock tick `tick` (counted from Start).
+    pub fn tick(&self, tick: u64) -> Vec<Message> {
+        if !self.enabled || self.cues.is_empty() {
+            return Vec::new();
+        }
+        let tick = self.in_song(tick);
+        let ahead = TICKS_PER_BAR / 2;
+        let mut messages = Vec::new();
+        for (index, cue) in self.cues.iter().enumerate() {
+            if cue.start == tick {
+                messages.extend(cue.snapshot.iter().cloned());
+            }
+            // The next entry's program, unless it is the same pattern
+            let next = match self.cues.get(index + 1) {
+                Some(next) => next,
+                None if self.looped => &self.cues[0],
+                None => continue,
+            };
+            if cue.end.checked_sub(ahead) == Some(tick) && next.program != cue.program {
+                messages.extend(next.program.iter().cloned());
+            }
+        }
+        messages
+    }
+}
@@ -0,0 +1,116 @@
+//! Song page of the GUI: the arrangement's entries in order, each a
+//! pattern with its length, repeats and snapshot, the one playing marked.
+
+use eframe::egui;
+use midi_ctrl::pattern::{BANKS, PATTERNS_PER_BANK};
+use midi_ctrl::{Pattern, Snapshot, Song, SongEntry};
+
+/// Longest pattern the Digitakt plays, in bars.
+const MAX_BARS: u32 = 64;
+
+/// The pattern added by the Add button.
+#[derive(Debug, Default)]
+pub struct SongEditor {
+    next: Option<Pattern>,
+}
+
+/// Bank letter and number pickers for `pattern`. Returns true on change.
+fn pattern_picker(ui: &mut egui::Ui, id: usize, pattern: &mut Pattern) -> bool {
+    let (mut bank, mut number) = (pattern.bank(), pattern.index() + 1);
+    egui::ComboBox::from_id_source(("song_bank", id))
+        .width(36.0)
+        .selected_text(pattern.bank_letter().to_string())
+        .show_ui(ui, |ui| {
+            for b in 0..BANKS {
+                ui.selectable_value(&mut bank, b, ((b'A' + b) as char).to_string());
+            }
+        });
+    ui.add(egui::DragValue::new(&mut number).clamp_range(1..=PATTERNS_PER_BANK));
+    match Pattern::new(bank, number - 1) {
+        Ok(picked) if picked != *pattern => {
+            *pattern = picked;
+            true
+        }
+        _ => false,
+    }
+}
+
+impl SongEditor {
+    /// Draws the entries with the one at index `playing` marked. Returns
+    /// true when the song was edited and should be sent and saved.
+    pub fn show(&mut self, ui: &mut egui::Ui, song: &mut Song, playing: Option<usize>) -> bool {
+        let mut edited = false;
+        let mut moved = None;
+        let mut removed = None;
+        let last = song.entries.len().saturating_sub(1);
+        egui::Grid::new("song_entries").striped(true).show(ui, |ui| {
+            ui.label("");
+            ui.strong("Pattern");
+            ui.strong("Bars");
+            ui.strong("Repeat");
+            ui.strong("Snapshot");
+            ui.end_row();
+            for (index, entry) in song.entries.iter_mut().enumerate() {
+                let number = format!("{}", index + 1);
+                if playing == Some(index) {
+                    ui.strong(format!("▶ {}", number));
+                } else {
+                    ui.label(number);
+                }
+                ui.horizontal(|ui| edited |= pattern_picker(ui, index, &mut entry.pattern));
+                let bars = egui::DragValue::new(&mut entry.bars).clamp_range(1..=MAX_BARS);
+                edited |= ui.add(bars).on_hover_text("The pattern's length").changed();
+                let repeat = egui::DragValue::new(&mut entry.repeat).clamp_range(1..=99);
+                edited |= ui.add(repeat.prefix("×")).changed();
+                egui::ComboBox::from_id_source(("song_snapshot", index))
+                    .selected_text(entry.snapshot.as_deref().unwrap_or("None"))
+                    .show_ui(ui, |ui| {
+                        edited |= ui.selectable_value(&mut entry.snapshot, None, "None").changed();
+                        for name in Snapshot::list().unwrap_or_default() {
+                            let value = Some(name.clone());
+                            let option = ui.selectable_value(&mut entry.snapshot, value, name);
+                            edited |= option.changed();
+                        }
+                    });
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(index > 0, egui::Button::new("⬆").small()).clicked() {
+                        moved = Some((index, index - 1));
+                    }
+                    if ui.add_enabled(index < last, egui::Button::new("⬇").small()).clicked() {
+                        moved = Some((index, index + 1));
+                    }
+                    if ui.small_button("🗑").on_hover_text("Remove entry").clicked() {
+                        removed = Some(index);
+                    }
+                });
+                ui.end_row();
+            }
+        });
+        if let Some((from, to)) = moved {
+            song.entries.swap(from, to);
+            edited = true;
+        }
+        if let Some(index) = removed {
+            song.entries.remove(index);
+            edited = true;
+        }
+
+        ui.horizontal(|ui| {
+            // The last entry's pattern, or A01, until one is picked
+            let last = song.entries.last().map(|e| e.pattern);
+            if let Some(mut next) = self.next.or(last).or(Pattern::new(0, 0).ok()) {
+                pattern_picker(ui, usize::MAX, &mut next);
+                self.next = Some(next);
+                if ui.button("Add").clicked() {
+                    song.entries.push(SongEntry::new(next));
+                    edited = true;
+                }
+            }
+            ui.separator();
+            let hover = "Start over after the last entry";
+            edited |= ui.checkbox(&mut song.looped, "Loop").on_hover_text(hover).changed();
+            ui.label(format!("{} bar(s)", song.length()));
+        });
+        edited
+    }
+}
@@ -0,0 +1,178 @@
+//! Sequencer page of the GUI: a row of steps per track, toggled by clicking,
+//! with where the track is in its cycle, and the right-clicked step's note,
+//! velocity, length and parameter locks below.
+
+use eframe::egui;
+use midi_ctrl::sequencer::{MAX_DIVISION, MAX_STEPS, TICKS_PER_STEP};
+use midi_ctrl::{Channel, MidiMap, Note, SeqTrack, Value7};
+
+const STEP_SIZE: f32 = 26.0;
+
+/// The step being edited, by track and step.
+#[derive(Debug, Default)]
+pub struct StepGrid {
+    selected: Option<(usize, usize)>,
+}
+
+fn note_name(number: f64) -> String {
+    Value7::new(number as u8).map_or(String::new(), |v| Note::new(v).to_string())
+}
+
+impl StepGrid {
+    /// Draws the tracks with the step playing at `tick` marked, and a
+    /// button adding a track on `channel`; locks pick parameters from
+    /// `midi_map`. Returns true when the tracks were edited and should be
+    /// sent and saved.
+    pub fn show(
+        &mut self,
+        ui: &mut egui::Ui,
+        tracks: &mut Vec<SeqTrack>,
+        tick: Option<u64>,
+        channel: Channel,
+        midi_map: &MidiMap,
+    ) -> bool {
+        let mut edited = false;
+        let mut removed = None;
+        egui::ScrollArea::horizontal().show(ui, |ui| {
+            for (index, track) in tracks.iter_mut().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("ch {:>2}", track.channel));
+                    edited |= ui.toggle_value(&mut track.mute, "M").on_hover_text("Mute").changed();
+                    let mut length = track.steps.len();
+                    let drag = egui::DragValue::new(&mut length).clamp_range(1..=MAX_STEPS);
+                    if ui.add(drag).on_hover_text("Steps").changed() {
+                        track.set_length(length);
+                        edited = true;
+                    }
+                    let drag = egui::DragValue::new(&mut track.division)
+                        .clamp_range(1..=MAX_DIVISION)
+                        .prefix("÷");
+                    let hover = "Clock division: 16ths per step";
+                    edited |= ui.add(drag).on_hover_text(hover).changed();
+                    if ui.small_button("🗑").on_hover_text("Remove track").clicked() {
+                        removed = Some(index);
+                    }
+                    let playing = tick.map(|tick| track.step_at(tick));
+                    // Where the track is in its own cycle, as it phases
+                    // against the others
+                    let cycle = match playing {
+                        Some(step) => format!("{:>2}/{:<2}", step + 1, track.steps.len()),
+                        None => format!("{:>2}/{:<2}", "-", track.steps.len()),
+                    };
+                    ui.monospace(cycle);
+                    ui.separator();
+                    for (number, step) in track.steps.iter_mut().enumerate() {
+                        if number > 0 && number % 4 == 0 {
+                            ui.add_space(6.0);
+                        }
+                        let selected = self.selected == Some((index, number));
+                        let mut button = egui::Button::new("")
+                            .min_size(egui::vec2(STEP_SIZE, STEP_SIZE))
+                            .selected(step.on);
+                        if playing == Some(number) || selected {
+                            let color = ui.visuals().strong_text_color();
+                            button = button.stroke(egui::Stroke::new(2.0, color));
+                        }
+                        let locks = match step.locks.len() {
+                            0 => String::new(),
+                            1 => ", 1 lock".to_string(),
+                            n => format!(", {} locks", n),
+                        };
+                        let hover = format!(
+                            "Step {}: {} vel {}{} (right-click to edit)",
+                            number + 1,
+                            step.note,
+                            step.velocity,
+                            locks
+                        );
+                        let response = ui.add(button).on_hover_text(hover);
+                        if response.clicked() {
+                            step.on = !step.on;
+                            edited = true;
+                        }
+                        if response.secondary_clicked() {
+                            self.selected = Some((index, number));
+                        }
+                    }
+                });
+            }
+        });
+        if let Some(index) = removed {
+            tracks.remove(index);
+            self.selected = None;
+            edited = true;
+        }
+        if ui.button(format!("Add track on ch {}", channel)).clicked() {
+            tracks.push(SeqTrack::new(channel));
+            edited = true;
+        }
+
+        let step = self
+            .selected
+            .and_then(|(track, number)| tracks.get_mut(track)?.steps.get_mut(number));
+        if let Some(step) = step {
+            ui.separator();
+            ui.horizontal(|ui| {
+                let (track, number) = self.selected.unwrap_or_default();
+                ui.strong(format!("Track {} step {}", track + 1, number + 1));
+                edited |= ui.checkbox(&mut step.on, "On").changed();
+                ui.label("Note:");
+                let mut note = step.note.value().get();
+                let drag = egui::DragValue::new(&mut note)
+                    .clamp_range(0..=127)
+                    .custom_formatter(|n, _| note_name(n));
+                if ui.add(drag).changed()
+                    && let Ok(value) = Value7::new(note)
+                {
+                    step.note = Note::new(value);
+                    edited = true;
+                }
+                ui.label("Velocity:");
+                let mut velocity = step.velocity.get();
+                if ui.add(egui::Slider::new(&mut velocity, 1..=127)).changed() {
+                    step.velocity = Value7::new(velocity).unwrap_or_default();
+                    edited = true;
+                }
+                ui.label("Length:");
+                let max = (MAX_STEPS as u64 * TICKS_PER_STEP).min(u8::MAX as u64) as u8;
+                let drag = egui::DragValue::new(&mut step.length).clamp_range(1..=max);
+                let hover = format!("Clock ticks, {} to a 16th", TICKS_PER_STEP);
+                edited |= ui.add(drag).on_hover_text(hover).changed();
+            });
+            ui.horizontal_wrapped(|ui| {
+                ui.label("Locks:").on_hover_text("Parameter values sent just before the step");
+                let mut removed = None;
+                for (index, lock) in step.locks.iter_mut().enumerate() {
+                    let Some(address) = lock.address else {
+                        ui.weak(&lock.parameter).on_hover_text("Not in this device's map");
+                        continue;
+                    };
+                    ui.label(&lock.parameter);
+                    let drag = egui::DragValue::new(&mut lock.value)
+                        .clamp_range(0..=127)
+                        .custom_formatter(|v, _| midi_map.format_value(address, v as u8));
+                    edited |= ui.add(drag).changed();
+                    if ui.small_button("✕").on_hover_text("Remove lock").clicked() {
+                        removed = Some(index);
+                    }
+                    ui.separator();
+                }
+                if let Some(index) = removed {
+                    step.locks.remove(index);
+                    edited = true;
+                }
+                egui::ComboBox::from_id_source("step_lock")
+                    .selected_text("Add lock")
+                    .show_ui(ui, |ui| {
+                        for param in midi_map.get_all_parameters() {
+                            if ui.selectable_label(false, &param.name).clicked() {
+                                step.set_lock(&param.name, param.address, param.default);
+                                edited = true;
+                            }
+                        }
+                    });
+            });
+        }
+        edited
+    }
+}
@@ -0,0 +1,107 @@
+//! `--strict`: the first error ends the run with an exit code saying what
+//! kind of failure it was and one JSON line on stderr, for scripts.
+
+use midi_ctrl::SendError;
+use serde::Serialize;
+
+/// An output port that was asked for but could not be found or opened.
+#[derive(Debug)]
+pub struct NoPort(pub String);
+
+impl std::fmt::Display for NoPort {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for NoPort {}
+
+/// Marks a failure to open a port as [`NoPort`].
+pub fn port_error(e: anyhow::Error) -> anyhow::Error {
+    NoPort(format!("{:#}", e)).into()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+enum Failure {
+    /// Unknown command, bad argument, unreadable file.
+    Command,
+    /// No port selected, not found, or gone.
+    Port,
+    /// A port rejected a send.
+    Send,
+}
+
+impl Failure {
+    /// What kind of failure `e` is. Downcasting the error itself rather
+    /// than each cause in its chain also finds a marker added as context,
+    /// as [`SendError::Failed`] is.
+    fn of(e: &anyhow::Error) -> Self {
+        if e.downcast_ref::<NoPort>().is_some() {
+            return Failure::Port;
+        }
+        match e.downcast_ref::<SendError>() {
+            Some(SendError::Failed(_)) => Failure::Send,
+            Some(_) => Failure::Port,
+            None => Failure::Command,
+        }
+    }
+
+    fn exit_code(self) -> i32 {
+        match self {
+            Failure::Command => 2,
+            Failure::Port => 3,
+            Failure::Send => 4,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorLine {
+    error: Failure,
+    message: String,
+}
+
+/// Prints `{"error":"port","message":"…"}` to stderr and exits with the
+/// failure's code: 2 for a bad command, 3 for a missing port, 4 for a
+/// failed send.
+pub fn exit_with(e: &anyhow::Error) -> ! {
+    let failure = Failure::of(e);
+    let line = ErrorLine { error: failure, message: format!("{:#}", e) };
+    match serde_json::to_string(&line) {
+        Ok(line) => eprintln!("{}", line),
+        Err(_) => eprintln!("{:#}", e),
+    }
+    std::process::exit(failure.exit_code())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn failed_send_added_as_context() {
+        // As `MidiController::for_each_target` reports a rejected send
+        let e = Err::<(), _>(anyhow::anyhow!("device gone"))
+            .with_context(|| SendError::Failed("Digitakt".to_string()))
+            .unwrap_err();
+        assert_eq!(Failure::of(&e), Failure::Send);
+        assert_eq!(Failure::of(&e.context("Sending cc")), Failure::Send);
+    }
+
+    #[test]
+    fn missing_port() {
+        let e: anyhow::Error = SendError::NotConnected.into();
+        assert_eq!(Failure::of(&e), Failure::Port);
+        let e = port_error(anyhow::anyhow!("No such port")).context("connect");
+        assert_eq!(Failure::of(&e), Failure::Port);
+    }
+
+    #[test]
+    fn other_errors_are_commands() {
+        let e = anyhow::anyhow!("Unknown command 'foo'");
+        assert_eq!(Failure::of(&e), Failure::Command);
+        assert_eq!(Failure::Command.exit_code(), 2);
+    }
+}
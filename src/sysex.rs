@@ -0,0 +1,48 @@
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+/// Default pause between SysEx packets; the Digitakt drops data if dumps are
+/// streamed back to back.
+pub const DEFAULT_PACKET_DELAY: Duration = Duration::from_millis(20);
+
+/// Splits a raw `.syx` byte stream into message payloads (0xF0/0xF7 stripped).
+pub fn split_messages(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut messages = Vec::new();
+    let mut current: Option<Vec<u8>> = None;
+
+    for (offset, &byte) in data.iter().enumerate() {
+        match (byte, current.as_mut()) {
+            (0xF0, None) => current = Some(Vec::new()),
+            (0xF7, Some(_)) => messages.extend(current.take()),
+            (b, Some(payload)) if b < 0x80 => payload.push(b),
+            (b, _) => bail!("Unexpected byte 0x{:02X} at offset {}", b, offset),
+        }
+    }
+    if current.is_some() {
+        bail!("Truncated SysEx message at end of data");
+    }
+    Ok(messages)
+}
+
+pub fn read_file(path: &Path) -> Result<Vec<Vec<u8>>> {
+    let data = fs::read(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let messages = split_messages(&data)
+        .with_context(|| format!("Invalid SysEx file {}", path.display()))?;
+    if messages.is_empty() {
+        bail!("No SysEx messages in {}", path.display());
+    }
+    Ok(messages)
+}
+
+/// Writes payloads back out as framed SysEx messages.
+pub fn write_file(path: &Path, messages: &[Vec<u8>]) -> Result<()> {
+    let mut data = Vec::new();
+    for payload in messages {
+        data.push(0xF0);
+        data.extend_from_slice(payload);
+        data.push(0xF7);
+    }
+    fs::write(path, data).with_context(|| format!("Failed to write {}", path.display()))
+}
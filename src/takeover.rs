@@ -0,0 +1,125 @@
+//! External controller bindings and how a hardware knob takes over a
+//! parameter whose value it does not match.
+
+use crate::types::{Channel, Controller};
+use serde::{Deserialize, Serialize};
+
+/// What an incoming value does to the bound parameter.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TakeoverMode {
+    /// The parameter follows the knob at once, possibly jumping.
+    Jump,
+    /// The knob has no effect until it reaches or crosses the parameter's
+    /// value, then it takes over.
+    #[default]
+    Pickup,
+    /// Endless encoders: 1-63 turn up by that much, 65-127 down by
+    /// 128 minus the value (two's complement).
+    Relative,
+}
+
+/// A CC from an external controller bound to a parameter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Binding {
+    pub channel: Channel,
+    pub controller: Controller,
+    /// Parameter name, as in the MIDI map.
+    pub parameter: String,
+    #[serde(default)]
+    pub mode: TakeoverMode,
+}
+
+/// Pickup progress of one binding.
+#[derive(Debug, Clone, Default)]
+pub struct Takeover {
+    /// The knob's previous value, to tell when it crosses the parameter.
+    last_in: Option<u8>,
+    /// The value this binding last set; anything else means the parameter
+    /// was changed elsewhere and must be picked up again.
+    last_out: Option<u8>,
+}
+
+impl Takeover {
+    /// The parameter's new value after the knob sent `incoming` while the
+    /// parameter was at `current`, or `None` to leave it.
+    pub fn feed(&mut self, mode: TakeoverMode, incoming: u8, current: u8) -> Option<u8> {
+        let last_in = self.last_in.replace(incoming);
+        let value = match mode {
+            TakeoverMode::Jump => incoming,
+            TakeoverMode::Relative => {
+                let step = match incoming {
+                    0..=63 => incoming as i16,
+                    64 => 0,
+                    _ => incoming as i16 - 128,
+                };
+                (current as i16 + step).clamp(0, 127) as u8
+            }
+            TakeoverMode::Pickup => {
+                let held = self.last_out == Some(current);
+                let crossed = last_in.is_some_and(|last| {
+                    (last.min(incoming)..=last.max(incoming)).contains(&current)
+                });
+                if !(held || crossed || incoming == current) {
+                    return None;
+                }
+                incoming
+            }
+        };
+        self.last_out = Some(value);
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pickup_waits_for_the_knob_to_cross() {
+        let mut takeover = Takeover::default();
+        let pickup = TakeoverMode::Pickup;
+        assert_eq!(takeover.feed(pickup, 10, 64), None);
+        assert_eq!(takeover.feed(pickup, 30, 64), None);
+        // From 30 to 70 passes 64
+        assert_eq!(takeover.feed(pickup, 70, 64), Some(70));
+        assert_eq!(takeover.feed(pickup, 72, 70), Some(72));
+        assert_eq!(takeover.feed(pickup, 20, 72), Some(20));
+
+        let mut takeover = Takeover::default();
+        assert_eq!(takeover.feed(pickup, 90, 64), None);
+        assert_eq!(takeover.feed(pickup, 64, 64), Some(64));
+    }
+
+    #[test]
+    fn pickup_again_after_a_change_elsewhere() {
+        let mut takeover = Takeover::default();
+        let pickup = TakeoverMode::Pickup;
+        assert_eq!(takeover.feed(pickup, 64, 64), Some(64));
+        // Moved to 100 by something else
+        assert_eq!(takeover.feed(pickup, 66, 100), None);
+        assert_eq!(takeover.feed(pickup, 90, 100), None);
+        assert_eq!(takeover.feed(pickup, 105, 100), Some(105));
+        assert_eq!(takeover.feed(pickup, 104, 105), Some(104));
+    }
+
+    #[test]
+    fn relative_steps() {
+        let mut takeover = Takeover::default();
+        let mut step = |incoming, current| takeover.feed(TakeoverMode::Relative, incoming, current);
+        assert_eq!(step(1, 64), Some(65));
+        assert_eq!(step(63, 64), Some(127));
+        assert_eq!(step(64, 64), Some(64));
+        assert_eq!(step(65, 64), Some(1));
+        assert_eq!(step(127, 64), Some(63));
+        assert_eq!(step(63, 120), Some(127));
+        assert_eq!(step(65, 2), Some(0));
+    }
+
+    #[test]
+    fn jump_follows_at_once() {
+        let mut takeover = Takeover::default();
+        assert_eq!(takeover.feed(TakeoverMode::Jump, 0, 127), Some(0));
+        assert_eq!(takeover.feed(TakeoverMode::Jump, 127, 50), Some(127));
+    }
+}
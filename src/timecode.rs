@@ -0,0 +1,256 @@
+use anyhow::{bail, Result};
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Drop-frame frames in a minute that drops two labels, and in ten minutes.
+const DROP_MINUTE: u64 = 60 * 30 - 2;
+const DROP_TEN_MINUTES: u64 = 10 * DROP_MINUTE + 2;
+
+/// SMPTE frame rates, numbered as in MMC and MTC (two rate bits).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FrameRate {
+    Fps24,
+    #[default]
+    Fps25,
+    /// 29.97 drop-frame: labelled at a nominal 30 fps, skipping frames 0
+    /// and 1 at the start of every minute except each tenth.
+    Fps2997Drop,
+    Fps30,
+}
+
+impl FrameRate {
+    pub const ALL: [FrameRate; 4] =
+        [FrameRate::Fps24, FrameRate::Fps25, FrameRate::Fps2997Drop, FrameRate::Fps30];
+
+    /// Rate code carried in the top bits of the hours byte.
+    pub fn code(self) -> u8 {
+        match self {
+            FrameRate::Fps24 => 0,
+            FrameRate::Fps25 => 1,
+            FrameRate::Fps2997Drop => 2,
+            FrameRate::Fps30 => 3,
+        }
+    }
+
+    pub fn from_code(code: u8) -> Self {
+        match code & 0x03 {
+            0 => FrameRate::Fps24,
+            1 => FrameRate::Fps25,
+            2 => FrameRate::Fps2997Drop,
+            _ => FrameRate::Fps30,
+        }
+    }
+
+    /// Actual frame rate, for scheduling.
+    pub fn per_second(self) -> f64 {
+        match self {
+            FrameRate::Fps2997Drop => 30_000.0 / 1001.0,
+            other => other.frames() as f64,
+        }
+    }
+
+    /// Frames per second as a fraction, for exact duration arithmetic.
+    fn ratio(self) -> (u64, u64) {
+        match self {
+            FrameRate::Fps2997Drop => (30_000, 1001),
+            other => (other.frames() as u64, 1),
+        }
+    }
+
+    /// Frame labels per second (`ff` runs up to one less).
+    pub fn frames(self) -> u8 {
+        match self {
+            FrameRate::Fps24 => 24,
+            FrameRate::Fps25 => 25,
+            FrameRate::Fps2997Drop | FrameRate::Fps30 => 30,
+        }
+    }
+}
+
+/// An `hh:mm:ss:ff` position at a given frame rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Timecode {
+    pub hours: u8,
+    pub minutes: u8,
+    pub seconds: u8,
+    pub frames: u8,
+    pub rate: FrameRate,
+}
+
+impl Timecode {
+    pub fn new(hours: u8, minutes: u8, seconds: u8, frames: u8, rate: FrameRate) -> Result<Self> {
+        let dropped = rate == FrameRate::Fps2997Drop && seconds == 0 && !minutes.is_multiple_of(10);
+        let out_of_range = hours > 23 || minutes > 59 || seconds > 59 || frames >= rate.frames();
+        if out_of_range || dropped && frames < 2 {
+            bail!(
+                "Invalid timecode {:02}:{:02}:{:02}:{:02} at {} fps",
+                hours,
+                minutes,
+                seconds,
+                frames,
+                rate.frames()
+            );
+        }
+        Ok(Self { hours, minutes, seconds, frames, rate })
+    }
+
+    /// Position `elapsed` from zero, wrapping after 24 hours.
+    pub fn from_duration(elapsed: Duration, rate: FrameRate) -> Self {
+        let (num, den) = rate.ratio();
+        let frames = elapsed.as_nanos() * num as u128 / (den as u128 * 1_000_000_000);
+        Self::from_frames(frames as u64, rate)
+    }
+
+    /// Position a whole number of frames from zero, wrapping after 24 hours.
+    pub fn from_frames(total_frames: u64, rate: FrameRate) -> Self {
+        let fps = rate.frames() as u64;
+        let mut total_frames = total_frames;
+        if rate == FrameRate::Fps2997Drop {
+            // Back to labels: 2 skipped per minute, 9 times per ten minutes
+            let ten_minutes = total_frames / DROP_TEN_MINUTES;
+            let rest = total_frames % DROP_TEN_MINUTES;
+            let minutes = rest.saturating_sub(2) / DROP_MINUTE;
+            total_frames = (total_frames + 18 * ten_minutes + 2 * minutes) % (24 * 3600 * fps);
+        }
+        let total_secs = total_frames / fps;
+        Self {
+            hours: (total_secs / 3600 % 24) as u8,
+            minutes: (total_secs / 60 % 60) as u8,
+            seconds: (total_secs % 60) as u8,
+            frames: (total_frames % fps) as u8,
+            rate,
+        }
+    }
+
+    /// Frames since zero.
+    pub fn to_frames(self) -> u64 {
+        let secs = self.hours as u64 * 3600 + self.minutes as u64 * 60 + self.seconds as u64;
+        let labels = secs * self.rate.frames() as u64 + self.frames as u64;
+        match self.rate {
+            FrameRate::Fps2997Drop => {
+                let minutes = self.hours as u64 * 60 + self.minutes as u64;
+                labels - 2 * (minutes - minutes / 10)
+            }
+            _ => labels,
+        }
+    }
+
+    /// Time from zero to the start of the frame.
+    pub fn to_duration(self) -> Duration {
+        let (num, den) = self.rate.ratio();
+        let nanos = (self.to_frames() as u128 * den as u128 * 1_000_000_000).div_ceil(num as u128);
+        Duration::from_nanos(nanos as u64)
+    }
+}
+
+impl FromStr for FrameRate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(match s {
+            "24" => FrameRate::Fps24,
+            "25" => FrameRate::Fps25,
+            "29.97" | "29.97df" => FrameRate::Fps2997Drop,
+            "30" => FrameRate::Fps30,
+            _ => bail!("Unknown frame rate '{}' (24, 25, 29.97 or 30)", s),
+        })
+    }
+}
+
+impl fmt::Display for FrameRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrameRate::Fps2997Drop => write!(f, "29.97df"),
+            other => write!(f, "{}", other.frames()),
+        }
+    }
+}
+
+impl FromStr for Timecode {
+    type Err = anyhow::Error;
+
+    /// Parses `hh:mm:ss:ff` at the default 25 fps, or `hh:mm:ss;ff` at
+    /// 29.97 drop-frame.
+    fn from_str(s: &str) -> Result<Self> {
+        let rate = match s.contains(';') {
+            true => FrameRate::Fps2997Drop,
+            false => FrameRate::default(),
+        };
+        let fields: Vec<u8> = s
+            .split([':', ';'])
+            .map(|f| f.parse::<u8>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| anyhow::anyhow!("Invalid timecode '{}' (expected hh:mm:ss:ff)", s))?;
+        let [hours, minutes, seconds, frames] = fields[..] else {
+            bail!("Invalid timecode '{}' (expected hh:mm:ss:ff)", s);
+        };
+        Timecode::new(hours, minutes, seconds, frames, rate)
+    }
+}
+
+impl fmt::Display for Timecode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let separator = if self.rate == FrameRate::Fps2997Drop { ';' } else { ':' };
+        write!(
+            f,
+            "{:02}:{:02}:{:02}{}{:02}",
+            self.hours, self.minutes, self.seconds, separator, self.frames
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn df(timecode: &str) -> Timecode {
+        let tc: Timecode = timecode.parse().unwrap();
+        assert_eq!(tc.rate, FrameRate::Fps2997Drop);
+        tc
+    }
+
+    fn next(tc: Timecode) -> Timecode {
+        Timecode::from_frames(tc.to_frames() + 1, tc.rate)
+    }
+
+    #[test]
+    fn drop_frame_skips_labels_at_minutes() {
+        assert_eq!(next(df("00:00:59;29")).to_string(), "00:01:00;02");
+        assert_eq!(next(df("00:09:59;29")).to_string(), "00:10:00;00");
+        assert_eq!(next(df("00:10:59;29")).to_string(), "00:11:00;02");
+        assert_eq!(next(df("23:59:59;29")).to_string(), "00:00:00;00");
+        assert_eq!(df("00:01:00;02").to_frames(), 1800);
+        assert_eq!(df("00:10:00;00").to_frames(), 17982);
+        assert!("00:01:00;01".parse::<Timecode>().is_err());
+        assert!("00:10:00;01".parse::<Timecode>().is_ok());
+    }
+
+    #[test]
+    fn frames_round_trip() {
+        for rate in FrameRate::ALL {
+            for frames in (0..24 * 3600 * 24).step_by(997) {
+                let tc = Timecode::from_frames(frames, rate);
+                assert_eq!(tc.to_frames(), frames, "{} at {}", tc, rate);
+                assert!(Timecode::new(tc.hours, tc.minutes, tc.seconds, tc.frames, rate).is_ok());
+            }
+        }
+    }
+
+    #[test]
+    fn durations_at_the_real_rate() {
+        for rate in FrameRate::ALL {
+            for frames in (0..24 * 3600 * 24).step_by(4999) {
+                let tc = Timecode::from_frames(frames, rate);
+                assert_eq!(Timecode::from_duration(tc.to_duration(), rate), tc);
+            }
+        }
+        // An hour of 29.97 fps is 107892 frames, which drop-frame labels as
+        // exactly an hour
+        let hour = Timecode::from_duration(Duration::from_secs(3600), FrameRate::Fps2997Drop);
+        assert_eq!(hour.to_frames(), 107_892);
+        assert_eq!(hour.to_string(), "01:00:00;00");
+        let tc = Timecode::new(1, 2, 3, 4, FrameRate::Fps25).unwrap();
+        assert_eq!(tc.to_duration(), Duration::from_millis(3_723_160));
+    }
+}
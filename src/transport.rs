@@ -0,0 +1,152 @@
+use crate::clock::PPQN;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Clocks per MIDI beat (a 16th note), the unit of Song Position Pointer.
+pub const TICKS_PER_MIDI_BEAT: u64 = 6;
+/// Quarter notes per bar; the Digitakt's default 4/4.
+pub const BEATS_PER_BAR: u64 = 4;
+pub const TICKS_PER_BAR: u64 = PPQN as u64 * BEATS_PER_BAR;
+
+/// Musical position, all fields 1-based like the hardware display
+/// (tick is the clock within the beat, 0-23).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub bar: u64,
+    pub beat: u64,
+    pub tick: u64,
+}
+
+impl Position {
+    pub fn from_ticks(ticks: u64) -> Self {
+        Self {
+            bar: ticks / TICKS_PER_BAR + 1,
+            beat: ticks % TICKS_PER_BAR / PPQN as u64 + 1,
+            tick: ticks % PPQN as u64,
+        }
+    }
+
+    /// Song Position (in MIDI beats) of the downbeat of `bar` (1-based).
+    pub fn bar_to_midi_beats(bar: u64) -> u64 {
+        bar.saturating_sub(1) * TICKS_PER_BAR / TICKS_PER_MIDI_BEAT
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{:02}", self.bar, self.beat, self.tick)
+    }
+}
+
+/// Where tempo and transport come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ClockSource {
+    /// midi_ctrl is the clock master.
+    #[default]
+    Internal,
+    /// Follow clock and Start/Stop/Continue arriving on the input port.
+    External,
+}
+
+/// Transport position shared between the engine, the clock thread and the
+/// input callback.
+#[derive(Debug, Default)]
+pub struct Transport {
+    running: AtomicBool,
+    /// Clock ticks since Start (24 per quarter note).
+    ticks: AtomicU64,
+    /// When the last tick was counted, to place upcoming ticks in time.
+    last_tick: Mutex<Option<Instant>>,
+}
+
+impl Transport {
+    pub fn is_running(&self) -> bool {
+        self.running.load(Ordering::Relaxed)
+    }
+
+    pub fn ticks(&self) -> u64 {
+        self.ticks.load(Ordering::Relaxed)
+    }
+
+    pub fn last_tick(&self) -> Option<Instant> {
+        *self.last_tick.lock().unwrap()
+    }
+
+    pub fn position(&self) -> Position {
+        Position::from_ticks(self.ticks())
+    }
+
+    /// Moves to a Song Position Pointer location (in MIDI beats).
+    pub fn locate(&self, midi_beats: u64) {
+        self.ticks.store(midi_beats * TICKS_PER_MIDI_BEAT, Ordering::Relaxed);
+    }
+
+    /// Start from the top.
+    pub fn start(&self) {
+        *self.last_tick.lock().unwrap() = None;
+        self.ticks.store(0, Ordering::Relaxed);
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    pub fn stop(&self) {
+        self.running.store(false, Ordering::Relaxed);
+    }
+
+    /// Continue from the current position.
+    pub fn resume(&self) {
+        self.running.store(true, Ordering::Relaxed);
+    }
+
+    /// Advances one clock tick if the transport is running.
+    pub fn tick(&self) {
+        if self.is_running() {
+            *self.last_tick.lock().unwrap() = Some(Instant::now());
+            self.ticks.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Estimates tempo from incoming clock ticks, averaged over one beat so
+/// USB jitter on individual ticks does not make the display flicker.
+#[derive(Debug, Default)]
+pub struct ClockFollower {
+    last_tick_us: Option<u64>,
+    intervals: VecDeque<u64>,
+}
+
+impl ClockFollower {
+    /// Gaps longer than this mean the master stopped sending clock.
+    const MAX_INTERVAL_US: u64 = 250_000;
+
+    /// Feeds one tick timestamp; returns the current BPM estimate.
+    pub fn tick(&mut self, timestamp_us: u64) -> Option<f32> {
+        if let Some(last) = self.last_tick_us.replace(timestamp_us) {
+            let interval = timestamp_us.saturating_sub(last);
+            if interval == 0 || interval > Self::MAX_INTERVAL_US {
+                self.intervals.clear();
+            } else {
+                if self.intervals.len() == PPQN as usize {
+                    self.intervals.pop_front();
+                }
+                self.intervals.push_back(interval);
+            }
+        }
+        self.bpm()
+    }
+
+    pub fn bpm(&self) -> Option<f32> {
+        if self.intervals.is_empty() {
+            return None;
+        }
+        let mean_us = self.intervals.iter().sum::<u64>() as f64 / self.intervals.len() as f64;
+        Some((60_000_000.0 / (mean_us * PPQN as f64)) as f32)
+    }
+
+    pub fn reset(&mut self) {
+        self.last_tick_us = None;
+        self.intervals.clear();
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// A MIDI channel, 1-16 as shown on the hardware.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "u8", into = "u8")]
+pub struct Channel(u8);
+
+impl Channel {
+    pub fn new(channel: u8) -> Result<Self> {
+        if !(1..=16).contains(&channel) {
+            bail!("MIDI channel {} out of range (1-16)", channel);
+        }
+        Ok(Self(channel))
+    }
+
+    /// Channel from the low nibble of a status byte.
+    pub fn from_index(index: u8) -> Self {
+        Self((index & 0x0F) + 1)
+    }
+
+    /// 1-based channel number.
+    pub fn get(self) -> u8 {
+        self.0
+    }
+
+    /// 0-based channel as carried in the status byte.
+    pub fn index(self) -> u8 {
+        self.0 - 1
+    }
+}
+
+impl Default for Channel {
+    fn default() -> Self {
+        Self(1)
+    }
+}
+
+impl TryFrom<u8> for Channel {
+    type Error = anyhow::Error;
+
+    fn try_from(channel: u8) -> Result<Self> {
+        Self::new(channel)
+    }
+}
+
+impl From<Channel> for u8 {
+    fn from(channel: Channel) -> u8 {
+        channel.0
+    }
+}
+
+impl FromStr for Channel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let channel = s
+            .parse::<u8>()
+            .with_context(|| format!("Invalid channel '{}'", s))?;
+        Self::new(channel)
+    }
+}
+
+impl fmt::Display for Channel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+/// Defines a 7-bit data byte newtype that rejects values above 127.
+macro_rules! data_byte {
+    ($(#[$doc:meta])* $name:ident, $what:literal) => {
+        $(#[$doc])*
+        #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+        #[derive(Serialize, Deserialize)]
+        #[serde(try_from = "u8", into = "u8")]
+        pub struct $name(u8);
+
+        impl $name {
+            pub const MAX: u8 = 0x7F;
+
+            pub fn new(value: u8) -> Result<Self> {
+                if value > Self::MAX {
+                    bail!("{} {} out of range (0-127)", $what, value);
+                }
+                Ok(Self(value))
+            }
+
+            /// The low 7 bits of `value`, for splitting wider values into
+            /// data bytes.
+            pub fn from_low_bits(value: u16) -> Self {
+                Self((value & 0x7F) as u8)
+            }
+
+            pub fn get(self) -> u8 {
+                self.0
+            }
+        }
+
+        impl TryFrom<u8> for $name {
+            type Error = anyhow::Error;
+
+            fn try_from(value: u8) -> Result<Self> {
+                Self::new(value)
+            }
+        }
+
+        impl From<$name> for u8 {
+            fn from(value: $name) -> u8 {
+                value.0
+            }
+        }
+
+        impl FromStr for $name {
+            type Err = anyhow::Error;
+
+            fn from_str(s: &str) -> Result<Self> {
+                let value = s
+                    .parse::<u8>()
+                    .with_context(|| format!("Invalid {} '{}'", $what.to_lowercase(), s))?;
+                Self::new(value)
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "{}", self.0)
+            }
+        }
+    };
+}
+
+data_byte!(
+    /// A Control Change controller number (0-127).
+    Controller,
+    "Controller"
+);
+
+data_byte!(
+    /// Any 7-bit data value: CC value, note, velocity, program, pressure.
+    Value7,
+    "Value"
+);
@@ -0,0 +1,45 @@
+//! Polling for edits to files read once at startup, such as the config and
+//! parameter maps, so they can be reloaded without restarting.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+/// Notices when a file is written, created or deleted, by comparing its
+/// modification time on each [`FileWatch::changed`] call.
+#[derive(Debug, Clone)]
+pub struct FileWatch {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+}
+
+impl FileWatch {
+    pub fn new(path: PathBuf) -> Self {
+        let modified = modified(&path);
+        Self { path, modified }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Whether the file changed since the last call (or since it was
+    /// marked seen).
+    pub fn changed(&mut self) -> bool {
+        let modified = modified(&self.path);
+        if modified == self.modified {
+            return false;
+        }
+        self.modified = modified;
+        true
+    }
+
+    /// Takes the file as it is now as seen, e.g. after writing it ourselves.
+    pub fn mark_seen(&mut self) {
+        self.modified = modified(&self.path);
+    }
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
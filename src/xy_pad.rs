@@ -0,0 +1,132 @@
+//! XY pad page of the GUI: one drag controls two parameters at once.
+
+use crate::gui::MidiCommand;
+use eframe::egui;
+use midi_ctrl::{Channel, MidiMap, MidiParameter, ParamAddress, Value7};
+use std::time::{Duration, Instant};
+
+const PAD_SIZE: f32 = 320.0;
+/// Sends per second while dragging, by default.
+const DEFAULT_RATE: u32 = 50;
+
+pub struct XyPad {
+    params: Vec<MidiParameter>,
+    x_param: ParamAddress,
+    y_param: ParamAddress,
+    x: u8,
+    y: u8,
+    /// Sends per second at most while dragging.
+    rate: u32,
+    last_send: Option<Instant>,
+    /// Values changed since the last send.
+    pending: bool,
+}
+
+impl XyPad {
+    pub fn new(midi_map: &MidiMap) -> Self {
+        let mut pad = Self {
+            params: Vec::new(),
+            x_param: ParamAddress::Cc(74),
+            y_param: ParamAddress::Cc(75),
+            x: 64,
+            y: 64,
+            rate: DEFAULT_RATE,
+            last_send: None,
+            pending: false,
+        };
+        pad.set_map(midi_map);
+        pad
+    }
+
+    /// Offers the parameters of `midi_map`, with filter frequency and
+    /// resonance on the axes where it has them.
+    pub fn set_map(&mut self, midi_map: &MidiMap) {
+        let address = |name: &str, cc: u8| {
+            midi_map.get_by_name(name).map_or(ParamAddress::Cc(cc), |p| p.address)
+        };
+        self.params = midi_map.get_all_parameters();
+        self.x_param = address("Filter Frequency", 74);
+        self.y_param = address("Resonance", 75);
+    }
+
+    fn param_combo(
+        ui: &mut egui::Ui,
+        id: &str,
+        params: &[MidiParameter],
+        selected: &mut ParamAddress,
+    ) {
+        let name = params
+            .iter()
+            .find(|p| p.address == *selected)
+            .map_or_else(|| selected.to_string(), |p| p.name.clone());
+        egui::ComboBox::from_id_source(id).selected_text(name).show_ui(ui, |ui| {
+            for param in params {
+                ui.selectable_value(selected, param.address, &param.name);
+            }
+        });
+    }
+
+    fn send_values(&mut self, channel: Channel, send: &mut dyn FnMut(MidiCommand)) {
+        for (address, value) in [(self.x_param, self.x), (self.y_param, self.y)] {
+            if let Ok(value) = Value7::new(value) {
+                send(MidiCommand::SendParam { channel, address, value });
+            }
+        }
+        self.last_send = Some(Instant::now());
+        self.pending = false;
+    }
+
+    /// Draws the parameter pickers and the pad. Drags send both values at
+    /// most `rate` times a second; the final position always goes out.
+    pub fn show(&mut self, ui: &mut egui::Ui, channel: Channel, send: &mut dyn FnMut(MidiCommand)) {
+        ui.horizontal(|ui| {
+            ui.label("X:");
+            Self::param_combo(ui, "xy_x", &self.params, &mut self.x_param);
+            ui.label("Y:");
+            Self::param_combo(ui, "xy_y", &self.params, &mut self.y_param);
+            ui.separator();
+            ui.label("Rate:");
+            ui.add(egui::DragValue::new(&mut self.rate).clamp_range(1..=200).suffix("/s"));
+        });
+        ui.add_space(8.0);
+
+        let (rect, response) =
+            ui.allocate_exact_size(egui::vec2(PAD_SIZE, PAD_SIZE), egui::Sense::click_and_drag());
+        if (response.dragged() || response.clicked())
+            && let Some(pos) = response.interact_pointer_pos()
+        {
+            let fraction = (pos - rect.min) / rect.size();
+            let x = (fraction.x.clamp(0.0, 1.0) * 127.0).round() as u8;
+            // Up is more
+            let y = ((1.0 - fraction.y.clamp(0.0, 1.0)) * 127.0).round() as u8;
+            if (x, y) != (self.x, self.y) {
+                self.x = x;
+                self.y = y;
+                self.pending = true;
+            }
+        }
+
+        let interval = Duration::from_secs_f32(1.0 / self.rate.max(1) as f32);
+        let due = self.last_send.is_none_or(|at| at.elapsed() >= interval);
+        if self.pending && (due || !response.dragged()) {
+            self.send_values(channel, send);
+        }
+        if self.pending {
+            // Make sure the held-back values go out even if the pointer stops
+            ui.ctx().request_repaint_after(interval);
+        }
+
+        let painter = ui.painter_at(rect);
+        let visuals = ui.visuals();
+        painter.rect_filled(rect, 6.0, visuals.extreme_bg_color);
+        let point = egui::pos2(
+            rect.left() + self.x as f32 / 127.0 * rect.width(),
+            rect.bottom() - self.y as f32 / 127.0 * rect.height(),
+        );
+        let stroke = egui::Stroke::new(1.0, visuals.weak_text_color());
+        painter.hline(rect.x_range(), point.y, stroke);
+        painter.vline(point.x, rect.y_range(), stroke);
+        painter.circle_filled(point, 8.0, visuals.selection.bg_fill);
+        ui.label(format!("X {} · Y {}", self.x, self.y));
+    }
+}